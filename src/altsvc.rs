@@ -0,0 +1,253 @@
+// Alt-Svc (RFC 7838) cache, stored in curl's --alt-svc file format:
+//
+//   h2 example.com 443 h2 alt.example.net 8443 "20261015 10:00:00" 0 0
+//
+// i.e. source ALPN, host and port, alternative ALPN, host and port, expiry
+// (UTC), persist flag and priority. Alternatives are only used for https
+// origins, and only for protocols this client speaks; h3 entries are kept
+// in the file for other tools but skipped here.
+
+use crate::errln;
+use crate::sigv4;
+use reqwest::Version;
+use reqwest::header::{ALT_SVC, HeaderMap};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+// Alt-Svc's default "ma" (max age).
+const DEFAULT_MAX_AGE: u64 = 86400;
+
+// ALPN ids that can be reached with this client's HTTP stack.
+const SUPPORTED_ALPNS: [&str; 3] = ["h2", "h1", "http/1.1"];
+
+#[derive(Clone)]
+struct Entry {
+    src_alpn: String,
+    src_host: String,
+    src_port: u16,
+    dst_alpn: String,
+    dst_host: String,
+    dst_port: u16,
+    expires: SystemTime,
+    persist: bool,
+    prio: u32,
+}
+
+pub struct Target {
+    pub alpn: String,
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Clone)]
+struct Session {
+    path: PathBuf,
+    host: String,
+    port: u16,
+}
+
+thread_local! {
+    static SESSION: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+// Remember which cache file and origin responses of this run belong to,
+// and return a usable alternative for that origin if one is cached.
+pub fn enable(path: &Path, url: &Url) -> Option<Target> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let port = url.port_or_known_default()?;
+    SESSION.set(Some(Session {
+        path: path.to_path_buf(),
+        host: host.clone(),
+        port,
+    }));
+    if url.scheme() != "https" {
+        return None;
+    }
+
+    let now = SystemTime::now();
+    load(path)
+        .into_iter()
+        .filter(|e| e.src_host == host && e.src_port == port && e.expires > now)
+        .find(|e| SUPPORTED_ALPNS.contains(&e.dst_alpn.as_str()))
+        .map(|e| Target {
+            alpn: e.dst_alpn,
+            host: e.dst_host,
+            port: e.dst_port,
+        })
+}
+
+// Update the cache from a response's Alt-Svc headers. No-op unless
+// --alt-svc is in use.
+pub fn record(headers: &HeaderMap, version: Version) {
+    let Some(session) = SESSION.with_borrow(Clone::clone) else {
+        return;
+    };
+    let values: Vec<&str> = headers
+        .get_all(ALT_SVC)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    if values.is_empty() {
+        return;
+    }
+
+    let src_alpn = if version == Version::HTTP_2 {
+        "h2"
+    } else {
+        "h1"
+    };
+    let now = SystemTime::now();
+    let mut entries: Vec<Entry> = load(&session.path)
+        .into_iter()
+        .filter(|e| e.expires > now)
+        .filter(|e| !(e.src_host == session.host && e.src_port == session.port))
+        .collect();
+
+    for value in values {
+        // "clear" drops every alternative for the origin
+        if value.trim().eq_ignore_ascii_case("clear") {
+            continue;
+        }
+        for alt in value.split(',') {
+            if let Some(entry) = parse_alternative(alt, src_alpn, &session, now) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    if let Err(e) = save(&session.path, &entries) {
+        errln!(
+            "Warning: Unable to write '{}': {}",
+            session.path.display(),
+            e
+        );
+    }
+}
+
+// One alternative from the header, e.g. `h2="alt.example.net:8443"; ma=3600`.
+fn parse_alternative(
+    alt: &str,
+    src_alpn: &str,
+    origin: &Session,
+    now: SystemTime,
+) -> Option<Entry> {
+    let mut params = alt.split(';');
+    let (alpn, authority) = params.next()?.trim().split_once('=')?;
+    let authority = authority.trim().trim_matches('"');
+    let (host, port) = authority.rsplit_once(':')?;
+    let port: u16 = port.parse().ok()?;
+    // An empty host (`h2=":443"`) means the origin's own host
+    let host = match host.trim_matches(['[', ']']) {
+        "" => origin.host.clone(),
+        h => h.to_ascii_lowercase(),
+    };
+
+    let mut max_age = DEFAULT_MAX_AGE;
+    let mut persist = false;
+    for param in params {
+        match param.trim().split_once('=') {
+            Some(("ma", v)) => max_age = v.trim().parse().ok()?,
+            Some(("persist", v)) => persist = v.trim() == "1",
+            _ => {}
+        }
+    }
+
+    Some(Entry {
+        src_alpn: src_alpn.to_string(),
+        src_host: origin.host.clone(),
+        src_port: origin.port,
+        dst_alpn: alpn.trim().to_ascii_lowercase(),
+        dst_host: host,
+        dst_port: port,
+        expires: now + Duration::from_secs(max_age),
+        persist,
+        prio: 0,
+    })
+}
+
+fn load(path: &Path) -> Vec<Entry> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(parse_line)
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let (head, rest) = line.split_once('"')?;
+    let (date, tail) = rest.split_once('"')?;
+    let f: Vec<&str> = head.split_whitespace().collect();
+    let t: Vec<&str> = tail.split_whitespace().collect();
+    if f.len() != 6 || t.len() != 2 {
+        return None;
+    }
+    Some(Entry {
+        src_alpn: f[0].to_string(),
+        src_host: f[1].to_string(),
+        src_port: f[2].parse().ok()?,
+        dst_alpn: f[3].to_string(),
+        dst_host: f[4].to_string(),
+        dst_port: f[5].parse().ok()?,
+        expires: parse_date(date)?,
+        persist: t[0] == "1",
+        prio: t[1].parse().ok()?,
+    })
+}
+
+fn save(path: &Path, entries: &[Entry]) -> std::io::Result<()> {
+    let mut out = String::from("# Your alt-svc cache. https://curl.se/docs/alt-svc.html\n");
+    for e in entries {
+        out.push_str(&format!(
+            "{} {} {} {} {} {} \"{}\" {} {}\n",
+            e.src_alpn,
+            e.src_host,
+            e.src_port,
+            e.dst_alpn,
+            e.dst_host,
+            e.dst_port,
+            format_date(e.expires),
+            e.persist as u8,
+            e.prio
+        ));
+    }
+    fs::write(path, out)
+}
+
+// "YYYYMMDD HH:MM:SS" in UTC.
+fn format_date(time: SystemTime) -> String {
+    let (compact, _) = sigv4::amz_timestamps(time);
+    format!(
+        "{} {}:{}:{}",
+        &compact[0..8],
+        &compact[9..11],
+        &compact[11..13],
+        &compact[13..15]
+    )
+}
+
+fn parse_date(date: &str) -> Option<SystemTime> {
+    let (day, time) = date.split_once(' ')?;
+    if day.len() != 8 {
+        return None;
+    }
+    let days = sigv4::days_from_civil(
+        day[0..4].parse().ok()?,
+        day[4..6].parse().ok()?,
+        day[6..8].parse().ok()?,
+    );
+    let hms: Vec<u64> = time
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    if hms.len() != 3 || days < 0 {
+        return None;
+    }
+    let secs = days as u64 * 86400 + hms[0] * 3600 + hms[1] * 60 + hms[2];
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
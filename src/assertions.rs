@@ -0,0 +1,110 @@
+// Response assertions (expected Content-Type, latency budgets, ...).
+//
+// Checks are recorded here instead of aborting on the spot so the rest of
+// the run (body output, pool report) still happens; `main` turns any failed
+// assertion into a non-zero exit status at the end, and the full list feeds
+// reports such as --report-junit.
+
+use crate::output;
+use std::cell::RefCell;
+use std::time::Duration;
+
+pub const EXIT_ASSERTION_FAILED: i32 = 1;
+
+#[derive(Clone)]
+pub struct Check {
+    // The request the check belongs to, e.g. "GET http://host/path"
+    pub request: String,
+    pub name: String,
+    pub failure: Option<String>,
+    pub time: Option<Duration>,
+    // Reported, but doesn't change the exit status (e.g. HTTP status codes)
+    pub informational: bool,
+}
+
+struct Log {
+    request: String,
+    checks: Vec<Check>,
+}
+
+thread_local! {
+    static LOG: RefCell<Log> = const {
+        RefCell::new(Log {
+            request: String::new(),
+            checks: Vec::new(),
+        })
+    };
+}
+
+// Label the checks recorded from now on.
+pub fn begin(request: String) {
+    LOG.with_borrow_mut(|log| log.request = request);
+}
+
+pub fn pass(name: &str, time: Option<Duration>) {
+    record(name, None, time, false);
+}
+
+// Print the failure like any other error and remember it for the exit code.
+pub fn fail(name: &str, message: String, time: Option<Duration>) {
+    output::error(&message);
+    record(name, Some(message), time, false);
+}
+
+// Record how the request itself went without treating it as an assertion;
+// the caller has already reported any error.
+pub fn outcome(failure: Option<String>, time: Option<Duration>) {
+    record("request", failure, time, true);
+}
+
+pub fn record(name: &str, failure: Option<String>, time: Option<Duration>, informational: bool) {
+    LOG.with_borrow_mut(|log| {
+        let request = log.request.clone();
+        log.checks.push(Check {
+            request,
+            name: name.to_string(),
+            failure,
+            time,
+            informational,
+        });
+    });
+}
+
+pub fn checks() -> Vec<Check> {
+    LOG.with_borrow(|log| log.checks.clone())
+}
+
+pub fn failed() -> bool {
+    LOG.with_borrow(|log| {
+        log.checks
+            .iter()
+            .any(|c| c.failure.is_some() && !c.informational)
+    })
+}
+
+// `expected` may be a full type or a wildcard such as `text/*`; parameters
+// like `charset` are ignored on both sides.
+pub fn content_type_matches(expected: &str, actual: Option<&str>) -> bool {
+    let Some(actual) = actual.and_then(|a| a.parse::<mime::Mime>().ok()) else {
+        return false;
+    };
+    let Ok(expected) = expected.parse::<mime::Mime>() else {
+        return false;
+    };
+    actual.type_() == expected.type_()
+        && (expected.subtype() == mime::STAR || actual.subtype() == expected.subtype())
+}
+
+// Servers that fail behind a proxy or login wall often answer with an HTML
+// page and a 200, which is easy to miss when a JSON API was expected. The
+// body is sniffed too since such pages are often mislabelled.
+pub fn looks_like_html(content_type: Option<&str>, body: &[u8]) -> bool {
+    if let Some(ct) = content_type.and_then(|c| c.parse::<mime::Mime>().ok())
+        && ct.essence_str() == mime::TEXT_HTML.essence_str()
+    {
+        return true;
+    }
+    let start = String::from_utf8_lossy(&body[..body.len().min(64)]).to_ascii_lowercase();
+    let start = start.trim_start();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
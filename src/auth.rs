@@ -0,0 +1,241 @@
+// Server authentication for -u, --bearer and --digest.
+//
+// Basic and Bearer are plain Authorization headers added to every request.
+// Digest (RFC 7616) needs the server's nonce first: like the NTLM
+// handshake, a request without a body fetches the 401 challenge and the
+// real request carries the computed response. MD5, SHA-256 and their
+// -sess variants are supported with qop "auth" (or no qop, RFC 2069).
+
+use crate::client::WebClient;
+use crate::method::Method;
+use crate::prompt;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use openssl::hash::{MessageDigest, hash};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+use url::Url;
+
+pub struct Credentials {
+    user: String,
+    password: String,
+}
+
+impl Credentials {
+    // "user:password"; without a password it's asked for on the terminal,
+    // as curl does.
+    pub fn parse(value: &str) -> Result<Credentials, String> {
+        let (user, password) = match value.split_once(':') {
+            Some((user, password)) => (user, password.to_string()),
+            None => (
+                value,
+                prompt::password(&format!("Enter host password for user '{}': ", value))?,
+            ),
+        };
+        Ok(Credentials {
+            user: user.to_string(),
+            password,
+        })
+    }
+
+    // Back to "user:password" for schemes with their own parsing (NTLM).
+    pub fn joined(&self) -> String {
+        format!("{}:{}", self.user, self.password)
+    }
+
+    pub fn basic(&self) -> HeaderValue {
+        let token = STANDARD.encode(self.joined());
+        sensitive(&format!("Basic {}", token)).unwrap()
+    }
+}
+
+pub fn bearer(token: &str) -> Result<HeaderValue, String> {
+    sensitive(&format!("Bearer {}", token.trim()))
+        .ok_or_else(|| "Invalid bearer token.".to_string())
+}
+
+fn sensitive(value: &str) -> Option<HeaderValue> {
+    let mut value = HeaderValue::from_str(value).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
+
+// Fetch the Digest challenge and answer it. None when the server doesn't
+// ask for authentication at all.
+pub fn digest_handshake(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    credentials: &Credentials,
+) -> Result<Option<HeaderValue>, String> {
+    let req = client.request(method, url.clone()).headers(headers.clone());
+    let res = client
+        .execute(req)
+        .map_err(|e| format!("Digest handshake failed: {}", e))?;
+    let status = res.status();
+    let challenge = res
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(digest_params);
+    // Drain the body so the connection can be reused
+    let _ = res.bytes();
+
+    if status != StatusCode::UNAUTHORIZED {
+        return Ok(None);
+    }
+    let Some(challenge) = challenge else {
+        return Err("The server doesn't offer Digest authentication.".to_string());
+    };
+    digest_response(&challenge, method.as_str(), url, credentials).map(Some)
+}
+
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: String,
+    qop: Option<String>,
+}
+
+// The parameters of a "Digest realm=..., nonce=..." challenge.
+fn digest_params(header: &str) -> Option<Challenge> {
+    let start = header.to_ascii_lowercase().find("digest ")?;
+    let mut rest = &header[start + "digest ".len()..];
+    let mut params: Vec<(String, String)> = Vec::new();
+    loop {
+        rest = rest.trim_start_matches([' ', ',']);
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => unquote(quoted),
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        params.push((key, value));
+        rest = next;
+    }
+    let get = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+    Some(Challenge {
+        realm: get("realm").unwrap_or_default(),
+        nonce: get("nonce")?,
+        opaque: get("opaque"),
+        algorithm: get("algorithm").unwrap_or_else(|| "MD5".to_string()),
+        qop: get("qop"),
+    })
+}
+
+// A quoted-string up to its closing quote, and what follows it.
+fn unquote(s: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    value.push(escaped);
+                }
+            }
+            '"' => return (value, &s[i + 1..]),
+            _ => value.push(c),
+        }
+    }
+    (value, "")
+}
+
+fn digest_response(
+    challenge: &Challenge,
+    method: &str,
+    url: &Url,
+    credentials: &Credentials,
+) -> Result<HeaderValue, String> {
+    let algorithm = challenge.algorithm.to_ascii_uppercase();
+    let (digest, session) = match algorithm.as_str() {
+        "MD5" => (MessageDigest::md5(), false),
+        "MD5-SESS" => (MessageDigest::md5(), true),
+        "SHA-256" => (MessageDigest::sha256(), false),
+        "SHA-256-SESS" => (MessageDigest::sha256(), true),
+        _ => {
+            return Err(format!(
+                "The server asks for Digest algorithm {}, which isn't supported.",
+                challenge.algorithm
+            ));
+        }
+    };
+    let h = |data: String| -> Result<String, String> {
+        let bytes = hash(digest, data.as_bytes()).map_err(|e| e.to_string())?;
+        Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    };
+
+    let qop = match &challenge.qop {
+        Some(offered) if offered.split(',').any(|q| q.trim() == "auth") => Some("auth"),
+        Some(offered) => {
+            return Err(format!(
+                "The server offers Digest qop '{}'; only 'auth' is supported.",
+                offered
+            ));
+        }
+        None => None,
+    };
+    let cnonce = cnonce()?;
+    let nc = "00000001";
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let mut ha1 = h(format!(
+        "{}:{}:{}",
+        credentials.user, challenge.realm, credentials.password
+    ))?;
+    if session {
+        ha1 = h(format!("{}:{}:{}", ha1, challenge.nonce, cnonce))?;
+    }
+    let ha2 = h(format!("{}:{}", method, uri))?;
+    let response = match qop {
+        Some(qop) => h(format!(
+            "{}:{}:{}:{}:{}:{}",
+            ha1, challenge.nonce, nc, cnonce, qop, ha2
+        ))?,
+        None => h(format!("{}:{}:{}", ha1, challenge.nonce, ha2))?,
+    };
+
+    let mut value = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        quote(&credentials.user),
+        quote(&challenge.realm),
+        quote(&challenge.nonce),
+        quote(&uri),
+        challenge.algorithm,
+        response
+    );
+    if let Some(qop) = qop {
+        value.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+    }
+    if let Some(opaque) = &challenge.opaque {
+        value.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+    }
+    sensitive(&value).ok_or_else(|| "Invalid characters in the Digest credentials.".to_string())
+}
+
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn cnonce() -> Result<String, String> {
+    let mut bytes = [0u8; 12];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
@@ -0,0 +1,169 @@
+// --bench: send the configured request -n times, --concurrency at a time,
+// and report throughput, latency percentiles, status codes and bytes
+// transferred, in the spirit of ab and hey.
+//
+// Latency is measured from sending the request to the end of the response
+// body, which is read in full and discarded.
+
+use crate::download::size;
+use crate::output;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_REQUESTS: usize = 200;
+pub const DEFAULT_CONCURRENCY: usize = 50;
+
+const HISTOGRAM_BUCKETS: usize = 10;
+const HISTOGRAM_WIDTH: usize = 40;
+
+pub struct Config {
+    pub requests: usize,
+    pub concurrency: usize,
+}
+
+// One response: the status code and the body size. An error is the short
+// cause it's counted under ("connection failed").
+pub type Outcome = Result<(u16, u64), String>;
+
+struct Sample {
+    latency: Duration,
+    outcome: Outcome,
+}
+
+pub fn run(config: &Config, send: impl Fn() -> Outcome + Sync) {
+    let concurrency = config.concurrency.clamp(1, config.requests.max(1));
+    output::status(format!(
+        "Benchmarking: {} requests, {} at a time",
+        config.requests, concurrency
+    ));
+
+    let next = AtomicUsize::new(0);
+    let samples = Mutex::new(Vec::with_capacity(config.requests));
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..concurrency {
+            scope.spawn(|| {
+                while next.fetch_add(1, Ordering::Relaxed) < config.requests {
+                    let began = Instant::now();
+                    let outcome = send();
+                    let sample = Sample {
+                        latency: began.elapsed(),
+                        outcome,
+                    };
+                    samples.lock().unwrap().push(sample);
+                }
+            });
+        }
+    });
+    report(&samples.into_inner().unwrap(), started.elapsed());
+}
+
+fn report(samples: &[Sample], total: Duration) {
+    let secs = total.as_secs_f64().max(f64::EPSILON);
+    let bytes: u64 = samples
+        .iter()
+        .filter_map(|s| s.outcome.as_ref().ok())
+        .map(|(_, n)| n)
+        .sum();
+    output::status(format!("Total time:   {}", duration(total)));
+    output::status(format!(
+        "Throughput:   {:.1} requests/s, {}/s",
+        samples.len() as f64 / secs,
+        size((bytes as f64 / secs) as u64)
+    ));
+    output::status(format!("Transferred:  {} of response bodies", size(bytes)));
+
+    let mut statuses: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut errors: BTreeMap<&str, usize> = BTreeMap::new();
+    for sample in samples {
+        match &sample.outcome {
+            Ok((status, _)) => *statuses.entry(*status).or_default() += 1,
+            Err(cause) => *errors.entry(cause.as_str()).or_default() += 1,
+        }
+    }
+    let mut counts: Vec<String> = statuses
+        .iter()
+        .map(|(status, n)| format!("{} x {}", status, n))
+        .collect();
+    counts.extend(errors.iter().map(|(cause, n)| format!("{} x {}", cause, n)));
+    output::status(format!("Responses:    {}", counts.join(", ")));
+
+    // Latencies of the requests that got a response
+    let mut latencies: Vec<Duration> = samples
+        .iter()
+        .filter(|s| s.outcome.is_ok())
+        .map(|s| s.latency)
+        .collect();
+    if latencies.is_empty() {
+        return;
+    }
+    latencies.sort();
+    let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+    output::status(format!(
+        "Latency:      min {}, mean {}, p50 {}, p95 {}, p99 {}, max {}",
+        duration(latencies[0]),
+        duration(mean),
+        duration(percentile(&latencies, 50.0)),
+        duration(percentile(&latencies, 95.0)),
+        duration(percentile(&latencies, 99.0)),
+        duration(latencies[latencies.len() - 1])
+    ));
+    histogram(&latencies);
+}
+
+// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// Equal-width buckets from the fastest to the slowest response, each
+// labeled with its upper bound.
+fn histogram(sorted: &[Duration]) {
+    let min = sorted[0];
+    let max = sorted[sorted.len() - 1];
+    let step = (max - min) / HISTOGRAM_BUCKETS as u32;
+    let mut buckets = [0usize; HISTOGRAM_BUCKETS];
+    for &latency in sorted {
+        let i = if step.is_zero() {
+            0
+        } else {
+            ((latency - min).as_nanos() / step.as_nanos()) as usize
+        };
+        buckets[i.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+    let tallest = buckets.iter().copied().max().unwrap_or(1).max(1);
+
+    output::status("Latency histogram:");
+    for (i, &count) in buckets.iter().enumerate() {
+        let bound = if i == HISTOGRAM_BUCKETS - 1 {
+            max
+        } else {
+            min + step * (i as u32 + 1)
+        };
+        let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(tallest));
+        let line = format!(
+            "  {:>10} [{:>width$}] {}",
+            duration(bound),
+            count,
+            bar,
+            width = sorted.len().to_string().len()
+        );
+        output::status(line.trim_end());
+        if step.is_zero() {
+            break;
+        }
+    }
+}
+
+// Sub-millisecond precision, which transfer::describe rounds away.
+fn duration(d: Duration) -> String {
+    if d.as_millis() < 1000 {
+        format!("{:.2} ms", d.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2} s", d.as_secs_f64())
+    }
+}
@@ -0,0 +1,337 @@
+// --cache DIR: GET responses kept on disk and reused by later runs.
+//
+// DIR/index.json maps each URL to its entry: the validators (ETag,
+// Last-Modified), when it was stored and how long it stays fresh, and the
+// request headers its Vary names. The decoded body is kept beside it, in a
+// file named after the SHA-256 of the URL.
+//
+// A fresh entry is served without asking the server. A stale one is
+// revalidated with If-None-Match / If-Modified-Since, and a 304 serves the
+// stored body and renews the entry. Cache-Control is followed as a private
+// cache would: no-store responses aren't kept, no-cache ones are always
+// revalidated, and max-age (less Age) beats Expires, which beats 10% of the
+// time since Last-Modified. --no-cache ignores the stored copy and replaces
+// it with the new response. --offline serves the stored copy however stale
+// it is and never asks the server.
+
+use crate::cache_report::{cache_control, seconds};
+use crate::sigv4::sha256_hex;
+use reqwest::header::{
+    AGE, CONTENT_TYPE, DATE, ETAG, EXPIRES, HeaderMap, HeaderValue, IF_MODIFIED_SINCE,
+    IF_NONE_MATCH, LAST_MODIFIED, PRAGMA, VARY,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const INDEX: &str = "index.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Entry {
+    // The body's file in the cache directory
+    body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    // Seconds since the Unix epoch
+    stored: u64,
+    // Seconds after `stored` the entry stays fresh; 0 revalidates every time
+    fresh_for: u64,
+    // Lowercased request header name -> the value this response was for
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    vary: BTreeMap<String, String>,
+}
+
+pub struct Cached {
+    pub body: Vec<u8>,
+    pub content_type: Option<String>,
+}
+
+pub enum Lookup {
+    // Served without a request, fresh for this much longer
+    Fresh(Cached, Duration),
+    // Stale: send the request with these conditional headers
+    Revalidate(HeaderMap),
+    Miss,
+}
+
+// The request being answered, between lookup and its response.
+struct Pending {
+    dir: PathBuf,
+    key: String,
+    request: HeaderMap,
+    entry: Option<Entry>,
+}
+
+thread_local! {
+    static PENDING: RefCell<Option<Pending>> = const { RefCell::new(None) };
+}
+
+pub fn lookup(dir: &Path, url: &Url, request: &HeaderMap, refresh: bool) -> Result<Lookup, String> {
+    let key = key(url);
+    let entry = if refresh {
+        None
+    } else {
+        read_index(dir)?
+            .remove(&key)
+            .filter(|entry| vary_matches(entry, request))
+    };
+    PENDING.set(Some(Pending {
+        dir: dir.to_path_buf(),
+        key,
+        request: request.clone(),
+        entry: entry.clone(),
+    }));
+    let Some(entry) = entry else {
+        return Ok(Lookup::Miss);
+    };
+
+    let age = now().saturating_sub(entry.stored);
+    if age < entry.fresh_for
+        && let Ok(body) = fs::read(dir.join(&entry.body))
+    {
+        let cached = Cached {
+            body,
+            content_type: entry.content_type,
+        };
+        return Ok(Lookup::Fresh(
+            cached,
+            Duration::from_secs(entry.fresh_for - age),
+        ));
+    }
+    let mut conditional = HeaderMap::new();
+    let validators = [
+        (IF_NONE_MATCH, &entry.etag),
+        (IF_MODIFIED_SINCE, &entry.last_modified),
+    ];
+    for (name, value) in validators {
+        if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            conditional.insert(name, value);
+        }
+    }
+    if conditional.is_empty() {
+        return Ok(Lookup::Miss);
+    }
+    Ok(Lookup::Revalidate(conditional))
+}
+
+// --offline: the stored copy and its age, fresh or not.
+pub fn stored(
+    dir: &Path,
+    url: &Url,
+    request: &HeaderMap,
+) -> Result<Option<(Cached, Duration)>, String> {
+    let entry = read_index(dir)?
+        .remove(&key(url))
+        .filter(|entry| vary_matches(entry, request));
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+    let path = dir.join(&entry.body);
+    let body = match fs::read(&path) {
+        Ok(body) => body,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Unable to read '{}': {}", path.display(), e)),
+    };
+    let cached = Cached {
+        body,
+        content_type: entry.content_type,
+    };
+    Ok(Some((
+        cached,
+        Duration::from_secs(now().saturating_sub(entry.stored)),
+    )))
+}
+
+// A 304 to the revalidation: the stored body, with the entry renewed from
+// the new response's headers.
+pub fn not_modified(headers: &HeaderMap) -> Result<Option<Cached>, String> {
+    let Some(Pending {
+        dir,
+        key,
+        entry: Some(mut entry),
+        ..
+    }) = PENDING.take()
+    else {
+        return Ok(None);
+    };
+    let path = dir.join(&entry.body);
+    let body = fs::read(&path)
+        .map_err(|e| format!("Unable to read the cached body '{}': {}", path.display(), e))?;
+    if let Some(etag) = header(headers, ETAG) {
+        entry.etag = Some(etag);
+    }
+    if let Some(last_modified) = header(headers, LAST_MODIFIED) {
+        entry.last_modified = Some(last_modified);
+    }
+    entry.stored = now();
+    entry.fresh_for = fresh_for(headers, entry.last_modified.as_deref());
+    let cached = Cached {
+        body,
+        content_type: entry.content_type.clone(),
+    };
+    update_index(&dir, &key, Some(entry))?;
+    Ok(Some(cached))
+}
+
+// A 200 response to the looked-up request, kept if it can be reused.
+// Returns whether it was.
+pub fn store(status: u16, headers: &HeaderMap, body: &[u8]) -> Result<bool, String> {
+    let Some(pending) = PENDING.take() else {
+        return Ok(false);
+    };
+    if status != 200 {
+        return Ok(false);
+    }
+    let directives = cache_control(headers);
+    let vary = header(headers, VARY).unwrap_or_default();
+    if directives.contains_key("no-store") || vary.trim() == "*" {
+        if pending.entry.is_some() {
+            update_index(&pending.dir, &pending.key, None)?;
+        }
+        return Ok(false);
+    }
+    let etag = header(headers, ETAG);
+    let last_modified = header(headers, LAST_MODIFIED);
+    let fresh_for = fresh_for(headers, last_modified.as_deref());
+    if etag.is_none() && last_modified.is_none() && fresh_for == 0 {
+        return Ok(false);
+    }
+
+    let entry = Entry {
+        body: sha256_hex(pending.key.as_bytes()),
+        etag,
+        last_modified,
+        content_type: header(headers, CONTENT_TYPE),
+        stored: now(),
+        fresh_for,
+        vary: vary
+            .split(',')
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = header(&pending.request, name.as_str()).unwrap_or_default();
+                (name, value)
+            })
+            .collect(),
+    };
+    fs::create_dir_all(&pending.dir)
+        .map_err(|e| format!("Unable to create '{}': {}", pending.dir.display(), e))?;
+    let path = pending.dir.join(&entry.body);
+    fs::write(&path, body).map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+    update_index(&pending.dir, &pending.key, Some(entry))?;
+    Ok(true)
+}
+
+// How long a response stays fresh, in seconds.
+fn fresh_for(headers: &HeaderMap, last_modified: Option<&str>) -> u64 {
+    let directives = cache_control(headers);
+    let pragma_no_cache =
+        directives.is_empty() && header(headers, PRAGMA).is_some_and(|p| p.contains("no-cache"));
+    if directives.contains_key("no-cache") || pragma_no_cache {
+        return 0;
+    }
+    let date = header(headers, DATE)
+        .and_then(|d| httpdate::parse_http_date(&d).ok())
+        .unwrap_or_else(SystemTime::now);
+    let since = |at: SystemTime| at.duration_since(date).map_or(0, |d| d.as_secs());
+    let lifetime = if let Some(max_age) = seconds(&directives, "max-age") {
+        max_age
+    } else if let Some(expires) = header(headers, EXPIRES) {
+        // Invalid dates such as "0" mean "already expired"
+        httpdate::parse_http_date(&expires).map_or(0, since)
+    } else if let Some(modified) = last_modified.and_then(|lm| httpdate::parse_http_date(lm).ok()) {
+        date.duration_since(modified)
+            .map_or(0, |d| d.as_secs() / 10)
+    } else {
+        0
+    };
+    let age = header(headers, AGE)
+        .and_then(|a| a.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    lifetime.saturating_sub(age)
+}
+
+fn vary_matches(entry: &Entry, request: &HeaderMap) -> bool {
+    entry
+        .vary
+        .iter()
+        .all(|(name, value)| header(request, name.as_str()).unwrap_or_default() == *value)
+}
+
+fn read_index(dir: &Path) -> Result<BTreeMap<String, Entry>, String> {
+    let path = dir.join(INDEX);
+    match fs::read_to_string(&path) {
+        Ok(text) => parse_index(&text, &path),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(format!("Unable to read '{}': {}", path.display(), e)),
+    }
+}
+
+fn parse_index(text: &str, path: &Path) -> Result<BTreeMap<String, Entry>, String> {
+    if text.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    serde_json::from_str(text).map_err(|_| format!("'{}' isn't a cache index", path.display()))
+}
+
+// Replace or remove one entry. Runs of several URLs share the index, so the
+// lock keeps their read-modify-write cycles apart.
+fn update_index(dir: &Path, key: &str, entry: Option<Entry>) -> Result<(), String> {
+    let path = dir.join(INDEX);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+    file.lock()
+        .map_err(|e| format!("Unable to lock '{}': {}", path.display(), e))?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)
+        .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+    let mut index = parse_index(&text, &path)?;
+    match entry {
+        Some(entry) => {
+            index.insert(key.to_string(), entry);
+        }
+        None => {
+            if let Some(removed) = index.remove(key) {
+                let _ = fs::remove_file(dir.join(removed.body));
+            }
+        }
+    }
+    let text = serde_json::to_string_pretty(&index).map_err(|e| e.to_string())?;
+    file.rewind()
+        .and_then(|_| file.set_len(0))
+        .and_then(|_| file.write_all(format!("{}\n", text).as_bytes()))
+        .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))
+}
+
+fn key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.to_string()
+}
+
+fn header(headers: &HeaderMap, name: impl reqwest::header::AsHeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
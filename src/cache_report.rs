@@ -0,0 +1,234 @@
+// --cache-report: explain how caches may treat a response.
+//
+// Follows RFC 9111: s-maxage beats max-age for shared caches, max-age beats
+// Expires, and without any explicit lifetime caches may fall back to 10% of
+// the time since Last-Modified for status codes that are cacheable by
+// default.
+
+use crate::duration::humanize;
+use reqwest::StatusCode;
+use reqwest::header::{
+    AGE, CACHE_CONTROL, DATE, ETAG, EXPIRES, HeaderMap, LAST_MODIFIED, PRAGMA, SET_COOKIE, VARY,
+};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+// Status codes caches may store without explicit freshness information.
+const HEURISTICALLY_CACHEABLE: [u16; 12] =
+    [200, 203, 204, 206, 300, 301, 308, 404, 405, 410, 414, 501];
+
+// Response headers CDNs use to say whether they served from cache.
+const CDN_HEADERS: [&str; 6] = [
+    "x-cache",
+    "cf-cache-status",
+    "x-cache-status",
+    "x-served-by",
+    "x-vercel-cache",
+    "cdn-cache-control",
+];
+
+pub fn report(status: StatusCode, headers: &HeaderMap) -> String {
+    let directives = cache_control(headers);
+    let mut lines = vec!["Cache report:".to_string()];
+
+    match headers.get(CACHE_CONTROL).and_then(|v| v.to_str().ok()) {
+        Some(cc) => lines.push(format!("  Cache-Control: {}", cc)),
+        None => lines.push("  Cache-Control: (none)".to_string()),
+    }
+
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let date = header(DATE).and_then(|d| httpdate::parse_http_date(d).ok());
+    let age = header(AGE)
+        .and_then(|a| a.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+
+    if directives.contains_key("no-store") {
+        lines.push("  No cache may store this response (no-store).".to_string());
+        return lines.join("\n");
+    }
+    if header(VARY).is_some_and(|v| v.trim() == "*") {
+        lines.push("  Vary: * makes the response effectively uncacheable.".to_string());
+        return lines.join("\n");
+    }
+
+    let private = directives.contains_key("private");
+    let max_age = seconds(&directives, "max-age");
+    let s_maxage = seconds(&directives, "s-maxage");
+    let expires = header(EXPIRES).map(|e| match (httpdate::parse_http_date(e), date) {
+        // Invalid dates such as "0" mean "already expired"
+        (Ok(at), Some(now)) => at.duration_since(now).map(|d| d.as_secs()).unwrap_or(0),
+        (Ok(at), None) => at
+            .duration_since(SystemTime::now())
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        (Err(_), _) => 0,
+    });
+    let heuristic = header(LAST_MODIFIED)
+        .and_then(|lm| httpdate::parse_http_date(lm).ok())
+        .filter(|_| HEURISTICALLY_CACHEABLE.contains(&status.as_u16()))
+        .map(|lm| {
+            date.unwrap_or_else(SystemTime::now)
+                .duration_since(lm)
+                .map(|d| d.as_secs() / 10)
+                .unwrap_or(0)
+        });
+
+    let (browser, browser_source) = match (max_age, expires, heuristic) {
+        (Some(s), _, _) => (Some(s), "max-age"),
+        (None, Some(s), _) => (Some(s), "Expires"),
+        (None, None, Some(s)) => (Some(s), "heuristic: 10% of the time since Last-Modified"),
+        _ => (None, ""),
+    };
+    let (shared, shared_source) = match s_maxage {
+        Some(s) => (Some(s), "s-maxage"),
+        None => (browser, browser_source),
+    };
+
+    let must_revalidate = directives.contains_key("no-cache")
+        || (!headers.contains_key(CACHE_CONTROL)
+            && header(PRAGMA).is_some_and(|p| p.contains("no-cache")));
+    if must_revalidate {
+        lines.push(
+            "  Caches may store it but must revalidate with the server before every use (no-cache)."
+                .to_string(),
+        );
+    }
+
+    if private {
+        lines.push("  Shared caches (CDNs, proxies): must not store it (private).".to_string());
+    } else if !must_revalidate {
+        lines.push(format!(
+            "  Shared caches (CDNs, proxies): {}",
+            lifetime(shared, shared_source, age)
+        ));
+        if !directives.contains_key("public") && headers.contains_key(SET_COOKIE) {
+            lines.push(
+                "    Note: the response sets a cookie; many shared caches won't store it without 'public'."
+                    .to_string(),
+            );
+        }
+    }
+    if !must_revalidate {
+        lines.push(format!(
+            "  Browsers (private caches): {}",
+            lifetime(browser, browser_source, age)
+        ));
+    }
+
+    if age > 0 {
+        lines.push(format!(
+            "  Age: {} (already served from a cache for that long)",
+            humanize(age)
+        ));
+    }
+
+    for (directive, meaning) in [
+        (
+            "must-revalidate",
+            "once stale, it must not be served without revalidation",
+        ),
+        (
+            "proxy-revalidate",
+            "like must-revalidate, but for shared caches only",
+        ),
+        (
+            "immutable",
+            "fresh copies are never revalidated, even on reload",
+        ),
+        ("no-transform", "intermediaries must not modify the body"),
+    ] {
+        if directives.contains_key(directive) {
+            lines.push(format!("  {}: {}", directive, meaning));
+        }
+    }
+    if let Some(s) = seconds(&directives, "stale-while-revalidate") {
+        lines.push(format!(
+            "  stale-while-revalidate: may be served stale for {} while refreshing in the background",
+            humanize(s)
+        ));
+    }
+    if let Some(s) = seconds(&directives, "stale-if-error") {
+        lines.push(format!(
+            "  stale-if-error: may be served stale for {} when the origin fails",
+            humanize(s)
+        ));
+    }
+
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    match (etag, last_modified) {
+        (None, None) => lines
+            .push("  Validators: none; stale copies must be fetched again in full.".to_string()),
+        _ => {
+            if let Some(etag) = etag {
+                lines.push(format!("  ETag: {} (revalidate with If-None-Match)", etag));
+            }
+            if let Some(lm) = last_modified {
+                lines.push(format!(
+                    "  Last-Modified: {} (revalidate with If-Modified-Since)",
+                    lm
+                ));
+            }
+        }
+    }
+
+    if let Some(vary) = header(VARY) {
+        lines.push(format!(
+            "  Vary: {} (a separate copy is kept per value of these request headers)",
+            vary
+        ));
+    }
+
+    for name in CDN_HEADERS {
+        if let Some(value) = headers.get(name).and_then(|v| v.to_str().ok()) {
+            lines.push(format!("  {}: {}", name, value));
+        }
+    }
+
+    lines.join("\n")
+}
+
+// Directive name (lowercased) -> optional argument.
+pub fn cache_control(headers: &HeaderMap) -> BTreeMap<String, Option<String>> {
+    let mut directives = BTreeMap::new();
+    for value in headers.get_all(CACHE_CONTROL) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for part in value.split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            let (name, arg) = match part.split_once('=') {
+                Some((n, a)) => (n, Some(a.trim().trim_matches('"').to_string())),
+                None => (part, None),
+            };
+            directives.insert(name.trim().to_ascii_lowercase(), arg);
+        }
+    }
+    directives
+}
+
+pub fn seconds(directives: &BTreeMap<String, Option<String>>, name: &str) -> Option<u64> {
+    directives.get(name)?.as_ref()?.parse().ok()
+}
+
+fn lifetime(secs: Option<u64>, source: &str, age: u64) -> String {
+    match secs {
+        None => "no freshness information; it is not cached.".to_string(),
+        Some(0) => format!("may store it, but it is stale immediately ({}).", source),
+        Some(s) if age == 0 => format!("may store it for {} ({}).", humanize(s), source),
+        Some(s) if age >= s => format!(
+            "may store it for {} ({}); this copy is already stale.",
+            humanize(s),
+            source
+        ),
+        Some(s) => format!(
+            "may store it for {} ({}), {} left for this copy.",
+            humanize(s),
+            source,
+            humanize(s - age)
+        ),
+    }
+}
@@ -0,0 +1,748 @@
+// The command line: its options and subcommands, and how a --profile
+// fills in what they leave unset. `run` carries out what they ask for.
+
+use crate::duration::parse_duration;
+use crate::transfer::TransferLimits;
+use crate::{
+    config, cors, diff, filter, jsondiff, jwt, monitor, negotiation, retry, s3, tls_info, url_norm,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use structopt::StructOpt;
+use url::Url;
+
+// curl's default window for --speed-limit.
+const DEFAULT_SPEED_TIME: Duration = Duration::from_secs(30);
+
+// `curl <URL>` is shorthand for `curl get <URL>`, except that a body
+// option makes it a POST. Options shared by every request are global, so
+// they go before or after the subcommand.
+#[derive(StructOpt, Clone, Debug)]
+#[structopt(name = "curl")]
+pub struct Cli {
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
+    pub url: Option<String>,
+
+    /// More URLs, fetched after the first (see --parallel)
+    pub urls: Vec<String>,
+
+    /// Also fetch the URLs listed in this file, one per line
+    #[structopt(long = "url-file", parse(from_os_str), global = true)]
+    pub url_file: Option<PathBuf>,
+
+    /// Query parameter 'name=value', percent-encoded and merged into the URL's query (repeatable)
+    #[structopt(long = "param", number_of_values = 1, global = true)]
+    pub params: Vec<String>,
+
+    /// Fill a {name} placeholder in the URL: 'name=value', encoded as one path segment (repeatable)
+    #[structopt(long = "path-var", number_of_values = 1, global = true)]
+    pub path_vars: Vec<String>,
+
+    /// Take base URL, headers, credentials, proxy and TLS options from this profile in ~/.config/web_client/config.toml
+    #[structopt(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Save this run's base URL, headers, credentials, proxy and TLS options as a profile
+    #[structopt(long = "save-session", global = true)]
+    pub save_session: Option<String>,
+
+    /// Start a prompt for sending requests, keeping a base URL, headers and credentials between them
+    #[structopt(long, conflicts_with = "save-session", global = true)]
+    pub interactive: bool,
+
+    /// With several URLs, how many to fetch at the same time (before any subcommand)
+    #[structopt(long)]
+    pub parallel: Option<usize>,
+
+    /// Request method for the implicit get (the method subcommands set their own)
+    #[structopt(short = "X", long)]
+    pub method: Option<String>,
+
+    #[structopt(flatten)]
+    pub body: BodyOpts,
+
+    /// Extra request header, e.g. 'Accept: application/xml' (repeatable)
+    #[structopt(short = "H", long, number_of_values = 1, global = true)]
+    pub header: Vec<String>,
+
+    /// Read a header value from a file at send time, e.g. 'Authorization@token.txt'
+    #[structopt(long = "header-file", number_of_values = 1, global = true)]
+    pub header_file: Vec<String>,
+
+    /// Send a cookie, e.g. 'session=abc', or load cookies from a file (repeatable)
+    #[structopt(short = "b", long = "cookie", number_of_values = 1, global = true)]
+    pub cookie: Vec<String>,
+
+    /// Cookie file to load and write back with the cookies the server sets
+    #[structopt(short = "c", long = "cookie-jar", parse(from_os_str), global = true)]
+    pub cookie_jar: Option<PathBuf>,
+
+    /// Authenticate with SPNEGO/Kerberos using the ticket cache (see kinit)
+    #[structopt(long, global = true)]
+    pub negotiate: bool,
+
+    /// Authenticate with NTLM (IIS, Exchange and other Windows servers)
+    #[structopt(long, global = true)]
+    pub ntlm: bool,
+
+    /// Authenticate with HTTP Digest, answering the server's 401 challenge
+    #[structopt(long, conflicts_with = "ntlm", global = true)]
+    pub digest: bool,
+
+    /// Server credentials as 'user:password', sent with Basic unless --digest or --ntlm; prompts if the password is omitted
+    #[structopt(short = "u", long, global = true)]
+    pub user: Option<String>,
+
+    /// Send 'Authorization: Bearer <token>'
+    #[structopt(long, conflicts_with = "bearer-file", global = true)]
+    pub bearer: Option<String>,
+
+    /// Send Accept-Language; alone, derived from the locale (value with '=': --accept-language=de)
+    #[structopt(long = "accept-language", require_equals = true, global = true)]
+    pub accept_language: Option<Option<String>>,
+
+    /// Send Accept-Encoding; alone, 'gzip, deflate'
+    #[structopt(long = "accept-encoding", require_equals = true, global = true)]
+    pub accept_encoding: Option<Option<String>>,
+
+    /// Send Accept-Charset; alone, 'utf-8, *;q=0.1'
+    #[structopt(long = "accept-charset", require_equals = true, global = true)]
+    pub accept_charset: Option<Option<String>>,
+
+    /// Read a bearer token from a file at send time
+    #[structopt(long = "bearer-file", parse(from_os_str), global = true)]
+    pub bearer_file: Option<PathBuf>,
+
+    /// Only fetch if modified since this file's mtime or HTTP date ('-' prefix: unmodified since)
+    #[structopt(
+        short = "z",
+        long = "time-cond",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    pub time_cond: Option<String>,
+
+    /// Send a literal HTTP/1.1 request from a file as-is to the URL's host
+    #[structopt(long = "raw-request", parse(from_os_str), global = true)]
+    pub raw_request: Option<PathBuf>,
+
+    /// Require AUTH TLS (explicit FTPS) on ftp:// URLs
+    #[structopt(long = "ftp-ssl", global = true)]
+    pub ftp_ssl: bool,
+
+    /// Write the response body bytes to stdout as-is, with no formatting, color or status lines
+    #[structopt(long, global = true)]
+    pub raw: bool,
+
+    /// Exit with status 22 when the server answers with an HTTP error status
+    #[structopt(short = "f", long, global = true)]
+    pub fail: bool,
+
+    /// How errors are printed: 'text' lines, or 'json' objects on stderr
+    #[structopt(long = "error-format", default_value = "text", possible_values = &["text", "json"], global = true)]
+    pub error_format: String,
+
+    /// Print the response status line and headers before the body
+    #[structopt(short = "i", long, global = true)]
+    pub include: bool,
+
+    /// With -i, only show this response header (repeatable; implies -i)
+    #[structopt(long = "show-header", number_of_values = 1, global = true)]
+    pub show_header: Vec<String>,
+
+    /// With -i, sort response headers by name (implies -i)
+    #[structopt(long = "sort-headers", global = true)]
+    pub sort_headers: bool,
+
+    /// With -i, only show headers whose 'name: value' line matches, e.g. 'x-ratelimit-*' (implies -i)
+    #[structopt(long = "header-grep", global = true)]
+    pub header_grep: Option<String>,
+
+    /// Print only this part of a JSON response: '.items[0].id', '.items[].name' or '$.items[*].id'
+    #[structopt(long, parse(try_from_str = filter::parse), global = true)]
+    pub filter: Option<jsondiff::JsonPath>,
+
+    /// Sort JSON object keys instead of keeping the order the server sent them in
+    #[structopt(long = "sort-keys", global = true)]
+    pub sort_keys: bool,
+
+    // The server's order is the default now; kept so existing scripts work
+    #[structopt(
+        long = "no-sort-keys",
+        hidden = true,
+        conflicts_with = "sort-keys",
+        global = true
+    )]
+    pub no_sort_keys: bool,
+
+    /// Only sort JSON object keys this many levels deep (1: top level only); implies --sort-keys
+    #[structopt(long = "sort-depth", conflicts_with = "no-sort-keys", global = true)]
+    pub sort_depth: Option<usize>,
+
+    /// Highlight JSON, XML and HTML bodies: auto (when stdout is a terminal and $NO_COLOR is unset), always or never
+    #[structopt(long, default_value = "auto", possible_values = &["auto", "always", "never"], global = true)]
+    pub color: String,
+
+    /// Pretty-print JSON as it arrives, keeping keys in the server's order (automatic over 8 MiB)
+    #[structopt(long = "stream-json", global = true)]
+    pub stream_json: bool,
+
+    /// Read the response as Server-Sent Events, printing each as it arrives (automatic for text/event-stream); --retry reconnects with Last-Event-ID
+    #[structopt(long, global = true)]
+    pub sse: bool,
+
+    /// Send a GraphQL operation as a JSON POST and print its data; errors in the reply fail the run (--filter applies to the data)
+    #[structopt(long, requires = "query", conflicts_with_all = &["data", "json", "data-binary", "form", "upload-file", "sse"], global = true)]
+    pub graphql: bool,
+
+    /// GraphQL document for --graphql, or @file / @- to read it from a file or stdin
+    #[structopt(long, requires = "graphql", global = true)]
+    pub query: Option<String>,
+
+    /// GraphQL variables as a JSON object, or @file / @-
+    #[structopt(long, requires = "graphql", global = true)]
+    pub variables: Option<String>,
+
+    /// Which operation of a --query document with several to run
+    #[structopt(long = "operation-name", requires = "graphql", global = true)]
+    pub operation_name: Option<String>,
+
+    /// Follow Link: rel="next" headers (or --next-field) through every page; JSON arrays, or --filter's matches, are merged into one output
+    #[structopt(long, conflicts_with_all = &["output", "remote-name", "sse", "graphql", "stream-json"], global = true)]
+    pub paginate: bool,
+
+    /// With --paginate, the JSON field holding the next page's URL, e.g. '.next_page_url'
+    #[structopt(long = "next-field", parse(try_from_str = filter::parse), requires = "paginate", global = true)]
+    pub next_field: Option<jsondiff::JsonPath>,
+
+    /// With --paginate, stop after this many pages
+    #[structopt(long = "max-pages", default_value = "100", global = true)]
+    pub max_pages: usize,
+
+    /// Keep GET responses in this directory: fresh copies are reused without a request, stale ones revalidated with If-None-Match / If-Modified-Since
+    #[structopt(long, parse(from_os_str), global = true)]
+    pub cache: Option<PathBuf>,
+
+    /// With --cache, fetch again instead of using the stored copy, and store the new response
+    #[structopt(long = "no-cache", requires = "cache", global = true)]
+    pub no_cache: bool,
+
+    /// With --cache, answer GET requests from the stored copies alone, however stale, and never send a request; a URL without one is an error
+    #[structopt(long, requires = "cache", conflicts_with = "no-cache", global = true)]
+    pub offline: bool,
+
+    /// Write the response body to this file instead of printing it
+    #[structopt(short = "o", long, parse(from_os_str), global = true)]
+    pub output: Option<PathBuf>,
+
+    /// Like -o, named after the last segment of the URL's path
+    #[structopt(
+        short = "O",
+        long = "remote-name",
+        conflicts_with = "output",
+        global = true
+    )]
+    pub remote_name: bool,
+
+    /// With -o/-O, continue a partial download from this byte, or '-' for the end of the file
+    #[structopt(
+        short = "C",
+        long = "continue-at",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    pub continue_at: Option<String>,
+
+    /// Save the parts of a multipart response into this directory
+    #[structopt(long = "save-parts", parse(from_os_str), global = true)]
+    pub save_parts: Option<PathBuf>,
+
+    /// Print connection details and the request and response headers to stderr
+    #[structopt(short = "v", long, global = true)]
+    pub verbose: bool,
+
+    /// Report the negotiated TLS version, cipher, ALPN protocol and handshake time
+    #[structopt(long = "tls-info", global = true)]
+    pub tls_info: bool,
+
+    /// Client certificate, a PKCS#12 bundle or PEM file: 'file[:password]' (prompts if needed)
+    #[structopt(long, global = true)]
+    pub cert: Option<String>,
+
+    /// Private key (PEM) for a PEM --cert (before any subcommand; jwt sign has its own --key)
+    #[structopt(long, parse(from_os_str), requires = "cert")]
+    pub key: Option<PathBuf>,
+
+    /// Trust the CA certificates in this PEM file for the server certificate
+    #[structopt(long, parse(from_os_str), global = true)]
+    pub cacert: Option<PathBuf>,
+
+    /// Don't verify the server certificate or host name
+    #[structopt(short = "k", long, global = true)]
+    pub insecure: bool,
+
+    /// Verify the server certificate even when the --profile sets insecure
+    #[structopt(long = "no-insecure", conflicts_with = "insecure", global = true)]
+    pub no_insecure: bool,
+
+    /// Check the server certificate's revocation status via OCSP; fail if revoked or unknown
+    #[structopt(long = "check-revocation", global = true)]
+    pub check_revocation: bool,
+
+    /// With --check-revocation, only warn when the status can't be determined
+    #[structopt(long = "revocation-best-effort", global = true)]
+    pub revocation_best_effort: bool,
+
+    /// Delay before racing the next address family on dual-stack hosts
+    #[structopt(
+        long = "happy-eyeballs-timeout-ms",
+        default_value = "200",
+        global = true
+    )]
+    pub happy_eyeballs_timeout_ms: u64,
+
+    /// Connect to these addresses for host:port instead of looking the host up, 'host:port:addr[,addr]' (repeatable)
+    #[structopt(long, number_of_values = 1, global = true)]
+    pub resolve: Vec<String>,
+
+    /// Send HTTP requests over this Unix domain socket instead of TCP, e.g. /var/run/docker.sock
+    #[structopt(
+        long = "unix-socket",
+        parse(from_os_str),
+        conflicts_with_all = &["proxy", "proxy-pac", "proxy-config", "alt-svc"],
+        global = true
+    )]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Read and update an Alt-Svc cache file, connecting to advertised alternatives
+    #[structopt(long = "alt-svc", parse(from_os_str), global = true)]
+    pub alt_svc: Option<PathBuf>,
+
+    /// Choose the proxy per request with a proxy auto-config (PAC) file or URL
+    #[structopt(long = "proxy-pac", global = true)]
+    pub proxy_pac: Option<String>,
+
+    /// Send requests through this HTTP or HTTPS proxy, e.g. 'proxy.example:3128'
+    #[structopt(short = "x", long, global = true)]
+    pub proxy: Option<String>,
+
+    /// Hosts, domains and CIDR blocks to reach without a proxy (overrides NO_PROXY)
+    #[structopt(long, global = true)]
+    pub noproxy: Option<String>,
+
+    /// YAML file mapping hosts to proxies
+    #[structopt(long = "proxy-config", parse(from_os_str), global = true)]
+    pub proxy_config: Option<PathBuf>,
+
+    /// Authenticate to the proxy with Basic credentials
+    #[structopt(long = "proxy-user", global = true)]
+    pub proxy_user: Option<String>,
+
+    /// Authenticate to the proxy with SPNEGO/Kerberos from the ticket cache
+    #[structopt(long = "proxy-negotiate", global = true)]
+    pub proxy_negotiate: bool,
+
+    /// Extra header for the proxy, e.g. 'X-Proxy-Token: abc' (repeatable)
+    #[structopt(long = "proxy-header", number_of_values = 1, global = true)]
+    pub proxy_header: Vec<String>,
+
+    /// Report connections opened, reused and found closed at the end of the run
+    #[structopt(long = "pool-stats", global = true)]
+    pub pool_stats: bool,
+
+    /// Maximum silence between received chunks (e.g. 10s)
+    #[structopt(long = "read-timeout", parse(try_from_str = parse_duration), global = true)]
+    pub read_timeout: Option<Duration>,
+
+    /// Maximum time to establish a connection (e.g. 5s)
+    #[structopt(long = "connect-timeout", parse(try_from_str = parse_duration), global = true)]
+    pub connect_timeout: Option<Duration>,
+
+    /// Maximum time for each attempt, from connecting to the last body byte
+    #[structopt(short = "m", long = "max-time", parse(try_from_str = parse_duration), global = true)]
+    pub max_time: Option<Duration>,
+
+    /// Retry connection errors and timeouts up to this many times
+    #[structopt(long, default_value = "0", global = true)]
+    pub retry: u32,
+
+    /// Wait before the first retry, doubling after each one (default 1s)
+    #[structopt(long = "retry-delay", parse(try_from_str = parse_duration), global = true)]
+    pub retry_delay: Option<Duration>,
+
+    /// With --retry, also retry 429 and 5xx responses, honoring Retry-After
+    #[structopt(long = "retry-http-errors", global = true)]
+    pub retry_http_errors: bool,
+
+    /// Follow redirects
+    #[structopt(short = "L", long, global = true)]
+    pub location: bool,
+
+    /// With -L, give up after this many redirects
+    #[structopt(long = "max-redirs", default_value = "50", global = true)]
+    pub max_redirs: usize,
+
+    /// Follow redirects, printing each hop's status and Location
+    #[structopt(long = "trace-redirects", global = true)]
+    pub trace_redirects: bool,
+
+    /// Maximum time an upload may stall without sending data
+    #[structopt(long = "write-timeout", parse(try_from_str = parse_duration), global = true)]
+    pub write_timeout: Option<Duration>,
+
+    /// Abort when the transfer is slower than this many bytes per second...
+    #[structopt(long = "speed-limit", global = true)]
+    pub speed_limit: Option<u64>,
+
+    /// ...for this long (default 30s)
+    #[structopt(long = "speed-time", parse(try_from_str = parse_duration), global = true)]
+    pub speed_time: Option<Duration>,
+
+    /// Fail unless the response Content-Type matches, e.g. application/json or text/*
+    #[structopt(long = "expect-content-type", global = true)]
+    pub expect_content_type: Option<String>,
+
+    /// Fail when the request takes longer than this (e.g. 500ms)
+    #[structopt(long = "max-response-time", parse(try_from_str = parse_duration), global = true)]
+    pub max_response_time: Option<Duration>,
+
+    /// Only warn when the request takes longer than this
+    #[structopt(long = "warn-response-time", parse(try_from_str = parse_duration), global = true)]
+    pub warn_response_time: Option<Duration>,
+
+    /// Compare the response body against a golden file, creating it on first run
+    #[structopt(long, parse(from_os_str), global = true)]
+    pub snapshot: Option<PathBuf>,
+
+    /// Overwrite the --snapshot file with the current response
+    #[structopt(long = "snapshot-update", global = true)]
+    pub snapshot_update: bool,
+
+    /// JSON path left out of snapshot comparisons, e.g. '$.meta.timestamp'
+    #[structopt(long = "ignore-path", number_of_values = 1, parse(try_from_str = jsondiff::parse_path), global = true)]
+    pub ignore_path: Vec<jsondiff::JsonPath>,
+
+    /// Explain how browsers and shared caches may cache the response
+    #[structopt(long = "cache-report", global = true)]
+    pub cache_report: bool,
+
+    /// Review the response's Set-Cookie headers for missing protections
+    #[structopt(long = "cookie-audit", global = true)]
+    pub cookie_audit: bool,
+
+    /// Grade the response's security headers (CSP, HSTS, framing, ...) out of 100
+    #[structopt(long = "security-audit", global = true)]
+    pub security_audit: bool,
+
+    /// Fail when the --security-audit score is below this
+    #[structopt(long = "min-security-score", global = true)]
+    pub min_security_score: Option<u32>,
+
+    /// Output format for audit reports
+    #[structopt(long = "audit-format", default_value = "text", possible_values = &["text", "json"], global = true)]
+    pub audit_format: String,
+
+    /// Write the request's checks as a JUnit XML report
+    #[structopt(long = "report-junit", parse(from_os_str), global = true)]
+    pub report_junit: Option<PathBuf>,
+
+    /// Hard limit for the whole operation, including retries and redirects
+    #[structopt(long, parse(try_from_str = parse_duration), global = true)]
+    pub deadline: Option<Duration>,
+
+    /// After the transfer, print this template with %{variable}s filled in (or @file)
+    #[structopt(short = "w", long = "write-out", global = true)]
+    pub write_out: Option<String>,
+
+    /// Record the request and response as an HTTP Archive (HAR 1.2) in this file
+    #[structopt(long, parse(from_os_str), global = true)]
+    pub har: Option<PathBuf>,
+
+    /// Send the request repeatedly and report throughput and latency (see -n, --concurrency)
+    #[structopt(long, global = true)]
+    pub bench: bool,
+
+    /// With --bench, how many requests to send (default 200)
+    #[structopt(short = "n", long, requires = "bench", global = true)]
+    pub requests: Option<usize>,
+
+    /// With --bench, how many requests are in flight at once (default 50)
+    #[structopt(long, requires = "bench", global = true)]
+    pub concurrency: Option<usize>,
+}
+
+// Request body options, for the implicit get and the methods that take one.
+#[derive(StructOpt, Clone, Debug, Default)]
+pub struct BodyOpts {
+    /// Form data 'a=1&b=2', or @file / @- to read it from a file or stdin (line breaks dropped)
+    #[structopt(short = "d", long)]
+    pub data: Option<String>,
+
+    /// JSON body, or @file / @- to read it from a file or stdin
+    #[structopt(long)]
+    pub json: Option<String>,
+
+    /// Body sent exactly as given: a string, @file (streamed) or @- for stdin
+    #[structopt(long = "data-binary", conflicts_with_all = &["data", "json", "upload-file"])]
+    pub data_binary: Option<String>,
+
+    /// Template variable for the body, 'name=value' or 'name:=json' (repeatable)
+    #[structopt(long = "var", number_of_values = 1)]
+    pub vars: Vec<String>,
+
+    /// Upload a local file (PUT for HTTP, STOR for FTP)
+    #[structopt(short = "T", long = "upload-file", parse(from_os_str))]
+    pub upload_file: Option<PathBuf>,
+
+    /// Multipart form field: 'name=value', 'name=@file' or 'name=<file', with optional ';type=' (repeatable)
+    #[structopt(short = "F", long, number_of_values = 1, conflicts_with_all = &["data", "json", "data-binary", "upload-file"])]
+    pub form: Vec<String>,
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct UrlArgs {
+    pub url: String,
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct BodyArgs {
+    pub url: String,
+
+    #[structopt(flatten)]
+    pub body: BodyOpts,
+}
+
+impl Cli {
+    pub fn limits(&self) -> TransferLimits {
+        // Like curl, --speed-time alone means "abort if nothing at all moves"
+        let speed_limit = match (self.speed_limit, self.speed_time) {
+            (None, Some(_)) => Some(1),
+            (limit, _) => limit,
+        };
+        TransferLimits {
+            read: self.read_timeout,
+            write: self.write_timeout,
+            speed_limit,
+            speed_time: self.speed_time.unwrap_or(DEFAULT_SPEED_TIME),
+        }
+    }
+
+    pub fn trust(&self) -> tls_info::Trust<'_> {
+        tls_info::Trust {
+            insecure: self.insecure,
+            ca_file: self.cacert.as_deref(),
+        }
+    }
+
+    pub fn retry_policy(&self) -> retry::Policy {
+        retry::Policy {
+            retries: self.retry,
+            delay: self.retry_delay.unwrap_or(retry::DEFAULT_DELAY),
+            http_errors: self.retry_http_errors,
+        }
+    }
+
+    // The -C offset, resolved to a byte count in `run`.
+    pub fn resume_from(&self) -> u64 {
+        self.continue_at
+            .as_deref()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn negotiation(&self) -> negotiation::Preferences {
+        negotiation::Preferences {
+            language: self.accept_language.clone(),
+            encoding: self.accept_encoding.clone(),
+            charset: self.accept_charset.clone(),
+        }
+    }
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub enum Command {
+    /// GET a URL (what `curl <URL>` does)
+    Get(UrlArgs),
+    /// Send a HEAD request
+    Head(UrlArgs),
+    /// Send an OPTIONS request
+    Options(UrlArgs),
+    /// POST a body given with -d, --json or -T
+    Post(BodyArgs),
+    /// PUT a body given with -d, --json or -T
+    Put(BodyArgs),
+    /// PATCH with a body given with -d, --json or -T
+    Patch(BodyArgs),
+    /// Send a DELETE request, optionally with a body
+    Delete(BodyArgs),
+    #[structopt(flatten)]
+    Tool(Tool),
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub enum Tool {
+    /// S3 helpers (presigned URLs, multipart uploads)
+    S3(s3::S3Command),
+    /// Simulate a browser CORS check (preflight and Access-Control-* rules)
+    Cors(cors::CorsCommand),
+    /// Compare two responses or files, structurally when both are JSON
+    Diff(diff::DiffCommand),
+    /// Run scheduled uptime checks from a YAML config
+    Monitor(monitor::MonitorCommand),
+    /// Decode JWTs or sign test tokens
+    Jwt(jwt::JwtCommand),
+    /// URL helpers (canonical form of internationalized URLs)
+    Url(url_norm::UrlCommand),
+}
+
+impl Command {
+    // The method, URL and body of a method subcommand.
+    pub fn into_request(self) -> Result<(&'static str, String, BodyOpts), Tool> {
+        let no_body = BodyOpts::default;
+        Ok(match self {
+            Command::Get(r) => ("GET", r.url, no_body()),
+            Command::Head(r) => ("HEAD", r.url, no_body()),
+            Command::Options(r) => ("OPTIONS", r.url, no_body()),
+            Command::Post(r) => ("POST", r.url, r.body),
+            Command::Put(r) => ("PUT", r.url, r.body),
+            Command::Patch(r) => ("PATCH", r.url, r.body),
+            Command::Delete(r) => ("DELETE", r.url, r.body),
+            Command::Tool(tool) => return Err(tool),
+        })
+    }
+}
+
+// ---------------- PROFILES ----------------
+
+// Fill in what the command line left unset.
+pub fn apply_profile(args: &mut Cli, profile: &config::Profile) {
+    let given: Vec<String> = args
+        .header
+        .iter()
+        .filter_map(|h| h.split_once(':'))
+        .map(|(name, _)| name.trim().to_ascii_lowercase())
+        .collect();
+    let defaults = profile
+        .headers
+        .iter()
+        .filter(|(name, _)| !given.contains(&name.to_ascii_lowercase()))
+        .map(|(name, value)| format!("{}: {}", name, value));
+    args.header = defaults.chain(args.header.drain(..)).collect();
+
+    if args.user.is_none() && args.bearer.is_none() && args.bearer_file.is_none() {
+        args.user.clone_from(&profile.user);
+        args.bearer.clone_from(&profile.bearer);
+    }
+    fill(&mut args.proxy, &profile.proxy);
+    fill(&mut args.proxy_user, &profile.proxy_user);
+    fill(&mut args.noproxy, &profile.noproxy);
+    args.insecure = !args.no_insecure && (args.insecure || profile.insecure);
+    fill(&mut args.cacert, &profile.cacert);
+    fill(&mut args.cert, &profile.cert);
+    fill(&mut args.key, &profile.key);
+}
+
+fn fill<T: Clone>(option: &mut Option<T>, default: &Option<T>) {
+    if option.is_none() {
+        option.clone_from(default);
+    }
+}
+
+// What --save-session stores: the profile's base URL if this run used one,
+// else the origin of the URL fetched. Secret references are kept as written.
+pub fn session_profile(
+    args: &Cli,
+    profile: Option<&config::Profile>,
+    url: Option<&String>,
+) -> config::Profile {
+    let base_url = profile.and_then(|p| p.base_url.clone()).or_else(|| {
+        let url = Url::parse(url?).ok()?;
+        url.has_host().then(|| url.origin().ascii_serialization())
+    });
+    config::Profile {
+        base_url,
+        headers: args
+            .header
+            .iter()
+            .filter_map(|h| h.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect(),
+        user: args.user.clone(),
+        bearer: args.bearer.clone(),
+        proxy: args.proxy.clone(),
+        proxy_user: args.proxy_user.clone(),
+        noproxy: args.noproxy.clone(),
+        insecure: args.insecure,
+        cacert: args.cacert.clone(),
+        cert: args.cert.clone(),
+        key: args.key.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(argv: &[&str]) -> Cli {
+        Cli::from_iter(["curl"].iter().chain(argv))
+    }
+
+    fn profile() -> config::Profile {
+        config::Profile {
+            headers: [("Accept", "text/plain"), ("X-Env", "staging")]
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            user: Some("profile:secret".to_string()),
+            bearer: Some("profile-token".to_string()),
+            insecure: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cli_header_replaces_the_profile_header_of_that_name() {
+        let mut args = cli(&["-H", "accept: application/json", "http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert_eq!(args.header, ["X-Env: staging", "accept: application/json"]);
+    }
+
+    #[test]
+    fn profile_credentials_apply_when_none_are_given() {
+        let mut args = cli(&["http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert_eq!(args.user.as_deref(), Some("profile:secret"));
+        assert_eq!(args.bearer.as_deref(), Some("profile-token"));
+    }
+
+    #[test]
+    fn cli_credentials_replace_both_profile_credentials() {
+        let mut args = cli(&["-u", "me:pw", "http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert_eq!(args.user.as_deref(), Some("me:pw"));
+        assert_eq!(args.bearer, None);
+
+        let mut args = cli(&["--bearer", "mine", "http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert_eq!(args.user, None);
+        assert_eq!(args.bearer.as_deref(), Some("mine"));
+
+        let mut args = cli(&["--bearer-file", "/tmp/token", "http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert_eq!(args.user, None);
+        assert_eq!(args.bearer, None);
+    }
+
+    #[test]
+    fn profile_insecure_can_be_turned_off() {
+        let mut args = cli(&["http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert!(args.insecure);
+
+        let mut args = cli(&["--no-insecure", "http://h/"]);
+        apply_profile(&mut args, &profile());
+        assert!(!args.insecure);
+    }
+}
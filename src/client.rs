@@ -0,0 +1,550 @@
+// WebClient: send a request described by a RequestSpec and get a
+// ResponseReport back, with the command line's URL preparation (IDN hosts,
+// percent-encoding), transfer limits, Content-Encoding decoding and JSON
+// formatting, but no output of its own.
+//
+//   let client = WebClient::new(ClientOptions::default())?;
+//   let spec = RequestSpec::new(Method::Get, "https://example.com/items")
+//       .header("Accept", "application/json");
+//   let report = client.send(&spec)?;
+//   println!("{} in {:?}\n{}", report.status, report.elapsed, report.pretty_body());
+//
+// URLs of schemes other than http and https go to the handlers of the
+// client's SchemeRegistry (see schemes.rs), which serve them or rewrite
+// them to http(s).
+//
+// The command line sends through the same client: `request` and `execute`
+// for requests it streams and prints itself, with the TLS, proxy, redirect
+// and timeout settings of ClientOptions applied the same way to both. The
+// URLs of a multi-URL run share one client, and with it its connections.
+
+use crate::client_cert;
+use crate::deadline;
+use crate::exit::{self, Category};
+use crate::format::{self, Style};
+use crate::headers;
+use crate::method::Method;
+use crate::pool_stats::PoolStats;
+use crate::proxy;
+use crate::retry;
+use crate::schemes::{Outcome, SchemeRegistry, TransferOptions};
+use crate::transfer::{self, TransferLimits, UploadError};
+use crate::url_norm;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Certificate, Proxy, redirect};
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+// Told of each redirect followed: the hop number, the status that
+// redirected, and the URLs it went from and to. It's called on the thread
+// that sent the request, once the response (or the error) is in.
+pub type RedirectObserver = Arc<dyn Fn(usize, u16, &Url, &Url) + Send + Sync>;
+
+// A redirect followed by a request that started at `origin`. reqwest
+// follows redirects on a thread of its own, so the policy only logs them.
+struct Hop {
+    origin: Url,
+    hop: usize,
+    status: u16,
+    from: Url,
+    to: Url,
+}
+
+#[derive(Clone, Default)]
+pub struct ClientOptions {
+    // For the whole request, body included
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    // Follow at most this many redirects, and fail past that or on a loop;
+    // None returns 3xx responses as they are, like the command line
+    // without -L
+    pub redirects: Option<usize>,
+    pub on_redirect: Option<RedirectObserver>,
+    // Skip verifying the server certificate and host name (-k)
+    pub insecure: bool,
+    // A PEM bundle of CAs trusted besides the system's
+    pub cacert: Option<PathBuf>,
+    // A client certificate, with its key when that is a file of its own
+    pub cert: Option<String>,
+    pub key: Option<PathBuf>,
+    // Hosts connected to at these addresses instead of being looked up
+    pub addresses: Vec<(String, Vec<SocketAddr>)>,
+    // Which proxy each request goes through, what to authenticate to it
+    // with, and the "user:password" whose rejection a 407 explains
+    pub proxy: Option<Arc<proxy::Router>>,
+    pub proxy_authorization: Option<HeaderValue>,
+    pub proxy_user: Option<String>,
+    // No proxy, not even one from the environment, and a new connection
+    // for every request
+    pub direct: bool,
+    pub limits: TransferLimits,
+    // Where the connections responses came over are counted (--pool-stats)
+    pub pool_stats: Option<Arc<PoolStats>>,
+    // Handlers for the schemes besides http and https; None for those
+    // compiled into this build
+    pub schemes: Option<Arc<SchemeRegistry>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Body {
+    Json(Value),
+    // Sent as application/x-www-form-urlencoded
+    Form(Vec<(String, String)>),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+pub struct RequestSpec {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Body>,
+}
+
+impl RequestSpec {
+    pub fn new(method: Method, url: &str) -> Self {
+        RequestSpec {
+            method,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+}
+
+pub struct ResponseReport {
+    // 0 when a scheme handler served the URL without HTTP
+    pub status: u16,
+    // Where the response came from, after any redirects
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    // Decoded if the server applied a Content-Encoding
+    pub body: Vec<u8>,
+    // From sending the request to the end of the body
+    pub elapsed: Duration,
+}
+
+impl ResponseReport {
+    pub fn is_success(&self) -> bool {
+        self.status == 0 || (200..300).contains(&self.status)
+    }
+
+    // The first header with this name, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // The body as text, in the charset its Content-Type names.
+    pub fn text(&self) -> String {
+        transfer::decode_text(&self.body, self.header("content-type"))
+    }
+
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_str(&self.text()).ok()
+    }
+
+    // The body as the command line prints it in this style.
+    pub fn pretty(&self, style: Style) -> String {
+        format::body(&self.text(), self.header("content-type"), style).text
+    }
+
+    // JSON with sorted keys, anything else as text.
+    pub fn pretty_body(&self) -> String {
+        self.pretty(Style {
+            sort_depth: usize::MAX,
+            ..Style::default()
+        })
+    }
+}
+
+pub struct WebClient {
+    client: Client,
+    options: ClientOptions,
+    hops: Arc<Mutex<Vec<Hop>>>,
+    // This client's connection pool, for --pool-stats
+    pool: usize,
+    schemes: Arc<SchemeRegistry>,
+}
+
+impl WebClient {
+    pub fn new(options: ClientOptions) -> Result<WebClient, exit::Error> {
+        let hops = Arc::default();
+        let mut builder = Client::builder().redirect(redirect_policy(&options, &hops));
+        if let Some(limit) = options.timeout {
+            builder = builder.timeout(limit);
+        }
+        if let Some(limit) = options.connect_timeout {
+            builder = builder.connect_timeout(limit);
+        }
+        // A later entry for a host replaces an earlier one
+        for (host, addrs) in &options.addresses {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+        if let Some(cert) = &options.cert {
+            builder = builder.identity(client_cert::load(cert, options.key.as_deref())?);
+        }
+        if let Some(path) = &options.cacert {
+            let pem = fs::read(path)
+                .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .ok()
+                .filter(|certs| !certs.is_empty())
+                .ok_or_else(|| format!("'{}' holds no PEM certificates.", path.display()))?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if options.insecure {
+            builder = builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+        if options.direct {
+            builder = builder.no_proxy().pool_max_idle_per_host(0);
+        } else if let Some(router) = options.proxy.clone() {
+            let mut proxy = Proxy::custom(move |url| router.route(url));
+            if let Some(auth) = options.proxy_authorization.clone() {
+                proxy = proxy.custom_http_auth(auth);
+            }
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("Unable to set up the HTTP client: {}", e))?;
+        let pool = options.pool_stats.as_ref().map_or(0, |stats| stats.pool());
+        let schemes = options
+            .schemes
+            .clone()
+            .unwrap_or_else(|| Arc::new(SchemeRegistry::with_defaults()));
+        Ok(WebClient {
+            client,
+            options,
+            hops,
+            pool,
+            schemes,
+        })
+    }
+
+    pub fn schemes(&self) -> &SchemeRegistry {
+        &self.schemes
+    }
+
+    // Hand a URL of another scheme to its handler, which may send requests
+    // of its own through this client. Returns what the handler did, or the
+    // http(s) URL it came down to.
+    pub fn dispatch(&self, url: &Url, opts: &TransferOptions) -> Result<Outcome, String> {
+        self.schemes.dispatch(url, opts, self)
+    }
+
+    pub fn limits(&self) -> &TransferLimits {
+        &self.options.limits
+    }
+
+    // A request to finish and hand to `execute`. However long the timeout,
+    // it never runs past the --deadline.
+    pub fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let req = self.client.request(method.to_reqwest(), url);
+        match (self.options.timeout, deadline::remaining()) {
+            (Some(limit), Some(left)) => req.timeout(limit.min(left)),
+            (None, Some(left)) => req.timeout(left),
+            (_, None) => req,
+        }
+    }
+
+    pub fn execute(&self, req: RequestBuilder) -> reqwest::Result<Response> {
+        let req = req.build()?;
+        let origin = req.url().clone();
+        let res = self.client.execute(req);
+        self.report_redirects(&origin);
+        self.count(res.as_ref().ok());
+        res
+    }
+
+    // `execute` for a body streamed from `source`, which fails once the
+    // upload stops making progress for longer than the write timeout.
+    pub fn upload<R: Read + Send + 'static>(
+        &self,
+        req: RequestBuilder,
+        source: R,
+        len: u64,
+    ) -> Result<Response, UploadError> {
+        let req = req.build().map_err(UploadError::Send)?;
+        let origin = req.url().clone();
+        let res = transfer::send_upload(&self.client, req, source, len, &self.options.limits);
+        self.report_redirects(&origin);
+        self.count(res.as_ref().ok());
+        res
+    }
+
+    fn count(&self, res: Option<&Response>) {
+        if let (Some(stats), Some(res)) = (&self.options.pool_stats, res) {
+            stats.record(self.pool, res);
+        }
+    }
+
+    // Tell the observer of the redirects the request from `origin` followed.
+    // Requests from other threads to the same URL can't be told apart, but
+    // then follow the same redirects.
+    fn report_redirects(&self, origin: &Url) {
+        let hops: Vec<Hop> = {
+            let mut hops = self.hops.lock().unwrap();
+            let (mine, others) = hops.drain(..).partition(|h| h.origin == *origin);
+            *hops = others;
+            mine
+        };
+        if let Some(observer) = &self.options.on_redirect {
+            for h in hops {
+                observer(h.hop, h.status, &h.from, &h.to);
+            }
+        }
+    }
+
+    // Why a request got no response, and whether trying again may help.
+    // `generic` is the message for a failure with no more specific one.
+    pub fn failure(&self, e: &reqwest::Error, generic: &str) -> retry::Failure {
+        if e.is_connect()
+            && e.is_timeout()
+            && let Some(limit) = self.options.connect_timeout
+        {
+            return retry::Failure {
+                message: format!(
+                    "No connection to the server within {} (--connect-timeout).",
+                    transfer::describe(limit)
+                ),
+                transient: Some("connect timeout"),
+                category: Category::Timeout,
+            };
+        }
+        if e.is_redirect() {
+            let cause = e
+                .source()
+                .map_or_else(|| "too many redirects".to_string(), |s| s.to_string());
+            return retry::Failure {
+                message: format!("Stopped following redirects: {}.", cause),
+                transient: None,
+                category: Category::Failed,
+            };
+        }
+        if e.is_timeout() {
+            let message = match self.options.timeout {
+                Some(limit) => format!(
+                    "The request took longer than {} (--max-time).",
+                    transfer::describe(limit)
+                ),
+                None => "The request timed out.".to_string(),
+            };
+            return retry::Failure {
+                message,
+                transient: Some("timed out"),
+                category: Category::Timeout,
+            };
+        }
+        let transient = if e.is_connect() {
+            Some("connection failed")
+        } else if connection_reset(e) {
+            Some("connection reset")
+        } else {
+            None
+        };
+        retry::Failure {
+            message: self.send_failure(e, generic),
+            transient,
+            category: exit::send_category(e),
+        }
+    }
+
+    pub fn upload_failure(&self, e: UploadError) -> retry::Failure {
+        match e {
+            UploadError::Send(e) => self.failure(&e, "Unable to connect to the server."),
+            UploadError::Stalled(message) => retry::Failure {
+                message,
+                transient: Some("upload stalled"),
+                category: Category::Timeout,
+            },
+            UploadError::Aborted(message) => retry::Failure {
+                message,
+                transient: None,
+                category: Category::Failed,
+            },
+        }
+    }
+
+    // What a 407 from the proxy means for the credentials given.
+    pub fn proxy_auth_failure(&self, challenges: &[String]) -> String {
+        let credentials = self
+            .options
+            .proxy_user
+            .as_deref()
+            .map(proxy::Credentials::parse);
+        proxy::explain_407(challenges, credentials.as_ref())
+    }
+
+    // A CONNECT tunnel refused with 407 only shows up as an error message
+    // somewhere down the error's source chain.
+    fn send_failure(&self, e: &reqwest::Error, generic: &str) -> String {
+        let mut source: Option<&dyn Error> = Some(e);
+        while let Some(err) = source {
+            match err.to_string().as_str() {
+                "proxy authentication required" => return self.proxy_auth_failure(&[]),
+                "unsuccessful tunnel" => {
+                    return "The proxy refused to open a CONNECT tunnel to the server.".to_string();
+                }
+                msg if msg.contains("certificate verify failed") => {
+                    return "The server certificate couldn't be verified; trust its CA with --cacert, or skip the check with -k.".to_string();
+                }
+                _ => {}
+            }
+            source = err.source();
+        }
+        generic.to_string()
+    }
+
+    // The error's category says what failed, as in the command line's
+    // exit codes.
+    pub fn send(&self, spec: &RequestSpec) -> Result<ResponseReport, exit::Error> {
+        if spec.body.is_some() && !spec.method.allows_body() {
+            return Err(format!("{} requests don't take a body.", spec.method).into());
+        }
+        let invalid = |e: String| exit::Error::new(Category::Url, e);
+        let prepared = url_norm::prepare(&spec.url).map_err(invalid)?;
+        let url = Url::parse(&prepared)
+            .map_err(|e| invalid(format!("Invalid URL '{}': {}", spec.url, e)))?;
+        // Other schemes are served, or rewritten to http(s), by their handler
+        let started = Instant::now();
+        let opts = TransferOptions {
+            upload: None,
+            ftp_ssl: false,
+            limits: self.options.limits,
+        };
+        let body = match self.dispatch(&url, &opts)? {
+            Outcome::Rewrite(url) => Ok(url),
+            Outcome::Body(body) => Err(body),
+            Outcome::Done(message) => Err(message.into_bytes()),
+        };
+        let url = match body {
+            Ok(url) => url,
+            Err(_) if spec.body.is_some() => {
+                return Err(format!("{}:// URLs don't take a body.", url.scheme()).into());
+            }
+            Err(body) => {
+                return Ok(ResponseReport {
+                    status: 0,
+                    url: url.to_string(),
+                    headers: Vec::new(),
+                    body,
+                    elapsed: started.elapsed(),
+                });
+            }
+        };
+
+        let mut map = HeaderMap::new();
+        for (name, value) in &spec.headers {
+            let name = headers::name(name)?;
+            let value = headers::value(&name, value)?;
+            map.append(name, value);
+        }
+        let mut req = self.request(spec.method, url).headers(map);
+        req = match &spec.body {
+            Some(Body::Json(value)) => req.json(value),
+            Some(Body::Form(pairs)) => req.form(pairs),
+            Some(Body::Bytes(bytes)) => req.body(bytes.clone()),
+            None => req,
+        };
+
+        let res = self.execute(req).map_err(|e| {
+            let failure = self.failure(&e, "Unable to connect to the server.");
+            exit::Error::new(failure.category, failure.message)
+        })?;
+        let status = res.status().as_u16();
+        let url = res.url().to_string();
+        let headers: Vec<(String, String)> = res
+            .headers()
+            .iter()
+            .map(|(n, v)| {
+                (
+                    n.to_string(),
+                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let (body, _) = transfer::read_decoded(res, &self.options.limits)?;
+
+        Ok(ResponseReport {
+            status,
+            url,
+            headers,
+            body,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+// reqwest applies the method and header rules for each hop; the policy
+// counts hops, stops loops and logs the hops for the observer.
+fn redirect_policy(options: &ClientOptions, hops: &Arc<Mutex<Vec<Hop>>>) -> redirect::Policy {
+    let Some(max) = options.redirects else {
+        return redirect::Policy::none();
+    };
+    let log = options.on_redirect.is_some().then(|| hops.clone());
+    redirect::Policy::custom(move |attempt| {
+        let hop = attempt.previous().len();
+        if attempt.previous().contains(attempt.url()) {
+            let looped = attempt.url().to_string();
+            return attempt.error(format!("redirect loop back to {}", looped));
+        }
+        if hop > max {
+            return attempt.error(format!("more than {} redirects", max));
+        }
+        if let Some(log) = &log
+            && let (Some(origin), Some(from)) =
+                (attempt.previous().first(), attempt.previous().last())
+        {
+            log.lock().unwrap().push(Hop {
+                origin: origin.clone(),
+                hop,
+                status: attempt.status().as_u16(),
+                from: from.clone(),
+                to: attempt.url().clone(),
+            });
+        }
+        attempt.follow()
+    })
+}
+
+// The server or something in between dropped the connection mid-request.
+fn connection_reset(e: &reqwest::Error) -> bool {
+    let mut source: Option<&dyn Error> = Some(e);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<io::Error>()
+            && matches!(
+                io.kind(),
+                io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+            )
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
@@ -0,0 +1,112 @@
+// --cert: TLS client certificates from PKCS#12 (.p12/.pfx) bundles or PEM
+// files.
+//
+// The password follows the file name after a colon, as in curl
+// ("client.p12:secret"); without one it's asked for on the terminal. A
+// path that exists as written is never split, so file names containing
+// colons keep working.
+//
+// A PEM certificate takes its private key from --key, or from the same
+// file when both are in it. The key may be PKCS#1, SEC1 or PKCS#8, and the
+// password only matters when the key is encrypted.
+
+use crate::prompt;
+use openssl::pkey::{PKey, Private};
+use reqwest::Identity;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub fn load(spec: &str, key: Option<&Path>) -> Result<Identity, String> {
+    let (path, password) = match spec.split_once(':') {
+        Some((path, password)) if !Path::new(spec).exists() && !is_drive_letter(path) => {
+            (path, Some(password.to_string()))
+        }
+        _ => (spec, None),
+    };
+    let der = fs::read(path).map_err(|e| format!("Unable to read '{}': {}", path, e))?;
+    if key.is_some() || is_pem(&der) {
+        return load_pem(path, &der, key, password);
+    }
+    let password = match password {
+        Some(p) => p,
+        None => prompt::password(&format!("Password for '{}': ", path))?,
+    };
+
+    Identity::from_pkcs12_der(&der, &password).map_err(|e| {
+        let mut detail = e.to_string();
+        let mut source = e.source();
+        while let Some(err) = source {
+            detail = err.to_string();
+            source = err.source();
+        }
+        if detail.contains("mac verify failure") {
+            format!("Wrong password for the certificate bundle '{}'.", path)
+        } else if detail.contains("unsupported") {
+            // OpenSSL 3 only reads RC2/3DES-encrypted bundles with its legacy provider
+            format!(
+                "'{}' uses an outdated encryption OpenSSL no longer reads; re-export it with AES (openssl pkcs12 -export ...).",
+                path
+            )
+        } else {
+            format!("'{}' is not a valid PKCS#12 bundle: {}", path, detail)
+        }
+    })
+}
+
+fn load_pem(
+    path: &str,
+    cert: &[u8],
+    key: Option<&Path>,
+    password: Option<String>,
+) -> Result<Identity, String> {
+    let (key_path, key_pem) = match key {
+        Some(key) => (
+            key.display().to_string(),
+            fs::read(key).map_err(|e| format!("Unable to read '{}': {}", key.display(), e))?,
+        ),
+        None => (path.to_string(), cert.to_vec()),
+    };
+    if !String::from_utf8_lossy(&key_pem).contains("PRIVATE KEY-----") {
+        return Err(format!(
+            "'{}' holds no private key; pass it with --key.",
+            key_path
+        ));
+    }
+
+    let private_key = private_key(&key_path, &key_pem, password)?;
+    // native-tls wants the key as PKCS#8
+    let pkcs8 = private_key
+        .private_key_to_pem_pkcs8()
+        .map_err(|e| e.to_string())?;
+    Identity::from_pkcs8_pem(cert, &pkcs8)
+        .map_err(|e| format!("'{}' is not a valid PEM certificate: {}", path, e))
+}
+
+fn private_key(path: &str, pem: &[u8], password: Option<String>) -> Result<PKey<Private>, String> {
+    let encrypted = String::from_utf8_lossy(pem).contains("ENCRYPTED");
+    let result = match password {
+        Some(p) => PKey::private_key_from_pem_passphrase(pem, p.as_bytes()),
+        None if encrypted => {
+            let p = prompt::password(&format!("Password for '{}': ", path))?;
+            PKey::private_key_from_pem_passphrase(pem, p.as_bytes())
+        }
+        None => PKey::private_key_from_pem(pem),
+    };
+    result.map_err(|_| {
+        if encrypted {
+            format!("Wrong password for the private key in '{}'.", path)
+        } else {
+            format!("'{}' has no private key OpenSSL can read.", path)
+        }
+    })
+}
+
+fn is_pem(data: &[u8]) -> bool {
+    String::from_utf8_lossy(data).contains("-----BEGIN ")
+}
+
+// "C:\certs\client.p12" has a colon without carrying a password.
+fn is_drive_letter(path: &str) -> bool {
+    path.len() == 1 && path.chars().all(|c| c.is_ascii_alphabetic())
+}
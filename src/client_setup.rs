@@ -0,0 +1,320 @@
+// The client a command-line request goes through: ClientOptions from the
+// options, name resolution, proxies and the TLS checks made before the
+// request is sent.
+
+use crate::cli::Cli;
+use crate::exit::Category;
+use crate::pool_stats::PoolStats;
+use crate::{ClientOptions, RedirectObserver, errln};
+use crate::{altsvc, dns, exit, har, negotiate, output, proxy, revocation, tls_info, writeout};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, PROXY_AUTHORIZATION};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+// How long a --write-out connect probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// The options of the command line that hold for every request: TLS,
+// timeouts, redirects, --resolve pins and transfer limits.
+pub fn base_options(args: &Cli) -> Result<ClientOptions, exit::Error> {
+    let mut addresses = Vec::new();
+    for spec in &args.resolve {
+        let pin = dns::Pin::parse(spec)?;
+        addresses.push((pin.host.clone(), pin.socket_addrs()));
+    }
+    Ok(ClientOptions {
+        timeout: args.max_time,
+        connect_timeout: args.connect_timeout,
+        // Redirects are only followed with -L
+        redirects: (args.location || args.trace_redirects).then_some(args.max_redirs),
+        on_redirect: Some(redirect_observer(args)),
+        insecure: args.insecure,
+        cacert: args.cacert.clone(),
+        cert: args.cert.clone(),
+        key: args.key.clone(),
+        addresses,
+        proxy_user: args.proxy_user.clone(),
+        limits: args.limits(),
+        ..ClientOptions::default()
+    })
+}
+
+// Hostnames are resolved here rather than inside reqwest so resolution
+// failures get a precise message and verbose mode can show the addresses.
+// With an Alt-Svc alternative, the origin's name is pinned to the
+// alternative host's addresses.
+pub fn client_options(
+    url: &Url,
+    alternative: Option<&altsvc::Target>,
+    router: Option<Arc<proxy::Router>>,
+    args: &Cli,
+    pool_stats: Option<&Arc<PoolStats>>,
+) -> Result<ClientOptions, exit::Error> {
+    let mut options = base_options(args)?;
+    let pins = args
+        .resolve
+        .iter()
+        .map(|spec| dns::Pin::parse(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(url::Host::Domain(host)) = url.host() {
+        let port = url.port_or_known_default().unwrap_or(80);
+        let lookup = alternative.map_or(host, |alt| alt.host.as_str());
+        let pinned = pins.iter().rev().find(|pin| pin.matches(lookup, port));
+        let began = Instant::now();
+        let resolved = match pinned {
+            Some(pin) => Ok(dns::Resolution {
+                host: lookup.to_string(),
+                addrs: pin.socket_addrs(),
+                elapsed: Duration::ZERO,
+            }),
+            None => dns::resolve(lookup, port),
+        };
+        match resolved {
+            Ok(resolution) => {
+                writeout::namelookup(began.elapsed());
+                har::namelookup(began.elapsed());
+                if args.verbose && pinned.is_some() {
+                    let ips: Vec<String> = resolution
+                        .addrs
+                        .iter()
+                        .map(|a| a.ip().to_string())
+                        .collect();
+                    errln!(
+                        "* Using {} for {}:{} (--resolve)",
+                        ips.join(", "),
+                        lookup,
+                        port
+                    );
+                } else if args.verbose {
+                    errln!("* {}", resolution.describe());
+                }
+                let mut addrs = resolution.addrs;
+                if dns::is_dual_stack(&addrs) {
+                    let delay = Duration::from_millis(args.happy_eyeballs_timeout_ms);
+                    // Put the address that won the race first so a broken
+                    // IPv6 path never stalls the real connection
+                    if let Some(winner) = dns::race(&addrs, delay) {
+                        if args.verbose {
+                            errln!("* Happy Eyeballs: {} connected first", winner.ip());
+                        }
+                        addrs.retain(|a| *a != winner);
+                        addrs.insert(0, winner);
+                    }
+                }
+                // After the pins, so this host's entry is the one used
+                options.addresses.push((host.to_string(), addrs));
+            }
+            // Behind a proxy the name only has to resolve on the proxy's side
+            Err(_) if uses_proxy(args) => {}
+            Err(_) => {
+                return Err(exit::Error::new(
+                    Category::Dns,
+                    format!("Could not resolve host: {}.", lookup),
+                ));
+            }
+        }
+    }
+
+    // Every request to the relay comes on its own connection, and never
+    // through a proxy from the environment
+    options.direct = args.unix_socket.is_some();
+    options.pool_stats = pool_stats.cloned();
+    if let Some(router) = router {
+        options.proxy_authorization = proxy_authorization(url, &router, args)?;
+        options.proxy = Some(router);
+    }
+    Ok(options)
+}
+
+// -L's hops: --trace-redirects prints each one, and --write-out counts them.
+fn redirect_observer(args: &Cli) -> RedirectObserver {
+    let trace = args.trace_redirects;
+    Arc::new(move |hop, status, from, to| {
+        if trace {
+            output::status(format!("Redirect {}: {} {} -> {}", hop, status, from, to));
+        }
+        writeout::redirected(hop);
+    })
+}
+
+// The name TLS verifies and the address the client will connect to.
+fn tls_endpoint<'a>(
+    origin: &'a Url,
+    alternative: Option<&'a altsvc::Target>,
+) -> Option<(&'a str, &'a str, u16)> {
+    let name = origin.host_str()?;
+    Some(match alternative {
+        Some(alt) => (name, alt.host.as_str(), alt.port),
+        None => (name, name, origin.port_or_known_default().unwrap_or(443)),
+    })
+}
+
+// --write-out's time_connect and time_appconnect, on a fresh connection.
+pub fn probe_connect(url: &Url, args: &Cli) -> Option<(Duration, Option<Duration>)> {
+    if url.scheme() == "https" {
+        let host = url.host_str()?;
+        let port = url.port_or_known_default()?;
+        let info = tls_info::probe(host, host, port, args.trust()).ok()?;
+        return Some((info.connect, Some(info.handshake)));
+    }
+    let addr = url.socket_addrs(|| None).ok()?.into_iter().next()?;
+    let began = Instant::now();
+    TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).ok()?;
+    Some((began.elapsed(), None))
+}
+
+pub fn report_tls(origin: &Url, alternative: Option<&altsvc::Target>, args: &Cli) {
+    let Some((name, host, port)) = tls_endpoint(origin, alternative) else {
+        return;
+    };
+    match tls_info::probe(name, host, port, args.trust()) {
+        Ok(info) => {
+            for line in info.describe() {
+                errln!("* {}", line);
+            }
+        }
+        Err(e) => errln!("Warning: TLS probe failed: {}", e),
+    }
+}
+
+// A revoked certificate always fails; an undeterminable status only warns
+// with --revocation-best-effort.
+pub fn check_revocation(
+    origin: &Url,
+    alternative: Option<&altsvc::Target>,
+    args: &Cli,
+) -> Result<(), String> {
+    let Some((name, host, port)) = tls_endpoint(origin, alternative) else {
+        return Ok(());
+    };
+    let unknown = |reason: String| {
+        if args.revocation_best_effort {
+            errln!("Warning: Revocation status unknown: {}.", reason);
+            Ok(())
+        } else {
+            Err(format!("Revocation status unknown: {}.", reason))
+        }
+    };
+    match revocation::check(name, host, port, args.trust()) {
+        Ok(revocation::Status::Good { source }) => {
+            if args.verbose {
+                errln!("* Certificate not revoked ({})", source);
+            }
+            Ok(())
+        }
+        Ok(revocation::Status::Revoked { reason, at }) => Err(format!(
+            "The server certificate was revoked ({}) at {}.",
+            reason, at
+        )),
+        Ok(revocation::Status::Unknown(reason)) => unknown(reason),
+        Err(e) => unknown(e),
+    }
+}
+
+pub fn proxy_router(url: &Url, args: &Cli) -> Result<proxy::Router, String> {
+    proxy::Router::new(
+        url,
+        args.proxy.as_deref(),
+        args.noproxy.as_deref(),
+        args.proxy_config.as_deref(),
+        args.proxy_pac.as_deref(),
+        args.verbose,
+    )
+}
+
+// A literal --proxy-header Proxy-Authorization wins over --proxy-negotiate,
+// which wins over --proxy-user.
+fn proxy_authorization(
+    url: &Url,
+    router: &proxy::Router,
+    args: &Cli,
+) -> Result<Option<HeaderValue>, String> {
+    for header in &args.proxy_header {
+        let (name, value) = parse_proxy_header(header)?;
+        if name == PROXY_AUTHORIZATION {
+            return Ok(Some(value));
+        }
+    }
+    if args.proxy_negotiate {
+        let Some(proxy) = router.route(url) else {
+            return Ok(None);
+        };
+        let token = negotiate::token(proxy.host_str().unwrap_or(""))
+            .map_err(|e| format!("Proxy Negotiate authentication: {}", e))?;
+        let mut value = HeaderValue::from_str(&token).map_err(|e| e.to_string())?;
+        value.set_sensitive(true);
+        return Ok(Some(value));
+    }
+    Ok(args
+        .proxy_user
+        .as_deref()
+        .map(|user| proxy::Credentials::parse(user).header()))
+}
+
+fn parse_proxy_header(header: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let invalid = || {
+        format!(
+            "Invalid --proxy-header '{}'; expected 'Name: value'.",
+            header
+        )
+    };
+    let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+    let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+    Ok((name, value))
+}
+
+// Plain http:// requests to a proxy carry the whole request, so proxy
+// headers ride along with it. reqwest builds CONNECT tunnels itself and
+// only lets Proxy-Authorization through.
+pub fn add_proxy_headers(
+    headers: &mut HeaderMap,
+    url: &Url,
+    router: Option<&proxy::Router>,
+    args: &Cli,
+) -> Result<(), String> {
+    let mut extra = Vec::new();
+    for header in &args.proxy_header {
+        let (name, value) = parse_proxy_header(header)?;
+        if name != PROXY_AUTHORIZATION {
+            extra.push((name, value));
+        }
+    }
+    if extra.is_empty() || router.and_then(|r| r.route(url)).is_none() {
+        return Ok(());
+    }
+    if url.scheme() == "https" {
+        errln!(
+            "Warning: --proxy-header is not sent on CONNECT tunnels (https:// URLs); only Proxy-Authorization is."
+        );
+        return Ok(());
+    }
+    for (name, value) in extra {
+        headers.append(name, value);
+    }
+    Ok(())
+}
+
+pub fn uses_proxy(args: &Cli) -> bool {
+    args.unix_socket.is_none()
+        && (args.proxy.is_some()
+            || args.proxy_pac.is_some()
+            || args.proxy_config.is_some()
+            || proxy_configured())
+}
+
+fn proxy_configured() -> bool {
+    [
+        "HTTP_PROXY",
+        "HTTPS_PROXY",
+        "ALL_PROXY",
+        "http_proxy",
+        "https_proxy",
+        "all_proxy",
+    ]
+    .iter()
+    .any(|var| std::env::var(var).is_ok_and(|v| !v.is_empty()))
+}
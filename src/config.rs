@@ -0,0 +1,185 @@
+// Named profiles: options for one environment kept in
+// ~/.config/web_client/config.toml and picked with `--profile staging`.
+//
+// [profiles.staging]
+// base_url = "https://staging.example.com/api"
+// headers = { "X-Env" = "staging", Accept = "application/json" }
+// bearer = "{{vault:secret/staging#token}}"
+// proxy = "http://proxy.internal:3128"
+// cacert = "/etc/ssl/staging-ca.pem"
+//
+// Options given on the command line win over the profile's: a header
+// replaces the profile's header of the same name, and -u, --bearer or
+// --bearer-file replace both of its credentials. A URL without a scheme
+// ("/users/1") is appended to base_url; the base URL alone is fetched when
+// no URL is given. `--save-session NAME` writes the current options back
+// as profile NAME, leaving the file's other profiles as they are.
+//
+// $WEB_CLIENT_CONFIG names a different file; without it the file is under
+// $XDG_CONFIG_HOME when that's set.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    // "user:password" as for -u
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noproxy: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub insecure: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cacert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Deserialize, Default)]
+struct File {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profile {
+    // The URL to fetch for a URL argument: relative ones go under base_url.
+    pub fn resolve_url(&self, url: &str) -> String {
+        match &self.base_url {
+            Some(base) if !url.contains("://") => format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                url.trim_start_matches('/')
+            ),
+            _ => url.to_string(),
+        }
+    }
+}
+
+pub fn path() -> PathBuf {
+    if let Some(path) = env::var_os("WEB_CLIENT_CONFIG") {
+        return PathBuf::from(path);
+    }
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".config"),
+    };
+    dir.join("web_client").join("config.toml")
+}
+
+pub fn load(name: &str) -> Result<Profile, String> {
+    let path = path();
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+    let mut file: File = toml::from_str(&text)
+        .map_err(|e| format!("Invalid config file '{}': {}", path.display(), e))?;
+    file.profiles.remove(name).ok_or_else(|| {
+        let names: Vec<&str> = file.profiles.keys().map(String::as_str).collect();
+        format!(
+            "No profile '{}' in '{}' (profiles: {}).",
+            name,
+            path.display(),
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        )
+    })
+}
+
+// Store `profile` as profile `name`, replacing any profile of that name.
+// Returns the file written.
+pub fn save(name: &str, profile: &Profile) -> Result<PathBuf, String> {
+    let path = path();
+    let mut doc: toml::Table = match fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text)
+            .map_err(|e| format!("Invalid config file '{}': {}", path.display(), e))?,
+        Err(_) => toml::Table::new(),
+    };
+    let value = toml::Value::try_from(profile).map_err(|e| e.to_string())?;
+    let profiles = doc
+        .entry("profiles")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let Some(profiles) = profiles.as_table_mut() else {
+        return Err(format!(
+            "'profiles' in '{}' is not a table.",
+            path.display()
+        ));
+    };
+    profiles.insert(name.to_string(), value);
+
+    let text = toml::to_string_pretty(&doc).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Unable to create '{}': {}", dir.display(), e))?;
+    }
+    fs::write(&path, text).map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+    restrict(&path);
+    Ok(path)
+}
+
+// Profiles can hold credentials, so only the owner may read the file.
+#[cfg(unix)]
+fn restrict(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+}
+
+#[cfg(not(unix))]
+fn restrict(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_base(base: &str) -> Profile {
+        Profile {
+            base_url: Some(base.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn relative_urls_go_under_base_url() {
+        let profile = with_base("https://staging.example.com/api/");
+        assert_eq!(
+            profile.resolve_url("/users/1"),
+            "https://staging.example.com/api/users/1"
+        );
+        assert_eq!(
+            profile.resolve_url("users/1"),
+            "https://staging.example.com/api/users/1"
+        );
+    }
+
+    #[test]
+    fn full_urls_are_kept() {
+        let profile = with_base("https://staging.example.com/api");
+        assert_eq!(
+            profile.resolve_url("http://other.example/x"),
+            "http://other.example/x"
+        );
+        assert_eq!(Profile::default().resolve_url("/x"), "/x");
+    }
+}
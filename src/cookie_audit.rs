@@ -0,0 +1,218 @@
+// --cookie-audit: review the Set-Cookie headers of a response for missing
+// protections (Secure, HttpOnly, SameSite), overly broad domains, long
+// lifetimes and misused __Secure-/__Host- prefixes.
+
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use serde::Serialize;
+use std::time::SystemTime;
+use url::Url;
+
+// Chrome and Firefox cap cookie lifetimes at 400 days.
+const MAX_LIFETIME_DAYS: u64 = 400;
+// Anything living longer than this is worth a mention.
+const LONG_LIFETIME_DAYS: u64 = 365;
+
+#[derive(Serialize)]
+pub struct CookieReport {
+    name: String,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<String>,
+    lifetime_days: Option<u64>,
+    findings: Vec<Finding>,
+}
+
+#[derive(Serialize)]
+struct Finding {
+    severity: &'static str,
+    message: String,
+}
+
+pub fn audit(url: &Url, headers: &HeaderMap) -> Vec<CookieReport> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| audit_cookie(url, v))
+        .collect()
+}
+
+pub fn render_text(reports: &[CookieReport]) -> String {
+    if reports.is_empty() {
+        return "Cookie audit: the response sets no cookies.".to_string();
+    }
+
+    let mut lines = vec![format!("Cookie audit: {} cookie(s)", reports.len())];
+    for r in reports {
+        let mut attrs = Vec::new();
+        if r.secure {
+            attrs.push("Secure".to_string());
+        }
+        if r.http_only {
+            attrs.push("HttpOnly".to_string());
+        }
+        if let Some(s) = &r.same_site {
+            attrs.push(format!("SameSite={}", s));
+        }
+        if let Some(d) = &r.domain {
+            attrs.push(format!("Domain={}", d));
+        }
+        if let Some(days) = r.lifetime_days {
+            attrs.push(format!("{} days", days));
+        }
+        lines.push(format!("  {} [{}]", r.name, attrs.join(", ")));
+        if r.findings.is_empty() {
+            lines.push("    ok".to_string());
+        }
+        for f in &r.findings {
+            lines.push(format!("    {}: {}", f.severity, f.message));
+        }
+    }
+    lines.join("\n")
+}
+
+pub fn render_json(reports: &[CookieReport]) -> String {
+    serde_json::to_string_pretty(&serde_json::json!({ "cookies": reports })).unwrap()
+}
+
+fn audit_cookie(url: &Url, header: &str) -> Option<CookieReport> {
+    let mut parts = header.split(';');
+    let (name, _) = parts.next()?.split_once('=')?;
+    let name = name.trim().to_string();
+
+    let mut report = CookieReport {
+        name,
+        domain: None,
+        path: None,
+        secure: false,
+        http_only: false,
+        same_site: None,
+        lifetime_days: None,
+        findings: Vec::new(),
+    };
+
+    for attr in parts {
+        let (key, value) = match attr.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => (attr.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "secure" => report.secure = true,
+            "httponly" => report.http_only = true,
+            "samesite" => report.same_site = Some(value.to_string()),
+            "domain" => report.domain = Some(value.trim_start_matches('.').to_ascii_lowercase()),
+            "path" => report.path = Some(value.to_string()),
+            // Max-Age wins over Expires
+            "max-age" => {
+                report.lifetime_days = value.parse::<i64>().ok().map(|s| s.max(0) as u64 / 86400);
+            }
+            "expires" if report.lifetime_days.is_none() => {
+                report.lifetime_days = httpdate::parse_http_date(value).ok().map(|at| {
+                    at.duration_since(SystemTime::now())
+                        .map(|d| d.as_secs() / 86400)
+                        .unwrap_or(0)
+                });
+            }
+            _ => {}
+        }
+    }
+
+    check(&mut report, url);
+    Some(report)
+}
+
+fn check(r: &mut CookieReport, url: &Url) {
+    let mut add = |severity, message: String| r.findings.push(Finding { severity, message });
+    let same_site = r.same_site.as_deref().map(str::to_ascii_lowercase);
+
+    if !r.secure {
+        let message = if url.scheme() == "https" {
+            "missing Secure; it would also be sent over plain HTTP".to_string()
+        } else {
+            "missing Secure, and it was set over plain HTTP".to_string()
+        };
+        add("high", message);
+    }
+    if !r.http_only {
+        add(
+            "medium",
+            "missing HttpOnly; scripts (and XSS payloads) can read it".to_string(),
+        );
+    }
+    match same_site.as_deref() {
+        None => add(
+            "low",
+            "no SameSite; browsers fall back to Lax, older ones send it cross-site".to_string(),
+        ),
+        Some("none") if !r.secure => add(
+            "high",
+            "SameSite=None without Secure is rejected by browsers".to_string(),
+        ),
+        Some("none") => add(
+            "low",
+            "SameSite=None: sent on all cross-site requests".to_string(),
+        ),
+        Some("lax") | Some("strict") => {}
+        Some(other) => add("medium", format!("unknown SameSite value '{}'", other)),
+    }
+
+    let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+    if let Some(domain) = r.domain.clone() {
+        if domain != host && !host.ends_with(&format!(".{}", domain)) {
+            add(
+                "high",
+                format!(
+                    "Domain={} doesn't cover {}; browsers will reject it",
+                    domain, host
+                ),
+            );
+        } else if !domain.contains('.') {
+            add(
+                "high",
+                format!(
+                    "Domain={} is a top-level domain; browsers will reject it",
+                    domain
+                ),
+            );
+        } else if domain != host {
+            add(
+                "medium",
+                format!(
+                    "Domain={} shares it with every subdomain of {}",
+                    domain, domain
+                ),
+            );
+        }
+    }
+
+    match r.lifetime_days {
+        Some(days) if days > MAX_LIFETIME_DAYS => add(
+            "medium",
+            format!(
+                "expires in {} days; browsers cap it at {}",
+                days, MAX_LIFETIME_DAYS
+            ),
+        ),
+        Some(days) if days > LONG_LIFETIME_DAYS => {
+            add("low", format!("long-lived: expires in {} days", days))
+        }
+        _ => {}
+    }
+
+    if r.name.starts_with("__Secure-") && !r.secure {
+        add(
+            "high",
+            "__Secure- prefix requires the Secure attribute".to_string(),
+        );
+    }
+    if r.name.starts_with("__Host-")
+        && (!r.secure || r.domain.is_some() || r.path.as_deref() != Some("/"))
+    {
+        add(
+            "high",
+            "__Host- prefix requires Secure, Path=/ and no Domain".to_string(),
+        );
+    }
+}
@@ -0,0 +1,299 @@
+// Cookie jar: -b <file> loads cookies, -c <file> loads them too and writes
+// them back after the response, so a login in one run carries over to the
+// next.
+//
+// Files are read in the Netscape format curl and browsers' cookies.txt
+// exporters use:
+//
+//   #HttpOnly_.example.com  TRUE  /  TRUE  1792000000  session  abc
+//
+// (domain, subdomains too, path, secure only, expiry as a Unix time with 0
+// for a session cookie, name, value) or as a JSON array of browser-export
+// objects. A jar is written back in the format of its file name: JSON for
+// '.json', Netscape otherwise.
+//
+// Only the final response's Set-Cookie headers are seen; with -L, cookies
+// set by the redirect responses themselves are not recorded.
+
+use crate::errln;
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Cookie {
+    name: String,
+    value: String,
+    // Without a leading dot; `host_only` says whether subdomains match
+    domain: String,
+    #[serde(rename = "hostOnly", default)]
+    host_only: bool,
+    #[serde(default = "root_path")]
+    path: String,
+    #[serde(default)]
+    secure: bool,
+    #[serde(rename = "httpOnly", default)]
+    http_only: bool,
+    // Unix time; None for a session cookie
+    #[serde(alias = "expirationDate", default, with = "unix_time")]
+    expires: Option<u64>,
+}
+
+struct Jar {
+    cookies: Vec<Cookie>,
+    save_to: Option<PathBuf>,
+}
+
+static JAR: OnceLock<Mutex<Jar>> = OnceLock::new();
+
+// Load the -b files and the -c file (if it exists yet) for this run.
+pub fn enable(files: &[&Path], save_to: Option<&Path>) -> Result<(), String> {
+    let mut cookies = Vec::new();
+    let existing_jar = save_to.filter(|p| p.exists());
+    for path in files.iter().copied().chain(existing_jar) {
+        for cookie in load(path)? {
+            store(&mut cookies, cookie);
+        }
+    }
+    let _ = JAR.set(Mutex::new(Jar {
+        cookies,
+        save_to: save_to.map(Path::to_path_buf),
+    }));
+    Ok(())
+}
+
+// The Cookie header value for a request to `url`, longest paths first.
+pub fn header(url: &Url) -> Option<String> {
+    let jar = JAR.get()?.lock().unwrap();
+    let host = url.host_str()?.to_ascii_lowercase();
+    let now = now();
+    let mut matching: Vec<&Cookie> = jar
+        .cookies
+        .iter()
+        .filter(|c| c.expires.is_none_or(|t| t > now))
+        .filter(|c| !c.secure || url.scheme() == "https")
+        .filter(|c| domain_match(&host, c))
+        .filter(|c| path_match(url.path(), &c.path))
+        .collect();
+    if matching.is_empty() {
+        return None;
+    }
+    matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+    let pairs: Vec<String> = matching
+        .iter()
+        .map(|c| format!("{}={}", c.name, c.value))
+        .collect();
+    Some(pairs.join("; "))
+}
+
+// Take in a response's Set-Cookie headers and, with -c, write the jar.
+// No-op unless a jar is in use.
+pub fn record(url: &Url, headers: &HeaderMap) {
+    let Some(jar) = JAR.get() else {
+        return;
+    };
+    let mut jar = jar.lock().unwrap();
+    for value in headers.get_all(SET_COOKIE).iter() {
+        if let Some(cookie) = value.to_str().ok().and_then(|v| parse_set_cookie(url, v)) {
+            store(&mut jar.cookies, cookie);
+        }
+    }
+
+    let Some(path) = &jar.save_to else {
+        return;
+    };
+    let now = now();
+    let live: Vec<&Cookie> = jar
+        .cookies
+        .iter()
+        .filter(|c| c.expires.is_none_or(|t| t > now))
+        .collect();
+    if let Err(e) = save(path, &live) {
+        errln!("Warning: Unable to write '{}': {}", path.display(), e);
+    }
+}
+
+// A cookie replaces the one with the same name, domain and path; an
+// already expired one only deletes it.
+fn store(cookies: &mut Vec<Cookie>, cookie: Cookie) {
+    cookies
+        .retain(|c| !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path));
+    if cookie.expires.is_none_or(|t| t > now()) {
+        cookies.push(cookie);
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<Cookie>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+    if text.trim_start().starts_with('[') {
+        let cookies: Vec<Cookie> = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid cookie file '{}': {}", path.display(), e))?;
+        return Ok(cookies
+            .into_iter()
+            .map(|mut c| {
+                c.domain = c.domain.trim_start_matches('.').to_ascii_lowercase();
+                c
+            })
+            .collect());
+    }
+    Ok(text.lines().filter_map(parse_netscape_line).collect())
+}
+
+fn parse_netscape_line(line: &str) -> Option<Cookie> {
+    let (http_only, line) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    if line.starts_with('#') || line.trim().is_empty() {
+        return None;
+    }
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+        return None;
+    };
+    let expires: u64 = expires.trim().parse().ok()?;
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.trim_end_matches('\r').to_string(),
+        domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+        host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+        path: path.to_string(),
+        secure: secure.eq_ignore_ascii_case("TRUE"),
+        http_only,
+        expires: (expires > 0).then_some(expires),
+    })
+}
+
+fn save(path: &Path, cookies: &[&Cookie]) -> Result<(), String> {
+    let is_json = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let text = if is_json {
+        serde_json::to_string_pretty(cookies).map_err(|e| e.to_string())?
+    } else {
+        let mut out = String::from("# Netscape HTTP Cookie File\n");
+        for c in cookies {
+            out.push_str(&format!(
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if c.http_only { HTTP_ONLY_PREFIX } else { "" },
+                if c.host_only { "" } else { "." },
+                c.domain,
+                if c.host_only { "FALSE" } else { "TRUE" },
+                c.path,
+                if c.secure { "TRUE" } else { "FALSE" },
+                c.expires.unwrap_or(0),
+                c.name,
+                c.value
+            ));
+        }
+        out
+    };
+    fs::write(path, text).map_err(|e| e.to_string())
+}
+
+// RFC 6265 section 5.2, without the public suffix list: a Domain attribute
+// must cover the request host.
+fn parse_set_cookie(url: &Url, header: &str) -> Option<Cookie> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut attrs = header.split(';');
+    let (name, value) = attrs.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url.path()),
+        secure: false,
+        http_only: false,
+        expires: None,
+    };
+    let mut max_age = None;
+    for attr in attrs {
+        let (key, val) = attr.split_once('=').unwrap_or((attr, ""));
+        let val = val.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !val.is_empty() => {
+                let domain = val.trim_start_matches('.').to_ascii_lowercase();
+                if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if val.starts_with('/') => cookie.path = val.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "max-age" => max_age = val.parse::<i64>().ok(),
+            "expires" => {
+                if let Ok(t) = httpdate::parse_http_date(val) {
+                    cookie.expires = Some(unix(t));
+                }
+            }
+            _ => {}
+        }
+    }
+    // Max-Age wins over Expires; zero or less deletes the cookie
+    if let Some(age) = max_age {
+        cookie.expires = Some(if age <= 0 { 0 } else { now() + age as u64 });
+    }
+    Some(cookie)
+}
+
+fn domain_match(host: &str, cookie: &Cookie) -> bool {
+    host == cookie.domain || (!cookie.host_only && host.ends_with(&format!(".{}", cookie.domain)))
+}
+
+fn path_match(request: &str, cookie: &str) -> bool {
+    request == cookie
+        || (request.starts_with(cookie)
+            && (cookie.ends_with('/') || request[cookie.len()..].starts_with('/')))
+}
+
+// The directory of the request path: /account/login -> /account
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+fn root_path() -> String {
+    "/".to_string()
+}
+
+fn now() -> u64 {
+    unix(SystemTime::now())
+}
+
+fn unix(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+// Browser exports write expirationDate as fractional seconds.
+mod unix_time {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(t: &Option<u64>, s: S) -> Result<S::Ok, S::Error> {
+        match t {
+            Some(t) => s.serialize_u64(*t),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+        let t = Option::<f64>::deserialize(d)?;
+        Ok(t.filter(|&t| t > 0.0).map(|t| t as u64))
+    }
+}
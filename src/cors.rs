@@ -0,0 +1,238 @@
+// `curl cors <URL>`: simulate a browser's CORS check.
+//
+// Sends the preflight a browser would send for the described request and
+// evaluates the Access-Control-* headers by the Fetch standard's rules.
+// Requests that don't need a preflight are checked against the actual
+// response instead, since that is what the browser looks at.
+
+use crate::assertions::EXIT_ASSERTION_FAILED;
+use reqwest::Method;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use std::process;
+use structopt::StructOpt;
+
+// Methods that never need a preflight.
+const SIMPLE_METHODS: [&str; 3] = ["GET", "HEAD", "POST"];
+
+// Request headers a page may set without a preflight. Content-Type is only
+// safelisted for form and plain-text bodies, so a listed Content-Type is
+// treated as needing one (the usual case: application/json).
+const SAFELISTED_HEADERS: [&str; 3] = ["accept", "accept-language", "content-language"];
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct CorsCommand {
+    url: String,
+
+    /// Origin of the page making the request
+    #[structopt(long)]
+    origin: String,
+
+    /// Method of the simulated request
+    #[structopt(long, default_value = "GET")]
+    method: String,
+
+    /// Comma-separated request headers the page sets, e.g. content-type,x-api-key
+    #[structopt(long, default_value = "")]
+    headers: String,
+
+    /// Simulate `credentials: "include"` (cookies, HTTP auth)
+    #[structopt(long)]
+    credentials: bool,
+}
+
+struct Verdict {
+    lines: Vec<String>,
+    allowed: bool,
+}
+
+impl Verdict {
+    fn pass(&mut self, message: String) {
+        self.lines.push(format!("  ok    {}", message));
+    }
+
+    fn fail(&mut self, message: String) {
+        self.lines.push(format!("  FAIL  {}", message));
+        self.allowed = false;
+    }
+
+    fn note(&mut self, message: String) {
+        self.lines.push(format!("  note  {}", message));
+    }
+}
+
+pub fn run(cmd: CorsCommand) -> Result<(), String> {
+    let method = cmd.method.to_ascii_uppercase();
+    let headers: Vec<String> = cmd
+        .headers
+        .split(',')
+        .map(|h| h.trim().to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect();
+    let unsafe_headers: Vec<&String> = headers
+        .iter()
+        .filter(|h| !SAFELISTED_HEADERS.contains(&h.as_str()))
+        .collect();
+    let needs_preflight = !SIMPLE_METHODS.contains(&method.as_str()) || !unsafe_headers.is_empty();
+
+    let client = Client::new();
+    let mut verdict = Verdict {
+        lines: Vec::new(),
+        allowed: true,
+    };
+
+    let res = if needs_preflight {
+        println!("Preflight: OPTIONS {} from {}", cmd.url, cmd.origin);
+        let mut req = client
+            .request(Method::OPTIONS, &cmd.url)
+            .header(ORIGIN, &cmd.origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, &method);
+        if !unsafe_headers.is_empty() {
+            let list: Vec<&str> = unsafe_headers.iter().map(|h| h.as_str()).collect();
+            req = req.header(ACCESS_CONTROL_REQUEST_HEADERS, list.join(","));
+        }
+        let res = req
+            .send()
+            .map_err(|e| format!("Unable to send the preflight: {}", e))?;
+        if res.status().is_success() {
+            verdict.pass(format!("preflight status {}", res.status().as_u16()));
+        } else {
+            verdict.fail(format!(
+                "preflight status {} (must be 2xx)",
+                res.status().as_u16()
+            ));
+        }
+        res
+    } else {
+        println!(
+            "No preflight needed for a simple {} request; checking the actual response.",
+            method
+        );
+        let method = Method::from_bytes(method.as_bytes())
+            .map_err(|_| format!("Invalid method '{}'.", cmd.method))?;
+        client
+            .request(method, &cmd.url)
+            .header(ORIGIN, &cmd.origin)
+            .send()
+            .map_err(|e| format!("Unable to send the request: {}", e))?
+    };
+
+    check_origin(&res, &cmd, &mut verdict);
+    if needs_preflight {
+        check_method(&res, &method, cmd.credentials, &mut verdict);
+        check_headers(&res, &unsafe_headers, cmd.credentials, &mut verdict);
+        if let Some(max_age) = header(&res, ACCESS_CONTROL_MAX_AGE) {
+            verdict.note(format!("preflight may be cached for {}s", max_age));
+        }
+    }
+
+    for line in &verdict.lines {
+        println!("{}", line);
+    }
+    if verdict.allowed {
+        println!("The browser would ALLOW this request.");
+        Ok(())
+    } else {
+        println!("The browser would BLOCK this request.");
+        process::exit(EXIT_ASSERTION_FAILED);
+    }
+}
+
+fn header(res: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    res.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+}
+
+fn list(res: &Response, name: reqwest::header::HeaderName) -> Vec<String> {
+    header(res, name)
+        .map(|v| {
+            v.split(',')
+                .map(|m| m.trim().to_ascii_lowercase())
+                .filter(|m| !m.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn check_origin(res: &Response, cmd: &CorsCommand, verdict: &mut Verdict) {
+    match header(res, ACCESS_CONTROL_ALLOW_ORIGIN) {
+        None => verdict.fail("no Access-Control-Allow-Origin header".to_string()),
+        Some(o) if o == "*" && cmd.credentials => verdict
+            .fail("Access-Control-Allow-Origin: * is not allowed with credentials".to_string()),
+        Some(o) if o == "*" => verdict.pass("Access-Control-Allow-Origin: *".to_string()),
+        Some(o) if o == cmd.origin => {
+            verdict.pass(format!("Access-Control-Allow-Origin: {}", o));
+            let varies = list(res, VARY).iter().any(|v| v == "origin" || v == "*");
+            if !varies {
+                verdict.note(
+                    "the origin is echoed without 'Vary: Origin'; shared caches may serve it to other origins"
+                        .to_string(),
+                );
+            }
+        }
+        Some(o) => verdict.fail(format!(
+            "Access-Control-Allow-Origin: {} does not match {}",
+            o, cmd.origin
+        )),
+    }
+
+    if cmd.credentials {
+        match header(res, ACCESS_CONTROL_ALLOW_CREDENTIALS).as_deref() {
+            Some("true") => verdict.pass("Access-Control-Allow-Credentials: true".to_string()),
+            _ => verdict
+                .fail("credentials require Access-Control-Allow-Credentials: true".to_string()),
+        }
+    }
+}
+
+fn check_method(res: &Response, method: &str, credentials: bool, verdict: &mut Verdict) {
+    let allowed = list(res, ACCESS_CONTROL_ALLOW_METHODS);
+    let lower = method.to_ascii_lowercase();
+    if SIMPLE_METHODS.contains(&method) {
+        verdict.pass(format!("{} is always allowed", method));
+    } else if allowed.contains(&lower) {
+        verdict.pass(format!("method {} is allowed", method));
+    } else if allowed.iter().any(|m| m == "*") && !credentials {
+        verdict.pass(format!("method {} is allowed by '*'", method));
+    } else if allowed.iter().any(|m| m == "*") {
+        verdict.fail(format!(
+            "method {} is only covered by '*', which doesn't apply with credentials",
+            method
+        ));
+    } else {
+        verdict.fail(format!(
+            "method {} is not in Access-Control-Allow-Methods ({})",
+            method,
+            if allowed.is_empty() {
+                "missing".to_string()
+            } else {
+                allowed.join(", ")
+            }
+        ));
+    }
+}
+
+fn check_headers(res: &Response, requested: &[&String], credentials: bool, verdict: &mut Verdict) {
+    let allowed = list(res, ACCESS_CONTROL_ALLOW_HEADERS);
+    let wildcard = allowed.iter().any(|h| h == "*") && !credentials;
+
+    for h in requested {
+        if allowed.contains(h) {
+            verdict.pass(format!("header {} is allowed", h));
+        // '*' never covers Authorization
+        } else if wildcard && h.as_str() != "authorization" {
+            verdict.pass(format!("header {} is allowed by '*'", h));
+        } else {
+            verdict.fail(format!(
+                "header {} is not in Access-Control-Allow-Headers",
+                h
+            ));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// --deadline: one budget bounding the whole run, so every retry, redirect
+// and follow-up request shares it, as do all the URLs of a multi-URL run:
+// the threads fetching them adopt the budget of the thread that started
+// it. When it runs out the process ends, whatever is still in flight.
+
+use crate::exit::{self, Category};
+use crate::output;
+use std::cell::Cell;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    limit: Duration,
+}
+
+impl Deadline {
+    pub fn after(limit: Duration) -> Self {
+        Deadline {
+            at: Instant::now() + limit,
+            limit,
+        }
+    }
+}
+
+thread_local! {
+    static DEADLINE: Cell<Option<Deadline>> = const { Cell::new(None) };
+}
+
+// Start the clock. The process ends when the budget runs out, even if
+// every thread is stuck inside a blocking call.
+pub fn start(limit: Duration) {
+    if DEADLINE.get().is_some() {
+        return;
+    }
+    DEADLINE.set(Some(Deadline::after(limit)));
+
+    thread::spawn(move || {
+        thread::sleep(limit);
+        output::error(exceeded(limit));
+        process::exit(Category::Timeout.code());
+    });
+}
+
+// This thread's budget, for another thread doing part of its work to adopt.
+pub fn current() -> Option<Deadline> {
+    DEADLINE.get()
+}
+
+pub fn adopt(deadline: Option<Deadline>) {
+    DEADLINE.set(deadline);
+}
+
+// Time left before the deadline, if one was set.
+pub fn remaining() -> Option<Duration> {
+    DEADLINE
+        .get()
+        .map(|d| d.at.saturating_duration_since(Instant::now()))
+}
+
+// Fail fast instead of starting work that can't finish in time.
+pub fn check() -> Result<(), exit::Error> {
+    match DEADLINE.get() {
+        Some(d) if Instant::now() >= d.at => Err(exceeded(d.limit)),
+        _ => Ok(()),
+    }
+}
+
+fn exceeded(limit: Duration) -> exit::Error {
+    exit::Error::new(
+        Category::Timeout,
+        format!("Operation exceeded the deadline of {:?}.", limit),
+    )
+}
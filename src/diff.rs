@@ -0,0 +1,62 @@
+// `curl diff <left> <right>`: compare two responses or local files.
+//
+// Each side is a URL (fetched with GET) or a file path. JSON on both sides
+// gets a structural diff; anything else falls back to a line diff.
+
+use crate::jsondiff::{self, JsonPath};
+use crate::snapshot;
+use serde_json::Value;
+use std::fs;
+use std::process;
+use structopt::StructOpt;
+
+// Like diff(1): 1 means the inputs differ.
+const EXIT_DIFFERENT: i32 = 1;
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct DiffCommand {
+    /// URL or file
+    left: String,
+
+    /// URL or file
+    right: String,
+}
+
+// `ignore` comes from the global --ignore-path.
+pub fn run(cmd: DiffCommand, ignore: &[JsonPath]) -> Result<(), String> {
+    let left = load(&cmd.left)?;
+    let right = load(&cmd.right)?;
+
+    let output = match (
+        serde_json::from_str::<Value>(&left),
+        serde_json::from_str::<Value>(&right),
+    ) {
+        (Ok(a), Ok(b)) => jsondiff::render(&jsondiff::diff(&a, &b, ignore)),
+        _ => snapshot::line_diff(&snapshot::normalize(&left), &snapshot::normalize(&right)),
+    };
+
+    if output.is_empty() {
+        println!("No differences.");
+        return Ok(());
+    }
+    println!("--- {}\n+++ {}\n{}", cmd.left, cmd.right, output);
+    process::exit(EXIT_DIFFERENT);
+}
+
+fn load(source: &str) -> Result<String, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let res = reqwest::blocking::get(source)
+            .map_err(|e| format!("Unable to fetch {}: {}", source, e))?;
+        if !res.status().is_success() {
+            return Err(format!(
+                "Request to {} failed with status code: {}.",
+                source,
+                res.status().as_u16()
+            ));
+        }
+        return res
+            .text()
+            .map_err(|e| format!("Unable to read the response from {}: {}", source, e));
+    }
+    fs::read_to_string(source).map_err(|e| format!("Unable to read '{}': {}", source, e))
+}
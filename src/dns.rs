@@ -0,0 +1,164 @@
+// Up-front hostname resolution so lookups can be timed, reported and pinned
+// into the HTTP client instead of happening invisibly inside it.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Upper bound on a single racing connection attempt.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Resolution {
+    pub host: String,
+    pub addrs: Vec<SocketAddr>,
+    pub elapsed: Duration,
+}
+
+pub fn resolve(host: &str, port: u16) -> io::Result<Resolution> {
+    let start = Instant::now();
+    let mut addrs: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+    let elapsed = start.elapsed();
+
+    addrs.dedup();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no addresses for {}", host),
+        ));
+    }
+
+    Ok(Resolution {
+        host: host.to_string(),
+        addrs,
+        elapsed,
+    })
+}
+
+impl Resolution {
+    pub fn describe(&self) -> String {
+        let ips: Vec<String> = self.addrs.iter().map(|a| a.ip().to_string()).collect();
+        format!(
+            "Resolved {} to {} in {:.1} ms",
+            self.host,
+            ips.join(", "),
+            self.elapsed.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+// ---------------- --resolve ----------------
+
+// `--resolve host:port:addr[,addr...]`: the addresses to connect to for one
+// host and port, used instead of looking the name up. IPv6 addresses may be
+// bracketed, as in `api.example:443:[::1]`.
+pub struct Pin {
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl Pin {
+    pub fn parse(spec: &str) -> Result<Pin, String> {
+        let invalid = || {
+            format!(
+                "--resolve expects host:port:address[,address...], not '{}'.",
+                spec
+            )
+        };
+        let mut parts = spec.splitn(3, ':');
+        let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let port = port.parse().map_err(|_| invalid())?;
+        let addrs = addrs
+            .split(',')
+            .map(|addr| {
+                let addr = addr.trim();
+                let addr = addr
+                    .strip_prefix('[')
+                    .and_then(|a| a.strip_suffix(']'))
+                    .unwrap_or(addr);
+                addr.parse::<IpAddr>()
+                    .map_err(|_| format!("--resolve: '{}' isn't an IP address.", addr))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Pin {
+            host: host.to_string(),
+            port,
+            addrs,
+        })
+    }
+
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        self.port == port && self.host.eq_ignore_ascii_case(host)
+    }
+
+    pub fn socket_addrs(&self) -> Vec<SocketAddr> {
+        self.addrs
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, self.port))
+            .collect()
+    }
+}
+
+// ---------------- HAPPY EYEBALLS (RFC 8305) ----------------
+
+// Addresses interleaved by family, IPv6 first, as RFC 8305 section 4 asks.
+pub fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.iter().partition(|a| a.is_ipv6());
+    let mut out = Vec::with_capacity(addrs.len());
+    let (mut a, mut b) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => break,
+            (x, y) => out.extend(x.into_iter().chain(y)),
+        }
+    }
+    out
+}
+
+pub fn is_dual_stack(addrs: &[SocketAddr]) -> bool {
+    addrs.iter().any(|a| a.is_ipv6()) && addrs.iter().any(|a| a.is_ipv4())
+}
+
+// Start a connection attempt to each address in turn, `delay` apart (or
+// immediately after the previous attempt fails), and return the first that
+// connects. The winning socket is dropped; callers pin the address.
+pub fn race(addrs: &[SocketAddr], delay: Duration) -> Option<SocketAddr> {
+    let (tx, rx) = mpsc::channel();
+    let mut pending = interleave(addrs).into_iter();
+    let mut in_flight = 0;
+
+    let mut start_next = |in_flight: &mut usize| {
+        if let Some(addr) = pending.next() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let ok = TcpStream::connect_timeout(&addr, ATTEMPT_TIMEOUT).is_ok();
+                let _ = tx.send((addr, ok));
+            });
+            *in_flight += 1;
+        }
+    };
+
+    start_next(&mut in_flight);
+    while in_flight > 0 {
+        match rx.recv_timeout(delay) {
+            Ok((addr, true)) => return Some(addr),
+            Ok((_, false)) => {
+                in_flight -= 1;
+                start_next(&mut in_flight);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                start_next(&mut in_flight);
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    None
+}
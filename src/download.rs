@@ -0,0 +1,345 @@
+// -o/-O: stream the response body to a file instead of printing it.
+//
+// Chunks go straight to disk as they arrive, so memory stays flat however
+// large the download is. When stderr is a terminal a progress bar shows
+// how far along the transfer is, measured against Content-Length when the
+// server sends one.
+//
+// With -C the request asks for the rest of the file (Range: bytes=N-). A
+// 206 whose Content-Range starts at N is appended; a server that ignores
+// the range answers 200 and the file is downloaded again from the start.
+//
+// Ctrl-C during a download flushes what has arrived to the file and says
+// how much that is, so `-C -` can pick up from there. The signal handler
+// only writes to a pipe; a thread waiting on it does the flushing of every
+// file in `PARTIAL` between two chunks.
+
+use crate::errln;
+use crate::output;
+use crate::transfer::{self, TransferLimits};
+use percent_encoding::percent_decode_str;
+use reqwest::StatusCode;
+use reqwest::blocking::Response;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_RANGE, HeaderMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+use url::Url;
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+// The exit status of a run ended by SIGINT, as a shell reports it
+const INTERRUPTED: i32 = 130;
+
+// A file being downloaded to
+struct Partial {
+    out: BufWriter<File>,
+    path: PathBuf,
+    // Bytes in the file, counting those there before a resumed transfer
+    written: u64,
+}
+
+// By the thread downloading, as the URLs of a multi-URL run download at
+// the same time
+static PARTIAL: Mutex<Vec<(ThreadId, Partial)>> = Mutex::new(Vec::new());
+
+// -O: the last path segment of the URL, decoded, as a local file name.
+pub fn remote_name(url: &Url) -> Result<PathBuf, String> {
+    let segment = url
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .unwrap_or("");
+    let decoded = percent_decode_str(segment).decode_utf8_lossy();
+    // A decoded segment may still hold separators; never leave the directory
+    let name = decoded.rsplit(['/', '\\']).next().unwrap_or("");
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("The URL has no file name to use with -O; name the file with -o.".to_string());
+    }
+    Ok(PathBuf::from(name))
+}
+
+// -C: '-' continues from the end of the existing file (0 if there is
+// none), a number from that byte.
+pub fn resume_offset(spec: &str, path: &Path) -> Result<u64, String> {
+    let existing = fs::metadata(path).map(|m| m.len()).ok();
+    if spec == "-" {
+        return Ok(existing.unwrap_or(0));
+    }
+    let offset: u64 = spec
+        .parse()
+        .map_err(|_| format!("Invalid -C offset '{}'; use a byte count or '-'.", spec))?;
+    match existing {
+        Some(len) if len >= offset => Ok(offset),
+        _ => Err(format!(
+            "'{}' is only {} bytes long; it can't be continued at byte {}.",
+            path.display(),
+            existing.unwrap_or(0),
+            offset
+        )),
+    }
+}
+
+// A 416 for a file that already has every byte: "bytes */<offset>".
+pub fn already_complete(headers: &HeaderMap, offset: u64) -> bool {
+    content_range(headers).is_some_and(|(start, total)| start.is_none() && total == Some(offset))
+}
+
+// Content-Range "bytes 100-999/1000" -> (Some(100), Some(1000)); the
+// unsatisfied form "bytes */1000" has no start.
+fn content_range(headers: &HeaderMap) -> Option<(Option<u64>, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-').and_then(|(s, _)| s.parse().ok());
+    Some((start, total.parse().ok()))
+}
+
+pub fn save(
+    res: Response,
+    path: &Path,
+    offset: u64,
+    limits: &TransferLimits,
+) -> Result<u64, String> {
+    let resumed = offset > 0 && res.status() == StatusCode::PARTIAL_CONTENT;
+    if resumed {
+        match content_range(res.headers()) {
+            Some((Some(start), _)) if start == offset => {}
+            Some((Some(start), _)) => {
+                return Err(format!(
+                    "The server resumed at byte {} instead of {}; the file is left as it was.",
+                    start, offset
+                ));
+            }
+            _ => {
+                return Err(
+                    "The partial response has no usable Content-Range; the file is left as it was."
+                        .to_string(),
+                );
+            }
+        }
+    } else if offset > 0 {
+        let ranges = res
+            .headers()
+            .get(ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("none");
+        if ranges.eq_ignore_ascii_case("none") {
+            errln!(
+                "Warning: The server doesn't support resuming (no Accept-Ranges); downloading the whole file again."
+            );
+        } else {
+            errln!(
+                "Warning: The server ignored the range request; downloading the whole file again."
+            );
+        }
+    }
+
+    let start = if resumed { offset } else { 0 };
+    let expected = res.content_length().map(|len| len + start);
+    let file = if resumed {
+        OpenOptions::new().append(true).open(path).and_then(|f| {
+            // An explicit -C offset may be short of the end of the file
+            f.set_len(offset)?;
+            Ok(f)
+        })
+    } else {
+        File::create(path)
+    }
+    .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+    on_interrupt();
+    let me = thread::current().id();
+    let partial = Partial {
+        out: BufWriter::new(file),
+        path: path.to_path_buf(),
+        written: start,
+    };
+    PARTIAL.lock().unwrap().push((me, partial));
+    let mut progress = Progress::new(expected, start);
+
+    let received = transfer::stream_body(res, limits, |chunk| {
+        let mut partials = PARTIAL.lock().unwrap();
+        let (_, partial) = partials
+            .iter_mut()
+            .find(|(id, _)| *id == me)
+            .ok_or("The download was interrupted.")?;
+        partial
+            .out
+            .write_all(chunk)
+            .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+        partial.written += chunk.len() as u64;
+        progress.advance(chunk.len());
+        Ok(())
+    });
+    let mine = {
+        let mut partials = PARTIAL.lock().unwrap();
+        let i = partials.iter().position(|(id, _)| *id == me);
+        i.map(|i| partials.remove(i).1)
+    };
+    let flushed = match mine {
+        Some(mut partial) => partial.out.flush(),
+        None => Ok(()),
+    }
+    .map_err(|e| format!("Unable to write '{}': {}", path.display(), e));
+    progress.finish();
+
+    let received = start
+        + received
+            .map_err(|e| format!("{}. The partial file is kept.", e.trim_end_matches('.')))?;
+    flushed?;
+    if let Some(expected) = expected
+        && received < expected
+    {
+        return Err(format!(
+            "The transfer ended after {} of {} bytes. The partial file is kept.",
+            received, expected
+        ));
+    }
+    Ok(received)
+}
+
+// Install the SIGINT handler the first time a download starts. Outside a
+// download Ctrl-C still just ends the run.
+#[cfg(unix)]
+fn on_interrupt() {
+    use std::sync::Once;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static INSTALL: Once = Once::new();
+    static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn handler(_: libc::c_int) {
+        let byte = 1u8;
+        // SAFETY: write(2) is async-signal-safe, and the descriptor stays
+        // open for the life of the process
+        unsafe { libc::write(PIPE.load(Ordering::Relaxed), (&raw const byte).cast(), 1) };
+    }
+
+    INSTALL.call_once(|| {
+        let mut fds = [0; 2];
+        // SAFETY: pipe(2) fills in two descriptors
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return;
+        }
+        PIPE.store(fds[1], Ordering::Relaxed);
+        thread::spawn(move || {
+            let mut byte = 0u8;
+            // SAFETY: reads one byte into a local from the pipe's read end
+            while unsafe { libc::read(fds[0], (&raw mut byte).cast(), 1) } != 1 {}
+            interrupted();
+        });
+        // SAFETY: the handler only calls write(2)
+        unsafe { libc::signal(libc::SIGINT, handler as *const () as libc::sighandler_t) };
+    });
+}
+
+#[cfg(not(unix))]
+fn on_interrupt() {}
+
+// Flush the partial files, say how far each got and exit. Holding the
+// lock keeps the downloads from writing another chunk.
+#[cfg(unix)]
+fn interrupted() -> ! {
+    let mut partials = PARTIAL.lock().unwrap_or_else(|e| e.into_inner());
+    for (_, partial) in partials.iter_mut() {
+        let saved = partial
+            .out
+            .flush()
+            .and_then(|_| partial.out.get_ref().sync_all());
+        // Past the progress bar's line
+        errln!();
+        match saved {
+            Ok(()) => errln!(
+                "Interrupted: saved {} ({} bytes) to {}; continue with -C -.",
+                size(partial.written),
+                partial.written,
+                partial.path.display()
+            ),
+            Err(e) => errln!(
+                "Interrupted: unable to write '{}': {}",
+                partial.path.display(),
+                e
+            ),
+        }
+    }
+    process::exit(INTERRUPTED);
+}
+
+// "512 B", "1.5 KiB", "3.2 MiB"
+pub fn size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+struct Progress {
+    expected: Option<u64>,
+    // Bytes already on disk when a resumed transfer started
+    base: u64,
+    received: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(expected: Option<u64>, base: u64) -> Self {
+        Progress {
+            expected,
+            base,
+            received: base,
+            started: Instant::now(),
+            drawn: None,
+            enabled: output::stderr_is_terminal(),
+        }
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.received += bytes as u64;
+        if self.enabled && self.drawn.is_none_or(|t| t.elapsed() >= REDRAW_INTERVAL) {
+            self.draw();
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.enabled {
+            self.draw();
+            errln!();
+        }
+    }
+
+    fn draw(&mut self) {
+        self.drawn = Some(Instant::now());
+        let elapsed = self.started.elapsed().as_secs_f64().max(0.001);
+        let rate = size(((self.received - self.base) as f64 / elapsed) as u64);
+        let line = match self.expected.filter(|&n| n > 0) {
+            Some(expected) => {
+                let fraction = (self.received as f64 / expected as f64).min(1.0);
+                let filled = (fraction * BAR_WIDTH as f64) as usize;
+                format!(
+                    "[{}{}] {:>3}%  {} / {}  {}/s",
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    (fraction * 100.0) as u32,
+                    size(self.received),
+                    size(expected),
+                    rate
+                )
+            }
+            None => format!("{}  {}/s", size(self.received), rate),
+        };
+        // Pad over whatever the previous, possibly longer, line left behind
+        eprint!("\r{:<70}", line);
+    }
+}
@@ -0,0 +1,127 @@
+// What went wrong, and the exit status it ends the run with.
+//
+// The codes are curl's for the same failures, so scripts written against
+// curl keep working:
+//
+//   1   anything else (unsupported protocol, bad option, unreadable file)
+//   3   the URL is malformed
+//   6   the host name couldn't be resolved
+//   7   no connection to the server
+//   22  the server answered with an error status (only with --fail)
+//   28  the request or the --deadline timed out
+//   35  the TLS handshake or certificate check failed
+
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Failed,
+    Url,
+    Dns,
+    Connect,
+    Http,
+    Timeout,
+    Tls,
+}
+
+impl Category {
+    pub fn code(self) -> i32 {
+        match self {
+            Category::Failed => 1,
+            Category::Url => 3,
+            Category::Dns => 6,
+            Category::Connect => 7,
+            Category::Http => 22,
+            Category::Timeout => 28,
+            Category::Tls => 35,
+        }
+    }
+
+    // The name --error-format json reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Failed => "error",
+            Category::Url => "url",
+            Category::Dns => "dns",
+            Category::Connect => "connect",
+            Category::Http => "http",
+            Category::Timeout => "timeout",
+            Category::Tls => "tls",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Error {
+    pub category: Category,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(category: Category, message: impl Into<String>) -> Error {
+        Error {
+            category,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+// Plain messages are the catch-all category.
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::new(Category::Failed, message)
+    }
+}
+
+impl From<&String> for Error {
+    fn from(message: &String) -> Error {
+        Error::new(Category::Failed, message.clone())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Error {
+        Error::new(Category::Failed, message)
+    }
+}
+
+impl From<&Error> for Error {
+    fn from(error: &Error) -> Error {
+        error.clone()
+    }
+}
+
+// The category of a request that got no response. reqwest only tells
+// timeouts and connection failures apart, so DNS and TLS failures are
+// recognized by the messages down the error's source chain.
+pub fn send_category(e: &reqwest::Error) -> Category {
+    if e.is_timeout() {
+        return Category::Timeout;
+    }
+    let mut source: Option<&dyn error::Error> = Some(e);
+    while let Some(err) = source {
+        let msg = err.to_string().to_ascii_lowercase();
+        if msg.contains("dns error") || msg.contains("failed to lookup address") {
+            return Category::Dns;
+        }
+        if ["certificate", "ssl", "tls", "handshake"]
+            .iter()
+            .any(|word| msg.contains(word))
+        {
+            return Category::Tls;
+        }
+        source = err.source();
+    }
+    if e.is_connect() {
+        Category::Connect
+    } else {
+        Category::Failed
+    }
+}
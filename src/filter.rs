@@ -0,0 +1,66 @@
+// --filter: print only part of a JSON response, picked with a jq-style
+// path (`.items[0].id`, `.items[].name`, `.` for all of it) or the
+// JSONPath subset of --ignore-path (`$.items[*].id`, `$["odd key"]`).
+
+use crate::jsondiff::{self, JsonPath};
+
+pub fn parse(input: &str) -> Result<JsonPath, String> {
+    let expr = input.trim();
+    let path = if expr == "." {
+        "$".to_string()
+    } else if expr.starts_with('$') {
+        expr.to_string()
+    } else if let Some(rest) = expr.strip_prefix(".[") {
+        format!("$[{}", rest)
+    } else if expr.starts_with(['.', '[']) {
+        format!("${}", expr)
+    } else {
+        return Err(format!(
+            "Invalid --filter '{}'; start it with '.' (jq style) or '$' (JSONPath).",
+            input
+        ));
+    };
+    jsondiff::parse_path(&path).map_err(|_| format!("Invalid --filter '{}'.", input))
+}
+
+// Shell-style matching: * is any run of characters, ? any single one.
+// Used by --header-grep and PAC scripts' shExpMatch.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            pi = bp + 1;
+            ti = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_match("*.example.com", "api.example.com"));
+        assert!(glob_match(
+            "content-type: *json*",
+            "content-type: application/json"
+        ));
+        assert!(glob_match("h?st", "host"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*.example.com", "example.org"));
+        assert!(!glob_match("h?st", "hst"));
+    }
+}
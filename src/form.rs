@@ -0,0 +1,228 @@
+// -F/--form: multipart/form-data request bodies (RFC 7578), built the way
+// curl reads its -F arguments:
+//
+//   name=value            a text field
+//   name=@photo.jpg       a file part, with a Content-Type from the extension
+//   name=<notes.txt       a text field whose value is read from a file
+//
+// followed by optional ';type=mime/type' and ';filename=name' modifiers.
+// File parts are streamed from disk when the request is sent.
+
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+pub struct Field {
+    name: String,
+    content: Content,
+    content_type: Option<String>,
+    filename: Option<String>,
+}
+
+enum Content {
+    Text(String),
+    File(PathBuf),
+}
+
+pub fn parse(spec: &str) -> Result<Field, String> {
+    let (name, rest) = spec
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| {
+            format!(
+                "Invalid -F '{}', expected 'name=value' or 'name=@file'.",
+                spec
+            )
+        })?;
+
+    // Trailing ';type=' and ';filename=' segments are modifiers; any other
+    // ';' belongs to the value
+    let mut value = rest;
+    let mut content_type = None;
+    let mut filename = None;
+    while let Some((head, modifier)) = value.rsplit_once(';') {
+        let modifier = modifier.trim();
+        if let Some(t) = modifier.strip_prefix("type=") {
+            content_type = Some(t.to_string());
+        } else if let Some(f) = modifier.strip_prefix("filename=") {
+            filename = Some(f.trim_matches('"').to_string());
+        } else {
+            break;
+        }
+        value = head;
+    }
+
+    let content = if let Some(path) = value.strip_prefix('@') {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(format!(
+                "Unable to read '{}' for -F {}.",
+                path.display(),
+                name
+            ));
+        }
+        filename.get_or_insert_with(|| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        content_type.get_or_insert_with(|| guess_type(&path).to_string());
+        Content::File(path)
+    } else if let Some(path) = value.strip_prefix('<') {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read '{}' for -F {}: {}", path, name, e))?;
+        Content::Text(text)
+    } else {
+        Content::Text(value.to_string())
+    };
+
+    Ok(Field {
+        name: name.to_string(),
+        content,
+        content_type,
+        filename,
+    })
+}
+
+impl Field {
+    // For the "Form:" status line.
+    pub fn describe(&self) -> String {
+        match &self.content {
+            Content::Text(text) => format!("{}={}", self.name, text),
+            Content::File(path) => format!(
+                "{}=@{} ({})",
+                self.name,
+                path.display(),
+                self.content_type.as_deref().unwrap_or("")
+            ),
+        }
+    }
+}
+
+pub struct Body {
+    pub content_type: String,
+    pub len: u64,
+    pub reader: Box<dyn Read + Send>,
+}
+
+// Called once per attempt; file parts are reopened each time.
+pub fn build(fields: &[Field]) -> Result<Body, String> {
+    let boundary = boundary()?;
+    let mut readers: Vec<Box<dyn Read + Send>> = Vec::new();
+    let mut len = 0u64;
+    let mut push = |reader: Box<dyn Read + Send>, n: u64| {
+        readers.push(reader);
+        len += n;
+    };
+
+    for field in fields {
+        let mut head = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            boundary,
+            quote(&field.name)
+        );
+        if let Some(filename) = &field.filename {
+            head.push_str(&format!("; filename=\"{}\"", quote(filename)));
+        }
+        if let Some(content_type) = &field.content_type {
+            head.push_str(&format!("\r\nContent-Type: {}", content_type));
+        }
+        head.push_str("\r\n\r\n");
+        let n = head.len() as u64;
+        push(Box::new(Cursor::new(head.into_bytes())), n);
+
+        match &field.content {
+            Content::Text(text) => {
+                let n = text.len() as u64;
+                push(Box::new(Cursor::new(text.clone().into_bytes())), n);
+            }
+            Content::File(path) => {
+                let file = File::open(path)
+                    .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+                let n = file.metadata().map(|m| m.len()).unwrap_or(0);
+                push(Box::new(file), n);
+            }
+        }
+        push(Box::new(Cursor::new(b"\r\n".to_vec())), 2);
+    }
+    let tail = format!("--{}--\r\n", boundary);
+    let n = tail.len() as u64;
+    push(Box::new(Cursor::new(tail.into_bytes())), n);
+
+    Ok(Body {
+        content_type: format!("multipart/form-data; boundary={}", boundary),
+        len,
+        reader: Box::new(Chain {
+            readers,
+            current: 0,
+        }),
+    })
+}
+
+fn boundary() -> Result<String, String> {
+    let mut bytes = [0u8; 12];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| e.to_string())?;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("------------------------{}", hex))
+}
+
+// Browsers percent-encode quotes and line breaks in these header values.
+fn quote(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn guess_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "wasm" => "application/wasm",
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "md" => "text/markdown",
+        "yaml" | "yml" => "application/yaml",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+// The part headers, bodies and files one after another.
+struct Chain {
+    readers: Vec<Box<dyn Read + Send>>,
+    current: usize,
+}
+
+impl Read for Chain {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(reader) = self.readers.get_mut(self.current) {
+            let n = reader.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            self.current += 1;
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,234 @@
+// How response bodies are shown: pretty-printed JSON, keys sorted when
+// asked for, highlighted for a terminal when color is on, and which bodies
+// are markup worth reindenting. The command line and WebClient's reports
+// both go through `body`.
+
+use serde::Serialize;
+use serde_json::ser::{Formatter, PrettyFormatter};
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+const KEY: &[u8] = b"\x1b[1;34m";
+const STRING: &[u8] = b"\x1b[32m";
+const NUMBER: &[u8] = b"\x1b[36m";
+const BOOL: &[u8] = b"\x1b[33m";
+const NULL: &[u8] = b"\x1b[90m";
+const RESET: &[u8] = b"\x1b[0m";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Markup {
+    Xml,
+    Html,
+}
+
+impl Markup {
+    pub fn name(self) -> &'static str {
+        match self {
+            Markup::Xml => "XML",
+            Markup::Html => "HTML",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Style {
+    // See `json`
+    pub sort_depth: usize,
+    pub color: bool,
+    // Reindent XML and HTML
+    pub markup: bool,
+}
+
+pub struct Pretty {
+    // "JSON", "XML" or "HTML"; None for text shown as it is
+    pub kind: Option<&'static str>,
+    pub text: String,
+}
+
+pub fn body(text: &str, content_type: Option<&str>, style: Style) -> Pretty {
+    if let Ok(json) = serde_json::from_str::<Value>(text) {
+        let text = if style.color {
+            json_colored(&json, style.sort_depth)
+        } else {
+            self::json(&json, style.sort_depth)
+        };
+        return Pretty {
+            kind: Some("JSON"),
+            text,
+        };
+    }
+    match markup(content_type, text).filter(|_| style.markup) {
+        Some(kind) => Pretty {
+            kind: Some(kind.name()),
+            text: crate::markup::pretty(text, kind, style.color),
+        },
+        None => Pretty {
+            kind: None,
+            text: text.to_string(),
+        },
+    }
+}
+
+// Keys are sorted `sort_depth` levels deep: 0 keeps the server's order,
+// usize::MAX sorts everything.
+pub fn json(value: &Value, sort_depth: usize) -> String {
+    serde_json::to_string_pretty(&sort_keys(value, sort_depth)).unwrap()
+}
+
+// Like json, with ANSI colors for keys, strings, numbers and literals.
+pub fn json_colored(value: &Value, sort_depth: usize) -> String {
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, Colored::default());
+    sort_keys(value, sort_depth)
+        .serialize(&mut serializer)
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+pub fn sort_keys(value: &Value, depth: usize) -> Value {
+    if depth == 0 {
+        return value.clone();
+    }
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<_> = map.keys().collect();
+            keys.sort();
+            let mut sorted = Map::new();
+            for k in keys {
+                sorted.insert(k.clone(), sort_keys(&map[k], depth - 1));
+            }
+            Value::Object(sorted)
+        }
+        // Objects inside arrays count as the array's level
+        Value::Array(items) => Value::Array(items.iter().map(|v| sort_keys(v, depth)).collect()),
+        other => other.clone(),
+    }
+}
+
+// XML or HTML, by Content-Type, or by the start of a body that came
+// without one.
+pub fn markup(content_type: Option<&str>, text: &str) -> Option<Markup> {
+    let Some(content_type) = content_type else {
+        let start: String = text.trim_start().chars().take(14).collect();
+        let start = start.to_ascii_lowercase();
+        return if start.starts_with("<!doctype html") || start.starts_with("<html") {
+            Some(Markup::Html)
+        } else if start.starts_with("<?xml") {
+            Some(Markup::Xml)
+        } else {
+            None
+        };
+    };
+    let mime = content_type.parse::<mime::Mime>().ok()?;
+    match (mime.subtype().as_str(), mime.suffix().map(|s| s.as_str())) {
+        ("html", _) | ("xhtml", Some("xml")) => Some(Markup::Html),
+        ("xml", _) | (_, Some("xml")) => Some(Markup::Xml),
+        _ => None,
+    }
+}
+
+// PrettyFormatter's layout, with each scalar wrapped in its color.
+#[derive(Default)]
+struct Colored {
+    pretty: PrettyFormatter<'static>,
+    in_key: bool,
+}
+
+impl Colored {
+    fn colored<W: ?Sized + Write>(writer: &mut W, color: &[u8], text: &[u8]) -> io::Result<()> {
+        writer.write_all(color)?;
+        writer.write_all(text)?;
+        writer.write_all(RESET)
+    }
+}
+
+impl Formatter for Colored {
+    fn write_null<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        Colored::colored(writer, NULL, b"null")
+    }
+
+    fn write_bool<W: ?Sized + Write>(&mut self, writer: &mut W, value: bool) -> io::Result<()> {
+        let text: &[u8] = if value { b"true" } else { b"false" };
+        Colored::colored(writer, BOOL, text)
+    }
+
+    fn write_i64<W: ?Sized + Write>(&mut self, writer: &mut W, value: i64) -> io::Result<()> {
+        Colored::colored(writer, NUMBER, value.to_string().as_bytes())
+    }
+
+    fn write_u64<W: ?Sized + Write>(&mut self, writer: &mut W, value: u64) -> io::Result<()> {
+        Colored::colored(writer, NUMBER, value.to_string().as_bytes())
+    }
+
+    fn write_f64<W: ?Sized + Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        Colored::colored(writer, NUMBER, value.to_string().as_bytes())
+    }
+
+    fn write_number_str<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        value: &str,
+    ) -> io::Result<()> {
+        Colored::colored(writer, NUMBER, value.as_bytes())
+    }
+
+    fn begin_string<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(if self.in_key { KEY } else { STRING })?;
+        writer.write_all(b"\"")
+    }
+
+    fn end_string<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"\"")?;
+        writer.write_all(RESET)
+    }
+
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.pretty.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.in_key = true;
+        self.pretty.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.in_key = false;
+        self.pretty.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_object_value(writer)
+    }
+}
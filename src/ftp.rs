@@ -0,0 +1,279 @@
+// Minimal FTP/FTPS client: passive-mode listings, downloads and uploads.
+//
+// `ftps://` URLs use implicit TLS (port 990); plain `ftp://` can be upgraded
+// with AUTH TLS ("explicit" FTPS), in which case data channels are also
+// protected (PROT P).
+
+use crate::stream::Stream;
+use crate::transfer::{self, TransferLimits};
+use native_tls::TlsConnector;
+use percent_encoding::percent_decode_str;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Session {
+    control: Stream,
+    host: String,
+    tls: Option<TlsConnector>,
+    protect_data: bool,
+    limits: TransferLimits,
+}
+
+// Download a file, or list a directory when the path ends with '/'.
+pub fn fetch(url: &Url, explicit_tls: bool, limits: &TransferLimits) -> Result<Vec<u8>, String> {
+    let mut session = Session::connect(url, explicit_tls, limits)?;
+    let path = remote_path(url);
+    let command = if path.ends_with('/') {
+        format!("LIST {}", path)
+    } else {
+        format!("RETR {}", path)
+    };
+
+    let data = session.start_transfer(&command)?;
+    let body =
+        transfer::read_body(data, limits).map_err(|e| format!("FTP transfer failed: {}", e))?;
+    session.finish_transfer()?;
+    session.quit();
+    Ok(body)
+}
+
+// Store a local file; a URL ending in '/' keeps the local file name.
+pub fn upload(
+    url: &Url,
+    file: &Path,
+    explicit_tls: bool,
+    limits: &TransferLimits,
+) -> Result<u64, String> {
+    let mut source =
+        File::open(file).map_err(|e| format!("Unable to read '{}': {}", file.display(), e))?;
+
+    let mut path = remote_path(url);
+    if path.ends_with('/') {
+        let name = file.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        path.push_str(&name);
+    }
+
+    let mut session = Session::connect(url, explicit_tls, limits)?;
+    let mut data = session.start_transfer(&format!("STOR {}", path))?;
+    let sent = io::copy(&mut source, &mut data).map_err(|e| format!("FTP upload failed: {}", e))?;
+    data.close();
+    session.finish_transfer()?;
+    session.quit();
+    Ok(sent)
+}
+
+fn remote_path(url: &Url) -> String {
+    let path = percent_decode_str(url.path()).decode_utf8_lossy().to_string();
+    if path.is_empty() { "/".to_string() } else { path }
+}
+
+impl Session {
+    fn connect(url: &Url, explicit_tls: bool, limits: &TransferLimits) -> Result<Self, String> {
+        let host = url
+            .host_str()
+            .ok_or("The FTP URL has no host.")?
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_string();
+        let implicit_tls = url.scheme() == "ftps";
+        let port = url
+            .port()
+            .unwrap_or(if implicit_tls { 990 } else { 21 });
+
+        let tcp = connect_tcp(&host, port)?;
+        apply_limits(&tcp, limits);
+        let tls = if implicit_tls || explicit_tls {
+            Some(TlsConnector::new().map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+
+        let control = match (&tls, implicit_tls) {
+            (Some(connector), true) => wrap_tls(connector, &host, tcp)?,
+            _ => Stream::Plain(tcp),
+        };
+
+        let mut session = Session {
+            control,
+            host,
+            tls,
+            protect_data: false,
+            limits: *limits,
+        };
+        session.expect_reply(&[220])?;
+
+        if explicit_tls && !implicit_tls {
+            session.command("AUTH TLS", &[234])?;
+            let Stream::Plain(tcp) = session.control else {
+                unreachable!("control channel is plain before AUTH TLS");
+            };
+            let connector = session.tls.as_ref().expect("TLS requested");
+            session.control = wrap_tls(connector, &session.host, tcp)?;
+        }
+        if session.tls.is_some() {
+            session.command("PBSZ 0", &[200])?;
+            session.command("PROT P", &[200])?;
+            session.protect_data = true;
+        }
+
+        let user = match url.username() {
+            "" => "anonymous".to_string(),
+            u => percent_decode_str(u).decode_utf8_lossy().to_string(),
+        };
+        let pass = url
+            .password()
+            .map(|p| percent_decode_str(p).decode_utf8_lossy().to_string())
+            .unwrap_or_else(|| "anonymous@".to_string());
+
+        let (code, _) = session.command(&format!("USER {}", user), &[230, 331])?;
+        if code == 331 {
+            session.command(&format!("PASS {}", pass), &[230, 202])?;
+        }
+        session.command("TYPE I", &[200])?;
+        Ok(session)
+    }
+
+    // Open a passive data connection and issue `command` over it.
+    fn start_transfer(&mut self, command: &str) -> Result<Stream, String> {
+        let addr = self.passive_address()?;
+        let tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Unable to open FTP data connection: {}", e))?;
+        apply_limits(&tcp, &self.limits);
+
+        self.command(command, &[125, 150])?;
+
+        // TLS on the data channel starts after the server accepts the command
+        match (&self.tls, self.protect_data) {
+            (Some(connector), true) => wrap_tls(connector, &self.host, tcp),
+            _ => Ok(Stream::Plain(tcp)),
+        }
+    }
+
+    fn finish_transfer(&mut self) -> Result<(), String> {
+        self.expect_reply(&[226, 250]).map(|_| ())
+    }
+
+    fn passive_address(&mut self) -> Result<SocketAddr, String> {
+        let peer = self
+            .control
+            .tcp()
+            .peer_addr()
+            .map_err(|e| e.to_string())?;
+
+        if let Ok((_, text)) = self.command("EPSV", &[229]) {
+            // 229 Entering Extended Passive Mode (|||port|)
+            let port = text
+                .rsplit("|||")
+                .next()
+                .and_then(|s| s.split('|').next())
+                .and_then(|s| s.parse::<u16>().ok())
+                .ok_or_else(|| format!("Unexpected EPSV reply: {}", text))?;
+            return Ok(SocketAddr::new(peer.ip(), port));
+        }
+
+        // 227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)
+        let (_, text) = self.command("PASV", &[227])?;
+        let inner = text
+            .split_once('(')
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .map(|(inner, _)| inner)
+            .ok_or_else(|| format!("Unexpected PASV reply: {}", text))?;
+        let nums: Vec<u8> = inner
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+        if nums.len() != 6 {
+            return Err(format!("Unexpected PASV reply: {}", text));
+        }
+        let ip = IpAddr::from([nums[0], nums[1], nums[2], nums[3]]);
+        let port = u16::from(nums[4]) << 8 | u16::from(nums[5]);
+        Ok(SocketAddr::new(ip, port))
+    }
+
+    fn command(&mut self, command: &str, ok: &[u16]) -> Result<(u16, String), String> {
+        self.control
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .map_err(|e| format!("FTP connection lost: {}", e))?;
+        self.expect_reply(ok)
+    }
+
+    fn expect_reply(&mut self, ok: &[u16]) -> Result<(u16, String), String> {
+        let (code, text) = self.read_reply()?;
+        if ok.contains(&code) {
+            Ok((code, text))
+        } else {
+            Err(format!("FTP server replied: {} {}", code, text))
+        }
+    }
+
+    // Replies may span lines: "123-first ... 123 last".
+    fn read_reply(&mut self) -> Result<(u16, String), String> {
+        let first = self.read_line()?;
+        let code: u16 = first
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| format!("Malformed FTP reply: {}", first))?;
+
+        let mut text = first[3..].trim_start_matches([' ', '-']).to_string();
+        if first.as_bytes().get(3) == Some(&b'-') {
+            let terminator = format!("{} ", code);
+            loop {
+                let line = self.read_line()?;
+                if line.starts_with(&terminator) {
+                    text = line[4..].to_string();
+                    break;
+                }
+            }
+        }
+        Ok((code, text))
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            match self.control.read(&mut byte) {
+                Ok(0) => return Err("FTP server closed the connection.".into()),
+                Ok(_) if byte[0] == b'\n' => break,
+                Ok(_) => line.push(byte[0]),
+                Err(e) => return Err(format!("FTP connection lost: {}", e)),
+            }
+        }
+        Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+    }
+
+    fn quit(mut self) {
+        let _ = self.command("QUIT", &[221]);
+        self.control.close();
+    }
+}
+
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream, String> {
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|_| format!("Unable to resolve FTP host '{}'.", host))?;
+    for addr in addrs {
+        if let Ok(stream) = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            return Ok(stream);
+        }
+    }
+    Err(format!("Unable to connect to FTP server {}:{}.", host, port))
+}
+
+// Socket limits make a silent peer surface as a "timed out" I/O error.
+fn apply_limits(tcp: &TcpStream, limits: &TransferLimits) {
+    let _ = tcp.set_read_timeout(limits.read);
+    let _ = tcp.set_write_timeout(limits.write);
+}
+
+fn wrap_tls(connector: &TlsConnector, host: &str, tcp: TcpStream) -> Result<Stream, String> {
+    connector
+        .connect(host, tcp)
+        .map(|s| Stream::Tls(Box::new(s)))
+        .map_err(|e| format!("FTP TLS handshake failed: {}", e))
+}
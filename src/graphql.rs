@@ -0,0 +1,121 @@
+// GraphQL over HTTP: --query, --variables and --operation-name become the
+// standard JSON POST envelope
+//
+//   {"query": "...", "variables": {...}, "operationName": "..."}
+//
+// and the reply's `data` is printed while each entry of `errors` is
+// reported as an error. A server can answer 200 with errors and partial
+// data, so the status alone doesn't say whether the operation worked.
+
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, Read};
+
+// Servers that implement the GraphQL-over-HTTP spec answer with the first,
+// older ones with the second.
+pub const ACCEPT: &str = "application/graphql-response+json, application/json";
+
+pub struct Reply {
+    // None when the server sent `"data": null` or left it out
+    pub data: Option<Value>,
+    pub errors: Vec<String>,
+}
+
+// The request body. The query and the variables may be @file, or @- for
+// stdin.
+pub fn envelope(
+    query: &str,
+    variables: Option<&str>,
+    operation_name: Option<&str>,
+) -> Result<Value, String> {
+    let query = read_arg(query)?;
+    if query.trim().is_empty() {
+        return Err("--query is empty.".to_string());
+    }
+    let mut envelope = Map::new();
+    envelope.insert("query".to_string(), Value::String(query));
+    if let Some(variables) = variables {
+        let text = read_arg(variables)?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("--variables isn't valid JSON: {}", e))?;
+        if !value.is_object() {
+            return Err("--variables must be a JSON object, e.g. '{\"id\":\"1\"}'.".to_string());
+        }
+        envelope.insert("variables".to_string(), value);
+    }
+    if let Some(name) = operation_name {
+        envelope.insert("operationName".to_string(), Value::String(name.to_string()));
+    }
+    Ok(Value::Object(envelope))
+}
+
+pub fn parse_reply(text: &str) -> Result<Reply, String> {
+    let Ok(Value::Object(mut reply)) = serde_json::from_str::<Value>(text) else {
+        return Err("The response isn't a GraphQL reply (not a JSON object).".to_string());
+    };
+    if !reply.contains_key("data") && !reply.contains_key("errors") {
+        return Err("The response has neither `data` nor `errors`.".to_string());
+    }
+    let errors = match reply.remove("errors") {
+        Some(Value::Array(errors)) => errors.iter().map(describe).collect(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(other) => vec![describe(&other)],
+    };
+    let data = reply.remove("data").filter(|d| !d.is_null());
+    Ok(Reply { data, errors })
+}
+
+// "Cannot query field \"nme\" on type \"User\" (at 3:5, path user.0.nme) [GRAPHQL_VALIDATION_FAILED]"
+fn describe(error: &Value) -> String {
+    let mut text = match error.get("message").and_then(Value::as_str) {
+        Some(message) => message.to_string(),
+        None => error.to_string(),
+    };
+    let mut place = Vec::new();
+    if let Some(locations) = error.get("locations").and_then(Value::as_array) {
+        let at: Vec<String> = locations
+            .iter()
+            .filter_map(|l| Some(format!("{}:{}", l.get("line")?, l.get("column")?)))
+            .collect();
+        if !at.is_empty() {
+            place.push(format!("at {}", at.join(", ")));
+        }
+    }
+    if let Some(path) = error.get("path").and_then(Value::as_array) {
+        let segments: Vec<String> = path
+            .iter()
+            .map(|s| match s {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect();
+        place.push(format!("path {}", segments.join(".")));
+    }
+    if !place.is_empty() {
+        text.push_str(&format!(" ({})", place.join(", ")));
+    }
+    if let Some(code) = error
+        .get("extensions")
+        .and_then(|e| e.get("code"))
+        .and_then(Value::as_str)
+    {
+        text.push_str(&format!(" [{}]", code));
+    }
+    text
+}
+
+fn read_arg(arg: &str) -> Result<String, String> {
+    match arg.strip_prefix('@') {
+        Some("-") => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Unable to read stdin: {}", e))?;
+            Ok(text)
+        }
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| format!("Unable to read '{}': {}", path, e))
+        }
+        None => Ok(arg.to_string()),
+    }
+}
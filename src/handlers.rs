@@ -0,0 +1,567 @@
+// One handler for each kind of command-line request: plain, form, JSON,
+// binary, multipart and upload bodies, pagination, SSE, --bench and --raw.
+// Each sends through the run's WebClient and hands the response to
+// `response`.
+
+use crate::cli::Cli;
+use crate::exit::Category;
+use crate::method::Method;
+use crate::response::includes_headers;
+use crate::response::print_body;
+use crate::response::print_headers;
+use crate::response::{check_snapshot, print_merged, print_response, request_failed};
+use crate::run::dispatch;
+use crate::schemes::SchemeRegistry;
+use crate::{WebClient, errln};
+use crate::{
+    assertions, bench, deadline, download, exit, form, har, output, paginate, raw, retry, sigv4,
+    sse, transfer, unix_socket,
+};
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HOST, HeaderMap,
+    HeaderValue,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+use url::Url;
+
+// A request without a body: GET, HEAD, OPTIONS, or DELETE/PUT/PATCH
+// without a body option.
+pub fn handle_request(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+) {
+    let mut started = Instant::now();
+    let res = retry::send(&args.retry_policy(), || {
+        let req = client.request(method, url.clone()).headers(headers.clone());
+        trace_request(&req, None, args);
+        started = Instant::now();
+        client.execute(req).map_err(|e| client.failure(&e, "Unable to connect to the server. Perhaps the network is offline or the server hostname cannot be resolved."))
+    });
+
+    match res {
+        Ok(r) => print_response(r, args, started),
+        Err(e) => request_failed(&e),
+    }
+}
+
+pub fn handle_form_post(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+    data: &str,
+) {
+    output::status(format!("Data: {}", data));
+    let form_data: Vec<(&str, &str)> = data.split('&').filter_map(|s| s.split_once('=')).collect();
+
+    let mut started = Instant::now();
+    let res = retry::send(&args.retry_policy(), || {
+        let req = client
+            .request(method, url.clone())
+            .headers(headers.clone())
+            .form(&form_data);
+        trace_request(&req, None, args);
+        started = Instant::now();
+        client
+            .execute(req)
+            .map_err(|e| client.failure(&e, "Unable to connect to the server."))
+    });
+
+    match res {
+        Ok(r) => print_response(r, args, started),
+        Err(e) => request_failed(&e),
+    }
+}
+
+pub fn handle_json_post(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+    json_str: &str,
+) {
+    output::status(format!("JSON: {}", json_str));
+
+    let parsed: Value = match serde_json::from_str(json_str) {
+        Ok(p) => p,
+        Err(e) => {
+            output::error(exit::Error::new(
+                Category::Failed,
+                format!("Invalid JSON in --json: {}.", e),
+            ));
+            return;
+        }
+    };
+
+    let mut started = Instant::now();
+    let res = retry::send(&args.retry_policy(), || {
+        let req = client
+            .request(method, url.clone())
+            .headers(headers.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .json(&parsed);
+        trace_request(&req, None, args);
+        started = Instant::now();
+        client
+            .execute(req)
+            .map_err(|e| client.failure(&e, "Unable to connect to the server."))
+    });
+
+    match res {
+        Ok(r) => print_response(r, args, started),
+        Err(e) => request_failed(&e),
+    }
+}
+
+// --paginate: every page is requested like the first. Credentials aren't
+// sent along when a page links to a different origin.
+pub fn handle_paginate(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: HeaderMap,
+    args: &Cli,
+) {
+    let mut headers = headers;
+    let mut url = url.clone();
+    let mut seen = HashSet::new();
+    loop {
+        seen.insert(url.to_string());
+        dispatch(client, method, &url, &headers, args);
+        let Some(next) = paginate::take_next() else {
+            break;
+        };
+        let pages = paginate::pages();
+        if seen.contains(next.as_str()) {
+            errln!("Warning: Page {} links back to {}; stopping.", pages, next);
+            break;
+        }
+        if pages >= args.max_pages {
+            errln!(
+                "Warning: Stopped after {} pages (--max-pages); the last one links to {}.",
+                pages,
+                next
+            );
+            break;
+        }
+        if let Err(e) = deadline::check() {
+            output::error(e);
+            break;
+        }
+        if next.origin() != url.origin() {
+            let auth = headers.remove(AUTHORIZATION).is_some();
+            let cookies = headers.remove(COOKIE).is_some();
+            if (auth || cookies) && args.verbose {
+                errln!(
+                    "* Not sending credentials to {}",
+                    next.origin().ascii_serialization()
+                );
+            }
+        }
+        output::status(format!("Next page: {}", next));
+        assertions::begin(output::redact(&format!("{} {}", method, next)));
+        url = next;
+    }
+    if let Some(items) = paginate::take_merged() {
+        print_merged(items, args);
+    }
+}
+
+// --sse: an event stream that ends is reconnected up to --retry times,
+// after the delay the server asked for with `retry:` (else --retry-delay),
+// resuming from the last event ID it sent.
+pub fn handle_sse(client: &WebClient, method: Method, url: &Url, headers: &HeaderMap, args: &Cli) {
+    let mut headers = headers.clone();
+    if !headers.contains_key(ACCEPT) {
+        headers.insert(ACCEPT, HeaderValue::from_static("text/event-stream"));
+    }
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    let mut reconnects = 0;
+    loop {
+        dispatch(client, method, url, &headers, args);
+        let Some(ended) = sse::take_ended() else {
+            return;
+        };
+        let wait = ended
+            .retry
+            .unwrap_or_else(|| args.retry_delay.unwrap_or(retry::DEFAULT_DELAY));
+        if reconnects >= args.retry || deadline::remaining().is_some_and(|left| wait >= left) {
+            if let Some(e) = ended.error {
+                output::error(e);
+            }
+            return;
+        }
+        reconnects += 1;
+        let id = sse::last_event_id();
+        errln!(
+            "Warning: The event stream {}; reconnecting in {} ({} of {}){}.",
+            ended.error.map_or("ended".to_string(), |e| format!(
+                "broke off ({})",
+                e.trim_end_matches('.')
+            )),
+            transfer::describe(wait),
+            reconnects,
+            args.retry,
+            id.as_ref()
+                .map_or(String::new(), |id| format!(", resuming after event {}", id))
+        );
+        if let Some(value) = id.and_then(|id| HeaderValue::from_str(&id).ok()) {
+            headers.insert("last-event-id", value);
+        }
+        thread::sleep(wait);
+    }
+}
+
+// --bench: the same request over and over, with the body prepared once.
+pub fn handle_bench(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+) {
+    let body = match bench_body(args) {
+        Ok(body) => body,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    let config = bench::Config {
+        requests: args.requests.unwrap_or(bench::DEFAULT_REQUESTS),
+        concurrency: args.concurrency.unwrap_or(bench::DEFAULT_CONCURRENCY),
+    };
+    bench::run(&config, || {
+        let mut req = client.request(method, url.clone()).headers(headers.clone());
+        if let Some((bytes, content_type)) = &body {
+            if let Some(content_type) = content_type
+                && !headers.contains_key(CONTENT_TYPE)
+            {
+                req = req.header(CONTENT_TYPE, *content_type);
+            }
+            req = req.body(bytes.clone());
+        }
+        let res = client.execute(req).map_err(|e| {
+            let failure = client.failure(&e, "connection failed");
+            failure.transient.map_or(failure.message, str::to_string)
+        })?;
+        let status = res.status().as_u16();
+        let body = res.bytes().map_err(|_| "body interrupted".to_string())?;
+        Ok((status, body.len() as u64))
+    });
+}
+
+// The bytes and default Content-Type of a --bench body.
+type BenchBody = (Vec<u8>, Option<&'static str>);
+
+// The body from whichever body option is set.
+fn bench_body(args: &Cli) -> Result<Option<BenchBody>, String> {
+    const FORM: Option<&str> = Some("application/x-www-form-urlencoded");
+    if !args.body.form.is_empty() {
+        return Err(
+            "--bench can't send -F forms; prepare the body for --data-binary instead.".to_string(),
+        );
+    }
+    if let Some(json) = &args.body.json {
+        return Ok(Some((json.clone().into_bytes(), Some("application/json"))));
+    }
+    if let Some(data) = &args.body.data {
+        let encoded = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(data.split('&').filter_map(|s| s.split_once('=')))
+            .finish();
+        return Ok(Some((encoded.into_bytes(), FORM)));
+    }
+    if let Some(data) = &args.body.data_binary {
+        let bytes = match data.strip_prefix('@') {
+            Some("-") => {
+                let mut bytes = Vec::new();
+                io::stdin()
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| format!("Unable to read stdin: {}", e))?;
+                bytes
+            }
+            Some(path) => {
+                fs::read(path).map_err(|e| format!("Unable to read '{}': {}", path, e))?
+            }
+            None => data.as_bytes().to_vec(),
+        };
+        return Ok(Some((bytes, FORM)));
+    }
+    if let Some(path) = &args.body.upload_file {
+        let bytes =
+            fs::read(path).map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+        return Ok(Some((bytes, None)));
+    }
+    Ok(None)
+}
+
+// --data-binary: the bytes go out untouched. A file is streamed from disk
+// on every attempt; stdin is read once and kept for retries.
+pub fn handle_binary_post(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+    data: &str,
+) {
+    let source = match data.strip_prefix('@') {
+        Some("-") => {
+            let mut bytes = Vec::new();
+            if let Err(e) = io::stdin().read_to_end(&mut bytes) {
+                output::error(format!("Unable to read stdin: {}", e));
+                return;
+            }
+            output::status(format!(
+                "Data: {} from stdin",
+                download::size(bytes.len() as u64)
+            ));
+            BinarySource::Bytes(bytes)
+        }
+        Some(path) => {
+            let path = PathBuf::from(path);
+            match fs::metadata(&path) {
+                Ok(meta) if meta.is_file() => {}
+                Ok(_) => {
+                    output::error(format!("'{}' is not a file.", path.display()));
+                    return;
+                }
+                Err(e) => {
+                    output::error(format!("Unable to read '{}': {}", path.display(), e));
+                    return;
+                }
+            }
+            output::status(format!("Data: @{}", path.display()));
+            BinarySource::File(path)
+        }
+        None => {
+            output::status(format!("Data: {}", data));
+            BinarySource::Bytes(data.as_bytes().to_vec())
+        }
+    };
+    // curl's default for -d bodies; an explicit -H Content-Type wins
+    let content_type =
+        (!headers.contains_key(CONTENT_TYPE)).then_some("application/x-www-form-urlencoded");
+
+    let mut started = Instant::now();
+    let res = retry::send(&args.retry_policy(), || {
+        let mut req = client.request(method, url.clone()).headers(headers.clone());
+        if let Some(content_type) = content_type {
+            req = req.header(CONTENT_TYPE, content_type);
+        }
+        match &source {
+            BinarySource::Bytes(bytes) => {
+                let req = req.body(bytes.clone());
+                trace_request(&req, None, args);
+                started = Instant::now();
+                client
+                    .execute(req)
+                    .map_err(|e| client.failure(&e, "Unable to connect to the server."))
+            }
+            BinarySource::File(path) => {
+                let file = fs::File::open(path).map_err(|e| retry::Failure {
+                    message: format!("Unable to read '{}': {}", path.display(), e),
+                    transient: None,
+                    category: Category::Failed,
+                })?;
+                let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+                trace_request(&req, Some(len), args);
+                started = Instant::now();
+                client
+                    .upload(req, file, len)
+                    .map_err(|e| client.upload_failure(e))
+            }
+        }
+    });
+
+    match res {
+        Ok(r) => print_response(r, args, started),
+        Err(e) => request_failed(&e),
+    }
+}
+
+enum BinarySource {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+pub fn handle_multipart(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+) {
+    let fields = match args
+        .body
+        .form
+        .iter()
+        .map(|spec| form::parse(spec))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(fields) => fields,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    let summary: Vec<String> = fields.iter().map(form::Field::describe).collect();
+    output::status(format!("Form: {}", summary.join(", ")));
+
+    let mut started = Instant::now();
+    let res = retry::send(&args.retry_policy(), || {
+        let body = form::build(&fields).map_err(|message| retry::Failure {
+            message,
+            transient: None,
+            category: Category::Failed,
+        })?;
+        let req = client
+            .request(method, url.clone())
+            .headers(headers.clone())
+            .header(CONTENT_TYPE, body.content_type);
+        trace_request(&req, Some(body.len), args);
+        started = Instant::now();
+        client
+            .upload(req, body.reader, body.len)
+            .map_err(|e| client.upload_failure(e))
+    });
+
+    match res {
+        Ok(r) => print_response(r, args, started),
+        Err(e) => request_failed(&e),
+    }
+}
+
+pub fn handle_upload(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    args: &Cli,
+    path: &Path,
+) {
+    if let Err(e) = fs::File::open(path) {
+        output::error(format!("Unable to read '{}': {}", path.display(), e));
+        return;
+    }
+
+    let mut started = Instant::now();
+    let res = retry::send(&args.retry_policy(), || {
+        // Each attempt sends the file from the start
+        let file = fs::File::open(path).map_err(|e| retry::Failure {
+            message: format!("Unable to read '{}': {}", path.display(), e),
+            transient: None,
+            category: Category::Failed,
+        })?;
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let req = client.request(method, url.clone()).headers(headers.clone());
+        trace_request(&req, Some(len), args);
+        started = Instant::now();
+        client
+            .upload(req, file, len)
+            .map_err(|e| client.upload_failure(e))
+    });
+
+    match res {
+        Ok(r) => print_response(r, args, started),
+        Err(e) => request_failed(&e),
+    }
+}
+
+// -v: the request line and headers as they go out. Host, Accept and
+// Content-Length are filled in by reqwest when absent, so they're shown
+// the way it will send them. Credentials marked sensitive are masked.
+fn trace_request(req: &RequestBuilder, body_len: Option<u64>, args: &Cli) {
+    if !args.verbose && args.har.is_none() {
+        return;
+    }
+    // Only a builder without a streaming body can be cloned; uploads get
+    // their body after this
+    let Some(req) = req.try_clone().and_then(|b| b.build().ok()) else {
+        return;
+    };
+    har::request(&req, body_len);
+    if !args.verbose {
+        return;
+    }
+    let url = req.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    errln!(
+        "> {} {} {:?}",
+        req.method(),
+        output::redact(&target),
+        req.version()
+    );
+    let headers = req.headers();
+    if !headers.contains_key(HOST) {
+        errln!("> host: {}", sigv4::host_header(url));
+    }
+    for (name, value) in headers {
+        if name == unix_socket::TOKEN_HEADER {
+            continue;
+        }
+        let value = if value.is_sensitive() {
+            "[redacted]".into()
+        } else {
+            output::redact(&String::from_utf8_lossy(value.as_bytes()))
+        };
+        errln!("> {}: {}", name, value);
+    }
+    if !headers.contains_key(ACCEPT) {
+        errln!("> accept: */*");
+    }
+    let body_len = body_len.or_else(|| {
+        req.body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| b.len() as u64)
+    });
+    if let Some(len) = body_len
+        && !headers.contains_key(CONTENT_LENGTH)
+    {
+        errln!("> content-length: {}", len);
+    }
+    errln!(">");
+}
+
+pub fn handle_raw(url: &Url, request: &[u8], args: &Cli) {
+    if !SchemeRegistry::is_http(url.scheme()) {
+        output::error("--raw-request only supports http:// and https:// URLs.");
+        return;
+    }
+
+    let started = Instant::now();
+    let res = match raw::send(url, request, &args.limits(), args.verbose) {
+        Ok(r) => r,
+        Err(e) => return request_failed(&e),
+    };
+    if includes_headers(args) {
+        print_headers(&format!("HTTP/1.1 {}", res.status), &res.headers, args);
+    }
+    if !(200..300).contains(&res.status) {
+        let message = format!("Request failed with status code: {}.", res.status);
+        output::error(exit::Error::new(Category::Http, message.clone()));
+        assertions::outcome(Some(message), Some(started.elapsed()));
+        return;
+    }
+
+    assertions::outcome(None, Some(started.elapsed()));
+    let text = transfer::decode_text(&res.body, res.header("content-type"));
+    print_body(&res.body, &text, res.header("content-type"), args);
+    check_snapshot(&text, args);
+}
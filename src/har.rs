@@ -0,0 +1,415 @@
+// --har FILE: record the exchange as an HTTP Archive (HAR 1.2) that browser
+// devtools and HAR viewers can open.
+//
+// An entry holds the request as sent (the last attempt when retried), the
+// final response with its decoded body, and timings. With several URLs
+// the file holds one entry for each, in the order given. Header values marked
+// sensitive, such as credentials, are written as "[redacted]", as -v
+// shows them.
+//
+// reqwest follows -L redirects internally, so such an entry pairs the
+// first request with the last response. A request that got no response is
+// kept with status 0, the way browsers export failed requests.
+
+use crate::sigv4;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{CONTENT_TYPE, COOKIE, HeaderMap, LOCATION, SET_COOKIE};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    started_date_time: String,
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    cache: Cache,
+    timings: Timings,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    query_string: Vec<NameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<PostData>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    status: u16,
+    status_text: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    content: Content,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<i64>,
+    mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct Cache {}
+
+// Milliseconds; -1 where the phase isn't known.
+#[derive(Serialize)]
+struct Timings {
+    blocked: f64,
+    dns: f64,
+    connect: f64,
+    ssl: f64,
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+struct Sent {
+    started: SystemTime,
+    clock: Instant,
+    request: HarRequest,
+}
+
+struct Received {
+    response: HarResponse,
+    // Since the request was sent
+    headers_at: Duration,
+    body_at: Option<Duration>,
+}
+
+struct State {
+    enabled: bool,
+    namelookup: Option<Duration>,
+    sent: Option<Sent>,
+    received: Option<Received>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const {
+        RefCell::new(State {
+            enabled: false,
+            namelookup: None,
+            sent: None,
+            received: None,
+        })
+    };
+}
+
+// The recording functions do nothing until this is called.
+pub fn enable() {
+    STATE.with_borrow_mut(|state| state.enabled = true);
+}
+
+pub fn namelookup(took: Duration) {
+    STATE.with_borrow_mut(|state| state.namelookup = Some(took));
+}
+
+// The request about to be sent. `body_len` is the size of a streamed body,
+// whose bytes aren't recorded.
+pub fn request(req: &Request, body_len: Option<u64>) {
+    if !STATE.with_borrow(|state| state.enabled) {
+        return;
+    }
+    let mut headers = pairs(req.headers());
+    if !req.headers().contains_key("host") {
+        let host = NameValue {
+            name: "host".to_string(),
+            value: sigv4::host_header(req.url()),
+        };
+        headers.insert(0, host);
+    }
+    let bytes = req.body().and_then(|b| b.as_bytes());
+    let post_data = bytes.map(|bytes| PostData {
+        mime_type: header(req.headers(), CONTENT_TYPE.as_str()),
+        text: String::from_utf8_lossy(bytes).into_owned(),
+    });
+    let body_size = bytes.map(|b| b.len() as u64).or(body_len).unwrap_or(0);
+    let request = HarRequest {
+        method: req.method().to_string(),
+        url: req.url().to_string(),
+        http_version: format!("{:?}", req.version()),
+        cookies: cookies(req.headers().get_all(COOKIE).iter().flat_map(|v| {
+            String::from_utf8_lossy(v.as_bytes())
+                .split(';')
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })),
+        headers,
+        query_string: req
+            .url()
+            .query_pairs()
+            .map(|(name, value)| NameValue {
+                name: name.into_owned(),
+                value: value.into_owned(),
+            })
+            .collect(),
+        post_data,
+        headers_size: -1,
+        body_size: body_size as i64,
+    };
+    STATE.with_borrow_mut(|state| {
+        state.sent = Some(Sent {
+            started: SystemTime::now(),
+            clock: Instant::now(),
+            request,
+        });
+        state.received = None;
+    });
+}
+
+// The final response's status line and headers have arrived.
+pub fn response(res: &Response) {
+    let Some(headers_at) = STATE.with_borrow(|state| Some(state.sent.as_ref()?.clock.elapsed()))
+    else {
+        return;
+    };
+    let status_line = format!("{:?} {}\r\n", res.version(), res.status()).len();
+    let header_lines: usize = res
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    let response = HarResponse {
+        status: res.status().as_u16(),
+        status_text: res
+            .status()
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_string(),
+        http_version: format!("{:?}", res.version()),
+        cookies: cookies(
+            res.headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned()),
+        ),
+        headers: pairs(res.headers()),
+        content: Content {
+            mime_type: header(res.headers(), CONTENT_TYPE.as_str()),
+            ..Content::default()
+        },
+        redirect_url: header(res.headers(), LOCATION.as_str()),
+        headers_size: (status_line + header_lines + 2) as i64,
+        body_size: -1,
+    };
+    STATE.with_borrow_mut(|state| {
+        state.received = Some(Received {
+            response,
+            headers_at,
+            body_at: None,
+        })
+    });
+}
+
+// The response body, decoded, and how many bytes came over the wire.
+pub fn content(body: &[u8], transferred: usize) {
+    STATE.with_borrow_mut(|state| {
+        let (Some(sent), Some(received)) = (&state.sent, &mut state.received) else {
+            return;
+        };
+        received.body_at = Some(sent.clock.elapsed());
+        let response = &mut received.response;
+        response.body_size = transferred as i64;
+        response.content.size = body.len() as i64;
+        response.content.compression = Some(body.len() as i64 - transferred as i64);
+        match std::str::from_utf8(body) {
+            Ok(text) => response.content.text = Some(text.to_string()),
+            Err(_) => {
+                response.content.text = Some(STANDARD.encode(body));
+                response.content.encoding = Some("base64".to_string());
+            }
+        }
+    });
+}
+
+// A body saved to a file (-o) is only counted.
+pub fn saved(transferred: u64) {
+    STATE.with_borrow_mut(|state| {
+        let (Some(sent), Some(received)) = (&state.sent, &mut state.received) else {
+            return;
+        };
+        received.body_at = Some(sent.clock.elapsed());
+        received.response.body_size = transferred as i64;
+        received.response.content.size = transferred as i64;
+    });
+}
+
+// The entry recorded for this request; None if no request was sent.
+pub fn entry() -> Result<Option<Value>, String> {
+    STATE.with_borrow(|state| {
+        state
+            .sent
+            .as_ref()
+            .map(|sent| to_entry(state, sent))
+            .transpose()
+    })
+}
+
+fn to_entry(state: &State, sent: &Sent) -> Result<Value, String> {
+    let total = sent.clock.elapsed();
+    let ms = |d: Duration| (d.as_secs_f64() * 1e6).round() / 1e3;
+    let (response, timings) = match &state.received {
+        Some(received) => {
+            let body_at = received.body_at.unwrap_or(received.headers_at);
+            let timings = Timings {
+                blocked: -1.0,
+                dns: state.namelookup.map_or(-1.0, ms),
+                connect: -1.0,
+                ssl: -1.0,
+                send: 0.0,
+                wait: ms(received.headers_at),
+                receive: ms(body_at - received.headers_at),
+            };
+            (received.response.clone(), timings)
+        }
+        None => (failed_response(), unknown_timings(ms(total))),
+    };
+    let entry = Entry {
+        started_date_time: date_time(sent.started),
+        time: timings.wait.max(0.0) + timings.receive.max(0.0),
+        request: sent.request.clone(),
+        response,
+        cache: Cache {},
+        timings,
+    };
+    serde_json::to_value(&entry).map_err(|e| e.to_string())
+}
+
+pub fn write(path: &Path, entries: Vec<Value>) -> Result<(), String> {
+    fs::write(path, format!("{:#}\n", archive(entries)))
+        .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))
+}
+
+fn archive(entries: Vec<Value>) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "entries": entries,
+        }
+    })
+}
+
+fn pairs(headers: &HeaderMap) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: if value.is_sensitive() {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            },
+        })
+        .collect()
+}
+
+fn header(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get(name)
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+        .unwrap_or_default()
+}
+
+// "name=value" pairs; Set-Cookie attributes after the first ';' are dropped.
+fn cookies(values: impl Iterator<Item = String>) -> Vec<NameValue> {
+    values
+        .filter_map(|v| {
+            let pair = v.split(';').next()?.trim().to_string();
+            let (name, value) = pair.split_once('=')?;
+            Some(NameValue {
+                name: name.to_string(),
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn failed_response() -> HarResponse {
+    HarResponse {
+        status: 0,
+        status_text: String::new(),
+        http_version: String::new(),
+        cookies: Vec::new(),
+        headers: Vec::new(),
+        content: Content::default(),
+        redirect_url: String::new(),
+        headers_size: -1,
+        body_size: -1,
+    }
+}
+
+fn unknown_timings(total: f64) -> Timings {
+    Timings {
+        blocked: -1.0,
+        dns: -1.0,
+        connect: -1.0,
+        ssl: -1.0,
+        send: 0.0,
+        wait: total,
+        receive: 0.0,
+    }
+}
+
+// ISO 8601 with milliseconds, e.g. 2026-10-14T10:49:58.123Z.
+fn date_time(at: SystemTime) -> String {
+    let (compact, _) = sigv4::amz_timestamps(at);
+    let millis = at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_millis();
+    format!(
+        "{}-{}-{}T{}:{}:{}.{:03}Z",
+        &compact[0..4],
+        &compact[4..6],
+        &compact[6..8],
+        &compact[9..11],
+        &compact[11..13],
+        &compact[13..15],
+        millis
+    )
+}
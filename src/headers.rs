@@ -0,0 +1,109 @@
+// Request headers from the forms they're given in: "Name: value" lines
+// (-H, a profile's headers, the interactive `header` command), --cookie
+// pairs and -z time conditions. Shared by the command line and WebClient,
+// so both accept and reject the same headers.
+
+use reqwest::header::{
+    COOKIE, HeaderMap, HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_UNMODIFIED_SINCE,
+};
+use std::fs;
+
+// "Name: value" -> ("Name", "value"), both trimmed.
+pub fn split(spec: &str) -> Result<(&str, &str), String> {
+    let (name, value) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid header '{}', expected 'Name: value'.", spec))?;
+    Ok((name.trim(), value.trim()))
+}
+
+pub fn name(name: &str) -> Result<HeaderName, String> {
+    HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("Invalid header name '{}'.", name.trim()))
+}
+
+pub fn value(name: &HeaderName, value: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header '{}'.", name))
+}
+
+// --cookie pairs are merged into a single Cookie header, after any Cookie
+// value that came from -H or --header-file.
+pub fn add_cookies(headers: &mut HeaderMap, cookies: &[String]) -> Result<(), String> {
+    let mut pairs: Vec<String> = headers
+        .get(COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| vec![v.to_string()])
+        .unwrap_or_default();
+
+    for cookie in cookies {
+        for pair in cookie.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            match pair.split_once('=') {
+                Some((name, _)) if !name.trim().is_empty() => pairs.push(pair.to_string()),
+                _ => return Err(format!("Invalid cookie '{}', expected 'name=value'.", pair)),
+            }
+        }
+    }
+
+    let value = HeaderValue::from_str(&pairs.join("; "))
+        .map_err(|_| "Invalid characters in --cookie.".to_string())?;
+    headers.insert(COOKIE, value);
+    Ok(())
+}
+
+// curl's -z: a file name uses the file's modification time, anything else
+// must be an HTTP date. A leading '-' asks for "unmodified since" instead.
+// None when `cond` is neither.
+pub fn time_condition(cond: &str) -> Option<(HeaderName, HeaderValue)> {
+    let (name, spec) = match cond.strip_prefix('-') {
+        Some(rest) => (IF_UNMODIFIED_SINCE, rest),
+        None => (IF_MODIFIED_SINCE, cond),
+    };
+    let time = fs::metadata(spec)
+        .and_then(|m| m.modified())
+        .ok()
+        .or_else(|| httpdate::parse_http_date(spec).ok())?;
+    let value = httpdate::fmt_http_date(time);
+    Some((name, HeaderValue::from_str(&value).unwrap()))
+}
+
+// A header value kept in a file, such as a token that other tooling
+// rotates, without its trailing line break.
+pub fn read_value_file(path: &str) -> Result<String, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read '{}': {}", path, e))?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_lines() {
+        assert_eq!(split(" X-Env :  staging ").unwrap(), ("X-Env", "staging"));
+        assert_eq!(split("X-Url: http://a/b").unwrap(), ("X-Url", "http://a/b"));
+        assert!(split("no colon").is_err());
+        assert!(name("Bad Name").is_err());
+        assert!(value(&name("X").unwrap(), "line\nbreak").is_err());
+    }
+
+    #[test]
+    fn cookies_follow_a_cookie_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_static("from=header"));
+        add_cookies(&mut headers, &["a=1; b=2".to_string(), "c=3".to_string()]).unwrap();
+        assert_eq!(headers[COOKIE], "from=header; a=1; b=2; c=3");
+        assert!(add_cookies(&mut headers, &["=1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn time_conditions() {
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(
+            time_condition(date),
+            Some((IF_MODIFIED_SINCE, HeaderValue::from_static(date)))
+        );
+        let (name, _) = time_condition(&format!("-{}", date)).unwrap();
+        assert_eq!(name, IF_UNMODIFIED_SINCE);
+        assert_eq!(time_condition("neither a file nor a date"), None);
+    }
+}
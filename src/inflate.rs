@@ -0,0 +1,464 @@
+// Content-Encoding decoding for gzip (RFC 1952) and deflate (RFC 1950/1951).
+//
+// The build has no compression backend, so this is a plain DEFLATE
+// decoder: bodies are small enough that a simple bit reader and canonical
+// Huffman tables decoded one bit at a time are plenty fast.
+//
+// A few hundred bytes of DEFLATE can stand for gigabytes, so decoding
+// stops with an error past MAX_DECODED bytes. The gzip CRC-32 and length
+// and the zlib Adler-32 trailers are checked, so a corrupt or truncated
+// body is an error rather than garbage.
+
+// The most a body may decode to.
+pub const MAX_DECODED: usize = 512 * 1024 * 1024;
+
+// Lengths and distances for codes 257..=285 and 0..=29 (RFC 1951 3.2.5).
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order of the code length code lengths in a dynamic block header.
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// Undo a Content-Encoding list such as "gzip" or "deflate, gzip"
+// (applied in order, so decoded last to first).
+pub fn decode(body: Vec<u8>, content_encoding: &str) -> Result<Vec<u8>, String> {
+    decode_with_limit(body, content_encoding, MAX_DECODED)
+}
+
+pub fn decode_with_limit(
+    body: Vec<u8>,
+    content_encoding: &str,
+    limit: usize,
+) -> Result<Vec<u8>, String> {
+    let mut body = body;
+    for coding in content_encoding.rsplit(',').map(str::trim) {
+        body = match coding.to_ascii_lowercase().as_str() {
+            "" | "identity" => body,
+            "gzip" | "x-gzip" => gunzip(&body, limit)?,
+            "deflate" => inflate_zlib(&body, limit)?,
+            other => return Err(format!("Content-Encoding '{}' is not supported", other)),
+        };
+    }
+    Ok(body)
+}
+
+fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let invalid = || "The gzip body is corrupt.".to_string();
+    if data.len() < 18 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+        return Err(invalid());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & 0x04 != 0 {
+        let extra = *data.get(pos).ok_or_else(invalid)? as usize
+            | (*data.get(pos + 1).ok_or_else(invalid)? as usize) << 8;
+        pos += 2 + extra;
+    }
+    // File name and comment, both NUL-terminated
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            pos += data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(invalid)?
+                + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+    let (out, used) = inflate(data.get(pos..).ok_or_else(invalid)?, limit)?;
+
+    // CRC-32 and length (mod 2^32) of the decoded data, little-endian
+    let trailer = data
+        .get(pos + used..pos + used + 8)
+        .ok_or("The gzip body is truncated.")?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err("The gzip body is corrupt: its checksum doesn't match.".to_string());
+    }
+    Ok(out)
+}
+
+// "deflate" is meant to be zlib-wrapped, but some servers send raw DEFLATE.
+fn inflate_zlib(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let wrapped = data.len() >= 2
+        && data[0] & 0x0f == 8
+        && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0;
+    if !wrapped {
+        return inflate(data, limit).map(|(out, _)| out);
+    }
+    let (out, used) = inflate(&data[2..], limit)?;
+    // Adler-32 of the decoded data, big-endian
+    let trailer = data
+        .get(2 + used..2 + used + 4)
+        .ok_or("The deflate body is truncated.")?;
+    if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != adler32(&out) {
+        return Err("The deflate body is corrupt: its checksum doesn't match.".to_string());
+    }
+    Ok(out)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+fn too_large(limit: usize) -> String {
+    format!(
+        "The body decodes to more than {} MiB; not decoding it further.",
+        limit / (1024 * 1024)
+    )
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or("The compressed body is truncated.")?;
+        let value = (byte >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(u32::from(value))
+    }
+
+    fn bits(&mut self, n: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..n {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+// A canonical Huffman code: symbol counts per length and symbols by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bit()? as i32;
+            let count = i32::from(self.counts[len]);
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("The compressed body has an invalid Huffman code.".to_string())
+    }
+}
+
+// Raw DEFLATE, and how many bytes of `data` it took up.
+fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = Vec::with_capacity((data.len() * 4).min(limit));
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or("The compressed body is truncated.")?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if nlen != !len {
+                    return Err("The compressed body has an invalid stored block.".to_string());
+                }
+                let len = usize::from(len);
+                bits.pos += 4;
+                let stored = data
+                    .get(bits.pos..bits.pos + len)
+                    .ok_or("The compressed body is truncated.")?;
+                if out.len() + len > limit {
+                    return Err(too_large(limit));
+                }
+                out.extend_from_slice(stored);
+                bits.pos += len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let lit = Huffman::new(&lengths);
+                let dist = Huffman::new(&[5; 30]);
+                codes(&mut bits, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut bits)?;
+                codes(&mut bits, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err("The compressed body has an invalid block type.".to_string()),
+        }
+        if last {
+            bits.align();
+            return Ok((out, bits.pos));
+        }
+    }
+}
+
+fn dynamic_tables(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let nlen = bits.bits(5)? as usize + 257;
+    let ndist = bits.bits(5)? as usize + 1;
+    let ncode = bits.bits(4)? as usize + 4;
+    let mut clen = [0u8; 19];
+    for &i in &CLEN_ORDER[..ncode] {
+        clen[i] = bits.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clen);
+
+    let mut lengths = Vec::with_capacity(nlen + ndist);
+    while lengths.len() < nlen + ndist {
+        let symbol = clen.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths
+                    .last()
+                    .ok_or("The compressed body repeats a missing code length.")?,
+                3 + bits.bits(2)?,
+            ),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > nlen + ndist {
+        return Err("The compressed body has invalid code lengths.".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen]),
+        Huffman::new(&lengths[nlen..]),
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = lit.decode(bits)? as usize;
+        match symbol {
+            0..=255 if out.len() >= limit => return Err(too_large(limit)),
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let i = symbol - 257;
+                let len = LENGTH_BASE[i] as usize + bits.bits(LENGTH_EXTRA[i])? as usize;
+                let d = dist.decode(bits)? as usize;
+                if d >= 30 {
+                    return Err("The compressed body has an invalid distance.".to_string());
+                }
+                let distance = DIST_BASE[d] as usize + bits.bits(DIST_EXTRA[d])? as usize;
+                if distance > out.len() {
+                    return Err("The compressed body refers before its start.".to_string());
+                }
+                if out.len() + len > limit {
+                    return Err(too_large(limit));
+                }
+                let start = out.len() - distance;
+                // Copies may overlap their own output ("abcabcabc...")
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+            _ => return Err("The compressed body has an invalid length code.".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const HELLO: &[u8] = b"hello, hello, hello world";
+    // HELLO in a fixed Huffman block, with back-references
+    const FIXED: &str = "cb48cdc9c9d751c840a214caf38b725200";
+    const GZIP: &str = "1f8b0800000000000203cb48cdc9c9d751c840a214caf38b72520096656dfd19000000";
+    const ZLIB: &str = "789ccb48cdc9c9d751c840a214caf38b7252007487091d";
+
+    fn raw(data: &str) -> Result<Vec<u8>, String> {
+        inflate(&hex(data), MAX_DECODED).map(|(out, _)| out)
+    }
+
+    #[test]
+    fn fixed_block() {
+        assert_eq!(raw(FIXED).unwrap(), HELLO);
+    }
+
+    #[test]
+    fn dynamic_block() {
+        let data = "05c10101000008c3a0acecf6cf200000000054556dbb07";
+        assert_eq!(
+            raw(data).unwrap(),
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabbbbbbbbcccd"
+        );
+    }
+
+    #[test]
+    fn stored_block() {
+        let data = "011100eeff73746f72656420626c6f636b2064617461";
+        assert_eq!(raw(data).unwrap(), b"stored block data");
+    }
+
+    #[test]
+    fn stored_block_with_a_bad_length_complement() {
+        let data = "011100eefe73746f72656420626c6f636b2064617461";
+        assert!(raw(data).unwrap_err().contains("stored block"));
+    }
+
+    #[test]
+    fn gzip_and_zlib_wrappers() {
+        assert_eq!(decode(hex(GZIP), "gzip").unwrap(), HELLO);
+        assert_eq!(decode(hex(ZLIB), "deflate").unwrap(), HELLO);
+        // Raw DEFLATE sent as "deflate"
+        assert_eq!(decode(hex(FIXED), "deflate").unwrap(), HELLO);
+        assert_eq!(decode(hex(GZIP), "identity, gzip").unwrap(), HELLO);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let gzip = hex(GZIP);
+        for len in [5, 20, gzip.len() - 3] {
+            assert!(decode(gzip[..len].to_vec(), "gzip").is_err(), "{}", len);
+        }
+        assert!(raw(&FIXED[..FIXED.len() - 6]).is_err());
+    }
+
+    #[test]
+    fn bad_gzip_checksum() {
+        let mut gzip = hex(GZIP);
+        let crc = gzip.len() - 8;
+        gzip[crc] ^= 1;
+        assert!(decode(gzip, "gzip").unwrap_err().contains("checksum"));
+    }
+
+    #[test]
+    fn bad_gzip_length() {
+        let mut gzip = hex(GZIP);
+        let size = gzip.len() - 4;
+        gzip[size] += 1;
+        assert!(decode(gzip, "gzip").unwrap_err().contains("checksum"));
+    }
+
+    #[test]
+    fn bad_zlib_checksum() {
+        let mut zlib = hex(ZLIB);
+        let last = zlib.len() - 1;
+        zlib[last] ^= 1;
+        assert!(decode(zlib, "deflate").unwrap_err().contains("checksum"));
+    }
+
+    #[test]
+    fn output_is_capped() {
+        let err = decode_with_limit(hex(GZIP), "gzip", 10).unwrap_err();
+        assert!(err.contains("more than"), "{}", err);
+        assert_eq!(
+            decode_with_limit(hex(GZIP), "gzip", HELLO.len()).unwrap(),
+            HELLO
+        );
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
@@ -0,0 +1,203 @@
+// Incremental JSON pretty-printing for bodies too large to hold as a Value.
+//
+// Bytes are reindented as they arrive: structure characters outside
+// strings drive the layout and everything else is copied through, so
+// numbers keep their exact digits and memory stays at one chunk plus the
+// nesting stack. Keys come out in the server's order; sorting them would
+// need the whole object.
+
+use std::io::{self, Write};
+
+// Bodies declared larger than this stream even without --stream-json.
+pub const STREAM_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+const INDENT: &[u8] = b"  ";
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+pub struct Pretty<W: Write> {
+    out: W,
+    // Open containers, b'{' or b'['
+    stack: Vec<u8>,
+    in_string: bool,
+    escaped: bool,
+    // Just printed an opening bracket; an empty container stays on one line
+    after_open: bool,
+    // Inside a top-level number or literal, which only whitespace ends
+    in_scalar: bool,
+    // A complete top-level value has been printed
+    done: bool,
+    offset: u64,
+}
+
+impl<W: Write> Pretty<W> {
+    pub fn new(out: W) -> Self {
+        Pretty {
+            out,
+            stack: Vec::new(),
+            in_string: false,
+            escaped: false,
+            after_open: false,
+            in_scalar: false,
+            done: false,
+            offset: 0,
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), String> {
+        let chunk = match chunk.strip_prefix(&UTF8_BOM) {
+            Some(rest) if self.offset == 0 => {
+                self.offset = 3;
+                rest
+            }
+            _ => chunk,
+        };
+        for &b in chunk {
+            self.byte(b).map_err(|e| self.error(e))?;
+            self.offset += 1;
+        }
+        Ok(())
+    }
+
+    // Called once the body has ended; fails if it stopped mid-value.
+    pub fn finish(mut self) -> Result<(), String> {
+        if self.in_string || !self.stack.is_empty() {
+            return Err(format!(
+                "The JSON body ends inside {} after {} bytes.",
+                if self.in_string {
+                    "a string"
+                } else {
+                    "an unclosed value"
+                },
+                self.offset
+            ));
+        }
+        if !self.done && !self.in_scalar {
+            return Err("The JSON body is empty.".to_string());
+        }
+        self.out
+            .write_all(b"\n")
+            .and_then(|_| self.out.flush())
+            .map_err(|e| e.to_string())
+    }
+
+    fn error(&self, e: Fault) -> String {
+        match e {
+            Fault::Io(e) => e.to_string(),
+            Fault::Unexpected(b) => format!(
+                "The body is not valid JSON: unexpected '{}' at byte {}.",
+                b.escape_ascii(),
+                self.offset
+            ),
+        }
+    }
+
+    fn byte(&mut self, b: u8) -> Result<(), Fault> {
+        if self.in_string {
+            self.out.write_all(&[b])?;
+            if self.escaped {
+                self.escaped = false;
+            } else if b == b'\\' {
+                self.escaped = true;
+            } else if b == b'"' {
+                self.in_string = false;
+                self.value_ended();
+            }
+            return Ok(());
+        }
+
+        match b {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                if self.stack.is_empty() && self.in_scalar {
+                    self.in_scalar = false;
+                    self.done = true;
+                }
+            }
+            b'{' | b'[' => {
+                self.start_value()?;
+                self.out.write_all(&[b])?;
+                self.stack.push(b);
+                self.after_open = true;
+            }
+            b'}' | b']' => {
+                let open = if b == b'}' { b'{' } else { b'[' };
+                if self.stack.pop() != Some(open) {
+                    return Err(Fault::Unexpected(b));
+                }
+                if !self.after_open {
+                    self.newline()?;
+                }
+                self.out.write_all(&[b])?;
+                self.after_open = false;
+                self.value_ended();
+            }
+            b',' => {
+                if self.stack.is_empty() || self.after_open {
+                    return Err(Fault::Unexpected(b));
+                }
+                self.out.write_all(b",")?;
+                self.newline()?;
+            }
+            b':' => {
+                if self.stack.last() != Some(&b'{') {
+                    return Err(Fault::Unexpected(b));
+                }
+                self.out.write_all(b": ")?;
+            }
+            b'"' => {
+                self.start_value()?;
+                self.out.write_all(b"\"")?;
+                self.in_string = true;
+            }
+            b'-' | b'+' | b'.' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' => {
+                // Numbers and literals; a second character of the same
+                // token continues it
+                if !(self.in_scalar && self.stack.is_empty()) {
+                    self.start_value()?;
+                    self.in_scalar = self.stack.is_empty();
+                }
+                self.out.write_all(&[b])?;
+            }
+            _ => return Err(Fault::Unexpected(b)),
+        }
+        Ok(())
+    }
+
+    fn start_value(&mut self) -> Result<(), Fault> {
+        if self.stack.is_empty() && (self.done || self.in_scalar) {
+            // Several top-level values (NDJSON): one after another
+            self.out.write_all(b"\n")?;
+            self.done = false;
+            self.in_scalar = false;
+        }
+        if self.after_open {
+            self.newline()?;
+            self.after_open = false;
+        }
+        Ok(())
+    }
+
+    fn value_ended(&mut self) {
+        if self.stack.is_empty() {
+            self.done = true;
+        }
+    }
+
+    fn newline(&mut self) -> io::Result<()> {
+        self.out.write_all(b"\n")?;
+        for _ in 0..self.stack.len() {
+            self.out.write_all(INDENT)?;
+        }
+        Ok(())
+    }
+}
+
+enum Fault {
+    Io(io::Error),
+    Unexpected(u8),
+}
+
+impl From<io::Error> for Fault {
+    fn from(e: io::Error) -> Self {
+        Fault::Io(e)
+    }
+}
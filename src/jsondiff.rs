@@ -0,0 +1,247 @@
+// Structural JSON diff: reports added, removed and changed paths instead of
+// text lines, so reordered keys or reformatting never show up as changes.
+//
+// Paths use a small JSONPath subset: `$.meta.timestamp`, `$.items[0]`,
+// `$.items[*].id`, `$.*.etag` and `$["odd key"]`. `[]` is the same as
+// `[*]`, as in jq.
+
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    AnyKey,
+    AnyIndex,
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonPath(Vec<Segment>);
+
+pub fn parse_path(input: &str) -> Result<JsonPath, String> {
+    let invalid = || format!("Invalid JSON path '{}'.", input);
+    let mut rest = input
+        .trim()
+        .strip_prefix('$')
+        .ok_or_else(|| format!("JSON path '{}' must start with '$'.", input))?;
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            if key.is_empty() {
+                return Err(invalid());
+            }
+            segments.push(if key == "*" {
+                Segment::AnyKey
+            } else {
+                Segment::Key(key.to_string())
+            });
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            segments.push(if inner == "*" || inner.is_empty() {
+                Segment::AnyIndex
+            } else if let Some(quoted) = inner
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+            {
+                Segment::Key(quoted.to_string())
+            } else {
+                Segment::Index(inner.parse().map_err(|_| invalid())?)
+            });
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(JsonPath(segments))
+}
+
+impl JsonPath {
+    fn matches(&self, path: &[Segment]) -> bool {
+        self.0.len() == path.len()
+            && self
+                .0
+                .iter()
+                .zip(path)
+                .all(|(pattern, seg)| match (pattern, seg) {
+                    (Segment::AnyKey, Segment::Key(_)) => true,
+                    (Segment::AnyIndex, Segment::Index(_)) => true,
+                    (p, s) => p == s,
+                })
+    }
+
+    // The values at this path; wildcards can match many, or none.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut found = vec![value];
+        for segment in &self.0 {
+            found = found
+                .into_iter()
+                .flat_map(|v| -> Vec<&Value> {
+                    match (segment, v) {
+                        (Segment::Key(k), Value::Object(map)) => map.get(k).into_iter().collect(),
+                        (Segment::AnyKey, Value::Object(map)) => map.values().collect(),
+                        (Segment::Index(i), Value::Array(items)) => {
+                            items.get(*i).into_iter().collect()
+                        }
+                        (Segment::AnyIndex, Value::Array(items)) => items.iter().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        found
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_path(&self.0))
+    }
+}
+
+pub enum Change {
+    Added(String, Value),
+    Removed(String, Value),
+    Changed(String, Value, Value),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(path, v) => write!(f, "+ {}: {}", path, v),
+            Change::Removed(path, v) => write!(f, "- {}: {}", path, v),
+            Change::Changed(path, old, new) => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+// Differences from `old` to `new`, skipping anything under an ignored path.
+pub fn diff(old: &Value, new: &Value, ignore: &[JsonPath]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    walk(old, new, &mut Vec::new(), ignore, &mut changes);
+    changes
+}
+
+pub fn render(changes: &[Change]) -> String {
+    changes
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn walk(
+    old: &Value,
+    new: &Value,
+    path: &mut Vec<Segment>,
+    ignore: &[JsonPath],
+    changes: &mut Vec<Change>,
+) {
+    if ignored(path, ignore) {
+        return;
+    }
+
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, av) in a {
+                path.push(Segment::Key(key.clone()));
+                match b.get(key) {
+                    Some(bv) => walk(av, bv, path, ignore, changes),
+                    None if !ignored(path, ignore) => {
+                        changes.push(Change::Removed(format_path(path), av.clone()))
+                    }
+                    None => {}
+                }
+                path.pop();
+            }
+            for (key, bv) in b {
+                if a.contains_key(key) {
+                    continue;
+                }
+                path.push(Segment::Key(key.clone()));
+                if !ignored(path, ignore) {
+                    changes.push(Change::Added(format_path(path), bv.clone()));
+                }
+                path.pop();
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                path.push(Segment::Index(i));
+                match (a.get(i), b.get(i)) {
+                    (Some(av), Some(bv)) => walk(av, bv, path, ignore, changes),
+                    (Some(av), None) if !ignored(path, ignore) => {
+                        changes.push(Change::Removed(format_path(path), av.clone()))
+                    }
+                    (None, Some(bv)) if !ignored(path, ignore) => {
+                        changes.push(Change::Added(format_path(path), bv.clone()))
+                    }
+                    _ => {}
+                }
+                path.pop();
+            }
+        }
+        (Value::Number(a), Value::Number(b))
+            if decimal(&a.to_string()) == decimal(&b.to_string()) => {}
+        (a, b) if a != b => changes.push(Change::Changed(format_path(path), a.clone(), b.clone())),
+        _ => {}
+    }
+}
+
+// Numbers keep the server's digits, so 1.50 and 1.5e0 differ as text but
+// not as values: compare sign, significant digits and exponent instead.
+fn decimal(number: &str) -> (bool, String, i64) {
+    let (negative, number) = match number.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, number),
+    };
+    let (mantissa, mut exponent) = match number.split_once(['e', 'E']) {
+        Some((m, e)) => (m, e.parse::<i64>().unwrap_or(0)),
+        None => (number, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    exponent -= frac.len() as i64;
+    let digits = format!("{}{}", int, frac);
+    let digits = digits.trim_start_matches('0');
+    let significant = digits.trim_end_matches('0');
+    if significant.is_empty() {
+        return (false, String::new(), 0);
+    }
+    exponent += (digits.len() - significant.len()) as i64;
+    (negative, significant.to_string(), exponent)
+}
+
+fn ignored(path: &[Segment], ignore: &[JsonPath]) -> bool {
+    ignore.iter().any(|p| p.matches(path))
+}
+
+fn format_path(path: &[Segment]) -> String {
+    let mut out = String::from("$");
+    for seg in path {
+        match seg {
+            Segment::Key(k) if is_identifier(k) => {
+                out.push('.');
+                out.push_str(k);
+            }
+            Segment::Key(k) => out.push_str(&format!("[{}]", Value::String(k.clone()))),
+            Segment::Index(i) => out.push_str(&format!("[{}]", i)),
+            Segment::AnyKey => out.push_str(".*"),
+            Segment::AnyIndex => out.push_str("[*]"),
+        }
+    }
+    out
+}
+
+fn is_identifier(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
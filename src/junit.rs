@@ -0,0 +1,66 @@
+// JUnit XML output for recorded checks, so CI systems can show pass/fail
+// per request natively.
+
+use crate::assertions::Check;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub fn write(path: &Path, suite: &str, checks: &[Check]) -> Result<(), String> {
+    fs::write(path, render(suite, checks))
+        .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))
+}
+
+pub fn render(suite: &str, checks: &[Check]) -> String {
+    let failures = checks.iter().filter(|c| c.failure.is_some()).count();
+    let total = checks
+        .iter()
+        .filter_map(|c| c.time)
+        .sum::<Duration>()
+        .as_secs_f64();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        checks.len(),
+        failures,
+        total
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        escape(suite),
+        checks.len(),
+        failures,
+        total
+    ));
+
+    for check in checks {
+        let time = check.time.map(|t| t.as_secs_f64()).unwrap_or(0.0);
+        let open = format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            escape(&check.request),
+            escape(&check.name),
+            time
+        );
+        match &check.failure {
+            None => xml.push_str(&format!("{}/>\n", open)),
+            Some(message) => xml.push_str(&format!(
+                "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                open,
+                escape(message),
+                escape(message)
+            )),
+        }
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,307 @@
+// JWT helpers: decode tokens for inspection and sign test tokens.
+//
+// `jwt decode` never verifies the signature; it's meant for looking at
+// what a server handed out. `jwt sign` prints just the token, so
+// `curl jwt sign ... > token.txt` feeds straight into --bearer-file.
+
+use crate::duration::{humanize, parse_duration};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
+use openssl::rsa::Padding;
+use openssl::sign::{RsaPssSaltlen, Signer};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Clone, Debug)]
+pub enum JwtCommand {
+    /// Show a token's header and claims, with its validity window
+    Decode {
+        /// The token; a leading "Bearer " is ignored
+        token: String,
+    },
+
+    /// Sign a token from a JSON claims file
+    Sign {
+        #[structopt(long, parse(from_os_str))]
+        claims: PathBuf,
+
+        /// PEM private key, or the shared secret for HS256/384/512
+        #[structopt(long, parse(from_os_str))]
+        key: PathBuf,
+
+        /// HS256/384/512, RS256/384/512, PS256/384/512 or ES256/384/512
+        #[structopt(long, default_value = "RS256")]
+        alg: String,
+
+        /// Key ID for the header
+        #[structopt(long)]
+        kid: Option<String>,
+
+        /// Set iat to now and exp this far ahead (e.g. 15m, 1h)
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        expires: Option<Duration>,
+    },
+}
+
+pub fn run(cmd: JwtCommand) -> Result<(), String> {
+    match cmd {
+        JwtCommand::Decode { token } => {
+            for line in decode(&token)? {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        JwtCommand::Sign {
+            claims,
+            key,
+            alg,
+            kid,
+            expires,
+        } => {
+            println!("{}", sign(&claims, &key, &alg, kid, expires)?);
+            Ok(())
+        }
+    }
+}
+
+// ---------------- DECODE ----------------
+
+fn decode(token: &str) -> Result<Vec<String>, String> {
+    let token = token.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(if parts.len() == 5 {
+            "This is an encrypted token (JWE); only signed tokens can be decoded.".to_string()
+        } else {
+            "Not a JWT: expected three dot-separated parts.".to_string()
+        });
+    }
+    let header = segment(parts[0], "header")?;
+    let claims = segment(parts[1], "claims")?;
+
+    let mut lines = vec!["Header:".to_string(), pretty(&header)];
+    lines.push("Claims:".to_string());
+    lines.push(pretty(&claims));
+
+    let now = unix_now();
+    for (name, label) in [("iat", "Issued"), ("nbf", "Not before"), ("exp", "Expires")] {
+        if let Some(at) = claims.get(name).and_then(Value::as_i64) {
+            lines.push(format!("{}: {} ({})", label, date(at), relative(at, now)));
+        }
+    }
+    match claims.get("exp").and_then(Value::as_i64) {
+        Some(exp) if exp <= now => lines.push("Status: expired".to_string()),
+        _ if claims
+            .get("nbf")
+            .and_then(Value::as_i64)
+            .is_some_and(|nbf| nbf > now) =>
+        {
+            lines.push("Status: not yet valid".to_string())
+        }
+        Some(_) => lines.push("Status: valid".to_string()),
+        None => lines.push("Status: never expires".to_string()),
+    }
+    lines.push("Signature: not verified".to_string());
+    Ok(lines)
+}
+
+fn segment(part: &str, name: &str) -> Result<Value, String> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(part.trim_end_matches('='))
+        .map_err(|_| format!("The token's {} is not valid base64url.", name))?;
+    serde_json::from_slice(&bytes).map_err(|_| format!("The token's {} is not JSON.", name))
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+fn date(at: i64) -> String {
+    let time = UNIX_EPOCH + Duration::from_secs(at.max(0) as u64);
+    httpdate::fmt_http_date(time)
+}
+
+fn relative(at: i64, now: i64) -> String {
+    let delta = humanize(at.abs_diff(now));
+    if at > now {
+        format!("in {}", delta)
+    } else {
+        format!("{} ago", delta)
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+// ---------------- SIGN ----------------
+
+fn sign(
+    claims: &Path,
+    key: &Path,
+    alg: &str,
+    kid: Option<String>,
+    expires: Option<Duration>,
+) -> Result<String, String> {
+    let alg = alg.to_ascii_uppercase();
+    let text = fs::read_to_string(claims)
+        .map_err(|e| format!("Unable to read '{}': {}", claims.display(), e))?;
+    let mut claims: Map<String, Value> = serde_json::from_str(&text)
+        .map_err(|e| format!("'{}' is not a JSON object: {}", claims.display(), e))?;
+    if let Some(expires) = expires {
+        let now = unix_now();
+        claims.insert("iat".to_string(), json!(now));
+        claims.insert("exp".to_string(), json!(now + expires.as_secs() as i64));
+    }
+
+    let mut header = json!({ "alg": alg, "typ": "JWT" });
+    if let Some(kid) = kid {
+        header["kid"] = json!(kid);
+    }
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(Value::Object(claims).to_string())
+    );
+
+    let key_bytes =
+        fs::read(key).map_err(|e| format!("Unable to read '{}': {}", key.display(), e))?;
+    let signature = signature(&alg, &key_bytes, signing_input.as_bytes())?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
+
+fn signature(alg: &str, key: &[u8], input: &[u8]) -> Result<Vec<u8>, String> {
+    let unsupported = || format!("Unsupported algorithm '{}'.", alg);
+    let (family, bits) = alg.get(..2).zip(alg.get(2..)).ok_or_else(unsupported)?;
+    let (digest, curve) = match bits {
+        "256" => (MessageDigest::sha256(), Nid::X9_62_PRIME256V1),
+        "384" => (MessageDigest::sha384(), Nid::SECP384R1),
+        "512" => (MessageDigest::sha512(), Nid::SECP521R1),
+        _ => return Err(unsupported()),
+    };
+
+    match family {
+        "HS" => {
+            // A secret file usually ends with a newline nobody meant to sign
+            let secret = key.strip_suffix(b"\n").unwrap_or(key);
+            let pkey = PKey::hmac(secret).map_err(|e| e.to_string())?;
+            sign_with(&pkey, digest, input, None)
+        }
+        "RS" | "PS" => {
+            let pkey = private_key(key, Id::RSA, alg)?;
+            let padding = (family == "PS").then_some(Padding::PKCS1_PSS);
+            sign_with(&pkey, digest, input, padding)
+        }
+        "ES" => {
+            let pkey = private_key(key, Id::EC, alg)?;
+            let ec = pkey.ec_key().map_err(|e| e.to_string())?;
+            // Each ES alg names its curve: ES256 is P-256, ES384 P-384 and
+            // ES512 P-521
+            if ec.group().curve_name() != Some(curve) {
+                let name = match curve {
+                    Nid::X9_62_PRIME256V1 => "P-256",
+                    Nid::SECP384R1 => "P-384",
+                    _ => "P-521",
+                };
+                return Err(format!("{} needs a {} key.", alg, name));
+            }
+            let der = sign_with(&pkey, digest, input, None)?;
+            // JWS wants the raw r || s pair rather than a DER sequence,
+            // each padded to the curve's size
+            let mut ctx = BigNumContext::new().map_err(|e| e.to_string())?;
+            let mut order = BigNum::new().map_err(|e| e.to_string())?;
+            ec.group()
+                .order(&mut order, &mut ctx)
+                .map_err(|e| e.to_string())?;
+            let size = order.num_bytes();
+            let sig = EcdsaSig::from_der(&der).map_err(|e| e.to_string())?;
+            let r = sig.r().to_vec_padded(size).map_err(|e| e.to_string())?;
+            let s = sig.s().to_vec_padded(size).map_err(|e| e.to_string())?;
+            Ok([r, s].concat())
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn private_key(pem: &[u8], id: Id, alg: &str) -> Result<PKey<Private>, String> {
+    let pkey = PKey::private_key_from_pem(pem)
+        .map_err(|_| "The key is not a PEM private key.".to_string())?;
+    if pkey.id() != id {
+        let kind = if id == Id::RSA { "an RSA" } else { "an EC" };
+        return Err(format!("{} needs {} private key.", alg, kind));
+    }
+    Ok(pkey)
+}
+
+fn sign_with(
+    pkey: &PKey<Private>,
+    digest: MessageDigest,
+    input: &[u8],
+    padding: Option<Padding>,
+) -> Result<Vec<u8>, String> {
+    let mut signer = Signer::new(digest, pkey).map_err(|e| e.to_string())?;
+    if let Some(padding) = padding {
+        signer.set_rsa_padding(padding).map_err(|e| e.to_string())?;
+        signer
+            .set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)
+            .map_err(|e| e.to_string())?;
+    }
+    signer.sign_oneshot_to_vec(input).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::ec::{EcGroup, EcKey};
+
+    fn ec_key(curve: Nid) -> Vec<u8> {
+        let group = EcGroup::from_curve_name(curve).unwrap();
+        EcKey::generate(&group)
+            .unwrap()
+            .private_key_to_pem()
+            .unwrap()
+    }
+
+    #[test]
+    fn unknown_algorithms_are_errors() {
+        for alg in ["€256", "H", "", "HS1024", "XX256"] {
+            assert_eq!(
+                signature(alg, b"secret", b"input"),
+                Err(format!("Unsupported algorithm '{}'.", alg))
+            );
+        }
+    }
+
+    #[test]
+    fn es_algorithms_need_their_curve() {
+        let p256 = ec_key(Nid::X9_62_PRIME256V1);
+        let p384 = ec_key(Nid::SECP384R1);
+        let p521 = ec_key(Nid::SECP521R1);
+        assert_eq!(signature("ES256", &p256, b"input").unwrap().len(), 64);
+        assert_eq!(signature("ES384", &p384, b"input").unwrap().len(), 96);
+        assert_eq!(signature("ES512", &p521, b"input").unwrap().len(), 132);
+        assert_eq!(
+            signature("ES256", &p384, b"input"),
+            Err("ES256 needs a P-256 key.".to_string())
+        );
+        assert_eq!(
+            signature("ES512", &p256, b"input"),
+            Err("ES512 needs a P-521 key.".to_string())
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// The request handling behind the curl binary, as a library.
+//
+// `WebClient`, `RequestSpec` and `ResponseReport` are the API for other
+// tools: build a request, send it and get the decoded response back
+// without anything being printed. The command line itself lives here too:
+// `cli` parses it, `run` carries it out through `request`, `handlers` and
+// `response`, and the binary only sets up output and exits. The modules
+// below are public so the binary can use them; their interfaces follow
+// the command line's needs and may change with it.
+
+mod client;
+
+pub use client::{Body, ClientOptions, RedirectObserver, RequestSpec, ResponseReport, WebClient};
+
+pub mod altsvc;
+pub mod assertions;
+pub mod auth;
+pub mod bench;
+pub mod cache;
+pub mod cache_report;
+pub mod cli;
+pub mod client_cert;
+pub mod client_setup;
+pub mod config;
+pub mod cookie_audit;
+pub mod cookie_jar;
+pub mod cors;
+pub mod deadline;
+pub mod diff;
+pub mod dns;
+pub mod download;
+pub mod duration;
+pub mod exit;
+pub mod filter;
+pub mod form;
+pub mod format;
+pub mod ftp;
+pub mod graphql;
+pub mod handlers;
+pub mod har;
+pub mod headers;
+pub mod inflate;
+pub mod json_stream;
+pub mod jsondiff;
+pub mod junit;
+pub mod jwt;
+pub mod markup;
+pub mod method;
+pub mod monitor;
+pub mod multi;
+pub mod multipart;
+pub mod negotiate;
+pub mod negotiation;
+pub mod ntlm;
+pub mod output;
+pub mod pac;
+pub mod paginate;
+pub mod pool_stats;
+pub mod prompt;
+pub mod proxy;
+pub mod raw;
+pub mod repl;
+pub mod request;
+pub mod response;
+pub mod retry;
+pub mod revocation;
+pub mod run;
+pub mod s3;
+pub mod schemes;
+pub mod secrets;
+pub mod security_audit;
+pub mod sigv4;
+pub mod snapshot;
+pub mod sse;
+pub mod stream;
+pub mod template;
+pub mod tls_info;
+pub mod transfer;
+pub mod unix_socket;
+pub mod url_build;
+pub mod url_norm;
+pub mod writeout;
@@ -0,0 +1,256 @@
+// XML and HTML reindented for reading on a terminal, and highlighted when
+// color is on.
+//
+// This is a display aid, not a parser: the text is split into tags, text,
+// comments and declarations (<!DOCTYPE>, <?xml?>, CDATA), and each
+// element's children go one level deeper. An element holding only text
+// stays on one line and whitespace between tags is dropped. HTML void
+// elements (<br>, <img>, ...) don't open a level, and the contents of
+// <script>, <style>, <pre> and <textarea> are kept exactly as sent.
+
+use crate::format::Markup;
+
+const TAG: &str = "\x1b[1;34m";
+const ATTRIBUTE: &str = "\x1b[36m";
+const VALUE: &str = "\x1b[32m";
+const COMMENT: &str = "\x1b[90m";
+const DECLARATION: &str = "\x1b[35m";
+const RESET: &str = "\x1b[0m";
+
+const INDENT: &str = "  ";
+
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "pre", "textarea"];
+
+enum Token<'a> {
+    Open {
+        name: &'a str,
+        raw: &'a str,
+        // <br/>, or an HTML void element
+        empty: bool,
+    },
+    Close {
+        name: &'a str,
+        raw: &'a str,
+    },
+    Text(&'a str),
+    // The untouched contents of a raw text element
+    Verbatim(&'a str),
+    Comment(&'a str),
+    Declaration(&'a str),
+}
+
+pub fn pretty(text: &str, kind: Markup, color: bool) -> String {
+    let tokens = tokenize(text, kind == Markup::Html);
+    let style = Style { color };
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let line = match &tokens[i] {
+            Token::Open {
+                raw, empty: true, ..
+            } => style.tag(raw),
+            Token::Open { name, raw, .. } => {
+                // <p></p>, <title>Text</title> and raw text elements stay
+                // on one line
+                let (inner, skip) = match tokens.get(i + 1) {
+                    Some(Token::Verbatim(text)) => (*text, 1),
+                    Some(Token::Text(text)) if !text.trim().contains('\n') => (text.trim(), 1),
+                    _ => ("", 0),
+                };
+                match tokens.get(i + 1 + skip) {
+                    Some(Token::Close {
+                        name: n,
+                        raw: close,
+                    }) if n.eq_ignore_ascii_case(name) => {
+                        i += 1 + skip;
+                        format!("{}{}{}", style.tag(raw), inner, style.tag(close))
+                    }
+                    _ => {
+                        lines.push(indented(depth, &style.tag(raw)));
+                        depth += 1;
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+            Token::Close { raw, .. } => {
+                depth = depth.saturating_sub(1);
+                style.tag(raw)
+            }
+            Token::Text(text) => {
+                for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                    lines.push(indented(depth, line));
+                }
+                i += 1;
+                continue;
+            }
+            Token::Verbatim(text) => text.to_string(),
+            Token::Comment(raw) => style.paint(COMMENT, raw),
+            Token::Declaration(raw) => style.paint(DECLARATION, raw),
+        };
+        lines.push(indented(depth, &line));
+        i += 1;
+    }
+    lines.join("\n")
+}
+
+fn indented(depth: usize, line: &str) -> String {
+    format!("{}{}", INDENT.repeat(depth), line)
+}
+
+fn tokenize(text: &str, html: bool) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('<') {
+        let at = pos + offset;
+        let tail = &text[at..];
+        let (token, len) = if let Some(body) = tail.strip_prefix("<!--") {
+            let end = body.find("-->").map_or(tail.len(), |e| e + 7);
+            (Token::Comment(&tail[..end]), end)
+        } else if tail.starts_with("<![CDATA[") {
+            let end = tail.find("]]>").map_or(tail.len(), |e| e + 3);
+            (Token::Declaration(&tail[..end]), end)
+        } else if tail.starts_with("<!") || tail.starts_with("<?") {
+            let end = tail.find('>').map_or(tail.len(), |e| e + 1);
+            (Token::Declaration(&tail[..end]), end)
+        } else if let Some(name) = tail.strip_prefix("</") {
+            let Some(end) = tail.find('>') else {
+                break;
+            };
+            let name = name[..end - 2].trim();
+            (
+                Token::Close {
+                    name,
+                    raw: &tail[..end + 1],
+                },
+                end + 1,
+            )
+        } else if tail[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let Some(end) = tag_end(tail) else {
+                break;
+            };
+            let raw = &tail[..end];
+            let name = &raw[1..raw[1..]
+                .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .map_or(raw.len(), |n| n + 1)];
+            let empty = raw.ends_with("/>")
+                || html && VOID_ELEMENTS.iter().any(|v| v.eq_ignore_ascii_case(name));
+            (Token::Open { name, raw, empty }, end)
+        } else {
+            // A '<' in text
+            pos = at + 1;
+            continue;
+        };
+
+        if text_start < at {
+            tokens.push(Token::Text(&text[text_start..at]));
+        }
+        let raw_text = match &token {
+            Token::Open {
+                name, empty: false, ..
+            } if html => RAW_TEXT_ELEMENTS
+                .iter()
+                .find(|r| r.eq_ignore_ascii_case(name))
+                .copied(),
+            _ => None,
+        };
+        tokens.push(token);
+        pos = at + len;
+        if let Some(element) = raw_text {
+            let closing = format!("</{}", element);
+            let end = text[pos..]
+                .to_ascii_lowercase()
+                .find(&closing)
+                .map_or(text.len(), |e| pos + e);
+            if pos < end {
+                tokens.push(Token::Verbatim(&text[pos..end]));
+            }
+            pos = end;
+        }
+        text_start = pos;
+        if pos >= text.len() {
+            break;
+        }
+    }
+    if text_start < text.len() {
+        tokens.push(Token::Text(&text[text_start..]));
+    }
+    tokens
+}
+
+// The end of a start tag: its '>', skipping any inside quoted attribute
+// values.
+fn tag_end(tail: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tail.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    // `<name attr="value">` with the name, attribute names and values
+    // colored.
+    fn tag(&self, raw: &str) -> String {
+        if !self.color {
+            return raw.to_string();
+        }
+        let open = if raw.starts_with("</") { 2 } else { 1 };
+        let name_end = raw[open..]
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .map_or(raw.len(), |n| n + open);
+        let mut out = format!("{}{}", &raw[..open], self.paint(TAG, &raw[open..name_end]));
+        let mut rest = &raw[name_end..];
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap_or_default();
+            if c.is_whitespace() || c == '/' || c == '>' {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+            let name_len = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '/' || c == '>')
+                .unwrap_or(rest.len());
+            out.push_str(&self.paint(ATTRIBUTE, &rest[..name_len]));
+            rest = &rest[name_len..];
+            let Some(value) = rest.strip_prefix('=') else {
+                continue;
+            };
+            out.push('=');
+            let value_len = match value.chars().next() {
+                Some(q @ ('"' | '\'')) => value[1..].find(q).map_or(value.len(), |e| e + 2),
+                _ => value
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(value.len()),
+            };
+            out.push_str(&self.paint(VALUE, &value[..value_len]));
+            rest = &value[value_len..];
+        }
+        out
+    }
+}
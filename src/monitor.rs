@@ -0,0 +1,372 @@
+// `curl monitor --config checks.yaml`: a small uptime monitor.
+//
+// Every check runs on its own thread at its own interval. A check is up
+// while all of its expectations hold; alert hooks fire when a check goes
+// down (after `failures_before_alert` consecutive failures) and again when
+// it recovers.
+//
+// interval: 60s
+// hooks:
+//   on_failure: ./page-oncall.sh
+//   on_recovery: ./resolve.sh
+//   webhook: https://hooks.example.com/uptime
+// checks:
+//   - name: api
+//     url: https://api.example.com/health
+//     interval: 30s
+//     expect:
+//       status: 200
+//       content_type: application/json
+//       max_response_time: 500ms
+//       body_contains: '"ok"'
+
+use crate::assertions;
+use crate::duration::parse_duration;
+use crate::junit;
+use crate::sigv4;
+use reqwest::Method;
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use structopt::StructOpt;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct MonitorCommand {
+    /// YAML file describing the checks
+    #[structopt(long, parse(from_os_str))]
+    config: PathBuf,
+
+    /// Run every check once and exit non-zero if any failed
+    #[structopt(long)]
+    once: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default, deserialize_with = "duration")]
+    interval: Option<Duration>,
+    #[serde(default, deserialize_with = "duration")]
+    timeout: Option<Duration>,
+    #[serde(default)]
+    hooks: Hooks,
+    checks: Vec<Check>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+struct Hooks {
+    // Shell commands, run with MONITOR_* variables describing the event
+    on_failure: Option<String>,
+    on_recovery: Option<String>,
+    // Receives a JSON description of both kinds of event
+    webhook: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct Check {
+    name: String,
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    #[serde(default, deserialize_with = "duration")]
+    interval: Option<Duration>,
+    #[serde(default, deserialize_with = "duration")]
+    timeout: Option<Duration>,
+    #[serde(default)]
+    expect: Expect,
+    #[serde(default = "default_failures_before_alert")]
+    failures_before_alert: u32,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+struct Expect {
+    // Any 2xx when unset
+    status: Option<u16>,
+    content_type: Option<String>,
+    #[serde(default, deserialize_with = "duration")]
+    max_response_time: Option<Duration>,
+    body_contains: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_failures_before_alert() -> u32 {
+    1
+}
+
+// Durations are written like on the command line ("30s", "500ms"); a bare
+// number means seconds.
+fn duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(f64),
+        Text(String),
+    }
+
+    match Raw::deserialize(d)? {
+        Raw::Seconds(s) => Ok(Some(Duration::from_secs_f64(s))),
+        Raw::Text(t) => parse_duration(&t)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+// With --once, the global --report-junit writes the results as JUnit XML.
+pub fn run(cmd: MonitorCommand, report_junit: Option<&Path>) -> Result<(), String> {
+    let text = fs::read_to_string(&cmd.config)
+        .map_err(|e| format!("Unable to read '{}': {}", cmd.config.display(), e))?;
+    let config: Config = serde_yaml::from_str(&text)
+        .map_err(|e| format!("Invalid monitor config '{}': {}", cmd.config.display(), e))?;
+    if config.checks.is_empty() {
+        return Err("The monitor config defines no checks.".to_string());
+    }
+
+    if cmd.once {
+        for check in &config.checks {
+            let client = client_for(check, &config)?;
+            let started = Instant::now();
+            let result = probe(&client, check);
+            log_result(check, &result);
+
+            assertions::begin(format!(
+                "{} {}",
+                check.method.to_ascii_uppercase(),
+                check.url
+            ));
+            assertions::record(&check.name, result.err(), Some(started.elapsed()), false);
+        }
+        if let Some(path) = report_junit {
+            junit::write(path, "monitor", &assertions::checks())?;
+        }
+        if assertions::failed() {
+            process::exit(assertions::EXIT_ASSERTION_FAILED);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "Monitoring {} check(s) from {}.",
+        config.checks.len(),
+        cmd.config.display()
+    );
+
+    let mut workers = Vec::new();
+    for check in config.checks.clone() {
+        let client = client_for(&check, &config)?;
+        let interval = check
+            .interval
+            .or(config.interval)
+            .unwrap_or(DEFAULT_INTERVAL);
+        let hooks = config.hooks.clone();
+        workers.push(thread::spawn(move || {
+            watch(&client, &check, interval, &hooks)
+        }));
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn client_for(check: &Check, config: &Config) -> Result<Client, String> {
+    Client::builder()
+        .timeout(check.timeout.or(config.timeout).unwrap_or(DEFAULT_TIMEOUT))
+        .build()
+        .map_err(|e| format!("Unable to set up check '{}': {}", check.name, e))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Unknown,
+    Up,
+    Down,
+}
+
+fn watch(client: &Client, check: &Check, interval: Duration, hooks: &Hooks) {
+    let mut state = State::Unknown;
+    let mut failures = 0;
+
+    loop {
+        let started = Instant::now();
+        let result = probe(client, check);
+        log_result(check, &result);
+
+        match &result {
+            Ok(_) => {
+                failures = 0;
+                if state == State::Down {
+                    println!("{} {} recovered.", timestamp(), check.name);
+                    alert(hooks, check, "up", "Check recovered.");
+                }
+                state = State::Up;
+            }
+            Err(message) => {
+                failures += 1;
+                if state != State::Down && failures >= check.failures_before_alert {
+                    println!("{} {} is down.", timestamp(), check.name);
+                    alert(hooks, check, "down", message);
+                    state = State::Down;
+                }
+            }
+        }
+
+        thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+struct Probe {
+    status: u16,
+    elapsed: Duration,
+}
+
+// Run one request and evaluate every expectation of the check.
+fn probe(client: &Client, check: &Check) -> Result<Probe, String> {
+    let method = Method::from_bytes(check.method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid method '{}'.", check.method))?;
+    let mut req = client.request(method, &check.url);
+    for (name, value) in &check.headers {
+        req = req.header(name, value);
+    }
+    if let Some(body) = &check.body {
+        req = req.body(body.clone());
+    }
+
+    let started = Instant::now();
+    let res = req.send().map_err(|e| describe_error(&e))?;
+    let status = res.status().as_u16();
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = res.text().map_err(|e| describe_error(&e))?;
+    let elapsed = started.elapsed();
+
+    let expect = &check.expect;
+    match expect.status {
+        Some(expected) if status != expected => {
+            return Err(format!("Expected status {} but got {}.", expected, status));
+        }
+        None if !(200..300).contains(&status) => {
+            return Err(format!("Request failed with status code: {}.", status));
+        }
+        _ => {}
+    }
+    if let Some(expected) = &expect.content_type
+        && !assertions::content_type_matches(expected, content_type.as_deref())
+    {
+        return Err(format!(
+            "Expected Content-Type {} but the server returned {}.",
+            expected,
+            content_type.as_deref().unwrap_or("none")
+        ));
+    }
+    if let Some(limit) = expect.max_response_time
+        && elapsed > limit
+    {
+        return Err(format!(
+            "Response took {} ms, over the {} ms limit.",
+            elapsed.as_millis(),
+            limit.as_millis()
+        ));
+    }
+    if let Some(needle) = &expect.body_contains
+        && !body.contains(needle.as_str())
+    {
+        return Err(format!("Response body does not contain '{}'.", needle));
+    }
+
+    Ok(Probe { status, elapsed })
+}
+
+fn describe_error(e: &reqwest::Error) -> String {
+    if e.is_timeout() {
+        "The request timed out.".to_string()
+    } else if e.is_connect() {
+        "Unable to connect to the server.".to_string()
+    } else {
+        e.to_string()
+    }
+}
+
+fn log_result(check: &Check, result: &Result<Probe, String>) {
+    match result {
+        Ok(p) => println!(
+            "{} {} UP {} in {} ms",
+            timestamp(),
+            check.name,
+            p.status,
+            p.elapsed.as_millis()
+        ),
+        Err(e) => println!("{} {} FAIL {}", timestamp(), check.name, e),
+    }
+}
+
+// Hooks are best-effort: a broken hook is reported but never stops the
+// monitor.
+fn alert(hooks: &Hooks, check: &Check, state: &str, message: &str) {
+    let command = match state {
+        "down" => &hooks.on_failure,
+        _ => &hooks.on_recovery,
+    };
+    if let Some(command) = command {
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("MONITOR_CHECK", &check.name)
+            .env("MONITOR_URL", &check.url)
+            .env("MONITOR_STATE", state)
+            .env("MONITOR_MESSAGE", message)
+            .status();
+        match status {
+            Ok(s) if s.success() => {}
+            Ok(s) => eprintln!("Warning: Hook '{}' exited with {}.", command, s),
+            Err(e) => eprintln!("Warning: Unable to run hook '{}': {}", command, e),
+        }
+    }
+
+    if let Some(webhook) = &hooks.webhook {
+        let payload = serde_json::json!({
+            "check": check.name,
+            "url": check.url,
+            "state": state,
+            "message": message,
+            "time": timestamp(),
+        });
+        if let Err(e) = Client::new().post(webhook).json(&payload).send() {
+            eprintln!("Warning: Unable to deliver webhook to {}: {}", webhook, e);
+        }
+    }
+}
+
+// RFC 3339 UTC time, e.g. 2026-10-14T10:49:58Z.
+fn timestamp() -> String {
+    let (compact, _) = sigv4::amz_timestamps(SystemTime::now());
+    format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &compact[0..4],
+        &compact[4..6],
+        &compact[6..8],
+        &compact[9..11],
+        &compact[11..13],
+        &compact[13..15]
+    )
+}
@@ -0,0 +1,221 @@
+// Several URLs in one run: `curl URL1 URL2 ...` or --url-file, fetched
+// --parallel N at a time.
+//
+// N workers take the URLs in turn and fetch each on a thread of its own,
+// so the per-request state kept in thread-locals (assertions, --write-out,
+// HAR, ...) starts out empty for every URL, exactly as in a single-URL
+// run. The URLs do share the --deadline budget, the cookie jar and one
+// client, whose connections to a host are reused from one URL to the next.
+// What a URL prints is captured and printed by the calling thread in one
+// labeled block when it finishes, followed by a summary of every URL once
+// all are done.
+
+use crate::deadline;
+use crate::output;
+use crate::transfer::describe;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The exit status of a URL whose request failed in a way that has no exit
+// code of its own, such as an error status without --fail.
+pub const EXIT_FAILED: i32 = 1;
+
+// One URL per line; blank lines and '#' comments are skipped.
+pub fn read_url_file(path: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+struct Outcome {
+    code: i32,
+    elapsed: Duration,
+}
+
+// Fetch every URL with `fetch`, which is given the URL's index and returns
+// its exit status, and return the exit code for the whole run: that of the
+// first URL in the list that failed.
+pub fn run(urls: &[String], parallel: usize, fetch: impl Fn(usize) -> i32 + Sync) -> i32 {
+    let started = Instant::now();
+    let budget = deadline::current();
+    let next = AtomicUsize::new(0);
+    let mut outcomes: Vec<Option<Outcome>> = (0..urls.len()).map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let (done, finished) = mpsc::channel();
+        for _ in 0..parallel.clamp(1, urls.len()) {
+            let done = done.clone();
+            let (next, fetch) = (&next, &fetch);
+            scope.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= urls.len() {
+                        break;
+                    }
+                    let began = Instant::now();
+                    let fetched = thread::scope(|s| {
+                        s.spawn(|| {
+                            deadline::adopt(budget);
+                            output::capture(|| fetch(i))
+                        })
+                        .join()
+                        .map_err(|_| ())
+                    });
+                    let _ = done.send((i, fetched, began.elapsed()));
+                }
+            });
+        }
+        drop(done);
+
+        for (i, fetched, elapsed) in finished {
+            output::status(format!("==> [{}/{}] {}", i + 1, urls.len(), urls[i]));
+            let code = match fetched {
+                Ok((code, captured)) => {
+                    captured.print();
+                    code
+                }
+                Err(()) => {
+                    output::error("The request ended unexpectedly.");
+                    EXIT_FAILED
+                }
+            };
+            output::status("");
+            outcomes[i] = Some(Outcome { code, elapsed });
+        }
+    });
+
+    let outcomes: Vec<Outcome> = outcomes.into_iter().flatten().collect();
+    let failed = outcomes.iter().filter(|o| o.code != 0).count();
+    output::status(format!(
+        "Fetched {} URLs in {}: {} succeeded, {} failed",
+        urls.len(),
+        describe(started.elapsed()),
+        urls.len() - failed,
+        failed
+    ));
+    for (url, outcome) in urls.iter().zip(&outcomes) {
+        let result = if outcome.code == 0 { "ok" } else { "failed" };
+        output::status(format!(
+            "  {:<7}{:>9}  {}",
+            result,
+            describe(outcome.elapsed),
+            url
+        ));
+    }
+    outcomes
+        .iter()
+        .map(|o| o.code)
+        .find(|&code| code != 0)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deadline::Deadline;
+    use crate::json_stream;
+    use crate::{errln, outln};
+    use std::io::{BufWriter, Write};
+    use std::sync::Mutex;
+
+    fn urls(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn the_first_failed_url_decides_the_exit_code() {
+        let urls = urls(&["a", "b", "c", "d"]);
+        let fetched = Mutex::new(Vec::new());
+        let (code, captured) = output::capture(|| {
+            run(&urls, 3, |i| {
+                fetched.lock().unwrap().push(i);
+                [0, 7, 0, 6][i]
+            })
+        });
+        assert_eq!(code, 7);
+        let mut fetched = fetched.into_inner().unwrap();
+        fetched.sort();
+        assert_eq!(fetched, [0, 1, 2, 3]);
+        let text = captured.text();
+        assert!(text.contains("4 URLs in"), "{}", text);
+        assert!(text.contains(": 2 succeeded, 2 failed\n"), "{}", text);
+        assert!(text.contains("  failed "), "{}", text);
+
+        let (code, _) = output::capture(|| run(&urls, 1, |_| 0));
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn each_block_holds_only_its_own_output() {
+        let urls = urls(&["http://a/stream", "http://b/events"]);
+        let ((), captured) = output::capture(|| {
+            run(&urls, 2, |i| {
+                for n in 0..3 {
+                    if i == 0 {
+                        // As --stream-json prints a body
+                        let mut pretty = json_stream::Pretty::new(BufWriter::new(output::stdout()));
+                        pretty.feed(format!("[{}]", n).as_bytes()).unwrap();
+                        pretty.finish().unwrap();
+                    } else {
+                        // As an SSE event is printed
+                        let _ = write!(output::stdout(), "data: {}\n\n", n);
+                        errln!("b warns {}", n);
+                    }
+                    thread::sleep(Duration::from_millis(20));
+                }
+                outln!("{} done", i);
+                0
+            });
+        });
+
+        let text = captured.text();
+        let block = |label: &str| {
+            let start = text.find(label).unwrap_or_else(|| panic!("{}", text)) + label.len();
+            let rest = &text[start..];
+            let end = ["==> ", "Fetched "]
+                .iter()
+                .filter_map(|next| rest.find(next))
+                .min()
+                .unwrap();
+            rest[..end].to_string()
+        };
+        let mut json = Vec::new();
+        for n in 0..3 {
+            let mut pretty = json_stream::Pretty::new(&mut json);
+            pretty.feed(format!("[{}]", n).as_bytes()).unwrap();
+            pretty.finish().unwrap();
+        }
+        assert_eq!(
+            block("==> [1/2] http://a/stream\n"),
+            format!("{}0 done\n\n", String::from_utf8(json).unwrap())
+        );
+        assert_eq!(
+            block("==> [2/2] http://b/events\n"),
+            "data: 0\n\nb warns 0\ndata: 1\n\nb warns 1\ndata: 2\n\nb warns 2\n1 done\n\n"
+        );
+    }
+
+    #[test]
+    fn the_deadline_bounds_the_whole_run() {
+        let urls = urls(&["a", "b", "c"]);
+        deadline::adopt(Some(Deadline::after(Duration::from_millis(150))));
+        let (code, _) = output::capture(|| {
+            run(&urls, 1, |_| {
+                thread::sleep(Duration::from_millis(60));
+                deadline::check().map_or_else(|e| e.category.code(), |()| 0)
+            })
+        });
+        deadline::adopt(None);
+        // Each URL alone is well within the budget; the third isn't
+        assert_eq!(code, crate::exit::Category::Timeout.code());
+    }
+}
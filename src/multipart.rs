@@ -0,0 +1,146 @@
+// multipart/* responses (RFC 2046): batch APIs answering with
+// multipart/mixed, and servers answering multi-range requests with
+// multipart/byteranges.
+//
+// Parsing is lenient about bare LF line endings, which some batch
+// endpoints emit, and ignores the preamble and epilogue.
+
+use crate::errln;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Part {
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Part {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// The boundary when `content_type` is a multipart type.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mime: mime::Mime = content_type.parse().ok()?;
+    if mime.type_() != mime::MULTIPART {
+        return None;
+    }
+    mime.get_param(mime::BOUNDARY).map(|b| b.to_string())
+}
+
+pub fn parse(body: &[u8], boundary: &str) -> Result<Vec<Part>, String> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let starts: Vec<usize> = (0..body.len())
+        .filter(|&i| (i == 0 || body[i - 1] == b'\n') && body[i..].starts_with(&delimiter))
+        .collect();
+    if starts.is_empty() {
+        return Err(format!(
+            "The multipart body has no '--{}' boundary.",
+            boundary
+        ));
+    }
+
+    let mut parts = Vec::new();
+    for pair in starts.windows(2) {
+        let after = pair[0] + delimiter.len();
+        if body[after..].starts_with(b"--") {
+            break;
+        }
+        // The rest of the delimiter line (transport padding), then the part
+        let Some(eol) = body[after..pair[1]].iter().position(|&b| b == b'\n') else {
+            continue;
+        };
+        let content = &body[after + eol + 1..pair[1]];
+        // The line break before the next delimiter belongs to it
+        let content = content
+            .strip_suffix(b"\r\n")
+            .or_else(|| content.strip_suffix(b"\n"))
+            .unwrap_or(content);
+        parts.push(part(content));
+    }
+    let last = *starts.last().unwrap_or(&0);
+    if !body[last + delimiter.len()..].starts_with(b"--") {
+        errln!("Warning: The multipart body ends without a closing boundary.");
+    }
+    Ok(parts)
+}
+
+fn part(content: &[u8]) -> Part {
+    let (head, body) = if content.starts_with(b"\r\n") {
+        (&content[..0], &content[2..])
+    } else if content.starts_with(b"\n") {
+        (&content[..0], &content[1..])
+    } else {
+        match find(content, b"\r\n\r\n") {
+            Some(i) => (&content[..i], &content[i + 4..]),
+            None => match find(content, b"\n\n") {
+                Some(i) => (&content[..i], &content[i + 2..]),
+                None => (content, &content[content.len()..]),
+            },
+        }
+    };
+    let headers = String::from_utf8_lossy(head)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(n, v)| (n.trim().to_string(), v.trim().to_string()))
+        .collect();
+    Part {
+        headers,
+        body: body.to_vec(),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// --save-parts: the part's own file name from Content-Disposition when it
+// has one, otherwise part-N with an extension from its Content-Type.
+pub fn save(part: &Part, index: usize, dir: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create '{}': {}", dir.display(), e))?;
+    let name = part
+        .header("Content-Disposition")
+        .and_then(disposition_filename)
+        .unwrap_or_else(|| format!("part-{}.{}", index, extension(part.header("Content-Type"))));
+    let path = dir.join(name);
+    fs::write(&path, &part.body)
+        .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+    Ok(path)
+}
+
+// Only the final path component, so a part can't write outside the directory.
+fn disposition_filename(value: &str) -> Option<String> {
+    let name = value
+        .split(';')
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("filename"))
+        .map(|(_, v)| v.trim().trim_matches('"'))?;
+    let name = name.rsplit(['/', '\\']).next()?;
+    (!name.is_empty() && name != "." && name != "..").then(|| name.to_string())
+}
+
+fn extension(content_type: Option<&str>) -> &'static str {
+    let Some(mime) = content_type.and_then(|ct| ct.parse::<mime::Mime>().ok()) else {
+        return "bin";
+    };
+    match (mime.type_().as_str(), mime.subtype().as_str()) {
+        (_, "json") => "json",
+        (_, "html") => "html",
+        (_, "xml") => "xml",
+        ("text", "csv") => "csv",
+        ("text", _) => "txt",
+        ("image", "png") => "png",
+        ("image", "jpeg") => "jpg",
+        ("application", "pdf") => "pdf",
+        ("message", "http") => "http",
+        _ => match mime.suffix().map(|s| s.as_str()) {
+            Some("json") => "json",
+            Some("xml") => "xml",
+            _ => "bin",
+        },
+    }
+}
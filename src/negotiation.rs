@@ -0,0 +1,123 @@
+// Content negotiation: --accept-language, --accept-encoding and
+// --accept-charset, and a report of what the server picked.
+//
+// Each flag given without a value sends a default: the user's locale
+// (LC_ALL / LC_MESSAGES / LANG) for languages, the codings this build can
+// decode for encodings and UTF-8 for charsets.
+
+use reqwest::header::{
+    ACCEPT_CHARSET, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONTENT_ENCODING, CONTENT_LANGUAGE,
+    CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, VARY,
+};
+
+pub const DEFAULT_ENCODING: &str = "gzip, deflate";
+pub const DEFAULT_CHARSET: &str = "utf-8, *;q=0.1";
+
+pub struct Preferences {
+    pub language: Option<Option<String>>,
+    pub encoding: Option<Option<String>>,
+    pub charset: Option<Option<String>>,
+}
+
+impl Preferences {
+    pub fn any(&self) -> bool {
+        self.language.is_some() || self.encoding.is_some() || self.charset.is_some()
+    }
+
+    pub fn add_headers(&self, headers: &mut HeaderMap) -> Result<(), String> {
+        let wanted = [
+            (ACCEPT_LANGUAGE, pick(&self.language, default_language)),
+            (
+                ACCEPT_ENCODING,
+                pick(&self.encoding, || DEFAULT_ENCODING.to_string()),
+            ),
+            (
+                ACCEPT_CHARSET,
+                pick(&self.charset, || DEFAULT_CHARSET.to_string()),
+            ),
+        ];
+        for (name, value) in wanted {
+            let Some(value) = value else { continue };
+            let value = HeaderValue::from_str(&value)
+                .map_err(|_| format!("Invalid {} value '{}'.", name, value))?;
+            headers.insert(name, value);
+        }
+        Ok(())
+    }
+}
+
+// The flag's value, its default when given alone, or None when absent.
+fn pick(flag: &Option<Option<String>>, default: fn() -> String) -> Option<String> {
+    flag.as_ref()
+        .map(|value| value.clone().unwrap_or_else(default))
+}
+
+// "de_DE.UTF-8" -> "de-DE,de;q=0.9"; the C/POSIX locale means English.
+fn default_language() -> String {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|v| std::env::var(v).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    let tag = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or("")
+        .replace('_', "-");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return "en-US,en;q=0.9".to_string();
+    }
+    match tag.split_once('-') {
+        Some((language, _)) => format!("{},{};q=0.9", tag, language),
+        None => tag,
+    }
+}
+
+// What the server chose, for the stderr report.
+pub fn report(
+    prefs: &Preferences,
+    headers: &HeaderMap,
+    encoded_len: usize,
+    decoded_len: usize,
+) -> String {
+    let mut sent = HeaderMap::new();
+    let _ = prefs.add_headers(&mut sent);
+    let get = |map: &HeaderMap, name: &HeaderName| {
+        map.get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let asked = |name: &HeaderName| {
+        get(&sent, name)
+            .map(|v| format!(" (asked for {})", v))
+            .unwrap_or_default()
+    };
+
+    let mut lines = vec!["Content negotiation:".to_string()];
+    lines.push(format!(
+        "  Language: {}{}",
+        get(headers, &CONTENT_LANGUAGE).unwrap_or_else(|| "not stated".to_string()),
+        asked(&ACCEPT_LANGUAGE)
+    ));
+    let encoding = match get(headers, &CONTENT_ENCODING) {
+        Some(coding) => format!("{} ({} -> {} bytes)", coding, encoded_len, decoded_len),
+        None => "identity".to_string(),
+    };
+    lines.push(format!(
+        "  Encoding: {}{}",
+        encoding,
+        asked(&ACCEPT_ENCODING)
+    ));
+    let charset = get(headers, &CONTENT_TYPE)
+        .and_then(|ct| {
+            ct.split(';')
+                .filter_map(|p| p.split_once('='))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+                .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "not stated".to_string());
+    lines.push(format!("  Charset: {}{}", charset, asked(&ACCEPT_CHARSET)));
+    if let Some(vary) = get(headers, &VARY) {
+        lines.push(format!("  Vary: {}", vary));
+    }
+    lines.join("\n")
+}
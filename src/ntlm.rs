@@ -0,0 +1,366 @@
+// --ntlm: NTLMv2 authentication (MS-NLMP).
+//
+// NTLM authenticates a connection rather than a request: the client sends
+// a NEGOTIATE message, the server answers 401 with a CHALLENGE, and the
+// AUTHENTICATE message computed from it has to go out on the same
+// keep-alive connection. The handshake runs before the real request,
+// without a body, and the real request then carries the AUTHENTICATE
+// message on the pooled connection.
+
+use crate::client::WebClient;
+use crate::method::Method;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::StatusCode;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+
+// AV_PAIR ids in the challenge's target info.
+const MSV_AV_EOL: u16 = 0;
+const MSV_AV_TIMESTAMP: u16 = 7;
+
+// Seconds between 1601-01-01 (the FILETIME epoch) and 1970-01-01.
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+pub struct Credentials {
+    domain: String,
+    user: String,
+    password: String,
+}
+
+impl Credentials {
+    // "DOMAIN\user:password" or "user:password".
+    pub fn parse(value: &str) -> Credentials {
+        let (account, password) = value.split_once(':').unwrap_or((value, ""));
+        let (domain, user) = account.split_once('\\').unwrap_or(("", account));
+        Credentials {
+            domain: domain.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+// Runs the NEGOTIATE/CHALLENGE legs and returns the Authorization value for
+// the real request.
+pub fn handshake(
+    client: &WebClient,
+    method: Method,
+    url: &Url,
+    headers: &HeaderMap,
+    credentials: &Credentials,
+) -> Result<HeaderValue, String> {
+    let negotiate = format!("NTLM {}", STANDARD.encode(negotiate_message()));
+    let req = client
+        .request(method, url.clone())
+        .headers(headers.clone())
+        .header(AUTHORIZATION, negotiate);
+    let res = client
+        .execute(req)
+        .map_err(|e| format!("NTLM handshake failed: {}", e))?;
+
+    let status = res.status();
+    let challenge = res
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(|v| v.strip_prefix("NTLM ").map(|t| t.trim().to_string()));
+    // Drain the body so the connection goes back to the pool for the
+    // AUTHENTICATE leg
+    let _ = res.bytes();
+
+    let Some(challenge) = challenge else {
+        return Err(if status == StatusCode::UNAUTHORIZED {
+            "The server doesn't offer NTLM authentication.".to_string()
+        } else {
+            format!(
+                "Expected an NTLM challenge, got status {}.",
+                status.as_u16()
+            )
+        });
+    };
+    let challenge = STANDARD
+        .decode(challenge)
+        .map_err(|_| "The NTLM challenge is not valid base64.".to_string())?;
+    let message = authenticate_message(&parse_challenge(&challenge)?, credentials)?;
+
+    let mut value = HeaderValue::from_str(&format!("NTLM {}", STANDARD.encode(message)))
+        .map_err(|e| e.to_string())?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+fn negotiate_message() -> Vec<u8> {
+    let flags = NEGOTIATE_OEM
+        | NEGOTIATE_UNICODE
+        | REQUEST_TARGET
+        | NEGOTIATE_NTLM
+        | NEGOTIATE_ALWAYS_SIGN
+        | NEGOTIATE_EXTENDED_SESSIONSECURITY;
+    let mut msg = Vec::with_capacity(32);
+    msg.extend_from_slice(SIGNATURE);
+    msg.extend_from_slice(&1u32.to_le_bytes());
+    msg.extend_from_slice(&flags.to_le_bytes());
+    // Empty domain and workstation fields
+    msg.extend_from_slice(&[0; 16]);
+    msg
+}
+
+struct Challenge {
+    flags: u32,
+    server_challenge: [u8; 8],
+    target_info: Vec<u8>,
+}
+
+fn parse_challenge(msg: &[u8]) -> Result<Challenge, String> {
+    let invalid = || "The NTLM challenge message is malformed.".to_string();
+    if msg.len() < 32 || &msg[..8] != SIGNATURE || u32_at(msg, 8) != 2 {
+        return Err(invalid());
+    }
+    let flags = u32_at(msg, 20);
+    let mut server_challenge = [0; 8];
+    server_challenge.copy_from_slice(&msg[24..32]);
+
+    let target_info = if flags & NEGOTIATE_TARGET_INFO != 0 && msg.len() >= 48 {
+        let len = u16_at(msg, 40) as usize;
+        let offset = u32_at(msg, 44) as usize;
+        msg.get(offset..offset + len).ok_or_else(invalid)?.to_vec()
+    } else {
+        Vec::new()
+    };
+    Ok(Challenge {
+        flags,
+        server_challenge,
+        target_info,
+    })
+}
+
+fn authenticate_message(challenge: &Challenge, creds: &Credentials) -> Result<Vec<u8>, String> {
+    let unicode = challenge.flags & NEGOTIATE_UNICODE != 0;
+    let encode = |s: &str| {
+        if unicode {
+            utf16le(s)
+        } else {
+            s.as_bytes().to_vec()
+        }
+    };
+
+    let nt_hash = md4(&utf16le(&creds.password));
+    let identity = utf16le(&format!("{}{}", creds.user.to_uppercase(), creds.domain));
+    let v2_hash = hmac_md5(&nt_hash, &identity)?;
+
+    let mut client_challenge = [0u8; 8];
+    openssl::rand::rand_bytes(&mut client_challenge).map_err(|e| e.to_string())?;
+    // A server timestamp means the client must use it, and send no LMv2
+    // response (MS-NLMP 3.1.5.1.2)
+    let server_time = av_timestamp(&challenge.target_info);
+    let timestamp = server_time.unwrap_or_else(filetime_now);
+
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp.to_le_bytes());
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(&challenge.target_info);
+    blob.extend_from_slice(&[0; 4]);
+
+    let proof = hmac_md5(&v2_hash, &[&challenge.server_challenge[..], &blob].concat())?;
+    let nt_response = [&proof[..], &blob].concat();
+    let lm_response = if server_time.is_some() {
+        vec![0; 24]
+    } else {
+        let lm = hmac_md5(
+            &v2_hash,
+            &[&challenge.server_challenge[..], &client_challenge].concat(),
+        )?;
+        [&lm[..], &client_challenge].concat()
+    };
+
+    let flags = (if unicode {
+        NEGOTIATE_UNICODE
+    } else {
+        NEGOTIATE_OEM
+    }) | NEGOTIATE_NTLM
+        | NEGOTIATE_ALWAYS_SIGN
+        | NEGOTIATE_EXTENDED_SESSIONSECURITY
+        | (challenge.flags & NEGOTIATE_TARGET_INFO);
+
+    let fields = [
+        lm_response,
+        nt_response,
+        encode(&creds.domain),
+        encode(&creds.user),
+        encode(""), // workstation
+        Vec::new(), // session key
+    ];
+    let header_len = 64;
+    let mut msg = Vec::new();
+    msg.extend_from_slice(SIGNATURE);
+    msg.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = header_len;
+    for field in &fields {
+        let len = field.len() as u16;
+        msg.extend_from_slice(&len.to_le_bytes());
+        msg.extend_from_slice(&len.to_le_bytes());
+        msg.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    msg.extend_from_slice(&flags.to_le_bytes());
+    for field in &fields {
+        msg.extend_from_slice(field);
+    }
+    Ok(msg)
+}
+
+fn av_timestamp(target_info: &[u8]) -> Option<u64> {
+    let mut pos = 0;
+    while pos + 4 <= target_info.len() {
+        let id = u16_at(target_info, pos);
+        let len = u16_at(target_info, pos + 2) as usize;
+        let value = target_info.get(pos + 4..pos + 4 + len)?;
+        match id {
+            MSV_AV_EOL => return None,
+            MSV_AV_TIMESTAMP if len == 8 => {
+                return Some(u64::from_le_bytes(value.try_into().ok()?));
+            }
+            _ => pos += 4 + len,
+        }
+    }
+    None
+}
+
+fn filetime_now() -> u64 {
+    let since_unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (since_unix.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000
+        + u64::from(since_unix.subsec_nanos() / 100)
+}
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn hmac_md5(key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    let key = PKey::hmac(key).map_err(|e| e.to_string())?;
+    let mut signer = Signer::new(MessageDigest::md5(), &key).map_err(|e| e.to_string())?;
+    signer.sign_oneshot_to_vec(data).map_err(|e| e.to_string())
+}
+
+// MD4 (RFC 1320), which OpenSSL 3 only ships in its legacy provider.
+fn md4(input: &[u8]) -> [u8; 16] {
+    let mut msg = input.to_vec();
+    let bit_len = (input.len() as u64).wrapping_mul(8);
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&bit_len.to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for block in msg.chunks(64) {
+        let x: Vec<u32> = block
+            .chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+
+        let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+        let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+        let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+        for &i in &[0, 4, 8, 12] {
+            a = a.wrapping_add(f(b, c, d)).wrapping_add(x[i]).rotate_left(3);
+            d = d
+                .wrapping_add(f(a, b, c))
+                .wrapping_add(x[i + 1])
+                .rotate_left(7);
+            c = c
+                .wrapping_add(f(d, a, b))
+                .wrapping_add(x[i + 2])
+                .rotate_left(11);
+            b = b
+                .wrapping_add(f(c, d, a))
+                .wrapping_add(x[i + 3])
+                .rotate_left(19);
+        }
+        for &i in &[0, 1, 2, 3] {
+            let k = 0x5a82_7999;
+            a = a
+                .wrapping_add(g(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(g(a, b, c))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(5);
+            c = c
+                .wrapping_add(g(d, a, b))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            b = b
+                .wrapping_add(g(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(13);
+        }
+        for &i in &[0, 2, 1, 3] {
+            let k = 0x6ed9_eba1;
+            a = a
+                .wrapping_add(h(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(h(a, b, c))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            c = c
+                .wrapping_add(h(d, a, b))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(11);
+            b = b
+                .wrapping_add(h(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(15);
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 16];
+    for (i, word) in state.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
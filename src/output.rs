@@ -0,0 +1,303 @@
+// Where status and error lines go.
+//
+// Normally everything is printed to stdout around the body. With --raw,
+// stdout carries the body bytes alone: status lines are dropped and
+// errors move to stderr. With --error-format json every error is instead
+// one JSON object on stderr:
+//
+//   {"category":"dns","exit_code":6,"message":"Could not resolve host: x."}
+//
+// Everything a request prints goes through stdout() and stderr() (or the
+// outln! and errln! macros), so a multi-URL run can capture each URL's
+// output on its own thread and print it as one block.
+
+use crate::deadline;
+use crate::exit::{self, Category};
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fmt::{self, Display};
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static RAW: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+static FAIL: AtomicBool = AtomicBool::new(false);
+// Resolved secret values and the placeholders printed in their place
+static MASKS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // The category of the error that decides this request's exit status
+    static FAILURE: Cell<Option<Category>> = const { Cell::new(None) };
+    // What this thread has printed, while capture() runs
+    static CAPTURE: RefCell<Option<Captured>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+// Output held back in the order it was written, to either stream.
+#[derive(Default)]
+pub struct Captured(Vec<(Stream, Vec<u8>)>);
+
+impl Captured {
+    // Through stdout() and stderr(), so a capture further out holds it.
+    pub fn print(&self) {
+        for (stream, bytes) in &self.0 {
+            let _ = Writer(*stream).write_all(bytes);
+        }
+        let _ = stdout().flush();
+    }
+
+    // Both streams as one text, in the order they were written.
+    #[cfg(test)]
+    pub(crate) fn text(&self) -> String {
+        let bytes: Vec<u8> = self.0.iter().flat_map(|(_, b)| b.clone()).collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+// Run `f`, holding back what it prints on this thread.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Captured) {
+    CAPTURE.with_borrow_mut(|c| *c = Some(Captured::default()));
+    let result = f();
+    let captured = CAPTURE.with_borrow_mut(Option::take).unwrap_or_default();
+    (result, captured)
+}
+
+pub struct Writer(Stream);
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURE.with_borrow_mut(|c| {
+            let Some(Captured(parts)) = c else {
+                return false;
+            };
+            match parts.last_mut() {
+                Some((stream, bytes)) if *stream == self.0 => bytes.extend_from_slice(buf),
+                _ => parts.push((self.0, buf.to_vec())),
+            }
+            true
+        });
+        match self.0 {
+            _ if captured => Ok(buf.len()),
+            Stream::Stdout => io::stdout().write(buf),
+            Stream::Stderr => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0 {
+            Stream::Stdout => io::stdout().flush(),
+            Stream::Stderr => io::stderr().flush(),
+        }
+    }
+}
+
+pub fn stdout() -> Writer {
+    Writer(Stream::Stdout)
+}
+
+pub fn stderr() -> Writer {
+    Writer(Stream::Stderr)
+}
+
+pub fn print(args: fmt::Arguments) {
+    let _ = stdout().write_fmt(args);
+}
+
+pub fn eprint(args: fmt::Arguments) {
+    let _ = stderr().write_fmt(args);
+}
+
+// println! for the output of a request.
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::output::print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+// eprintln! for the output of a request.
+#[macro_export]
+macro_rules! errln {
+    () => {
+        $crate::output::eprint(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::eprint(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+pub fn set_raw(raw: bool) {
+    RAW.store(raw, Ordering::Relaxed);
+}
+
+pub fn is_raw() -> bool {
+    RAW.load(Ordering::Relaxed)
+}
+
+pub fn is_terminal() -> bool {
+    io::stdout().is_terminal()
+}
+
+// Whether stderr is a terminal this thread writes to directly, which a
+// progress meter can redraw.
+pub fn stderr_is_terminal() -> bool {
+    io::stderr().is_terminal() && CAPTURE.with_borrow(Option::is_none)
+}
+
+// --color: "auto" colors a terminal's output unless $NO_COLOR is set.
+pub fn set_color(when: &str) {
+    let color = match when {
+        "always" => true,
+        "never" => false,
+        _ => is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    };
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+// Whether bodies are highlighted; never for --raw.
+pub fn color() -> bool {
+    COLOR.load(Ordering::Relaxed) && !is_raw()
+}
+
+pub fn set_json_errors(json: bool) {
+    JSON_ERRORS.store(json, Ordering::Relaxed);
+}
+
+// --fail: an HTTP error status ends the run with a failure.
+pub fn set_fail(fail: bool) {
+    FAIL.store(fail, Ordering::Relaxed);
+}
+
+// A summary line such as "Requesting URL: ...".
+pub fn status(line: impl Display) {
+    if !is_raw() {
+        outln!("{}", redact(&line.to_string()));
+    }
+}
+
+// Keep `value` out of printed lines, showing `placeholder` instead, both
+// as given and as percent-encoded into a URL.
+pub fn mask(value: &str, placeholder: &str) {
+    if value.is_empty() {
+        return;
+    }
+    let mut masks = MASKS.lock().unwrap();
+    for value in [value.to_string(), crate::url_norm::escape_path(value)] {
+        if !masks.iter().any(|(v, _)| *v == value) {
+            masks.push((value, placeholder.to_string()));
+        }
+    }
+    // Longest first, so a secret containing another is replaced whole
+    masks.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+}
+
+pub fn redact(text: &str) -> String {
+    let masks = MASKS.lock().unwrap();
+    let mut text = text.to_string();
+    for (value, placeholder) in masks.iter() {
+        if text.contains(value.as_str()) {
+            text = text.replace(value.as_str(), placeholder);
+        }
+    }
+    text
+}
+
+pub fn error(error: impl Into<exit::Error>) {
+    // Past the --deadline, whatever failed, failed because of it
+    let mut error = match deadline::check() {
+        Err(exceeded) => exceeded,
+        Ok(()) => error.into(),
+    };
+    error.message = redact(&error.message);
+    {
+        // The first error decides, unless it was only an HTTP status
+        if FAILURE.get().is_none_or(|c| c == Category::Http) {
+            FAILURE.set(Some(error.category));
+        }
+    }
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        let line = json!({
+            "category": error.category.name(),
+            "exit_code": code(error.category),
+            "message": error.message,
+        });
+        errln!("{}", line);
+    } else if is_raw() {
+        errln!("Error: {}", error);
+    } else {
+        outln!("Error: {}", error);
+    }
+}
+
+// Whether any error was printed during this request.
+pub fn errored() -> bool {
+    FAILURE.get().is_some()
+}
+
+// The exit status for the errors printed so far; an HTTP error status only
+// counts with --fail, as in curl.
+pub fn exit_code() -> i32 {
+    FAILURE.get().map_or(0, code)
+}
+
+fn code(category: Category) -> i32 {
+    match category {
+        Category::Http if !FAIL.load(Ordering::Relaxed) => 0,
+        category => category.code(),
+    }
+}
+
+// The body exactly as received.
+pub fn raw_body(body: &[u8]) {
+    let mut stdout = stdout();
+    let _ = stdout.write_all(body).and_then(|_| stdout.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masked_values_show_their_placeholder() {
+        mask("s3cr3t value", "{{vault:kv/app#token}}");
+        assert_eq!(
+            redact("https://x/?key=s3cr3t%20value and s3cr3t value"),
+            "https://x/?key={{vault:kv/app#token}} and {{vault:kv/app#token}}"
+        );
+        assert_eq!(redact("nothing here"), "nothing here");
+    }
+
+    #[test]
+    fn capture_keeps_the_order_of_both_streams() {
+        let ((), captured) = capture(|| {
+            outln!("one");
+            errln!("two");
+            let _ = write!(stderr(), "three");
+            outln!("four");
+        });
+        let parts: Vec<(bool, &[u8])> = captured
+            .0
+            .iter()
+            .map(|(stream, bytes)| (*stream == Stream::Stdout, bytes.as_slice()))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (true, &b"one\n"[..]),
+                (false, b"two\nthree"),
+                (true, b"four\n")
+            ]
+        );
+    }
+}
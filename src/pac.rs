@@ -0,0 +1,827 @@
+// --proxy-pac: proxy auto-config files.
+//
+// A PAC file is JavaScript defining FindProxyForURL(url, host), which
+// returns e.g. "PROXY proxy.corp:8080; DIRECT". Real-world PAC files stick
+// to a small part of the language (functions, var, if/else, return, string
+// and boolean expressions and the PAC helpers such as shExpMatch and
+// isInNet), so this is a small interpreter for exactly that part rather
+// than a full JavaScript engine. Anything outside it is reported as a
+// syntax error when the file is loaded.
+
+use crate::filter::glob_match;
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use url::Url;
+
+// Bound on nested calls, so a recursive PAC file can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone)]
+pub struct Pac {
+    functions: Arc<HashMap<String, Function>>,
+}
+
+struct Function {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+}
+
+// One entry of the FindProxyForURL result.
+#[derive(Debug, PartialEq)]
+pub enum Route {
+    Direct,
+    Proxy(Url),
+}
+
+impl Pac {
+    // `source` is a file path or an http(s):// URL.
+    pub fn load(source: &str) -> Result<Pac, String> {
+        let script = if source.starts_with("http://") || source.starts_with("https://") {
+            // The PAC file itself is always fetched directly
+            reqwest::blocking::Client::builder()
+                .no_proxy()
+                .build()
+                .and_then(|c| c.get(source).send())
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.text())
+                .map_err(|e| format!("Unable to fetch the PAC file: {}", e))?
+        } else {
+            fs::read_to_string(source)
+                .map_err(|e| format!("Unable to read the PAC file '{}': {}", source, e))?
+        };
+        Pac::parse(&script)
+    }
+
+    pub fn parse(script: &str) -> Result<Pac, String> {
+        let tokens = lex(script).map_err(|(line, e)| format!("PAC file line {}: {}", line, e))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let mut functions = HashMap::new();
+        while !parser.at_end() {
+            // Top-level statements other than functions run once in a
+            // browser; PAC files in practice only use them for dead code
+            if let Stmt::Function(name, params, body) = parser
+                .statement()
+                .map_err(|(line, e)| format!("PAC file line {}: {}", line, e))?
+            {
+                functions.insert(name, Function { params, body });
+            }
+        }
+        if !functions.contains_key("FindProxyForURL") {
+            return Err("The PAC file doesn't define FindProxyForURL(url, host).".to_string());
+        }
+        Ok(Pac {
+            functions: Arc::new(functions),
+        })
+    }
+
+    // The raw FindProxyForURL result for a request URL.
+    pub fn find_proxy(&self, url: &Url) -> Result<String, String> {
+        // Like browsers, only reveal the origin of https:// URLs to the script
+        let mut url = url.clone();
+        if url.scheme() == "https" {
+            url.set_path("/");
+            url.set_query(None);
+        }
+        url.set_fragment(None);
+        let host = url.host_str().unwrap_or("").to_string();
+
+        let mut vm = Vm {
+            pac: self,
+            depth: 0,
+        };
+        let result = vm.call(
+            "FindProxyForURL",
+            vec![Value::Str(url.to_string()), Value::Str(host)],
+        )?;
+        match result {
+            Value::Str(s) => Ok(s),
+            other => Err(format!(
+                "FindProxyForURL returned {} instead of a string",
+                other.type_name()
+            )),
+        }
+    }
+}
+
+// The usable routes of a result such as "PROXY a:8080; SOCKS b:1080; DIRECT",
+// in order. SOCKS entries are skipped since this build has no SOCKS support.
+pub fn routes(result: &str) -> Vec<Route> {
+    result
+        .split(';')
+        .filter_map(|entry| {
+            let mut parts = entry.split_whitespace();
+            let kind = parts.next()?.to_ascii_uppercase();
+            let target = parts.next();
+            match (kind.as_str(), target) {
+                ("DIRECT", _) => Some(Route::Direct),
+                ("PROXY" | "HTTP", Some(t)) => {
+                    Url::parse(&format!("http://{}", t)).ok().map(Route::Proxy)
+                }
+                ("HTTPS", Some(t)) => Url::parse(&format!("https://{}", t)).ok().map(Route::Proxy),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// ---------------- LEXER ----------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Punct(&'static str),
+}
+
+type Error = (usize, String);
+
+const PUNCTUATION: [&str; 27] = [
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", ",", ";", ".", "!", "<",
+    ">", "+", "-", "*", "/", "%", "?", ":", "=", "[",
+];
+
+fn lex(src: &str) -> Result<Vec<(usize, Tok)>, Error> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return Err((line, "unterminated string".to_string())),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(&other) => s.push(other),
+                            None => return Err((line, "unterminated string".to_string())),
+                        }
+                    }
+                    Some(&other) => s.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push((line, Tok::Str(s)));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| (line, format!("bad number '{}'", text)))?;
+            tokens.push((line, Tok::Num(n)));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push((line, Tok::Ident(chars[start..i].iter().collect())));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+            let Some(p) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) else {
+                return Err((line, format!("unexpected character '{}'", c)));
+            };
+            i += p.len();
+            tokens.push((line, Tok::Punct(p)));
+        }
+    }
+    Ok(tokens)
+}
+
+// ---------------- PARSER ----------------
+
+enum Stmt {
+    Function(String, Vec<String>, Vec<Stmt>),
+    Var(Vec<(String, Option<Expr>)>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    Return(Option<Expr>),
+    Block(Vec<Stmt>),
+    Expr(Expr),
+    Empty,
+}
+
+enum Expr {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Null,
+    Ident(String),
+    Assign(String, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Member(Box<Expr>, String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<(usize, Tok)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(l, _)| *l)
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, Error> {
+        Err((self.line(), message))
+    }
+
+    fn next(&mut self) -> Result<Tok, Error> {
+        let Some((_, tok)) = self.tokens.get(self.pos).cloned() else {
+            return self.error("unexpected end of file".to_string());
+        };
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Punct(q)) if *q == p)
+    }
+
+    fn is_keyword(&self, k: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(i)) if i == k)
+    }
+
+    fn eat(&mut self, p: &str) -> bool {
+        if self.is_punct(p) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), Error> {
+        if self.eat(p) {
+            Ok(())
+        } else {
+            let found = self.peek().map_or("end of file".to_string(), describe);
+            self.error(format!("expected '{}', found {}", p, found))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.next()? {
+            Tok::Ident(name) => Ok(name),
+            other => {
+                self.pos -= 1;
+                self.error(format!("expected a name, found {}", describe(&other)))
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Error> {
+        if self.eat(";") {
+            return Ok(Stmt::Empty);
+        }
+        if self.eat("{") {
+            return Ok(Stmt::Block(self.block_rest()?));
+        }
+        let keyword = match self.peek() {
+            Some(Tok::Ident(k)) => k.clone(),
+            _ => String::new(),
+        };
+        match keyword.as_str() {
+            "function" => {
+                self.pos += 1;
+                let name = self.ident()?;
+                self.expect("(")?;
+                let mut params = Vec::new();
+                while !self.eat(")") {
+                    params.push(self.ident()?);
+                    if !self.is_punct(")") {
+                        self.expect(",")?;
+                    }
+                }
+                self.expect("{")?;
+                let body = self.block_rest()?;
+                Ok(Stmt::Function(name, params, body))
+            }
+            "var" | "let" | "const" => {
+                self.pos += 1;
+                let mut vars = Vec::new();
+                loop {
+                    let name = self.ident()?;
+                    let init = if self.eat("=") {
+                        Some(self.expression()?)
+                    } else {
+                        None
+                    };
+                    vars.push((name, init));
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.eat(";");
+                Ok(Stmt::Var(vars))
+            }
+            "if" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let cond = self.expression()?;
+                self.expect(")")?;
+                let then = Box::new(self.statement()?);
+                let otherwise = if self.is_keyword("else") {
+                    self.pos += 1;
+                    Some(Box::new(self.statement()?))
+                } else {
+                    None
+                };
+                Ok(Stmt::If(cond, then, otherwise))
+            }
+            "return" => {
+                self.pos += 1;
+                let value = if self.is_punct(";") || self.is_punct("}") || self.at_end() {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.eat(";");
+                Ok(Stmt::Return(value))
+            }
+            "for" | "while" | "do" | "switch" | "try" | "new" | "throw" => {
+                self.error(format!("'{}' is not supported in PAC files here", keyword))
+            }
+            _ => {
+                let expr = self.expression()?;
+                self.eat(";");
+                Ok(Stmt::Expr(expr))
+            }
+        }
+    }
+
+    // Statements up to the closing brace, which is consumed.
+    fn block_rest(&mut self) -> Result<Vec<Stmt>, Error> {
+        let mut body = Vec::new();
+        while !self.eat("}") {
+            if self.at_end() {
+                return self.error("missing '}'".to_string());
+            }
+            body.push(self.statement()?);
+        }
+        Ok(body)
+    }
+
+    fn expression(&mut self) -> Result<Expr, Error> {
+        let target = self.conditional()?;
+        if self.eat("=") {
+            let Expr::Ident(name) = target else {
+                return self.error("can only assign to a variable".to_string());
+            };
+            return Ok(Expr::Assign(name, Box::new(self.expression()?)));
+        }
+        Ok(target)
+    }
+
+    fn conditional(&mut self) -> Result<Expr, Error> {
+        let cond = self.binary(0)?;
+        if self.eat("?") {
+            let a = self.expression()?;
+            self.expect(":")?;
+            let b = self.expression()?;
+            return Ok(Expr::Cond(Box::new(cond), Box::new(a), Box::new(b)));
+        }
+        Ok(cond)
+    }
+
+    // Binary operators by precedence level, loosest first.
+    fn binary(&mut self, level: usize) -> Result<Expr, Error> {
+        const LEVELS: [&[&str]; 6] = [
+            &["||"],
+            &["&&"],
+            &["===", "!==", "==", "!="],
+            &["<=", ">=", "<", ">"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = LEVELS[level].iter().find(|op| self.is_punct(op)) {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat("(") {
+                let mut args = Vec::new();
+                while !self.eat(")") {
+                    args.push(self.expression()?);
+                    if !self.is_punct(")") {
+                        self.expect(",")?;
+                    }
+                }
+                expr = Expr::Call(Box::new(expr), args);
+            } else if self.eat(".") {
+                let name = self.ident()?;
+                expr = Expr::Member(Box::new(expr), name);
+            } else if self.is_punct("[") {
+                return self.error("arrays are not supported in PAC files here".to_string());
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.next()? {
+            Tok::Str(s) => Ok(Expr::Str(s)),
+            Tok::Num(n) => Ok(Expr::Num(n)),
+            Tok::Ident(i) => Ok(match i.as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                "null" | "undefined" => Expr::Null,
+                _ => Expr::Ident(i),
+            }),
+            Tok::Punct("(") => {
+                let e = self.expression()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            other => {
+                self.pos -= 1;
+                self.error(format!("unexpected {}", describe(&other)))
+            }
+        }
+    }
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Ident(i) => format!("'{}'", i),
+        Tok::Str(s) => format!("\"{}\"", s),
+        Tok::Num(n) => n.to_string(),
+        Tok::Punct(p) => format!("'{}'", p),
+    }
+}
+
+// ---------------- INTERPRETER ----------------
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Str(s) => !s.is_empty(),
+            Value::Num(n) => *n != 0.0 && !n.is_nan(),
+            Value::Bool(b) => *b,
+            Value::Null => false,
+        }
+    }
+
+    fn num(&self) -> f64 {
+        match self {
+            Value::Str(s) => s.trim().parse().unwrap_or(f64::NAN),
+            Value::Num(n) => *n,
+            Value::Bool(b) => f64::from(u8::from(*b)),
+            Value::Null => 0.0,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Value::Str(s) => s.clone(),
+            Value::Num(n) if n.fract() == 0.0 && n.is_finite() => format!("{}", *n as i64),
+            Value::Num(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Null => "null".to_string(),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "a string",
+            Value::Num(_) => "a number",
+            Value::Bool(_) => "a boolean",
+            Value::Null => "null",
+        }
+    }
+}
+
+struct Vm<'a> {
+    pac: &'a Pac,
+    depth: usize,
+}
+
+type Scope = HashMap<String, Value>;
+
+impl Vm<'_> {
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let Some(function) = self.pac.functions.get(name) else {
+            return builtin(name, &args);
+        };
+        if self.depth == MAX_DEPTH {
+            return Err(format!("calls nested too deeply in {}()", name));
+        }
+        self.depth += 1;
+        let mut scope: Scope = function
+            .params
+            .iter()
+            .cloned()
+            .zip(args.into_iter().chain(std::iter::repeat(Value::Null)))
+            .collect();
+        let result = self.block(&function.body, &mut scope);
+        self.depth -= 1;
+        Ok(result?.unwrap_or(Value::Null))
+    }
+
+    // Some(value) once a return statement ran.
+    fn block(&mut self, body: &[Stmt], scope: &mut Scope) -> Result<Option<Value>, String> {
+        for stmt in body {
+            if let Some(v) = self.exec(stmt, scope)? {
+                return Ok(Some(v));
+            }
+        }
+        Ok(None)
+    }
+
+    fn exec(&mut self, stmt: &Stmt, scope: &mut Scope) -> Result<Option<Value>, String> {
+        match stmt {
+            Stmt::Function(..) => Err("nested functions are not supported".to_string()),
+            Stmt::Var(vars) => {
+                for (name, init) in vars {
+                    let value = match init {
+                        Some(e) => self.eval(e, scope)?,
+                        None => Value::Null,
+                    };
+                    scope.insert(name.clone(), value);
+                }
+                Ok(None)
+            }
+            Stmt::If(cond, then, otherwise) => {
+                if self.eval(cond, scope)?.truthy() {
+                    self.exec(then, scope)
+                } else if let Some(otherwise) = otherwise {
+                    self.exec(otherwise, scope)
+                } else {
+                    Ok(None)
+                }
+            }
+            Stmt::Return(value) => Ok(Some(match value {
+                Some(e) => self.eval(e, scope)?,
+                None => Value::Null,
+            })),
+            Stmt::Block(body) => self.block(body, scope),
+            Stmt::Expr(e) => self.eval(e, scope).map(|_| None),
+            Stmt::Empty => Ok(None),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, scope: &mut Scope) -> Result<Value, String> {
+        Ok(match expr {
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Num(n) => Value::Num(*n),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Null => Value::Null,
+            Expr::Ident(name) => scope
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("{} is not defined", name))?,
+            Expr::Assign(name, value) => {
+                let value = self.eval(value, scope)?;
+                scope.insert(name.clone(), value.clone());
+                value
+            }
+            Expr::Call(callee, args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for a in args {
+                    values.push(self.eval(a, scope)?);
+                }
+                match callee.as_ref() {
+                    Expr::Ident(name) => self.call(name, values)?,
+                    Expr::Member(target, method) => {
+                        let target = self.eval(target, scope)?;
+                        string_method(&target, method, &values)?
+                    }
+                    _ => return Err("only named functions can be called".to_string()),
+                }
+            }
+            Expr::Member(target, property) => {
+                match (self.eval(target, scope)?, property.as_str()) {
+                    (Value::Str(s), "length") => Value::Num(s.chars().count() as f64),
+                    (v, p) => return Err(format!("{} has no property '{}'", v.type_name(), p)),
+                }
+            }
+            Expr::Not(e) => Value::Bool(!self.eval(e, scope)?.truthy()),
+            Expr::Neg(e) => Value::Num(-self.eval(e, scope)?.num()),
+            Expr::Cond(c, a, b) => {
+                if self.eval(c, scope)?.truthy() {
+                    self.eval(a, scope)?
+                } else {
+                    self.eval(b, scope)?
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left, scope)?;
+                // && and || short-circuit and yield an operand, as in JS
+                match *op {
+                    "&&" if !left.truthy() => return Ok(left),
+                    "||" if left.truthy() => return Ok(left),
+                    "&&" | "||" => return self.eval(right, scope),
+                    _ => {}
+                }
+                let right = self.eval(right, scope)?;
+                binary(op, left, right)
+            }
+        })
+    }
+}
+
+fn binary(op: &str, l: Value, r: Value) -> Value {
+    let loose_eq = |l: &Value, r: &Value| match (l, r) {
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Null, Value::Null) => true,
+        (Value::Null, _) | (_, Value::Null) => false,
+        _ => l.num() == r.num(),
+    };
+    match op {
+        "===" => Value::Bool(l == r),
+        "!==" => Value::Bool(l != r),
+        "==" => Value::Bool(loose_eq(&l, &r)),
+        "!=" => Value::Bool(!loose_eq(&l, &r)),
+        "+" => match (&l, &r) {
+            (Value::Str(_), _) | (_, Value::Str(_)) => Value::Str(l.text() + &r.text()),
+            _ => Value::Num(l.num() + r.num()),
+        },
+        "-" => Value::Num(l.num() - r.num()),
+        "*" => Value::Num(l.num() * r.num()),
+        "/" => Value::Num(l.num() / r.num()),
+        "%" => Value::Num(l.num() % r.num()),
+        _ => {
+            let ordering = match (&l, &r) {
+                (Value::Str(a), Value::Str(b)) => a.partial_cmp(b),
+                _ => l.num().partial_cmp(&r.num()),
+            };
+            Value::Bool(ordering.is_some_and(|o| match op {
+                "<" => o.is_lt(),
+                ">" => o.is_gt(),
+                "<=" => o.is_le(),
+                _ => o.is_ge(),
+            }))
+        }
+    }
+}
+
+fn string_method(target: &Value, method: &str, args: &[Value]) -> Result<Value, String> {
+    let Value::Str(s) = target else {
+        return Err(format!("{} has no method '{}'", target.type_name(), method));
+    };
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Null);
+    let chars: Vec<char> = s.chars().collect();
+    let index = |v: Value, default: usize| match v {
+        Value::Null => default,
+        v => (v.num().max(0.0) as usize).min(chars.len()),
+    };
+    Ok(match method {
+        "toLowerCase" => Value::Str(s.to_lowercase()),
+        "toUpperCase" => Value::Str(s.to_uppercase()),
+        "indexOf" => {
+            let needle = arg(0).text();
+            Value::Num(
+                s.find(&needle)
+                    .map_or(-1.0, |b| s[..b].chars().count() as f64),
+            )
+        }
+        "substring" => {
+            let (a, b) = (index(arg(0), 0), index(arg(1), chars.len()));
+            Value::Str(chars[a.min(b)..a.max(b)].iter().collect())
+        }
+        "charAt" => {
+            let i = index(arg(0), 0);
+            Value::Str(chars.get(i).map(|c| c.to_string()).unwrap_or_default())
+        }
+        "startsWith" => Value::Bool(s.starts_with(&arg(0).text())),
+        "endsWith" => Value::Bool(s.ends_with(&arg(0).text())),
+        _ => return Err(format!("unsupported string method '{}'", method)),
+    })
+}
+
+// ---------------- PAC HELPER FUNCTIONS ----------------
+
+fn builtin(name: &str, args: &[Value]) -> Result<Value, String> {
+    let arg = |i: usize| args.get(i).map(Value::text).unwrap_or_default();
+    Ok(match name {
+        "isPlainHostName" => Value::Bool(!arg(0).contains('.')),
+        "dnsDomainIs" => Value::Bool(
+            arg(0)
+                .to_ascii_lowercase()
+                .ends_with(&arg(1).to_ascii_lowercase()),
+        ),
+        "localHostOrDomainIs" => {
+            let (host, full) = (arg(0).to_ascii_lowercase(), arg(1).to_ascii_lowercase());
+            Value::Bool(
+                host == full || (!host.contains('.') && full.starts_with(&format!("{}.", host))),
+            )
+        }
+        "dnsDomainLevels" => Value::Num(arg(0).matches('.').count() as f64),
+        "shExpMatch" => Value::Bool(glob_match(&arg(1), &arg(0))),
+        "isResolvable" => Value::Bool(resolve(&arg(0)).is_some()),
+        "dnsResolve" => resolve(&arg(0)).map_or(Value::Null, |ip| Value::Str(ip.to_string())),
+        "myIpAddress" => Value::Str(my_ip_address().to_string()),
+        "isInNet" => {
+            let (Ok(pattern), Ok(mask)) = (arg(1).parse::<Ipv4Addr>(), arg(2).parse::<Ipv4Addr>())
+            else {
+                return Ok(Value::Bool(false));
+            };
+            let mask = u32::from(mask);
+            Value::Bool(
+                resolve(&arg(0))
+                    .is_some_and(|ip| u32::from(ip) & mask == u32::from(pattern) & mask),
+            )
+        }
+        "alert" => Value::Null,
+        _ => return Err(format!("{} is not defined", name)),
+    })
+}
+
+fn resolve(host: &str) -> Option<Ipv4Addr> {
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        return Some(ip);
+    }
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .find_map(|a| match a.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+}
+
+// The address of the interface that routes to the internet. Connecting a
+// UDP socket sends nothing; it only selects the route.
+fn my_ip_address() -> Ipv4Addr {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|s| s.connect("192.0.2.1:80").map(|_| s))
+        .and_then(|s| s.local_addr())
+        .ok()
+        .and_then(|a| match a.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .unwrap_or(Ipv4Addr::LOCALHOST)
+}
@@ -0,0 +1,154 @@
+// --paginate: after each page, request the next one until a page doesn't
+// name one or --max-pages is reached.
+//
+// The next page is the RFC 5988 `Link: <...>; rel="next"` of the response,
+// or with --next-field the URL at that path in the JSON body (falling back
+// to the Link header when it's missing or null). Relative URLs are resolved
+// against the page they came from.
+//
+// When the first page is a JSON array, or --filter is given, the pages are
+// merged: the array items (or the filter's matches) of every page are
+// printed together after the last one. Other pages are printed one after
+// another as they arrive.
+
+use crate::jsondiff::JsonPath;
+use reqwest::header::{HeaderMap, LINK};
+use serde_json::Value;
+use std::cell::RefCell;
+use url::Url;
+
+enum Mode {
+    // No page has been read yet
+    Undecided,
+    Merge(Vec<Value>),
+    Concatenate,
+}
+
+struct State {
+    // The page being read and the next page its Link header names
+    url: Option<Url>,
+    link: Option<Url>,
+    // Set once the page's body has been read
+    next: Option<Url>,
+    pages: usize,
+    mode: Mode,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const {
+        RefCell::new(State {
+            url: None,
+            link: None,
+            next: None,
+            pages: 0,
+            mode: Mode::Undecided,
+        })
+    };
+}
+
+// A page's response arrived.
+pub fn response(url: &Url, headers: &HeaderMap) {
+    STATE.with_borrow_mut(|state| {
+        state.link = next_link(headers).and_then(|link| url.join(&link).ok());
+        state.url = Some(url.clone());
+        state.next = None;
+    });
+}
+
+// A page's body was read; `json` is None when it isn't JSON.
+pub fn body(json: Option<&Value>, next_field: Option<&JsonPath>) {
+    let field = next_field
+        .zip(json)
+        .and_then(|(path, json)| path.select(json).into_iter().next())
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty());
+    STATE.with_borrow_mut(|state| {
+        state.pages += 1;
+        state.next = match (field, &state.url) {
+            (Some(field), Some(url)) => url.join(field).ok(),
+            _ => state.link.take(),
+        };
+    });
+}
+
+// Add a page's items to the merged output. False when the pages are being
+// printed as they arrive instead, because the first page wasn't mergeable.
+pub fn merge(items: Option<Vec<Value>>) -> Result<bool, String> {
+    STATE.with_borrow_mut(|state| {
+        match (&mut state.mode, items) {
+            (Mode::Undecided, Some(items)) => state.mode = Mode::Merge(items),
+            (Mode::Undecided, None) => {
+                state.mode = Mode::Concatenate;
+                return Ok(false);
+            }
+            (Mode::Merge(merged), Some(items)) => merged.extend(items),
+            (Mode::Merge(_), None) => {
+                state.next = None;
+                return Err(format!(
+                    "Page {} isn't a JSON array, so it can't be merged with the pages before it.",
+                    state.pages
+                ));
+            }
+            (Mode::Concatenate, _) => return Ok(false),
+        }
+        Ok(true)
+    })
+}
+
+// The page to request next, if the last one was read and named one.
+pub fn take_next() -> Option<Url> {
+    STATE.with_borrow_mut(|state| state.next.take())
+}
+
+pub fn pages() -> usize {
+    STATE.with_borrow(|state| state.pages)
+}
+
+// The merged items of all pages, if the pages were merged.
+pub fn take_merged() -> Option<Vec<Value>> {
+    STATE.with_borrow_mut(
+        |state| match std::mem::replace(&mut state.mode, Mode::Undecided) {
+            Mode::Merge(items) => Some(items),
+            _ => None,
+        },
+    )
+}
+
+// The rel="next" target of the Link headers, as written.
+pub fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(links)
+        .find(|(_, rels)| {
+            rels.split_ascii_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("next"))
+        })
+        .map(|(target, _)| target)
+}
+
+// `<url>; rel="next"; title="x", <url>; rel=last` as (url, rel) pairs. The
+// URL can itself contain commas and semicolons, so it's cut out by its
+// angle brackets before the parameters are split.
+fn links(value: &str) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let target = rest[start + 1..start + end].trim().to_string();
+        rest = &rest[start + end + 1..];
+        let params_end = rest.find(',').unwrap_or(rest.len());
+        let rel = rest[..params_end]
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .map(|(_, value)| value.trim().trim_matches('"').to_string())
+            .unwrap_or_default();
+        found.push((target, rel));
+        rest = &rest[params_end..];
+    }
+    found
+}
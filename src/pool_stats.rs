@@ -0,0 +1,268 @@
+// --pool-stats: how many connections the run opened, how often it reused
+// one and how many it found closed, per host.
+//
+// reqwest doesn't expose its pool, but every response carries the local
+// and remote address of the connection it came over. WebClient hands each
+// response here: a connection not seen before was opened for it, any other
+// was reused. Requests are sent one at a time per thread, so a thread's
+// connection is busy from its response until that thread's next response
+// (or until it ends), which may be longer than it really is. When a client
+// opens a new connection to a host while one of its connections there is
+// not busy, its pool would have reused that one had it been open, so that
+// one counts as idle-closed: timed out in the pool or closed by the server.
+// Taking connections for busy longer than they are can only leave out
+// closed ones, never count open ones as closed.
+// Redirects reqwest follows internally only count their final response.
+
+use hyper::client::connect::HttpInfo;
+use reqwest::blocking::Response;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default, Clone)]
+pub struct HostStats {
+    pub opened: u64,
+    pub reused: u64,
+    pub idle_closed: u64,
+}
+
+// (local, remote) address of a connection
+type Connection = (SocketAddr, SocketAddr);
+
+struct Open {
+    // The WebClient whose pool holds it
+    pool: usize,
+    host: String,
+    // Threads whose last response came over it
+    busy: usize,
+}
+
+#[derive(Default)]
+struct State {
+    connections: HashMap<Connection, Open>,
+    hosts: BTreeMap<String, HostStats>,
+}
+
+#[derive(Default)]
+pub struct PoolStats {
+    state: Arc<Mutex<State>>,
+    pools: AtomicUsize,
+}
+
+// The connection this thread's last response came over, released when the
+// thread records its next response or ends.
+struct Held(Option<(Arc<Mutex<State>>, Connection)>);
+
+impl Held {
+    fn release(&mut self) {
+        if let Some((state, conn)) = self.0.take()
+            && let Some(open) = state.lock().unwrap().connections.get_mut(&conn)
+        {
+            open.busy -= 1;
+        }
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+thread_local! {
+    static HELD: RefCell<Held> = const { RefCell::new(Held(None)) };
+}
+
+impl PoolStats {
+    // An ID for a new client's pool, to hand to `record`
+    pub fn pool(&self) -> usize {
+        self.pools.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn record(&self, pool: usize, res: &Response) {
+        let Some(info) = res.extensions().get::<HttpInfo>() else {
+            return;
+        };
+        let url = res.url();
+        let host = format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        let conn = (info.local_addr(), info.remote_addr());
+        HELD.with_borrow_mut(|held| {
+            held.release();
+            let mut state = self.state.lock().unwrap();
+            let state = &mut *state;
+            let stats = state.hosts.entry(host.clone()).or_default();
+            match state.connections.get_mut(&conn) {
+                Some(open) => {
+                    open.busy += 1;
+                    stats.reused += 1;
+                }
+                None => {
+                    let before = state.connections.len();
+                    state
+                        .connections
+                        .retain(|_, open| open.pool != pool || open.host != host || open.busy > 0);
+                    stats.idle_closed += (before - state.connections.len()) as u64;
+                    stats.opened += 1;
+                    state.connections.insert(
+                        conn,
+                        Open {
+                            pool,
+                            host,
+                            busy: 1,
+                        },
+                    );
+                }
+            }
+            held.0 = Some((self.state.clone(), conn));
+        });
+    }
+
+    pub fn report(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut total = HostStats::default();
+        for s in state.hosts.values() {
+            total.opened += s.opened;
+            total.reused += s.reused;
+            total.idle_closed += s.idle_closed;
+        }
+
+        let mut out = format!(
+            "Pool stats: {} opened, {} reused, {} idle-closed",
+            total.opened, total.reused, total.idle_closed
+        );
+        for (host, s) in &state.hosts {
+            out.push_str(&format!(
+                "\n  {}: {} opened, {} reused, {} idle-closed",
+                host, s.opened, s.reused, s.idle_closed
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    // A keep-alive server answering every request with "ok". It closes the
+    // connection after a request for /close and takes a while to send the
+    // body of /slow.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for conn in listener.incoming().flatten() {
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(conn.try_clone().unwrap());
+                    let mut conn = conn;
+                    let mut path = String::new();
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+                        if let Some(rest) = line.strip_prefix("GET ") {
+                            path = rest.split(' ').next().unwrap_or_default().to_string();
+                        }
+                        if line == "\r\n" {
+                            let close = path == "/close";
+                            let connection = if close { "close" } else { "keep-alive" };
+                            let _ = write!(
+                                conn,
+                                "HTTP/1.1 200 OK\r\nconnection: {}\r\ncontent-length: 2\r\n\r\no",
+                                connection
+                            );
+                            if path == "/slow" {
+                                let _ = conn.flush();
+                                thread::sleep(Duration::from_millis(300));
+                            }
+                            let _ = conn.write_all(b"k");
+                            if close {
+                                return;
+                            }
+                        }
+                        line.clear();
+                    }
+                });
+            }
+        });
+        format!("http://{}/", addr)
+    }
+
+    fn get(client: &(Client, usize), stats: &PoolStats, url: &str) -> Response {
+        let res = client.0.get(url).send().unwrap();
+        stats.record(client.1, &res);
+        res
+    }
+
+    fn new_client(stats: &PoolStats) -> (Client, usize) {
+        (Client::new(), stats.pool())
+    }
+
+    fn counts(stats: &PoolStats) -> String {
+        stats.report().lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn connections_are_told_apart_by_their_addresses() {
+        let url = serve();
+        let stats = PoolStats::default();
+        let client = new_client(&stats);
+        for _ in 0..2 {
+            get(&client, &stats, &url).text().unwrap();
+        }
+        get(&new_client(&stats), &stats, &url);
+
+        let host = url.trim_start_matches("http://").trim_end_matches('/');
+        assert_eq!(
+            stats.report(),
+            format!(
+                "Pool stats: 2 opened, 1 reused, 0 idle-closed\n  {}: 2 opened, 1 reused, 0 idle-closed",
+                host
+            )
+        );
+    }
+
+    #[test]
+    fn a_connection_replaced_while_idle_was_closed() {
+        let url = serve();
+        let stats = PoolStats::default();
+        let client = new_client(&stats);
+        get(&client, &stats, &format!("{}close", url))
+            .text()
+            .unwrap();
+        get(&client, &stats, &url).text().unwrap();
+        get(&client, &stats, &url).text().unwrap();
+        assert_eq!(
+            counts(&stats),
+            "Pool stats: 2 opened, 1 reused, 1 idle-closed"
+        );
+    }
+
+    #[test]
+    fn busy_connections_are_not_closed() {
+        let url = serve();
+        let stats = PoolStats::default();
+        let client = new_client(&stats);
+        // The first connection is still busy with /slow when the second
+        // request goes out
+        thread::scope(|s| {
+            let first = get(&client, &stats, &format!("{}slow", url));
+            s.spawn(|| get(&client, &stats, &url).text().unwrap());
+            first.text().unwrap();
+        });
+        assert_eq!(
+            counts(&stats),
+            "Pool stats: 2 opened, 0 reused, 0 idle-closed"
+        );
+    }
+}
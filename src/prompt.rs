@@ -0,0 +1,85 @@
+// Reading secrets from the terminal.
+//
+// The prompt goes to and the answer comes from the controlling terminal
+// rather than stdin/stdout, so a password can be asked for even while
+// the body is piped in or the output redirected. Echo is switched off
+// while typing.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+pub fn password(prompt: &str) -> Result<String, String> {
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(TTY)
+        .map_err(|_| "No terminal to prompt for the password on.".to_string())?;
+    write!(tty, "{}", prompt).map_err(|e| e.to_string())?;
+    let _ = tty.flush();
+
+    let line = {
+        let _echo = EchoOff::new(&tty);
+        let mut line = String::new();
+        BufReader::new(&tty)
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+        line
+    };
+    // The Enter keypress wasn't echoed either
+    let _ = writeln!(tty);
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(unix)]
+const TTY: &str = "/dev/tty";
+#[cfg(not(unix))]
+const TTY: &str = "CONIN$";
+
+#[cfg(unix)]
+struct EchoOff {
+    fd: i32,
+    saved: Option<libc::termios>,
+}
+
+#[cfg(unix)]
+impl EchoOff {
+    fn new(tty: &File) -> EchoOff {
+        use std::os::unix::io::AsRawFd;
+        let fd = tty.as_raw_fd();
+        // SAFETY: tcgetattr/tcsetattr on an open descriptor with a
+        // properly sized termios
+        let saved = unsafe {
+            let mut term: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut term) != 0 {
+                None
+            } else {
+                let mut quiet = term;
+                quiet.c_lflag &= !libc::ECHO;
+                libc::tcsetattr(fd, libc::TCSANOW, &quiet);
+                Some(term)
+            }
+        };
+        EchoOff { fd, saved }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        if let Some(term) = &self.saved {
+            // SAFETY: restores the settings read in new()
+            unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, term) };
+        }
+    }
+}
+
+// No echo control on other platforms: the password is visible while typed.
+#[cfg(not(unix))]
+struct EchoOff;
+
+#[cfg(not(unix))]
+impl EchoOff {
+    fn new(_tty: &File) -> EchoOff {
+        EchoOff
+    }
+}
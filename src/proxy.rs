@@ -0,0 +1,371 @@
+// Per-request proxy selection.
+//
+// For every URL the first of these that has an answer decides:
+//
+//   1. --noproxy, or NO_PROXY from the environment: connect directly
+//   2. the --proxy given on the command line
+//   3. the first matching rule of a --proxy-config file
+//   4. the --proxy-pac script
+//   5. https_proxy / http_proxy / all_proxy from the environment
+//
+// Proxies are http:// or, for a TLS connection to the proxy itself,
+// https://.
+//
+// A --proxy-config file maps hosts to proxies:
+//
+// rules:
+//   - hosts: [corp.example, 10.0.0.0/8]
+//     proxy: DIRECT
+//   - hosts: ["*"]
+//     proxy: http://egress.corp.example:3128
+
+use crate::errln;
+use crate::pac::{self, Pac};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use url::Url;
+
+// Hosts as written in NO_PROXY: "*" for everything, an IP address or
+// CIDR block, or a domain that also covers its subdomains (a leading "."
+// or "*." is ignored).
+pub struct HostList(Vec<HostPattern>);
+
+enum HostPattern {
+    All,
+    Net(IpAddr, u8),
+    Domain(String),
+}
+
+impl HostList {
+    pub fn parse(list: &str) -> Result<HostList, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(HostPattern::parse)
+            .collect::<Result<_, _>>()
+            .map(HostList)
+    }
+
+    pub fn from_env() -> Option<HostList> {
+        let list = std::env::var("NO_PROXY")
+            .or_else(|_| std::env::var("no_proxy"))
+            .ok()?;
+        match HostList::parse(&list) {
+            Ok(hosts) => Some(hosts),
+            Err(e) => {
+                errln!("Warning: Ignoring NO_PROXY: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let ip = host.parse::<IpAddr>().ok();
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.0.iter().any(|p| match p {
+            HostPattern::All => true,
+            HostPattern::Net(net, prefix) => ip.is_some_and(|ip| in_net(ip, *net, *prefix)),
+            HostPattern::Domain(d) => host == *d || host.ends_with(&format!(".{}", d)),
+        })
+    }
+}
+
+impl HostPattern {
+    fn parse(entry: &str) -> Result<HostPattern, String> {
+        if entry == "*" {
+            return Ok(HostPattern::All);
+        }
+        if let Some((net, prefix)) = entry.split_once('/') {
+            let net: IpAddr = net
+                .parse()
+                .map_err(|_| format!("'{}' is not a valid CIDR block", entry))?;
+            let max = if net.is_ipv4() { 32 } else { 128 };
+            let prefix = prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("'{}' has an invalid prefix length", entry))?;
+            return Ok(HostPattern::Net(net, prefix));
+        }
+        let bare = entry.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = bare.parse::<IpAddr>() {
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            return Ok(HostPattern::Net(ip, max));
+        }
+        let domain = entry
+            .trim_start_matches("*.")
+            .trim_start_matches('.')
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if domain.is_empty() || domain.contains(['/', ':', '*']) {
+            return Err(format!("'{}' is not a host, domain or CIDR block", entry));
+        }
+        Ok(HostPattern::Domain(domain))
+    }
+}
+
+fn in_net(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+// ---------------- CONFIG FILE ----------------
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    rules: Vec<RuleConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    hosts: Vec<String>,
+    proxy: String,
+}
+
+struct Rule {
+    hosts: HostList,
+    // None: connect directly
+    proxy: Option<Url>,
+}
+
+fn load_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+    let config: Config = serde_yaml::from_str(&text)
+        .map_err(|e| format!("Invalid proxy config '{}': {}", path.display(), e))?;
+    config
+        .rules
+        .into_iter()
+        .map(|r| {
+            let hosts = HostList::parse(&r.hosts.join(","))?;
+            let proxy = if r.proxy.eq_ignore_ascii_case("direct") {
+                None
+            } else {
+                Some(parse_proxy(&r.proxy)?)
+            };
+            Ok(Rule { hosts, proxy })
+        })
+        .collect()
+}
+
+// Proxy URLs without a scheme are HTTP proxies, as in curl.
+pub fn parse_proxy(proxy: &str) -> Result<Url, String> {
+    let with_scheme = if proxy.contains("://") {
+        proxy.to_string()
+    } else {
+        format!("http://{}", proxy)
+    };
+    let url = Url::parse(&with_scheme).map_err(|e| format!("Invalid proxy '{}': {}", proxy, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Unsupported proxy scheme '{}' in '{}'; use http:// or https://.",
+            url.scheme(),
+            proxy
+        ));
+    }
+    Ok(url)
+}
+
+// ---------------- ROUTER ----------------
+
+pub struct Router {
+    no_proxy: Option<HostList>,
+    proxy: Option<Url>,
+    rules: Vec<Rule>,
+    pac: Option<Pac>,
+    // reqwest only hands the proxy hook the scheme, host and port, so PAC
+    // scripts see the full URL for the request's own origin and just the
+    // origin for redirect targets
+    request: Url,
+    verbose: bool,
+    // The hook runs several times per connection
+    cache: Mutex<HashMap<String, Option<Url>>>,
+}
+
+impl Router {
+    // `no_proxy` overrides NO_PROXY from the environment.
+    pub fn new(
+        request: &Url,
+        proxy: Option<&str>,
+        no_proxy: Option<&str>,
+        config: Option<&Path>,
+        pac: Option<&str>,
+        verbose: bool,
+    ) -> Result<Router, String> {
+        let no_proxy = match no_proxy {
+            Some(list) => Some(HostList::parse(list).map_err(|e| format!("--noproxy: {}", e))?),
+            None => HostList::from_env(),
+        };
+        Ok(Router {
+            no_proxy,
+            proxy: proxy.map(parse_proxy).transpose()?,
+            rules: config.map(load_rules).transpose()?.unwrap_or_default(),
+            pac: pac.map(Pac::load).transpose()?,
+            request: request.clone(),
+            verbose,
+            cache: Mutex::default(),
+        })
+    }
+
+    pub fn route(&self, url: &Url) -> Option<Url> {
+        let url = if url.origin() == self.request.origin() {
+            &self.request
+        } else {
+            url
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(route) = cache.get(url.as_str()) {
+            return route.clone();
+        }
+
+        let (route, reason) = self.decide(url);
+        if self.verbose {
+            let host = url.host_str().unwrap_or("");
+            match &route {
+                Some(proxy) => errln!("* Proxy for {}: {} ({})", host, proxy, reason),
+                None => errln!("* Proxy for {}: DIRECT ({})", host, reason),
+            }
+        }
+        cache.insert(url.to_string(), route.clone());
+        route
+    }
+
+    fn decide(&self, url: &Url) -> (Option<Url>, String) {
+        let host = url.host_str().unwrap_or("");
+        if self.no_proxy.as_ref().is_some_and(|np| np.matches(host)) {
+            return (None, "no-proxy list".to_string());
+        }
+        if let Some(proxy) = &self.proxy {
+            return (Some(proxy.clone()), "--proxy".to_string());
+        }
+        if let Some(rule) = self.rules.iter().find(|r| r.hosts.matches(host)) {
+            return (rule.proxy.clone(), "proxy config".to_string());
+        }
+        if let Some(pac) = &self.pac {
+            // A failing script means connecting directly, as browsers do
+            return match pac.find_proxy(url) {
+                Ok(result) => match pac::routes(&result).into_iter().next() {
+                    Some(pac::Route::Proxy(proxy)) => (Some(proxy), format!("PAC: \"{}\"", result)),
+                    Some(pac::Route::Direct) => (None, format!("PAC: \"{}\"", result)),
+                    None => {
+                        errln!(
+                            "Warning: PAC result \"{}\" has no usable proxy; connecting directly.",
+                            result
+                        );
+                        (None, "PAC".to_string())
+                    }
+                },
+                Err(e) => {
+                    errln!(
+                        "Warning: PAC evaluation failed: {}; connecting directly.",
+                        e
+                    );
+                    (None, "PAC".to_string())
+                }
+            };
+        }
+        match env_proxy(url.scheme()) {
+            Some(proxy) => (Some(proxy), "environment".to_string()),
+            None => (None, "no proxy configured".to_string()),
+        }
+    }
+}
+
+fn env_proxy(scheme: &str) -> Option<Url> {
+    let vars: &[&str] = match scheme {
+        "https" => &["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"],
+        _ => &["http_proxy", "HTTP_PROXY", "all_proxy", "ALL_PROXY"],
+    };
+    let value = vars
+        .iter()
+        .find_map(|v| std::env::var(v).ok().filter(|v| !v.is_empty()))?;
+    match parse_proxy(&value) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            errln!("Warning: Ignoring the proxy environment: {}", e);
+            None
+        }
+    }
+}
+
+// ---------------- AUTHENTICATION ----------------
+
+// --proxy-user credentials, sent as Basic. reqwest gives the proxy one
+// fixed Proxy-Authorization value (on CONNECT tunnels too), so a
+// challenge-based scheme such as Digest would plug in here by answering
+// the proxy's first 407 and building the value from its nonce.
+pub struct Credentials {
+    user: String,
+    password: String,
+}
+
+impl Credentials {
+    // "user:password"; the password may itself contain colons.
+    pub fn parse(value: &str) -> Credentials {
+        let (user, password) = value.split_once(':').unwrap_or((value, ""));
+        Credentials {
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    pub fn header(&self) -> HeaderValue {
+        let token = STANDARD.encode(format!("{}:{}", self.user, self.password));
+        let mut value = HeaderValue::from_str(&format!("Basic {}", token)).unwrap();
+        value.set_sensitive(true);
+        value
+    }
+}
+
+// The message for a 407 Proxy Authentication Required, from the
+// Proxy-Authenticate challenges (empty when they're unknown, as for
+// CONNECT tunnels).
+pub fn explain_407(challenges: &[String], credentials: Option<&Credentials>) -> String {
+    let schemes: Vec<&str> = challenges
+        .iter()
+        .filter_map(|c| c.split_whitespace().next())
+        .collect();
+    let offered = if schemes.is_empty() {
+        String::new()
+    } else {
+        format!(" (it accepts: {})", schemes.join(", "))
+    };
+
+    match credentials {
+        None => format!(
+            "The proxy requires authentication{}; pass --proxy-user user:password.",
+            offered
+        ),
+        Some(_)
+            if !schemes.is_empty() && !schemes.iter().any(|s| s.eq_ignore_ascii_case("basic")) =>
+        {
+            format!(
+                "The proxy doesn't accept Basic authentication{}; only Basic is supported.",
+                offered
+            )
+        }
+        Some(c) => format!(
+            "The proxy rejected the --proxy-user credentials for '{}'{}.",
+            c.user, offered
+        ),
+    }
+}
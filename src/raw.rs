@@ -0,0 +1,238 @@
+// --raw-request: replay a literal HTTP/1.1 request read from a file.
+//
+// The request bytes go out exactly as written (request line, headers and
+// body), bypassing reqwest, over a fresh connection to the URL's host.
+// The only change made is turning bare LF line endings in the header block
+// into CRLF when the file has no CRLF at all, since editors tend to save
+// requests that way. Interim 1xx responses are visible on this path.
+
+use crate::deadline;
+use crate::errln;
+use crate::stream::Stream;
+use crate::transfer::TransferLimits;
+use native_tls::TlsConnector;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use url::Url;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct RawResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RawResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// The method from the request line, for display.
+pub fn method(request: &[u8]) -> Option<String> {
+    let line = request.split(|b| *b == b'\n').next()?;
+    let method = String::from_utf8_lossy(line)
+        .split_whitespace()
+        .next()?
+        .to_string();
+    Some(method)
+}
+
+pub fn send(
+    url: &Url,
+    request: &[u8],
+    limits: &TransferLimits,
+    verbose: bool,
+) -> Result<RawResponse, String> {
+    let host = url.host_str().ok_or("The URL has no host.")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<_> = (host, port)
+        .to_socket_addrs()
+        .map_err(|_| format!("Could not resolve host: {}.", host))?
+        .collect();
+    let tcp = addrs
+        .iter()
+        .find_map(|addr| TcpStream::connect_timeout(addr, CONNECT_TIMEOUT).ok())
+        .ok_or("Unable to connect to the server.")?;
+    if verbose && let Ok(peer) = tcp.peer_addr() {
+        errln!("* Connected to {} port {}", peer.ip(), peer.port());
+    }
+
+    let read_timeout = match (limits.read, deadline::remaining()) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    let _ = tcp.set_read_timeout(read_timeout);
+    let _ = tcp.set_write_timeout(limits.write);
+
+    let mut stream = if url.scheme() == "https" {
+        let connector = TlsConnector::new().map_err(|e| e.to_string())?;
+        let tls = connector
+            .connect(host, tcp)
+            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+        Stream::Tls(Box::new(tls))
+    } else {
+        Stream::Plain(tcp)
+    };
+
+    let request = prepare(request);
+    if verbose {
+        let end = request
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .unwrap_or(request.len());
+        for line in String::from_utf8_lossy(&request[..end]).lines() {
+            errln!("> {}", line);
+        }
+        errln!(">");
+    }
+    stream
+        .write_all(&request)
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("Unable to send the request: {}", e))?;
+
+    let is_head = method(&request).is_some_and(|m| m.eq_ignore_ascii_case("HEAD"));
+    let mut reader = BufReader::new(stream);
+    let response = read_response(&mut reader, is_head, verbose)
+        .map_err(|e| format!("Error while reading the response: {}", e))?;
+    reader.into_inner().close();
+    Ok(response)
+}
+
+fn prepare(request: &[u8]) -> Vec<u8> {
+    if request.windows(2).any(|w| w == b"\r\n") {
+        return request.to_vec();
+    }
+    let split = request
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .map_or(request.len(), |i| i + 2);
+    let (head, body) = request.split_at(split);
+    let mut out = Vec::with_capacity(request.len() + 32);
+    for &b in head {
+        if b == b'\n' {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+fn read_response<R: BufRead>(
+    reader: &mut R,
+    is_head: bool,
+    verbose: bool,
+) -> std::io::Result<RawResponse> {
+    let head = loop {
+        let head = read_head(reader)?;
+        if verbose {
+            errln!("< {}", head.line);
+            for (name, value) in &head.headers {
+                errln!("< {}: {}", name, value);
+            }
+            errln!("<");
+        }
+        // 100 Continue, 103 Early Hints, ...; 101 ends HTTP on this connection
+        if (100..200).contains(&head.status) && head.status != 101 {
+            continue;
+        }
+        break head;
+    };
+
+    let status = head.status;
+    let mut response = RawResponse {
+        status,
+        headers: head.headers,
+        body: Vec::new(),
+    };
+    if is_head || status == 204 || status == 304 || status == 101 {
+        return Ok(response);
+    }
+
+    let chunked = response
+        .header("transfer-encoding")
+        .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"));
+    let length = response
+        .header("content-length")
+        .and_then(|l| l.trim().parse::<u64>().ok());
+
+    if chunked {
+        response.body = read_chunked(reader)?;
+    } else if let Some(length) = length {
+        reader
+            .by_ref()
+            .take(length)
+            .read_to_end(&mut response.body)?;
+    } else {
+        reader.read_to_end(&mut response.body)?;
+    }
+    Ok(response)
+}
+
+// Status line and headers of one (possibly interim) response.
+struct Head {
+    line: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+fn read_head<R: BufRead>(reader: &mut R) -> std::io::Result<Head> {
+    let status_line = read_line(reader)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid(format!("bad status line '{}'", status_line)))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok(Head {
+        line: status_line,
+        status,
+        headers,
+    })
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = u64::from_str_radix(size, 16)
+            .map_err(|_| invalid(format!("bad chunk size '{}'", line)))?;
+        if size == 0 {
+            // Skip trailers
+            while !read_line(reader)?.is_empty() {}
+            return Ok(body);
+        }
+        reader.by_ref().take(size).read_to_end(&mut body)?;
+        read_line(reader)?;
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> std::io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(invalid("connection closed early".to_string()));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
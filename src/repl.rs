@@ -0,0 +1,401 @@
+// --interactive: a prompt for trying out an API, with the base URL,
+// default headers and credentials set once for the whole session.
+//
+//   > base https://api.example.com/v1
+//   > header Accept: application/json
+//   > auth bearer {{env:API_TOKEN}}
+//   > get /users/1
+//   > post /orders {"item": 42, "quantity": 2}
+//   > show last
+//
+// Every request goes through one WebClient, so connections to the server
+// are pooled and reused between commands. Paths without a scheme go under
+// the base URL; a full URL is used as given. A request's body is sent as
+// JSON when it parses as JSON and as text otherwise.
+//
+// Commands are kept in `history`, and `!N` or `!!` runs one again. They're
+// also appended to a history file beside the config file and read back
+// next time, except `auth` and `header` lines, which can hold credentials.
+
+use crate::auth::{self, Credentials};
+use crate::client::{Body, ClientOptions, RequestSpec, ResponseReport, WebClient};
+use crate::config;
+use crate::exit;
+use crate::format::Style;
+use crate::method::Method;
+use crate::output;
+use crate::secrets::SecretResolver;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+// Lines of the history file read back at start
+const HISTORY_LIMIT: usize = 500;
+
+const HELP: &str = "\
+Requests:
+  get|head|options PATH                Send a request; PATH is under the base URL
+  post|put|patch|delete PATH [BODY]    The same with BODY, as JSON when it parses as JSON
+Session:
+  base [URL]                           Show or set the base URL
+  header NAME: VALUE                   Send this header with every request
+  unheader NAME                        Stop sending a header
+  headers                              List the session's headers
+  auth USER:PASSWORD | bearer TOKEN    Authenticate every request
+  auth none                            Stop authenticating
+  show last                            The last response again, with its headers
+  history                              Commands run so far; !N or !! runs one again
+  help                                 This list
+  exit | quit                          Leave (Ctrl-D works too)";
+
+#[derive(Default)]
+pub struct Session {
+    pub base_url: Option<String>,
+    pub headers: Vec<(String, String)>,
+    // The Authorization value, kept apart so `auth` replaces it
+    pub authorization: Option<String>,
+}
+
+enum Command {
+    Request {
+        method: Method,
+        target: String,
+        body: Option<String>,
+    },
+    Base(Option<String>),
+    Header(String, String),
+    Unheader(String),
+    Headers,
+    Auth(Option<String>),
+    ShowLast,
+    History,
+    Rerun(Rerun),
+    Help,
+    Quit,
+}
+
+enum Rerun {
+    Last,
+    Number(usize),
+}
+
+struct Repl {
+    client: WebClient,
+    session: Session,
+    secrets: SecretResolver,
+    last: Option<ResponseReport>,
+    history: Vec<String>,
+    history_file: PathBuf,
+}
+
+pub fn run(session: Session, options: ClientOptions) -> Result<(), exit::Error> {
+    let history_file = config::path().with_file_name("history");
+    let mut repl = Repl {
+        client: WebClient::new(options)?,
+        session,
+        secrets: SecretResolver::with_defaults(),
+        last: None,
+        history: read_history(&history_file),
+        history_file,
+    };
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("Interactive mode: 'help' lists the commands, 'exit' leaves.");
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            print!("{}> ", repl.prompt());
+            let _ = io::stdout().flush();
+        }
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match repl.execute(line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+    if interactive {
+        println!();
+    }
+    Ok(())
+}
+
+impl Repl {
+    // The base URL's host, so the prompt says where requests go.
+    fn prompt(&self) -> String {
+        self.session
+            .base_url
+            .as_deref()
+            .and_then(|base| url::Url::parse(base).ok())
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    // False when the session is over.
+    fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let command = parse(line)?;
+        let line = match &command {
+            Command::Rerun(rerun) => {
+                let line = match rerun {
+                    Rerun::Last => self.history.last(),
+                    Rerun::Number(n) => n.checked_sub(1).and_then(|i| self.history.get(i)),
+                }
+                .cloned()
+                .ok_or_else(|| format!("No command {} in the history.", line))?;
+                println!("{}", line);
+                return match parse(&line)? {
+                    Command::Rerun(_) => Err("A history entry can't rerun another.".into()),
+                    command => {
+                        self.remember(&line);
+                        self.apply(command)
+                    }
+                };
+            }
+            _ => line,
+        };
+        self.remember(line);
+        self.apply(command)
+    }
+
+    fn apply(&mut self, command: Command) -> Result<bool, String> {
+        match command {
+            Command::Request {
+                method,
+                target,
+                body,
+            } => self.send(method, &target, body)?,
+            Command::Base(Some(url)) => {
+                if !url.contains("://") {
+                    return Err(format!(
+                        "The base URL needs a scheme, as in https://{}.",
+                        url
+                    ));
+                }
+                self.session.base_url = Some(url);
+            }
+            Command::Base(None) => match &self.session.base_url {
+                Some(url) => println!("{}", url),
+                None => println!("No base URL; requests need full URLs."),
+            },
+            Command::Header(name, value) => {
+                if name.eq_ignore_ascii_case("authorization") {
+                    self.session.authorization = Some(value);
+                } else {
+                    self.session
+                        .headers
+                        .retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+                    self.session.headers.push((name, value));
+                }
+            }
+            Command::Unheader(name) => {
+                if name.eq_ignore_ascii_case("authorization") {
+                    self.session.authorization = None;
+                }
+                self.session
+                    .headers
+                    .retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+            }
+            Command::Headers => {
+                for (name, value) in &self.session.headers {
+                    println!("{}: {}", name, output::redact(value));
+                }
+                if self.session.authorization.is_some() {
+                    println!("Authorization: [redacted]");
+                }
+            }
+            Command::Auth(None) => self.session.authorization = None,
+            Command::Auth(Some(value)) => {
+                let value = match value.strip_prefix("bearer ") {
+                    Some(token) => auth::bearer(&self.secrets.resolve(token)?)?,
+                    None => Credentials::parse(&self.secrets.resolve(&value)?)?.basic(),
+                };
+                let value = value.to_str().map_err(|e| e.to_string())?;
+                self.session.authorization = Some(value.to_string());
+            }
+            Command::ShowLast => match &self.last {
+                Some(report) => print_report(report, true),
+                None => println!("No response yet."),
+            },
+            Command::History => {
+                for (i, line) in self.history.iter().enumerate() {
+                    println!("{:>5}  {}", i + 1, line);
+                }
+            }
+            Command::Rerun(_) => unreachable!("reruns are expanded by execute"),
+            Command::Help => println!("{}", HELP),
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn send(&mut self, method: Method, target: &str, body: Option<String>) -> Result<(), String> {
+        let url = match &self.session.base_url {
+            Some(base) if !target.contains("://") => format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                target.trim_start_matches('/')
+            ),
+            None if !target.contains("://") => {
+                return Err(format!(
+                    "'{}' isn't a full URL and no base URL is set; use 'base URL'.",
+                    target
+                ));
+            }
+            _ => target.to_string(),
+        };
+        let mut spec = RequestSpec::new(method, &self.secrets.resolve(&url)?);
+        for (name, value) in &self.session.headers {
+            spec = spec.header(name, &self.secrets.resolve(value)?);
+        }
+        if let Some(value) = &self.session.authorization {
+            spec = spec.header("Authorization", &self.secrets.resolve(value)?);
+        }
+        if let Some(body) = body {
+            let body = self.secrets.resolve(&body)?;
+            spec = spec.body(match serde_json::from_str::<Value>(&body) {
+                Ok(json) => Body::Json(json),
+                Err(_) => Body::Bytes(body.into_bytes()),
+            });
+        }
+
+        let report = self.client.send(&spec).map_err(|e| e.to_string())?;
+        print_report(&report, false);
+        self.last = Some(report);
+        Ok(())
+    }
+
+    fn remember(&mut self, line: &str) {
+        self.history.push(line.to_string());
+        let private = ["auth", "header"]
+            .iter()
+            .any(|word| line.split_whitespace().next() == Some(word));
+        if private {
+            return;
+        }
+        if let Some(dir) = self.history_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.history_file);
+        if let Ok(mut file) = file {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+fn parse(line: &str) -> Result<Command, String> {
+    if line == "!!" {
+        return Ok(Command::Rerun(Rerun::Last));
+    }
+    if let Some(n) = line.strip_prefix('!') {
+        let n = n
+            .parse()
+            .map_err(|_| format!("'{}' isn't a history number.", line))?;
+        return Ok(Command::Rerun(Rerun::Number(n)));
+    }
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let arg = (!rest.is_empty()).then(|| rest.to_string());
+
+    if let Ok(method) = Method::parse(word) {
+        let (target, body) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if target.is_empty() {
+            return Err(format!("Usage: {} PATH", word.to_ascii_lowercase()));
+        }
+        let body = body.trim();
+        if !body.is_empty() && !method.allows_body() {
+            return Err(format!("{} requests don't take a body.", method));
+        }
+        return Ok(Command::Request {
+            method,
+            target: target.to_string(),
+            body: (!body.is_empty()).then(|| body.to_string()),
+        });
+    }
+
+    Ok(match word.to_ascii_lowercase().as_str() {
+        "base" => Command::Base(arg),
+        "header" => {
+            let (name, value) = rest
+                .split_once(':')
+                .filter(|(name, _)| !name.trim().is_empty())
+                .ok_or("Usage: header NAME: VALUE")?;
+            Command::Header(name.trim().to_string(), value.trim().to_string())
+        }
+        "unheader" => Command::Unheader(arg.ok_or("Usage: unheader NAME")?),
+        "headers" => Command::Headers,
+        "auth" => match rest {
+            "" => return Err("Usage: auth USER:PASSWORD | bearer TOKEN | none".into()),
+            "none" => Command::Auth(None),
+            _ => Command::Auth(arg),
+        },
+        "show" if rest == "last" => Command::ShowLast,
+        "show" => return Err("Usage: show last".into()),
+        "history" => Command::History,
+        "help" | "?" => Command::Help,
+        "exit" | "quit" => Command::Quit,
+        _ => {
+            return Err(format!(
+                "Unknown command '{}'; 'help' lists the commands.",
+                word
+            ));
+        }
+    })
+}
+
+// The status and timing, the headers when asked for, then the body as
+// the command line prints it, JSON in the server's key order.
+fn print_report(report: &ResponseReport, headers: bool) {
+    let reason = StatusCode::from_u16(report.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+    println!(
+        "{} {} in {} ms",
+        report.status,
+        reason,
+        report.elapsed.as_millis()
+    );
+    if headers {
+        println!("URL: {}", report.url);
+        for (name, value) in &report.headers {
+            println!("{}: {}", name, value);
+        }
+    }
+    if report.body.is_empty() {
+        return;
+    }
+    if headers {
+        println!();
+    }
+    let style = Style {
+        sort_depth: 0,
+        color: output::color(),
+        markup: output::is_terminal(),
+    };
+    println!("{}", report.pretty(style).trim_end());
+}
+
+fn read_history(path: &Path) -> Vec<String> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<String> = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    let skip = lines.len().saturating_sub(HISTORY_LIMIT);
+    lines.into_iter().skip(skip).collect()
+}
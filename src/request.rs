@@ -0,0 +1,72 @@
+// The headers of a command-line request, from -H and --header-file, the
+// credentials options, cookies and the conditional and range options.
+
+use crate::cli::Cli;
+use crate::errln;
+use crate::secrets::SecretResolver;
+use crate::{auth, headers};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+
+// Header values backed by files are read right before sending, so tokens
+// rotated by other tooling are always picked up fresh.
+pub fn build_headers(args: &Cli, secrets: &mut SecretResolver) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+
+    let within = |spec: &str| {
+        let spec = spec.to_string();
+        move |e: String| format!("{} in '{}'.", e.trim_end_matches('.'), spec)
+    };
+    for spec in &args.header {
+        let (name, value) = headers::split(spec)?;
+        let name = headers::name(name).map_err(within(spec))?;
+        let value = headers::value(&name, &secrets.resolve(value)?).map_err(within(spec))?;
+        headers.append(name, value);
+    }
+
+    for spec in &args.header_file {
+        let (name, path) = spec
+            .split_once('@')
+            .ok_or_else(|| format!("Invalid --header-file '{}', expected 'Name@file'.", spec))?;
+        let name = headers::name(name)?;
+        let value = secrets.resolve(&headers::read_value_file(path)?)?;
+        let value = headers::value(&name, &value).map_err(within(path))?;
+        headers.append(name, value);
+    }
+
+    if let Some(token) = &args.bearer {
+        headers.insert(AUTHORIZATION, auth::bearer(&secrets.resolve(token)?)?);
+    }
+    if let Some(path) = &args.bearer_file {
+        let token = secrets.resolve(&headers::read_value_file(&path.to_string_lossy())?)?;
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| format!("Invalid bearer token in '{}'.", path.display()))?;
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    if let Some(cond) = &args.time_cond {
+        match headers::time_condition(cond) {
+            Some((name, value)) => {
+                headers.insert(name, value);
+            }
+            None => errln!(
+                "Warning: '{}' is neither a file nor an HTTP date; ignoring -z.",
+                cond.trim_start_matches('-')
+            ),
+        }
+    }
+
+    // Like curl, a -b argument without '=' names a cookie file
+    let cookies: Vec<String> = args
+        .cookie
+        .iter()
+        .filter(|c| c.contains('='))
+        .cloned()
+        .collect();
+    if !cookies.is_empty() {
+        headers::add_cookies(&mut headers, &cookies)?;
+    }
+
+    args.negotiation().add_headers(&mut headers)?;
+
+    Ok(headers)
+}
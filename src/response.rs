@@ -0,0 +1,763 @@
+// Printing a command-line response: the status and headers asked for, the
+// body formatted by its type (or filtered, merged, saved or streamed), and
+// the checks on it (--expect-*, --max-latency, snapshots).
+
+use crate::cli::Cli;
+use crate::exit::Category;
+use crate::format::Style;
+use crate::method::Method;
+use crate::{
+    altsvc, assertions, cache, cache_report, cookie_audit, cookie_jar, download, exit, filter,
+    format, graphql, har, json_stream, jsondiff, multipart, negotiation, output, paginate, proxy,
+    security_audit, snapshot, sse, transfer, writeout,
+};
+use crate::{errln, outln};
+use reqwest::StatusCode;
+use reqwest::blocking::Response;
+use reqwest::header::{
+    CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, HeaderMap, LOCATION, PROXY_AUTHENTICATE,
+};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// `started` is when the request was sent; latency budgets cover everything
+// up to the last body byte.
+pub fn print_response(res: Response, args: &Cli, started: Instant) {
+    if args.verbose
+        && args.unix_socket.is_none()
+        && let Some(addr) = res.remote_addr()
+    {
+        errln!("* Connected to {} port {}", addr.ip(), addr.port());
+    }
+    if args.verbose {
+        errln!("< {:?} {}", res.version(), res.status());
+        for (name, value) in res.headers() {
+            errln!("< {}: {}", name, String::from_utf8_lossy(value.as_bytes()));
+        }
+        errln!("<");
+    }
+
+    let status = res.status();
+    writeout::response(&res);
+    har::response(&res);
+    if args.paginate {
+        paginate::response(res.url(), res.headers());
+    }
+    altsvc::record(res.headers(), res.version());
+    cookie_jar::record(res.url(), res.headers());
+    if args.cache_report {
+        errln!("{}", cache_report::report(status, res.headers()));
+    }
+    if args.cookie_audit {
+        let reports = cookie_audit::audit(res.url(), res.headers());
+        match args.audit_format.as_str() {
+            "json" => errln!("{}", cookie_audit::render_json(&reports)),
+            _ => errln!("{}", cookie_audit::render_text(&reports)),
+        }
+    }
+    if args.security_audit || args.min_security_score.is_some() {
+        check_security(&res, args);
+    }
+
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let response_headers = res.headers().clone();
+    if includes_headers(args) {
+        let headers: Vec<(String, String)> = response_headers
+            .iter()
+            .map(|(n, v)| {
+                (
+                    n.to_string(),
+                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        print_headers(&format!("{:?} {}", res.version(), status), &headers, args);
+    }
+
+    if status == StatusCode::NOT_MODIFIED && args.cache.is_some() {
+        match cache::not_modified(res.headers()) {
+            Ok(Some(cached)) => {
+                output::status("Not modified; serving the cached copy.");
+                let elapsed = started.elapsed();
+                assertions::outcome(None, Some(elapsed));
+                print_content(&cached.body, cached.content_type.as_deref(), elapsed, args);
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                output::error(&e);
+                assertions::outcome(Some(e), Some(started.elapsed()));
+                return;
+            }
+        }
+    }
+
+    if status == StatusCode::NOT_MODIFIED && args.time_cond.is_some() {
+        output::status("Not modified; nothing to transfer.");
+        assertions::outcome(None, Some(started.elapsed()));
+        return;
+    }
+
+    if status == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        let challenges: Vec<String> = res
+            .headers()
+            .get_all(PROXY_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .map(str::to_string)
+            .collect();
+        request_failed(proxy_auth_failure(&challenges, args));
+        return;
+    }
+
+    if status == StatusCode::RANGE_NOT_SATISFIABLE
+        && let Some(path) = &args.output
+        && download::already_complete(res.headers(), args.resume_from())
+    {
+        output::status(format!(
+            "{} is already complete; nothing to resume.",
+            path.display()
+        ));
+        assertions::outcome(None, Some(started.elapsed()));
+        return;
+    }
+
+    if !status.is_success() {
+        if status.is_redirection()
+            && let Some(location) = res.headers().get(LOCATION).and_then(|v| v.to_str().ok())
+        {
+            errln!(
+                "Warning: The server redirected to {}; use -L to follow it.",
+                location
+            );
+        }
+        if args.body.json.is_some() && assertions::looks_like_html(content_type.as_deref(), &[]) {
+            warn_html_reply();
+        }
+        let message = format!("Request failed with status code: {}.", status.as_u16());
+        output::error(exit::Error::new(Category::Http, message.clone()));
+        assertions::outcome(Some(message), Some(started.elapsed()));
+        if (args.har.is_some() || args.graphql)
+            && let Some(body) = read_error_body(res, args)
+            && args.graphql
+            && let Ok(reply) =
+                graphql::parse_reply(&transfer::decode_text(&body, content_type.as_deref()))
+        {
+            report_graphql_errors(&reply);
+        }
+        return;
+    }
+
+    if is_head(args) {
+        let elapsed = started.elapsed();
+        assertions::outcome(None, Some(elapsed));
+        check_response_time(elapsed, args);
+        return;
+    }
+
+    if let Some(path) = &args.output {
+        save_body(res, path, args, started);
+        return;
+    }
+
+    if args.sse || is_event_stream(content_type.as_deref()) {
+        stream_sse(res, content_type.as_deref(), args, started);
+        return;
+    }
+
+    if streams_json(&response_headers, content_type.as_deref(), args) {
+        stream_json(res, content_type.as_deref(), args, started);
+        return;
+    }
+
+    let (body, encoded_len) = match transfer::read_decoded(res, &args.limits()) {
+        Ok(body) => body,
+        Err(e) => {
+            output::error(&e);
+            assertions::outcome(Some(e), Some(started.elapsed()));
+            return;
+        }
+    };
+    let elapsed = started.elapsed();
+    writeout::downloaded(encoded_len as u64);
+    har::content(&body, encoded_len);
+    let prefs = args.negotiation();
+    if prefs.any() {
+        errln!(
+            "{}",
+            negotiation::report(&prefs, &response_headers, encoded_len, body.len())
+        );
+    }
+    assertions::outcome(None, Some(elapsed));
+    if args.cache.is_some()
+        && let Err(e) = cache::store(status.as_u16(), &response_headers, &body)
+    {
+        errln!("Warning: Unable to cache the response: {}", e);
+    }
+    print_content(&body, content_type.as_deref(), elapsed, args);
+}
+
+// A complete, decoded body, from the server or the --cache.
+pub fn print_content(body: &[u8], content_type: Option<&str>, elapsed: Duration, args: &Cli) {
+    if args.body.json.is_some() && assertions::looks_like_html(content_type, body) {
+        warn_html_reply();
+    }
+
+    if !check_content_type(content_type, args) {
+        return;
+    }
+
+    let text = transfer::decode_text(body, content_type);
+    match content_type
+        .and_then(multipart::boundary)
+        .filter(|_| !output::is_raw())
+    {
+        Some(boundary) => print_parts(body, &text, &boundary, args),
+        None => print_body(body, &text, content_type, args),
+    }
+    check_response_time(elapsed, args);
+    check_snapshot(&text, args);
+}
+
+fn check_security(res: &Response, args: &Cli) {
+    let report = security_audit::audit(res.url(), res.headers());
+    if args.security_audit {
+        match args.audit_format.as_str() {
+            "json" => errln!("{}", security_audit::render_json(&report)),
+            _ => errln!("{}", security_audit::render_text(&report)),
+        }
+    }
+    if let Some(min) = args.min_security_score {
+        if report.score < min {
+            assertions::fail(
+                "security-score",
+                format!(
+                    "Security score {} is below the minimum of {}.",
+                    report.score, min
+                ),
+                None,
+            );
+        } else {
+            assertions::pass("security-score", None);
+        }
+    }
+}
+
+fn check_response_time(elapsed: Duration, args: &Cli) {
+    if let Some(limit) = args.max_response_time {
+        if elapsed > limit {
+            assertions::fail(
+                "response-time",
+                format!(
+                    "Response took {}, over the {} limit.",
+                    transfer::describe(elapsed),
+                    transfer::describe(limit)
+                ),
+                Some(elapsed),
+            );
+            return;
+        }
+        assertions::pass("response-time", Some(elapsed));
+    }
+
+    if let Some(limit) = args.warn_response_time
+        && elapsed > limit
+    {
+        errln!(
+            "Warning: Response took {}, over the {} warning threshold.",
+            transfer::describe(elapsed),
+            transfer::describe(limit)
+        );
+    }
+}
+
+pub fn check_snapshot(text: &str, args: &Cli) {
+    let Some(path) = &args.snapshot else {
+        return;
+    };
+    match snapshot::check(path, text, args.snapshot_update, &args.ignore_path) {
+        Ok(snapshot::Verdict::Created) => {
+            errln!("Snapshot saved to {}.", path.display());
+            assertions::pass("snapshot", None);
+        }
+        Ok(snapshot::Verdict::Updated) => {
+            errln!("Snapshot {} updated.", path.display());
+            assertions::pass("snapshot", None);
+        }
+        Ok(snapshot::Verdict::Matched) => assertions::pass("snapshot", None),
+        Ok(snapshot::Verdict::Changed(diff)) => assertions::fail(
+            "snapshot",
+            format!(
+                "Response does not match snapshot {}:\n{}",
+                path.display(),
+                diff
+            ),
+            None,
+        ),
+        Err(e) => assertions::fail("snapshot", e, None),
+    }
+}
+
+pub fn request_failed(error: impl Into<exit::Error>) {
+    let error = error.into();
+    assertions::outcome(Some(error.message.clone()), None);
+    output::error(error);
+}
+
+fn proxy_auth_failure(challenges: &[String], args: &Cli) -> String {
+    let credentials = args.proxy_user.as_deref().map(proxy::Credentials::parse);
+    proxy::explain_407(challenges, credentials.as_ref())
+}
+
+fn warn_html_reply() {
+    errln!(
+        "Warning: A JSON request got an HTML page back; this is usually a proxy, login or error page."
+    );
+}
+
+// --raw writes the bytes untouched; otherwise the decoded text is shown,
+// JSON pretty-printed and XML or HTML reindented on a terminal.
+pub fn print_body(body: &[u8], text: &str, content_type: Option<&str>, args: &Cli) {
+    if args.graphql {
+        print_graphql(body, text, args);
+        return;
+    }
+    if args.paginate && merge_page(text, args) {
+        return;
+    }
+    if let Some(filter) = &args.filter {
+        print_filtered(text, filter, args);
+        return;
+    }
+    if output::is_raw() {
+        output::raw_body(body);
+        return;
+    }
+    let style = Style {
+        sort_depth: sort_depth(args),
+        color: output::color(),
+        markup: output::is_terminal(),
+    };
+    let pretty = format::body(text, content_type, style);
+    match pretty.kind {
+        Some("JSON") if style.sort_depth > 0 => {
+            outln!("Response body (JSON with sorted keys):\n{}", pretty.text)
+        }
+        Some(kind) => outln!("Response body ({}):\n{}", kind, pretty.text),
+        None => outln!("Response body:\n{}", pretty.text),
+    }
+}
+
+// --filter: each match on its own line, strings without quotes so the
+// output can go straight into a shell variable.
+fn print_filtered(text: &str, filter: &jsondiff::JsonPath, args: &Cli) {
+    let Ok(json) = serde_json::from_str::<Value>(text) else {
+        output::error("The response isn't JSON, so --filter can't be applied.");
+        return;
+    };
+    print_matches(&json, filter, args);
+}
+
+fn print_matches(json: &Value, filter: &jsondiff::JsonPath, args: &Cli) {
+    let matches = filter.select(json);
+    if matches.is_empty() {
+        output::error(format!(
+            "--filter {} matched nothing in the response.",
+            filter
+        ));
+        return;
+    }
+    print_values(matches, args);
+}
+
+fn print_values<'a>(values: impl IntoIterator<Item = &'a Value>, args: &Cli) {
+    for value in values {
+        match value {
+            Value::String(s) => outln!("{}", s),
+            other => outln!("{}", format_json(other, args)),
+        }
+    }
+}
+
+// --paginate: whether the page went into the merged output; if not, it's
+// printed like any other body.
+fn merge_page(text: &str, args: &Cli) -> bool {
+    let json = serde_json::from_str::<Value>(text).ok();
+    paginate::body(json.as_ref(), args.next_field.as_ref());
+    let items = match (&args.filter, json) {
+        (Some(filter), Some(json)) => Some(filter.select(&json).into_iter().cloned().collect()),
+        (None, Some(Value::Array(items))) => Some(items),
+        _ => None,
+    };
+    match paginate::merge(items) {
+        Ok(merged) => merged,
+        Err(e) => {
+            output::error(e);
+            true
+        }
+    }
+}
+
+pub fn print_merged(items: Vec<Value>, args: &Cli) {
+    let pages = paginate::pages();
+    if let Some(filter) = &args.filter {
+        if items.is_empty() {
+            output::error(format!(
+                "--filter {} matched nothing in {} pages.",
+                filter, pages
+            ));
+        }
+        print_values(&items, args);
+        return;
+    }
+    let merged = Value::Array(items);
+    if output::is_raw() {
+        output::raw_body(merged.to_string().as_bytes());
+        return;
+    }
+    outln!(
+        "Response body (JSON array of {} pages):\n{}",
+        pages,
+        format_json(&merged, args)
+    );
+}
+
+// --graphql: every entry of `errors` is an error, even next to partial
+// data; `data` is printed, or what --filter selects from it.
+fn print_graphql(body: &[u8], text: &str, args: &Cli) {
+    let reply = match graphql::parse_reply(text) {
+        Ok(reply) => reply,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    report_graphql_errors(&reply);
+    if output::is_raw() {
+        output::raw_body(body);
+        return;
+    }
+    let Some(data) = &reply.data else {
+        return;
+    };
+    match &args.filter {
+        Some(filter) => print_matches(data, filter, args),
+        None => outln!("GraphQL data:\n{}", format_json(data, args)),
+    }
+}
+
+fn report_graphql_errors(reply: &graphql::Reply) {
+    for error in &reply.errors {
+        output::error(format!("GraphQL: {}", error));
+    }
+}
+
+// An error response's body isn't printed, but the HAR entry keeps it, and
+// --graphql reports the errors in it.
+fn read_error_body(res: Response, args: &Cli) -> Option<Vec<u8>> {
+    let (body, encoded_len) = transfer::read_decoded(res, &args.limits()).ok()?;
+    if args.har.is_some() {
+        har::content(&body, encoded_len);
+    }
+    Some(body)
+}
+
+// -o/-O. The body is saved as received, still compressed if the server
+// applied a Content-Encoding.
+fn save_body(res: Response, path: &Path, args: &Cli, started: Instant) {
+    if let Some(coding) = res
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+    {
+        errln!("Warning: Saving the {}-encoded body as received.", coding);
+    }
+    match download::save(res, path, args.resume_from(), &args.limits()) {
+        Ok(len) => {
+            let elapsed = started.elapsed();
+            writeout::downloaded(len);
+            har::saved(len);
+            output::status(format!(
+                "Saved {} to {} in {}.",
+                download::size(len),
+                path.display(),
+                transfer::describe(elapsed)
+            ));
+            assertions::outcome(None, Some(elapsed));
+            check_response_time(elapsed, args);
+        }
+        Err(e) => {
+            output::error(&e);
+            assertions::outcome(Some(e), Some(started.elapsed()));
+        }
+    }
+}
+
+fn is_head(args: &Cli) -> bool {
+    args.method.as_deref() == Some(Method::Head.as_str())
+}
+
+// --stream-json, or a JSON body past STREAM_THRESHOLD. Bodies that need
+// decoding or a snapshot comparison are still read in full.
+fn streams_json(headers: &HeaderMap, content_type: Option<&str>, args: &Cli) -> bool {
+    let is_json = content_type
+        .and_then(|ct| ct.parse::<mime::Mime>().ok())
+        .is_some_and(|m| m.subtype() == mime::JSON || m.suffix() == Some(mime::JSON));
+    if output::is_raw() || !is_json || args.paginate {
+        return false;
+    }
+    let large = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .is_some_and(|len| len > json_stream::STREAM_THRESHOLD);
+    if !args.stream_json && !large {
+        return false;
+    }
+    let encoded = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| !v.trim().eq_ignore_ascii_case("identity"));
+    if encoded || args.snapshot.is_some() || args.filter.is_some() {
+        if args.stream_json {
+            errln!(
+                "Warning: --stream-json is ignored {}; reading the whole body.",
+                if encoded {
+                    "for compressed responses"
+                } else if args.snapshot.is_some() {
+                    "with --snapshot"
+                } else {
+                    "with --filter"
+                }
+            );
+        }
+        return false;
+    }
+    true
+}
+
+fn stream_json(res: Response, content_type: Option<&str>, args: &Cli, started: Instant) {
+    if !check_content_type(content_type, args) {
+        assertions::outcome(None, Some(started.elapsed()));
+        return;
+    }
+    let headers = res.headers().clone();
+    outln!("Response body (JSON, streamed in server order):");
+    let result = {
+        let mut pretty = json_stream::Pretty::new(io::BufWriter::new(output::stdout()));
+        transfer::stream_body(res, &args.limits(), |chunk| pretty.feed(chunk))
+            .and_then(|len| pretty.finish().map(|_| len))
+    };
+    let elapsed = started.elapsed();
+    match result {
+        Ok(len) => {
+            let prefs = args.negotiation();
+            if prefs.any() {
+                errln!(
+                    "{}",
+                    negotiation::report(&prefs, &headers, len as usize, len as usize)
+                );
+            }
+            assertions::outcome(None, Some(elapsed));
+            check_response_time(elapsed, args);
+        }
+        Err(e) => {
+            // The output so far ends mid-line
+            outln!();
+            output::error(&e);
+            assertions::outcome(Some(e), Some(elapsed));
+        }
+    }
+}
+
+fn is_event_stream(content_type: Option<&str>) -> bool {
+    content_type
+        .and_then(|ct| ct.parse::<mime::Mime>().ok())
+        .is_some_and(|m| m.essence_str() == "text/event-stream")
+}
+
+// Each event as it completes; with --raw the bytes pass through as they
+// arrive. Under --sse, handle_sse decides whether an error ends the run or
+// leads to a reconnect.
+fn stream_sse(res: Response, content_type: Option<&str>, args: &Cli, started: Instant) {
+    if !check_content_type(content_type, args) {
+        assertions::outcome(None, Some(started.elapsed()));
+        return;
+    }
+    // 204 No Content tells the client to stop reconnecting
+    if res.status() == StatusCode::NO_CONTENT {
+        output::status("The server ended the event stream (204 No Content).");
+        assertions::outcome(None, Some(started.elapsed()));
+        return;
+    }
+    let mut parser = sse::Parser::resume();
+    let mut events = 0usize;
+    output::status("Response body (event stream):");
+    let result = transfer::stream_body(res, &args.limits(), |chunk| {
+        if output::is_raw() {
+            output::raw_body(chunk);
+        }
+        parser.feed(chunk, |event| {
+            events += 1;
+            if !output::is_raw() {
+                print_event(&event);
+            }
+            Ok(())
+        })
+    });
+    let elapsed = started.elapsed();
+    match result {
+        Ok(_) => {
+            output::status(format!(
+                "Event stream closed after {} event{} in {}.",
+                events,
+                if events == 1 { "" } else { "s" },
+                transfer::describe(elapsed)
+            ));
+            parser.finish(None);
+            assertions::outcome(None, Some(elapsed));
+        }
+        Err(e) if args.sse => {
+            parser.finish(Some(e.clone()));
+            assertions::outcome(Some(e), Some(elapsed));
+        }
+        Err(e) => {
+            output::error(&e);
+            assertions::outcome(Some(e), Some(elapsed));
+        }
+    }
+}
+
+fn print_event(event: &sse::Event) {
+    let mut text = format!("event: {}\n", event.name);
+    if let Some(id) = &event.id {
+        text.push_str(&format!("id: {}\n", id));
+    }
+    for line in event.data.split('\n') {
+        text.push_str(&format!("data: {}\n", line));
+    }
+    text.push('\n');
+    let mut stdout = output::stdout();
+    let _ = stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush());
+}
+
+// --expect-content-type
+fn check_content_type(content_type: Option<&str>, args: &Cli) -> bool {
+    let Some(expected) = &args.expect_content_type else {
+        return true;
+    };
+    if !assertions::content_type_matches(expected, content_type) {
+        assertions::fail(
+            "content-type",
+            format!(
+                "Expected Content-Type {} but the server returned {}.",
+                expected,
+                content_type.unwrap_or("none")
+            ),
+            None,
+        );
+        return false;
+    }
+    assertions::pass("content-type", None);
+    true
+}
+
+pub fn includes_headers(args: &Cli) -> bool {
+    is_head(args)
+        || args.include
+        || args.sort_headers
+        || !args.show_header.is_empty()
+        || args.header_grep.is_some()
+}
+
+// -i: the status line and response headers ahead of the body, narrowed to
+// --show-header names and --header-grep matches.
+pub fn print_headers(status_line: &str, headers: &[(String, String)], args: &Cli) {
+    let grep = args
+        .header_grep
+        .as_ref()
+        .map(|p| format!("*{}*", p.to_ascii_lowercase()));
+    let mut shown: Vec<&(String, String)> = headers
+        .iter()
+        .filter(|(name, _)| {
+            args.show_header.is_empty()
+                || args
+                    .show_header
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(name))
+        })
+        .filter(|(name, value)| {
+            grep.as_ref().is_none_or(|g| {
+                filter::glob_match(g, &format!("{}: {}", name, value).to_ascii_lowercase())
+            })
+        })
+        .collect();
+    if args.sort_headers {
+        // Stable, so repeated headers keep their order
+        shown.sort_by_key(|(name, _)| name.to_ascii_lowercase());
+    }
+
+    outln!("{}", status_line);
+    for (name, value) in shown {
+        outln!("{}: {}", name, value);
+    }
+    outln!();
+}
+
+// multipart/mixed and multipart/byteranges bodies, part by part.
+fn print_parts(body: &[u8], text: &str, boundary: &str, args: &Cli) {
+    let parts = match multipart::parse(body, boundary) {
+        Ok(parts) => parts,
+        Err(e) => {
+            errln!("Warning: {}", e);
+            print_body(body, text, None, args);
+            return;
+        }
+    };
+    outln!("Response body ({} parts):", parts.len());
+    for (i, part) in parts.iter().enumerate() {
+        outln!("--- Part {} ---", i + 1);
+        for (name, value) in &part.headers {
+            outln!("{}: {}", name, value);
+        }
+        if let Some(dir) = &args.save_parts {
+            match multipart::save(part, i + 1, dir) {
+                Ok(path) => outln!("(saved {} bytes to {})", part.body.len(), path.display()),
+                Err(e) => output::error(e),
+            }
+            continue;
+        }
+        let text = transfer::decode_text(&part.body, part.header("Content-Type"));
+        match serde_json::from_str::<Value>(&text) {
+            Ok(json) => outln!("\n{}", format_json(&json, args)),
+            Err(_) => outln!("\n{}", text),
+        }
+    }
+}
+
+// Pretty-printed JSON in the server's order, or with object keys sorted
+// down to --sort-depth levels (all of them for --sort-keys), highlighted
+// with --color.
+fn format_json(value: &Value, args: &Cli) -> String {
+    if output::color() {
+        format::json_colored(value, sort_depth(args))
+    } else {
+        format::json(value, sort_depth(args))
+    }
+}
+
+fn sort_depth(args: &Cli) -> usize {
+    match args.sort_depth {
+        Some(depth) => depth,
+        None if args.sort_keys && !args.no_sort_keys => usize::MAX,
+        None => 0,
+    }
+}
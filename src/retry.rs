@@ -0,0 +1,121 @@
+// --retry: send a request again after a transient failure.
+//
+// Connection errors and timeouts are always retried; with --retry-http-errors
+// so are 429 and 5xx responses. The wait doubles after every attempt,
+// starting at --retry-delay, unless the server asks for a specific one with
+// Retry-After. No attempt starts that the --deadline wouldn't leave time for.
+
+use crate::deadline;
+use crate::errln;
+use crate::exit::{self, Category};
+use crate::output;
+use crate::transfer::describe;
+use reqwest::StatusCode;
+use reqwest::blocking::Response;
+use reqwest::header::RETRY_AFTER;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_DELAY: Duration = Duration::from_secs(1);
+
+// Backoff never waits longer than this between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(120);
+
+pub struct Policy {
+    pub retries: u32,
+    pub delay: Duration,
+    pub http_errors: bool,
+}
+
+// Why an attempt ended without a response.
+pub struct Failure {
+    pub message: String,
+    // When trying again may help, the short cause ("connection failed")
+    pub transient: Option<&'static str>,
+    pub category: Category,
+}
+
+// Run `attempt` until it yields a response worth keeping or the retries
+// run out. Once there was more than one attempt a summary is printed.
+pub fn send(
+    policy: &Policy,
+    mut attempt: impl FnMut() -> Result<Response, Failure>,
+) -> Result<Response, exit::Error> {
+    let mut failures: Vec<String> = Vec::new();
+    loop {
+        let result = attempt();
+        let (reason, wait) = match &result {
+            Ok(res) if policy.http_errors && retryable(res.status()) => {
+                (label(&result), retry_after(res))
+            }
+            Err(f) if f.transient.is_some() => (label(&result), None),
+            _ => return finish(result, &failures),
+        };
+        let n = failures.len() as u32 + 1;
+        if n > policy.retries {
+            return finish(result, &failures);
+        }
+
+        let wait = wait
+            .unwrap_or_else(|| backoff(policy.delay, n))
+            .min(MAX_DELAY);
+        if deadline::remaining().is_some_and(|left| wait >= left) {
+            errln!(
+                "Warning: Not retrying after {}; the deadline leaves no time for another attempt.",
+                reason
+            );
+            return finish(result, &failures);
+        }
+        errln!(
+            "Warning: Attempt {} of {} failed ({}); retrying in {}.",
+            n,
+            policy.retries + 1,
+            reason,
+            describe(wait)
+        );
+        failures.push(reason);
+        thread::sleep(wait);
+    }
+}
+
+// "Attempts: 3 (connection failed, HTTP 503, HTTP 200)"
+fn finish(result: Result<Response, Failure>, failures: &[String]) -> Result<Response, exit::Error> {
+    if !failures.is_empty() {
+        output::status(format!(
+            "Attempts: {} ({}, {})",
+            failures.len() + 1,
+            failures.join(", "),
+            label(&result)
+        ));
+    }
+    result.map_err(|f| exit::Error::new(f.category, f.message))
+}
+
+fn label(result: &Result<Response, Failure>) -> String {
+    match result {
+        Ok(res) => format!("HTTP {}", res.status().as_u16()),
+        Err(f) => f.transient.map_or_else(
+            || f.message.trim_end_matches('.').to_string(),
+            str::to_string,
+        ),
+    }
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// 1s, 2s, 4s, ... from the base delay.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1 << (attempt - 1).min(16))
+}
+
+// Retry-After is either a number of seconds or an HTTP date.
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
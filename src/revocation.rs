@@ -0,0 +1,177 @@
+// --check-revocation: ask OCSP whether the server certificate was revoked.
+//
+// The handshake requests a stapled OCSP response (status_request); servers
+// that don't staple are checked by querying the responder named in the
+// certificate's Authority Information Access extension. Either way the
+// response must be signed by the issuer (or a responder it delegated to)
+// and be current.
+
+use crate::deadline;
+use crate::tls_info::Trust;
+use openssl::hash::MessageDigest;
+use openssl::ocsp::{
+    OcspCertId, OcspCertStatus, OcspFlag, OcspRequest, OcspResponse, OcspResponseStatus,
+    OcspRevokedStatus,
+};
+use openssl::ssl::{SslConnector, SslMethod, StatusType};
+use openssl::stack::StackRef;
+use openssl::x509::X509;
+use openssl::x509::store::X509StoreBuilder;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const RESPONDER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Clock skew tolerated when checking thisUpdate/nextUpdate.
+const VALIDITY_LEEWAY_SECS: u32 = 300;
+
+pub enum Status {
+    Good { source: &'static str },
+    Revoked { reason: &'static str, at: String },
+    Unknown(String),
+}
+
+// `name` is the server name sent via SNI; `host` and `port` are where to
+// connect.
+pub fn check(name: &str, host: &str, port: u16, trust: Trust) -> Result<Status, String> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|_| format!("Could not resolve host: {}.", host))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve host: {}.", host))?;
+    let tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Unable to connect to {}: {}", addr, e))?;
+    let _ = tcp.set_read_timeout(Some(CONNECT_TIMEOUT));
+
+    let mut builder = SslConnector::builder(SslMethod::tls_client()).map_err(|e| e.to_string())?;
+    trust.apply(&mut builder)?;
+    let connector = builder.build();
+    let mut ssl = connector
+        .configure()
+        .and_then(|c| c.into_ssl(name))
+        .map_err(|e| e.to_string())?;
+    ssl.set_status_type(StatusType::OCSP)
+        .map_err(|e| e.to_string())?;
+    let mut stream = ssl
+        .connect(tcp)
+        .map_err(|e| format!("TLS handshake failed: {}", e))?;
+
+    let ssl = stream.ssl();
+    let stapled = ssl.ocsp_status().map(<[u8]>::to_vec);
+    let Some(chain) = ssl.verified_chain() else {
+        return Ok(Status::Unknown("no verified certificate chain".to_string()));
+    };
+    let status = evaluate(chain, stapled);
+    let _ = stream.shutdown();
+    status
+}
+
+fn evaluate(chain: &StackRef<X509>, stapled: Option<Vec<u8>>) -> Result<Status, String> {
+    let (Some(leaf), Some(issuer)) = (chain.get(0), chain.get(1)) else {
+        return Ok(Status::Unknown(
+            "the certificate has no issuer in the chain (self-signed?)".to_string(),
+        ));
+    };
+    let id = || OcspCertId::from_cert(MessageDigest::sha1(), leaf, issuer);
+
+    let (der, source) = match stapled {
+        Some(der) => (der, "stapled OCSP response"),
+        None => {
+            let responders = leaf.ocsp_responders().map_err(|e| e.to_string())?;
+            let Some(url) = responders.iter().next().map(|r| r.to_string()) else {
+                return Ok(Status::Unknown(
+                    "nothing stapled and the certificate names no OCSP responder".to_string(),
+                ));
+            };
+            let mut request = OcspRequest::new().map_err(|e| e.to_string())?;
+            request
+                .add_id(id().map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+            let body = request.to_der().map_err(|e| e.to_string())?;
+            match query(&url, body) {
+                Ok(der) => (der, "OCSP responder"),
+                Err(e) => return Ok(Status::Unknown(e)),
+            }
+        }
+    };
+
+    let response = OcspResponse::from_der(&der).map_err(|e| e.to_string())?;
+    if response.status() != OcspResponseStatus::SUCCESSFUL {
+        return Ok(Status::Unknown(format!(
+            "the {} reported an error (status {})",
+            source,
+            response.status().as_raw()
+        )));
+    }
+    let basic = response.basic().map_err(|e| e.to_string())?;
+
+    let mut store = X509StoreBuilder::new().map_err(|e| e.to_string())?;
+    store.set_default_paths().map_err(|e| e.to_string())?;
+    let store = store.build();
+    if let Err(e) = basic.verify(chain, &store, OcspFlag::empty()) {
+        return Ok(Status::Unknown(format!(
+            "the {} has an invalid signature: {}",
+            source, e
+        )));
+    }
+
+    let id = id().map_err(|e| e.to_string())?;
+    let Some(status) = basic.find_status(&id) else {
+        return Ok(Status::Unknown(format!(
+            "the {} doesn't cover this certificate",
+            source
+        )));
+    };
+    if status.check_validity(VALIDITY_LEEWAY_SECS, None).is_err() {
+        return Ok(Status::Unknown(format!("the {} is outdated", source)));
+    }
+
+    Ok(match status.status {
+        OcspCertStatus::GOOD => Status::Good { source },
+        OcspCertStatus::REVOKED => Status::Revoked {
+            reason: reason(status.reason),
+            at: status
+                .revocation_time
+                .map_or_else(|| "an unknown time".to_string(), |t| t.to_string()),
+        },
+        _ => Status::Unknown(format!("the {} doesn't know this certificate", source)),
+    })
+}
+
+fn query(url: &str, body: Vec<u8>) -> Result<Vec<u8>, String> {
+    let timeout = deadline::remaining().map_or(RESPONDER_TIMEOUT, |r| r.min(RESPONDER_TIMEOUT));
+    let res = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .and_then(|c| {
+            c.post(url)
+                .header("Content-Type", "application/ocsp-request")
+                .body(body)
+                .send()
+        })
+        .map_err(|e| format!("the OCSP responder {} is unreachable: {}", url, e))?;
+    if !res.status().is_success() {
+        return Err(format!(
+            "the OCSP responder {} returned status {}",
+            url,
+            res.status().as_u16()
+        ));
+    }
+    res.bytes()
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("the OCSP responder {} failed: {}", url, e))
+}
+
+fn reason(reason: OcspRevokedStatus) -> &'static str {
+    match reason {
+        OcspRevokedStatus::KEY_COMPROMISE => "key compromise",
+        OcspRevokedStatus::CA_COMPROMISE => "CA compromise",
+        OcspRevokedStatus::AFFILIATION_CHANGED => "affiliation changed",
+        OcspRevokedStatus::STATUS_SUPERSEDED => "superseded",
+        OcspRevokedStatus::STATUS_CESSATION_OF_OPERATION => "cessation of operation",
+        OcspRevokedStatus::STATUS_CERTIFICATE_HOLD => "certificate hold",
+        OcspRevokedStatus::REMOVE_FROM_CRL => "removed from CRL",
+        _ => "unspecified",
+    }
+}
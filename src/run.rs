@@ -0,0 +1,994 @@
+// Carrying out a command line: the URLs to fetch, one or several, then for
+// each the body, the scheme handler or the offline cache, the client and
+// the handler that sends the request.
+
+use crate::cli::{Cli, Tool, apply_profile, session_profile};
+use crate::client_setup::add_proxy_headers;
+use crate::client_setup::check_revocation;
+use crate::client_setup::client_options;
+use crate::client_setup::proxy_router;
+use crate::client_setup::report_tls;
+use crate::client_setup::uses_proxy;
+use crate::client_setup::{base_options, probe_connect};
+use crate::duration::humanize;
+use crate::exit::Category;
+use crate::handlers::handle_bench;
+use crate::handlers::handle_paginate;
+use crate::handlers::handle_sse;
+use crate::handlers::{
+    handle_binary_post, handle_form_post, handle_json_post, handle_multipart, handle_raw,
+    handle_request, handle_upload,
+};
+use crate::method::Method;
+use crate::pool_stats::PoolStats;
+use crate::request::build_headers;
+use crate::response::{check_snapshot, print_body, print_content, request_failed};
+use crate::schemes::{Outcome, SchemeRegistry, TransferOptions};
+use crate::secrets::SecretResolver;
+use crate::{ClientOptions, WebClient, errln};
+use crate::{
+    altsvc, assertions, auth, cache, config, cookie_jar, cors, deadline, diff, download, exit,
+    graphql, har, headers, junit, jwt, monitor, multi, negotiate, ntlm, output, raw, repl, s3,
+    sigv4, template, unix_socket, url_build, url_norm, writeout,
+};
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, HOST, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, RANGE,
+};
+use std::fs;
+use std::io::{self, Read};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+pub fn start(args: &mut Cli) {
+    if let Some(command) = args.command.take() {
+        match command.into_request() {
+            Ok((method, url, body)) => {
+                if args.method.is_some() {
+                    output::error(format!(
+                        "-X can't be combined with the '{}' subcommand.",
+                        method.to_ascii_lowercase()
+                    ));
+                    return;
+                }
+                args.method = Some(method.to_string());
+                args.url = Some(url);
+                args.body = body;
+            }
+            Err(tool) => {
+                let result = match tool {
+                    Tool::S3(cmd) => s3::run(cmd),
+                    Tool::Cors(cmd) => cors::run(cmd),
+                    Tool::Diff(cmd) => diff::run(cmd, &args.ignore_path),
+                    Tool::Monitor(cmd) => monitor::run(cmd, args.report_junit.as_deref()),
+                    Tool::Jwt(cmd) => jwt::run(cmd),
+                    Tool::Url(cmd) => url_norm::run(cmd),
+                };
+                if let Err(e) = result {
+                    output::error(e);
+                }
+                return;
+            }
+        }
+    }
+
+    let profile = match args.profile.as_deref().map(config::load).transpose() {
+        Ok(profile) => profile,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    if let Some(profile) = &profile {
+        apply_profile(args, profile);
+    }
+    if args.interactive {
+        if let Err(e) = interactive(args, profile.as_ref()) {
+            output::error(e);
+        }
+        return;
+    }
+
+    let urls = match all_urls(args, profile.as_ref()) {
+        Ok(urls) => urls,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    if let Some(name) = &args.save_session {
+        let session = session_profile(args, profile.as_ref(), urls.first());
+        match config::save(name, &session) {
+            Ok(path) => errln!("Saved profile '{}' to {}.", name, path.display()),
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        }
+    }
+    if urls.len() > 1
+        && [&args.body.data, &args.body.json, &args.body.data_binary]
+            .iter()
+            .any(|b| b.as_deref() == Some("@-"))
+    {
+        output::error("A body from stdin (@-) can't be sent to several URLs.");
+        return;
+    }
+
+    let pool_stats = args.pool_stats.then(Arc::<PoolStats>::default);
+
+    let write_out = match args
+        .write_out
+        .as_deref()
+        .map(writeout::template)
+        .transpose()
+    {
+        Ok(template) => template,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+
+    // One budget for the whole run, however many URLs it fetches
+    if let Some(limit) = args.deadline {
+        deadline::start(limit);
+    }
+    if urls.len() > 1 {
+        let code = fetch_all(args, &urls, write_out.as_deref(), pool_stats.as_ref());
+        if let Some(stats) = pool_stats {
+            errln!("{}", stats.report());
+        }
+        std::process::exit(code);
+    }
+    args.url = urls.into_iter().next();
+    run(args, None, pool_stats.as_ref());
+
+    if let Some(template) = &write_out {
+        writeout::print(template, |url| probe_connect(url, args));
+    }
+    if let Some(path) = &args.har {
+        let written = har::entry().and_then(|entry| match entry {
+            Some(entry) => har::write(path, vec![entry]),
+            None => Ok(()),
+        });
+        if let Err(e) = written {
+            output::error(e);
+        }
+    }
+
+    if let Some(stats) = pool_stats {
+        errln!("{}", stats.report());
+    }
+
+    if let Some(path) = &args.report_junit
+        && let Err(e) = junit::write(path, "curl", &assertions::checks())
+    {
+        output::error(e);
+    }
+}
+
+// Fetch several URLs --parallel at a time through one client, each with
+// its own copy of the options, and write the HAR file and JUnit report
+// once for all of them. Returns the run's exit status.
+fn fetch_all(
+    args: &Cli,
+    urls: &[String],
+    write_out: Option<&str>,
+    pool_stats: Option<&Arc<PoolStats>>,
+) -> i32 {
+    let options = base_options(args).map(|options| ClientOptions {
+        pool_stats: pool_stats.cloned(),
+        ..options
+    });
+    let client = match options.and_then(WebClient::new) {
+        Ok(client) => client,
+        Err(e) => {
+            output::error(e);
+            return output::exit_code();
+        }
+    };
+    let entries = Mutex::new(vec![None; urls.len()]);
+    let checks = Mutex::new(vec![Vec::new(); urls.len()]);
+
+    let code = multi::run(urls, args.parallel.unwrap_or(1), |i| {
+        let mut args = args.clone();
+        args.url = Some(urls[i].clone());
+        run(&mut args, Some(&client), pool_stats);
+        if let Some(template) = write_out {
+            writeout::print(template, |url| probe_connect(url, &args));
+        }
+        match har::entry() {
+            Ok(entry) => entries.lock().unwrap()[i] = entry,
+            Err(e) => output::error(e),
+        }
+        checks.lock().unwrap()[i] = assertions::checks();
+        exit_status(true)
+    });
+
+    if let Some(path) = &args.har {
+        let entries = entries
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        if let Err(e) = har::write(path, entries) {
+            output::error(e);
+        }
+    }
+    if let Some(path) = &args.report_junit {
+        let checks: Vec<_> = checks.into_inner().unwrap().into_iter().flatten().collect();
+        if let Err(e) = junit::write(path, "curl", &checks) {
+            output::error(e);
+        }
+    }
+    match output::exit_code() {
+        0 => code,
+        failed => failed,
+    }
+}
+
+// The first error's exit code, else a failed assertion's.
+pub fn exit_status(several: bool) -> i32 {
+    match output::exit_code() {
+        0 => {}
+        code => return code,
+    }
+    if assertions::failed() {
+        return assertions::EXIT_ASSERTION_FAILED;
+    }
+    // In a multi-URL run, a URL whose request went wrong counts as failed
+    // even when the status alone doesn't fail it
+    if several && assertions::checks().iter().any(|c| c.failure.is_some()) {
+        return multi::EXIT_FAILED;
+    }
+    0
+}
+
+// The URL arguments followed by those of --url-file.
+// A profile's base URL prefixes the relative ones, and is the only URL
+// when none is given.
+fn all_urls(args: &Cli, profile: Option<&config::Profile>) -> Result<Vec<String>, String> {
+    let mut urls: Vec<String> = args.url.iter().chain(&args.urls).cloned().collect();
+    if let Some(path) = &args.url_file {
+        urls.extend(multi::read_url_file(path)?);
+    }
+    let Some(profile) = profile else {
+        return Ok(urls);
+    };
+    if urls.is_empty() {
+        urls.extend(profile.base_url.clone());
+    }
+    Ok(urls.iter().map(|url| profile.resolve_url(url)).collect())
+}
+
+// `shared` is the client of a multi-URL run.
+fn run(args: &mut Cli, shared: Option<&WebClient>, pool_stats: Option<&Arc<PoolStats>>) {
+    let body_file = match load_body_file(args) {
+        Ok(path) => path,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    // --graphql goes out as a --json body
+    if args.graphql {
+        let query = args.query.as_deref().unwrap_or_default();
+        match graphql::envelope(
+            query,
+            args.variables.as_deref(),
+            args.operation_name.as_deref(),
+        ) {
+            Ok(envelope) => args.body.json = Some(envelope.to_string()),
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        }
+    }
+
+    // Expand {{provider:path#field}} secret references before anything is sent
+    let mut secrets = SecretResolver::with_defaults();
+    let resolved = resolve_secrets(args, &mut secrets);
+    mask_secrets(&secrets);
+    if let Err(e) = resolved {
+        output::error(e);
+        return;
+    }
+    if let Err(e) = render_body_templates(args, body_file.as_deref()) {
+        output::error(e);
+        return;
+    }
+
+    let Some(url) = args.url.clone() else {
+        output::error("No URL specified.");
+        return;
+    };
+    let url = match url_build::build(&url, &args.path_vars, &args.params) {
+        Ok(url) => url,
+        Err(e) => {
+            output::error(exit::Error::new(Category::Url, e));
+            return;
+        }
+    };
+
+    // Without -X, -d, --json, --data-binary and -F mean POST and -T means PUT
+    let has_body = args.body.json.is_some()
+        || args.body.data.is_some()
+        || args.body.data_binary.is_some()
+        || !args.body.form.is_empty();
+    let mut method = match &args.method {
+        Some(m) => m.to_ascii_uppercase(),
+        None if args.body.upload_file.is_some() => "PUT".to_string(),
+        None if has_body => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+
+    let raw_request = match &args.raw_request {
+        Some(path) => match fs::read(path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                output::error(format!("Unable to read '{}': {}", path.display(), e));
+                return;
+            }
+        },
+        None => None,
+    };
+    if let Some(m) = raw_request.as_deref().and_then(raw::method) {
+        method = m;
+    }
+
+    output::status(format!("Requesting URL: {}", url));
+    output::status(format!("Method: {}", method));
+    assertions::begin(output::redact(&format!("{} {}", method, url)));
+    if args.write_out.is_some() {
+        writeout::enable(&method);
+    }
+    if args.har.is_some() {
+        har::enable();
+    }
+
+    // Punycode the host and percent-encode the rest, then parse
+    let prepared = match url_norm::prepare(&url) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            output::error(exit::Error::new(Category::Url, e));
+            return;
+        }
+    };
+    if args.verbose && prepared != url {
+        errln!("* Encoded URL: {}", output::redact(&prepared));
+    }
+    let parsed = match Url::parse(&prepared) {
+        Ok(u) => u,
+        Err(e) => {
+            handle_url_error(e);
+            return;
+        }
+    };
+
+    if let Some(request) = raw_request {
+        handle_raw(&parsed, &request, args);
+        return;
+    }
+
+    if args.remote_name {
+        match download::remote_name(&parsed) {
+            Ok(name) => args.output = Some(name),
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        }
+    }
+    if let Some(spec) = &args.continue_at {
+        let Some(path) = &args.output else {
+            output::error("-C continues a download; use it with -o or -O.");
+            return;
+        };
+        match download::resume_offset(spec, path) {
+            Ok(offset) => args.continue_at = Some(offset.to_string()),
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        }
+    }
+
+    let method = match Method::parse(&method) {
+        Ok(m) => m,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    if !method.allows_body() && (has_body || args.body.upload_file.is_some()) {
+        output::error(format!(
+            "{} requests don't take a body (-d, --json, --data-binary, -F or -T).",
+            method
+        ));
+        return;
+    }
+    // print_response shows a HEAD response's headers instead of its body
+    args.method = Some(method.to_string());
+
+    // Other schemes go to the handlers of the client's registry: the
+    // shared one's, or that of a client with the run's settings
+    let for_schemes;
+    let schemes = match shared {
+        _ if SchemeRegistry::is_http(parsed.scheme()) => None,
+        Some(client) => Some(client),
+        None => match base_options(args).and_then(WebClient::new) {
+            Ok(client) => {
+                for_schemes = client;
+                Some(&for_schemes)
+            }
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        },
+    };
+    // Reject unsupported protocols early
+    if schemes.is_some_and(|client| !client.schemes().supports(parsed.scheme())) {
+        output::error("The URL does not have a valid base protocol.");
+        return;
+    }
+
+    // Before anything that could touch the network: proxies, DNS, TLS
+    if args.offline {
+        serve_offline(&parsed, method, has_body, args, &mut secrets);
+        return;
+    }
+
+    // Non-HTTP schemes are either served by their handler or rewritten to HTTP
+    let opts = TransferOptions {
+        upload: args.body.upload_file.as_deref(),
+        ftp_ssl: args.ftp_ssl,
+        limits: args.limits(),
+    };
+    let dispatched = match schemes {
+        Some(client) => client.dispatch(&parsed, &opts),
+        None => Ok(Outcome::Rewrite(parsed)),
+    };
+    let parsed = match dispatched {
+        Ok(Outcome::Rewrite(url)) => url,
+        Ok(Outcome::Body(body)) => {
+            let text = String::from_utf8_lossy(&body);
+            print_body(&body, &text, None, args);
+            assertions::outcome(None, None);
+            check_snapshot(&text, args);
+            return;
+        }
+        Ok(Outcome::Done(message)) => {
+            output::status(message);
+            assertions::outcome(None, None);
+            return;
+        }
+        Err(e) => {
+            request_failed(&e);
+            return;
+        }
+    };
+
+    if let Err(e) = deadline::check() {
+        output::error(e);
+        return;
+    }
+
+    // A cached alternative keeps the origin's name for TLS and Host but
+    // connects to the advertised endpoint
+    let alternative = args
+        .alt_svc
+        .as_deref()
+        .and_then(|path| altsvc::enable(path, &parsed));
+    let origin = parsed.clone();
+    let mut parsed = parsed;
+    if let Some(alt) = &alternative {
+        if args.verbose {
+            errln!("* Alt-Svc: using {} at {}:{}", alt.alpn, alt.host, alt.port);
+        }
+        let _ = parsed.set_port(Some(alt.port));
+    }
+    // The URL's host stays in Host while the connection goes to the relay
+    let relay = match &args.unix_socket {
+        Some(_) if parsed.scheme() != "http" => {
+            output::error(exit::Error::new(
+                Category::Url,
+                "--unix-socket takes http:// URLs only.",
+            ));
+            return;
+        }
+        Some(path) => match unix_socket::relay(path) {
+            Ok(relay) => {
+                if args.verbose {
+                    errln!("* Connecting through Unix socket {}", path.display());
+                }
+                let _ = parsed.set_ip_host(Ipv4Addr::LOCALHOST.into());
+                let _ = parsed.set_port(Some(relay.port));
+                Some(relay)
+            }
+            Err(e) => {
+                output::error(exit::Error::new(Category::Connect, e));
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let router = match uses_proxy(args)
+        .then(|| proxy_router(&parsed, args))
+        .transpose()
+    {
+        Ok(r) => r.map(Arc::new),
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+
+    let options = match client_options(
+        &parsed,
+        alternative.as_ref(),
+        router.clone(),
+        args,
+        pool_stats,
+    ) {
+        Ok(options) => options,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    // The shared client connects to the URL's own host, directly or through
+    // the proxy from the environment
+    let own;
+    let client = match shared {
+        Some(client) if alternative.is_none() && relay.is_none() && router.is_none() => client,
+        _ => match WebClient::new(options) {
+            Ok(client) => {
+                own = client;
+                &own
+            }
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        },
+    };
+
+    if parsed.scheme() == "https" && (args.verbose || args.tls_info) {
+        report_tls(&origin, alternative.as_ref(), args);
+    }
+    if parsed.scheme() == "https"
+        && args.check_revocation
+        && let Err(e) = check_revocation(&origin, alternative.as_ref(), args)
+    {
+        request_failed(exit::Error::new(Category::Tls, e));
+        return;
+    }
+
+    let headers = build_headers(args, &mut secrets);
+    mask_secrets(&secrets);
+    let mut headers = match headers {
+        Ok(h) => h,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    let cookie_files: Vec<&Path> = args
+        .cookie
+        .iter()
+        .filter(|c| !c.contains('='))
+        .map(Path::new)
+        .collect();
+    if !cookie_files.is_empty() || args.cookie_jar.is_some() {
+        if let Err(e) = cookie_jar::enable(&cookie_files, args.cookie_jar.as_deref()) {
+            output::error(e);
+            return;
+        }
+        if let Some(cookies) = cookie_jar::header(&origin)
+            && let Err(e) = headers::add_cookies(&mut headers, &[cookies])
+        {
+            output::error(e);
+            return;
+        }
+    }
+    if args.resume_from() > 0 {
+        if args.verbose {
+            errln!("* Resuming at byte {}", args.resume_from());
+        }
+        if let Ok(range) = HeaderValue::from_str(&format!("bytes={}-", args.resume_from())) {
+            headers.insert(RANGE, range);
+        }
+    }
+    if args.graphql && !headers.contains_key(ACCEPT) {
+        headers.insert(ACCEPT, HeaderValue::from_static(graphql::ACCEPT));
+    }
+    if (alternative.is_some() && origin.port() != parsed.port() || relay.is_some())
+        && let Ok(host) = HeaderValue::from_str(&sigv4::host_header(&origin))
+    {
+        headers.insert(HOST, host);
+    }
+    if let Some(relay) = &relay
+        && let Ok(mut token) = HeaderValue::from_str(&relay.token)
+    {
+        token.set_sensitive(true);
+        headers.insert(unix_socket::TOKEN_HEADER, token);
+    }
+    if args.negotiate {
+        let token = negotiate::token(origin.host_str().unwrap_or(""))
+            .and_then(|t| HeaderValue::from_str(&t).map_err(|e| e.to_string()));
+        match token {
+            Ok(mut value) => {
+                value.set_sensitive(true);
+                headers.insert(AUTHORIZATION, value);
+            }
+            Err(e) => {
+                output::error(format!("Negotiate authentication: {}", e));
+                return;
+            }
+        }
+    }
+    // Another scheme already supplies Authorization; -u would override it
+    let other_auth = if args.negotiate {
+        Some("--negotiate")
+    } else if args.bearer.is_some() || args.bearer_file.is_some() {
+        Some("a bearer token")
+    } else {
+        None
+    };
+    let credentials = match args.user.as_deref() {
+        Some(_) if other_auth.is_some() && !(args.ntlm || args.digest) => {
+            errln!(
+                "Warning: -u is not used with {}; ignoring it.",
+                other_auth.unwrap_or_default()
+            );
+            None
+        }
+        Some(user) => match auth::Credentials::parse(user) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                output::error(e);
+                return;
+            }
+        },
+        None => None,
+    };
+    if args.ntlm {
+        let Some(credentials) = &credentials else {
+            output::error("--ntlm requires credentials; pass -u 'DOMAIN\\user:password'.");
+            return;
+        };
+        let credentials = ntlm::Credentials::parse(&credentials.joined());
+        match ntlm::handshake(client, method, &parsed, &headers, &credentials) {
+            Ok(value) => {
+                headers.insert(AUTHORIZATION, value);
+            }
+            Err(e) => {
+                request_failed(format!("NTLM authentication: {}", e));
+                return;
+            }
+        }
+    } else if args.digest {
+        let Some(credentials) = &credentials else {
+            output::error("--digest requires credentials; pass -u user:password.");
+            return;
+        };
+        match auth::digest_handshake(client, method, &parsed, &headers, credentials) {
+            Ok(Some(value)) => {
+                headers.insert(AUTHORIZATION, value);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                request_failed(format!("Digest authentication: {}", e));
+                return;
+            }
+        }
+    } else if let Some(credentials) = &credentials {
+        headers.insert(AUTHORIZATION, credentials.basic());
+    }
+    if let Err(e) = add_proxy_headers(&mut headers, &parsed, router.as_deref(), args) {
+        output::error(e);
+        return;
+    }
+
+    if args.bench {
+        handle_bench(client, method, &parsed, &headers, args);
+        return;
+    }
+
+    // --cache: a fresh copy is served without a request, a stale one is
+    // revalidated. Conditions of the user's own are left alone.
+    if let Some(dir) = args.cache.as_deref()
+        && method == Method::Get
+        && !has_body
+        && args.time_cond.is_none()
+        && !headers.contains_key(IF_NONE_MATCH)
+        && !headers.contains_key(IF_MODIFIED_SINCE)
+    {
+        match cache::lookup(dir, &origin, &headers, args.no_cache) {
+            Ok(cache::Lookup::Fresh(cached, remaining)) => {
+                output::status(format!(
+                    "Serving the cached copy (fresh for another {}).",
+                    humanize(remaining.as_secs())
+                ));
+                assertions::outcome(None, None);
+                print_content(
+                    &cached.body,
+                    cached.content_type.as_deref(),
+                    Duration::ZERO,
+                    args,
+                );
+                return;
+            }
+            Ok(cache::Lookup::Revalidate(conditional)) => {
+                if args.verbose {
+                    errln!("* Revalidating the cached copy");
+                }
+                headers.extend(conditional);
+            }
+            Ok(cache::Lookup::Miss) => {}
+            Err(e) => errln!("Warning: {}; not using the cache.", e),
+        }
+    }
+
+    if args.sse {
+        handle_sse(client, method, &parsed, &headers, args);
+    } else if args.paginate {
+        handle_paginate(client, method, &parsed, headers, args);
+    } else {
+        dispatch(client, method, &parsed, &headers, args);
+    }
+}
+
+// --offline: the cached copy or an error, without a request.
+fn serve_offline(
+    url: &Url,
+    method: Method,
+    has_body: bool,
+    args: &Cli,
+    secrets: &mut SecretResolver,
+) {
+    let Some(dir) = args.cache.as_deref() else {
+        return;
+    };
+    if method != Method::Get || has_body || args.body.upload_file.is_some() {
+        output::error("--offline can only answer GET requests without a body.");
+        return;
+    }
+    if !matches!(url.scheme(), "http" | "https") {
+        output::error(format!(
+            "--offline has no cached copies of {}:// URLs.",
+            url.scheme()
+        ));
+        return;
+    }
+    // Headers named by a stored response's Vary have to match
+    let headers = build_headers(args, secrets);
+    mask_secrets(secrets);
+    let headers = match headers {
+        Ok(h) => h,
+        Err(e) => {
+            output::error(e);
+            return;
+        }
+    };
+    match cache::stored(dir, url, &headers) {
+        Ok(Some((cached, age))) => {
+            output::status(format!(
+                "Serving the cached copy from {} ago (--offline).",
+                humanize(age.as_secs())
+            ));
+            assertions::outcome(None, None);
+            print_content(
+                &cached.body,
+                cached.content_type.as_deref(),
+                Duration::ZERO,
+                args,
+            );
+        }
+        Ok(None) => output::error(format!(
+            "--offline: '{}' has no cached copy of {}.",
+            dir.display(),
+            url
+        )),
+        Err(e) => output::error(e),
+    }
+}
+
+// Send the request with whichever body option is set.
+pub fn dispatch(client: &WebClient, method: Method, url: &Url, headers: &HeaderMap, args: &Cli) {
+    if let Some(path) = &args.body.upload_file {
+        handle_upload(client, method, url, headers, args, path);
+    } else if !args.body.form.is_empty() {
+        handle_multipart(client, method, url, headers, args);
+    } else if let Some(json_data) = &args.body.json {
+        handle_json_post(client, method, url, headers, args, json_data);
+    } else if let Some(data) = &args.body.data {
+        handle_form_post(client, method, url, headers, args, data);
+    } else if let Some(data) = &args.body.data_binary {
+        handle_binary_post(client, method, url, headers, args, data);
+    } else if method == Method::Post {
+        output::error("POST method requires -d, --json or --data-binary data.");
+    } else {
+        handle_request(client, method, url, headers, args);
+    }
+}
+
+fn interactive(args: &Cli, profile: Option<&config::Profile>) -> Result<(), exit::Error> {
+    repl::run(session(args, profile)?, base_options(args)?)
+}
+
+// The session starts with the URL given (or the profile's base URL) as its
+// base, and the command line's headers and credentials.
+fn session(args: &Cli, profile: Option<&config::Profile>) -> Result<repl::Session, exit::Error> {
+    let mut session = repl::Session {
+        base_url: args
+            .url
+            .clone()
+            .or_else(|| profile.and_then(|p| p.base_url.clone())),
+        ..Default::default()
+    };
+    // The headers and credentials a one-off request would send
+    let mut secrets = SecretResolver::with_defaults();
+    for (name, value) in &build_headers(args, &mut secrets)? {
+        let value = value
+            .to_str()
+            .map_err(|_| format!("The {} header isn't text.", name))?
+            .to_string();
+        if name == AUTHORIZATION {
+            session.authorization = Some(value);
+        } else {
+            session.headers.push((name.to_string(), value));
+        }
+    }
+    if session.authorization.is_none()
+        && let Some(user) = &args.user
+    {
+        let user = secrets.resolve(user)?;
+        let basic = auth::Credentials::parse(&user)?.basic();
+        session.authorization = basic.to_str().ok().map(str::to_string);
+    }
+    mask_secrets(&secrets);
+    Ok(session)
+}
+
+// ---------------- URL ERROR HANDLING ----------------
+
+fn handle_url_error(err: url::ParseError) {
+    let msg = err.to_string();
+
+    let message = if msg.contains("relative URL") {
+        "The URL does not have a valid base protocol.".to_string()
+    } else if msg.contains("invalid port number") {
+        "The URL contains an invalid port number.".to_string()
+    } else if msg.contains("invalid IPv4 address") {
+        "The URL contains an invalid IPv4 address.".to_string()
+    } else if msg.contains("invalid IPv6 address") {
+        "The URL contains an invalid IPv6 address.".to_string()
+    } else {
+        msg
+    };
+    output::error(exit::Error::new(Category::Url, message));
+}
+
+// ---------------- BODY TEMPLATES ----------------
+
+const TEMPLATE_EXTENSIONS: [&str; 3] = ["tera", "tmpl", "j2"];
+
+// -d @file and --json @file read the body from a file, @- from stdin.
+// Like curl, -d drops the line breaks of what it reads. Returns the path
+// of a --json file, which may be a template.
+fn load_body_file(args: &mut Cli) -> Result<Option<PathBuf>, String> {
+    if args.body.data.as_deref() == Some("@-") && args.body.json.as_deref() == Some("@-") {
+        return Err("Only one of -d and --json can read stdin.".to_string());
+    }
+    if let Some(source) = args.body.data.as_deref().and_then(|d| d.strip_prefix('@')) {
+        let text = read_body_text(source)?;
+        args.body.data = Some(text.replace(['\r', '\n'], ""));
+    }
+    let Some(source) = args.body.json.as_deref().and_then(|j| j.strip_prefix('@')) else {
+        return Ok(None);
+    };
+    let path = (source != "-").then(|| PathBuf::from(source));
+    args.body.json = Some(read_body_text(source)?);
+    Ok(path)
+}
+
+// A file name, or '-' for stdin.
+fn read_body_text(source: &str) -> Result<String, String> {
+    if source == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Unable to read stdin: {}", e))?;
+        return Ok(text);
+    }
+    fs::read_to_string(source).map_err(|e| format!("Unable to read '{}': {}", source, e))
+}
+
+// Bodies are templates when they come from a template file or any --var
+// is given.
+fn render_body_templates(args: &mut Cli, body_file: Option<&Path>) -> Result<(), String> {
+    let template_file = body_file.is_some_and(|p| {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| TEMPLATE_EXTENSIONS.contains(&e))
+    });
+    if args.body.vars.is_empty() && !template_file {
+        return Ok(());
+    }
+    let vars = template::parse_vars(&args.body.vars)?;
+    if let Some(json) = &args.body.json {
+        let name = body_file.map_or("--json".to_string(), |p| p.display().to_string());
+        args.body.json = Some(template::render(&name, json, &vars)?);
+    }
+    if let Some(data) = &args.body.data {
+        args.body.data = Some(template::render("-d", data, &vars)?);
+    }
+    Ok(())
+}
+
+// ---------------- SECRETS ----------------
+
+fn resolve_secrets(args: &mut Cli, secrets: &mut SecretResolver) -> Result<(), String> {
+    if let Some(url) = &args.url {
+        args.url = Some(secrets.resolve(url)?);
+    }
+    if let Some(data) = &args.body.data {
+        args.body.data = Some(secrets.resolve(data)?);
+    }
+    if let Some(json) = &args.body.json {
+        args.body.json = Some(secrets.resolve(json)?);
+    }
+    Ok(())
+}
+
+// The resolved values are only sent; status lines, errors and the verbose
+// trace show their {{provider:path#field}} placeholders instead.
+fn mask_secrets(secrets: &SecretResolver) {
+    for (placeholder, value) in secrets.resolved() {
+        output::mask(value, &placeholder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn cli(argv: &[&str]) -> Cli {
+        Cli::from_iter(["curl"].iter().chain(argv))
+    }
+
+    #[test]
+    fn the_session_starts_with_the_headers_a_request_would_send() {
+        let token = std::env::temp_dir().join(format!("repl-token-{}", std::process::id()));
+        fs::write(&token, "from-file\n").unwrap();
+        let args = cli(&[
+            "-H",
+            "X-Team: core",
+            "--bearer-file",
+            token.to_str().unwrap(),
+            "--interactive",
+            "http://h/",
+        ]);
+        let session = session(&args, None).unwrap();
+        fs::remove_file(&token).unwrap();
+        assert_eq!(session.base_url.as_deref(), Some("http://h/"));
+        let team = ("x-team".to_string(), "core".to_string());
+        assert!(session.headers.contains(&team));
+        assert_eq!(session.authorization.as_deref(), Some("Bearer from-file"));
+    }
+
+    #[test]
+    fn the_session_rejects_headers_a_request_would_reject() {
+        let args = cli(&["-H", "Bad Name: x", "--interactive"]);
+        assert!(session(&args, None).is_err());
+    }
+}
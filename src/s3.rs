@@ -0,0 +1,478 @@
+// S3 helpers built on the SigV4 primitives.
+
+use crate::duration::parse_duration;
+use crate::sigv4::{self, Credentials};
+use reqwest::blocking::{Client, Response};
+use reqwest::Method;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+use url::Url;
+
+// SigV4 caps presigned URLs at seven days.
+const MAX_PRESIGN_EXPIRY: Duration = Duration::from_secs(7 * 24 * 3600);
+
+// S3 rejects non-final parts smaller than 5 MiB.
+const MIN_PART_SIZE_MIB: u64 = 5;
+
+// S3's limits on a multipart upload's part count and part size.
+const MAX_PARTS: u64 = 10_000;
+const MAX_PART_SIZE: u64 = 5 * 1024 * MIB;
+
+const MIB: u64 = 1024 * 1024;
+
+#[derive(StructOpt, Clone, Debug)]
+pub enum S3Command {
+    /// Generate a presigned URL for an object
+    Presign {
+        /// Object location, e.g. s3://bucket/key
+        target: String,
+
+        /// How long the URL stays valid (e.g. 15m, 1h, 7d)
+        #[structopt(long, default_value = "1h", parse(try_from_str = parse_duration))]
+        expires: Duration,
+
+        /// HTTP method the URL is valid for (GET or PUT)
+        #[structopt(short = "X", long, default_value = "GET")]
+        method: String,
+
+        #[structopt(long)]
+        region: Option<String>,
+
+        /// S3-compatible endpoint; uses path-style addressing
+        #[structopt(long)]
+        endpoint: Option<String>,
+    },
+
+    /// Upload a file using a multipart upload
+    Upload {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        /// Destination, e.g. s3://bucket/key
+        target: String,
+
+        /// S3-compatible endpoint; uses path-style addressing
+        #[structopt(long)]
+        endpoint: Option<String>,
+
+        #[structopt(long)]
+        region: Option<String>,
+
+        /// Part size in MiB (minimum 5)
+        #[structopt(long, default_value = "8")]
+        part_size: u64,
+
+        /// Number of parts uploaded concurrently
+        #[structopt(long, default_value = "4")]
+        parallel: usize,
+
+        /// Attempts per part before giving up
+        #[structopt(long, default_value = "3")]
+        retries: u32,
+    },
+}
+
+pub fn run(cmd: S3Command) -> Result<(), String> {
+    match cmd {
+        S3Command::Presign {
+            target,
+            expires,
+            method,
+            region,
+            endpoint,
+        } => {
+            let method = method.to_ascii_uppercase();
+            if method != "GET" && method != "PUT" {
+                return Err(format!("Presigned URLs support GET or PUT, not {}.", method));
+            }
+            if expires > MAX_PRESIGN_EXPIRY {
+                return Err("Presigned URLs cannot be valid for more than 7 days.".into());
+            }
+
+            let creds = Credentials::load()?;
+            let region = region.unwrap_or_else(sigv4::default_region);
+            let (bucket, key) = parse_s3_target(&target)?;
+            let url = object_url(&bucket, &key, &region, endpoint.as_deref())?;
+
+            let signed = sigv4::presign_url(
+                &method,
+                &url,
+                &creds,
+                &region,
+                "s3",
+                expires,
+                SystemTime::now(),
+            );
+            println!("{}", signed);
+            Ok(())
+        }
+        S3Command::Upload {
+            file,
+            target,
+            endpoint,
+            region,
+            part_size,
+            parallel,
+            retries,
+        } => {
+            let part_size = part_size_bytes(part_size)?;
+            let region = region.unwrap_or_else(sigv4::default_region);
+            let (bucket, key) = parse_s3_target(&target)?;
+            let uploader = Uploader {
+                client: Client::builder()
+                    .timeout(None)
+                    .build()
+                    .map_err(|e| e.to_string())?,
+                creds: Credentials::load()?,
+                region: region.clone(),
+                url: object_url(&bucket, &key, &region, endpoint.as_deref())?,
+                retries: retries.max(1),
+            };
+            uploader.upload(&file, part_size, parallel.max(1))
+        }
+    }
+}
+
+// ---------------- MULTIPART UPLOAD ----------------
+
+struct Uploader {
+    client: Client,
+    creds: Credentials,
+    region: String,
+    url: Url,
+    retries: u32,
+}
+
+impl Uploader {
+    fn upload(&self, path: &Path, part_size: u64, parallel: usize) -> Result<(), String> {
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?
+            .len();
+        let configured = part_size;
+        let part_size = fitted_part_size(size, configured)?;
+        if part_size > configured {
+            println!(
+                "Using {} MiB parts to stay within S3's {} parts per upload.",
+                part_size / MIB,
+                MAX_PARTS
+            );
+        }
+        let part_count = size.div_ceil(part_size).max(1);
+
+        let upload_id = self.initiate()?;
+        println!("Initiated multipart upload {} ({} parts).", upload_id, part_count);
+
+        match self.upload_parts(path, size, part_size, part_count, parallel, &upload_id) {
+            Ok(etags) => {
+                self.complete(&upload_id, &etags)?;
+                println!("Uploaded {} bytes to {}.", size, self.url);
+                Ok(())
+            }
+            Err(e) => {
+                // Don't leave orphaned parts behind accruing storage charges
+                let _ = self.send(Method::DELETE, &upload_query(&upload_id), Vec::new());
+                Err(e)
+            }
+        }
+    }
+
+    fn initiate(&self) -> Result<String, String> {
+        let res = self.send(Method::POST, "uploads", Vec::new())?;
+        let body = checked_body(res)?;
+        xml_tag(&body, "UploadId").ok_or_else(|| "S3 did not return an UploadId.".to_string())
+    }
+
+    fn upload_parts(
+        &self,
+        path: &Path,
+        size: u64,
+        part_size: u64,
+        part_count: u64,
+        parallel: usize,
+        upload_id: &str,
+    ) -> Result<Vec<String>, String> {
+        let next_part = Mutex::new(1u64);
+        let etags = Mutex::new(vec![String::new(); part_count as usize]);
+        let failure: Mutex<Option<String>> = Mutex::new(None);
+
+        thread::scope(|scope| {
+            for _ in 0..parallel.min(part_count as usize) {
+                scope.spawn(|| {
+                    loop {
+                        if failure.lock().unwrap().is_some() {
+                            return;
+                        }
+                        let number = {
+                            let mut next = next_part.lock().unwrap();
+                            if *next > part_count {
+                                return;
+                            }
+                            *next += 1;
+                            *next - 1
+                        };
+
+                        let offset = (number - 1) * part_size;
+                        let len = part_size.min(size - offset);
+                        match self.upload_part(path, upload_id, number, offset, len) {
+                            Ok(etag) => {
+                                etags.lock().unwrap()[number as usize - 1] = etag;
+                                println!("Uploaded part {}/{}.", number, part_count);
+                            }
+                            Err(e) => {
+                                *failure.lock().unwrap() = Some(e);
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        match failure.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(etags.into_inner().unwrap()),
+        }
+    }
+
+    fn upload_part(
+        &self,
+        path: &Path,
+        upload_id: &str,
+        number: u64,
+        offset: u64,
+        len: u64,
+    ) -> Result<String, String> {
+        let mut data = vec![0u8; len as usize];
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|e| format!("Unable to read part {}: {}", number, e))?;
+
+        let query = format!("partNumber={}&{}", number, upload_query(upload_id));
+        let mut last_error = String::new();
+        for attempt in 1..=self.retries {
+            match self.send(Method::PUT, &query, data.clone()).and_then(checked) {
+                Ok(res) => {
+                    return res
+                        .headers()
+                        .get("etag")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string)
+                        .ok_or_else(|| format!("S3 returned no ETag for part {}.", number));
+                }
+                Err(e) => {
+                    last_error = e;
+                    if attempt < self.retries {
+                        thread::sleep(Duration::from_millis(500 * 2u64.pow(attempt - 1)));
+                    }
+                }
+            }
+        }
+        Err(format!(
+            "Part {} failed after {} attempts: {}",
+            number, self.retries, last_error
+        ))
+    }
+
+    fn complete(&self, upload_id: &str, etags: &[String]) -> Result<(), String> {
+        let mut xml = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in etags.iter().enumerate() {
+            xml.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                i + 1,
+                etag
+            ));
+        }
+        xml.push_str("</CompleteMultipartUpload>");
+
+        let res = self.send(Method::POST, &upload_query(upload_id), xml.into_bytes())?;
+        let body = checked_body(res)?;
+        // CompleteMultipartUpload can fail with a 200 and an <Error> body
+        if body.contains("<Error>") {
+            return Err(s3_error(&body));
+        }
+        Ok(())
+    }
+
+    fn send(&self, method: Method, query: &str, body: Vec<u8>) -> Result<Response, String> {
+        let mut url = self.url.clone();
+        url.set_query(Some(query));
+
+        let headers = sigv4::authorization_headers(
+            method.as_str(),
+            &url,
+            &sigv4::sha256_hex(&body),
+            &self.creds,
+            &self.region,
+            "s3",
+            SystemTime::now(),
+        );
+        let mut req = self.client.request(method, url).body(body);
+        for (name, value) in headers {
+            req = req.header(name, value);
+        }
+        req.send()
+            .map_err(|_| "Unable to connect to the server.".to_string())
+    }
+}
+
+// --part-size in bytes, within the sizes S3 accepts for a part.
+fn part_size_bytes(mib: u64) -> Result<u64, String> {
+    if mib < MIN_PART_SIZE_MIB {
+        return Err(format!("--part-size must be at least {} MiB.", MIN_PART_SIZE_MIB));
+    }
+    mib.checked_mul(MIB)
+        .filter(|&bytes| bytes <= MAX_PART_SIZE)
+        .ok_or_else(|| format!("--part-size must be at most {} MiB.", MAX_PART_SIZE / MIB))
+}
+
+// The configured part size, raised to a whole number of MiB when the file
+// would otherwise need more parts than S3 allows.
+fn fitted_part_size(size: u64, configured: u64) -> Result<u64, String> {
+    let needed = size.div_ceil(MAX_PARTS).div_ceil(MIB) * MIB;
+    let part_size = configured.max(needed);
+    if part_size > MAX_PART_SIZE {
+        return Err(format!(
+            "The file is too large for a multipart upload: {} parts of at most 5 GiB.",
+            MAX_PARTS
+        ));
+    }
+    Ok(part_size)
+}
+
+fn upload_query(upload_id: &str) -> String {
+    format!("uploadId={}", sigv4::uri_encode(upload_id))
+}
+
+fn checked(res: Response) -> Result<Response, String> {
+    if res.status().is_success() {
+        Ok(res)
+    } else {
+        let status = res.status().as_u16();
+        let body = res.text().unwrap_or_default();
+        Err(format!("{} (status {})", s3_error(&body), status))
+    }
+}
+
+fn checked_body(res: Response) -> Result<String, String> {
+    checked(res)?.text().map_err(|e| e.to_string())
+}
+
+fn s3_error(body: &str) -> String {
+    match (xml_tag(body, "Code"), xml_tag(body, "Message")) {
+        (Some(code), Some(message)) => format!("S3 error {}: {}", code, message),
+        (Some(code), None) => format!("S3 error {}", code),
+        _ => "S3 request failed".to_string(),
+    }
+}
+
+fn xml_tag(body: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = body.find(&open)? + open.len();
+    let end = body[start..].find(&close)? + start;
+    Some(body[start..end].to_string())
+}
+
+pub fn parse_s3_target(target: &str) -> Result<(String, String), String> {
+    let rest = target
+        .strip_prefix("s3://")
+        .ok_or_else(|| format!("Expected an s3://bucket/key location, got '{}'.", target))?;
+    match rest.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => {
+            Ok((bucket.to_string(), key.to_string()))
+        }
+        _ => Err(format!("'{}' must include both a bucket and a key.", target)),
+    }
+}
+
+// Virtual-hosted style on AWS, path style for custom endpoints (MinIO etc.).
+// The key's segments are percent-encoded as path segments, so a '?', '#'
+// or '%' in it stays part of the key.
+pub fn object_url(
+    bucket: &str,
+    key: &str,
+    region: &str,
+    endpoint: Option<&str>,
+) -> Result<Url, String> {
+    let (base, path_bucket) = match endpoint {
+        Some(ep) => (ep.to_string(), Some(bucket)),
+        None => (
+            format!("https://{}.s3.{}.amazonaws.com", bucket, region),
+            None,
+        ),
+    };
+    let invalid = || format!("Invalid S3 endpoint '{}'.", base);
+    let mut url = Url::parse(&base).map_err(|_| invalid())?;
+    url.path_segments_mut()
+        .map_err(|_| invalid())?
+        .pop_if_empty()
+        .extend(path_bucket)
+        .extend(key.split('/'));
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_url_keeps_special_characters_in_the_key() {
+        let url = object_url("bucket", "dir/a?b#c%d e.txt", "us-east-1", None).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://bucket.s3.us-east-1.amazonaws.com/dir/a%3Fb%23c%25d%20e.txt"
+        );
+        assert_eq!(url.query(), None);
+        assert_eq!(url.fragment(), None);
+    }
+
+    #[test]
+    fn object_url_uses_path_style_for_endpoints() {
+        let url = object_url("bucket", "a/b?.txt", "", Some("http://minio:9000/")).unwrap();
+        assert_eq!(url.as_str(), "http://minio:9000/bucket/a/b%3F.txt");
+        let url = object_url("bucket", "k", "", Some("http://host/prefix")).unwrap();
+        assert_eq!(url.as_str(), "http://host/prefix/bucket/k");
+    }
+
+    #[test]
+    fn signed_path_matches_the_key() {
+        let url = object_url("bucket", "a+b/c%d", "us-east-1", None).unwrap();
+        assert_eq!(sigv4::canonical_uri(&url), "/a%2Bb/c%25d");
+    }
+
+    #[test]
+    fn part_size_option_is_bounded() {
+        assert_eq!(part_size_bytes(8), Ok(8 * MIB));
+        assert_eq!(part_size_bytes(5120), Ok(MAX_PART_SIZE));
+        assert!(part_size_bytes(4).is_err());
+        let too_large = Err("--part-size must be at most 5120 MiB.".to_string());
+        assert_eq!(part_size_bytes(5121), too_large);
+        assert_eq!(part_size_bytes(u64::MAX), too_large);
+    }
+
+    #[test]
+    fn part_size_is_kept_when_the_parts_fit() {
+        assert_eq!(fitted_part_size(100 * MIB, 8 * MIB), Ok(8 * MIB));
+        assert_eq!(fitted_part_size(0, 8 * MIB), Ok(8 * MIB));
+    }
+
+    #[test]
+    fn part_size_grows_to_stay_within_the_part_limit() {
+        // 100 GiB in 8 MiB parts would be 12,800 parts
+        let size = 100 * 1024 * MIB;
+        let part_size = fitted_part_size(size, 8 * MIB).unwrap();
+        assert_eq!(part_size, 11 * MIB);
+        assert!(size.div_ceil(part_size) <= MAX_PARTS);
+    }
+
+    #[test]
+    fn files_beyond_the_upload_limit_are_rejected() {
+        assert!(fitted_part_size(MAX_PARTS * MAX_PART_SIZE + 1, 8 * MIB).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+// Pluggable URL scheme handlers.
+//
+// http and https are served by the built-in HTTP path. Every other scheme
+// is looked up here: a handler either performs the transfer itself or
+// rewrites the URL into one another handler (or HTTP) can serve, e.g.
+// `consul://web/health` -> `http://10.0.0.5:8080/health`.
+//
+// Each WebClient holds a registry, set with ClientOptions::schemes, and
+// the command line dispatches through its client's; a handler that makes
+// HTTP requests of its own sends them through that client, so they get
+// its TLS, proxy, timeout and --resolve settings.
+
+use crate::WebClient;
+use crate::ftp;
+use crate::method::Method;
+use crate::transfer::{self, TransferLimits};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+// Guards against handlers that rewrite into each other forever.
+const MAX_REWRITES: usize = 10;
+
+pub struct TransferOptions<'a> {
+    pub upload: Option<&'a Path>,
+    pub ftp_ssl: bool,
+    pub limits: TransferLimits,
+}
+
+pub enum Outcome {
+    // The handler fetched a body, formatted like any HTTP response body
+    Body(Vec<u8>),
+    // The handler finished (e.g. an upload) and has a status line to report
+    Done(String),
+    // Continue with a different URL
+    Rewrite(Url),
+}
+
+pub trait SchemeHandler: Send + Sync {
+    fn handle(
+        &self,
+        url: &Url,
+        opts: &TransferOptions,
+        client: &WebClient,
+    ) -> Result<Outcome, String>;
+}
+
+#[derive(Default)]
+pub struct SchemeRegistry {
+    handlers: BTreeMap<String, Box<dyn SchemeHandler>>,
+}
+
+impl SchemeRegistry {
+    pub fn new() -> Self {
+        SchemeRegistry {
+            handlers: BTreeMap::new(),
+        }
+    }
+
+    // Registry with every handler compiled into this build.
+    pub fn with_defaults() -> Self {
+        let mut registry = SchemeRegistry::new();
+        registry.register("file", Box::new(FileHandler));
+        registry.register("ftp", Box::new(FtpHandler));
+        registry.register("ftps", Box::new(FtpHandler));
+        #[cfg(feature = "consul")]
+        registry.register("consul", Box::new(ConsulHandler));
+        registry
+    }
+
+    pub fn register(&mut self, scheme: &str, handler: Box<dyn SchemeHandler>) {
+        self.handlers.insert(scheme.to_ascii_lowercase(), handler);
+    }
+
+    pub fn is_http(scheme: &str) -> bool {
+        scheme == "http" || scheme == "https"
+    }
+
+    pub fn supports(&self, scheme: &str) -> bool {
+        Self::is_http(scheme) || self.handlers.contains_key(scheme)
+    }
+
+    // Run handlers until the URL is either served or becomes plain HTTP.
+    // Returns the final HTTP URL, or the handler's outcome.
+    pub fn dispatch(
+        &self,
+        url: &Url,
+        opts: &TransferOptions,
+        client: &WebClient,
+    ) -> Result<Outcome, String> {
+        let mut current = url.clone();
+        for _ in 0..MAX_REWRITES {
+            if Self::is_http(current.scheme()) {
+                return Ok(Outcome::Rewrite(current));
+            }
+            let handler = self
+                .handlers
+                .get(current.scheme())
+                .ok_or_else(|| format!("No handler registered for '{}://'.", current.scheme()))?;
+            match handler.handle(&current, opts, client)? {
+                Outcome::Rewrite(next) => current = next,
+                done => return Ok(done),
+            }
+        }
+        Err(format!("Too many scheme rewrites starting from {}.", url))
+    }
+}
+
+// ---------------- BUILT-IN HANDLERS ----------------
+
+struct FileHandler;
+
+impl SchemeHandler for FileHandler {
+    fn handle(&self, url: &Url, opts: &TransferOptions, _: &WebClient) -> Result<Outcome, String> {
+        let path = url
+            .to_file_path()
+            .map_err(|_| "The file URL does not point to a local path.".to_string())?;
+
+        if let Some(source) = opts.upload {
+            let bytes = fs::copy(source, &path)
+                .map_err(|e| format!("Unable to write '{}': {}", path.display(), e))?;
+            return Ok(Outcome::Done(format!(
+                "Wrote {} bytes to {}.",
+                bytes,
+                path.display()
+            )));
+        }
+
+        if path.is_dir() {
+            let entries = fs::read_dir(&path)
+                .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))?;
+            let mut names: Vec<String> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            return Ok(Outcome::Body(names.join("\n").into_bytes()));
+        }
+
+        fs::read(&path)
+            .map(Outcome::Body)
+            .map_err(|e| format!("Unable to read '{}': {}", path.display(), e))
+    }
+}
+
+struct FtpHandler;
+
+impl SchemeHandler for FtpHandler {
+    fn handle(&self, url: &Url, opts: &TransferOptions, _: &WebClient) -> Result<Outcome, String> {
+        match opts.upload {
+            Some(path) => ftp::upload(url, path, opts.ftp_ssl, &opts.limits)
+                .map(|bytes| Outcome::Done(format!("Uploaded {} bytes.", bytes))),
+            None => ftp::fetch(url, opts.ftp_ssl, &opts.limits).map(Outcome::Body),
+        }
+    }
+}
+
+// consul://service/path -> http://<healthy instance>/path, using the agent at
+// CONSUL_HTTP_ADDR (default http://127.0.0.1:8500).
+#[cfg(feature = "consul")]
+struct ConsulHandler;
+
+#[cfg(feature = "consul")]
+impl SchemeHandler for ConsulHandler {
+    fn handle(
+        &self,
+        url: &Url,
+        _opts: &TransferOptions,
+        client: &WebClient,
+    ) -> Result<Outcome, String> {
+        let service = url.host_str().ok_or("The consul URL has no service name.")?;
+        let agent = std::env::var("CONSUL_HTTP_ADDR")
+            .unwrap_or_else(|_| "http://127.0.0.1:8500".to_string());
+        let agent = if agent.contains("://") {
+            agent
+        } else {
+            format!("http://{}", agent)
+        };
+
+        let lookup = format!(
+            "{}/v1/health/service/{}?passing=true",
+            agent.trim_end_matches('/'),
+            service
+        );
+        let failed = |e: String| format!("Consul lookup for '{}' failed: {}", service, e);
+        let lookup = Url::parse(&lookup).map_err(|e| failed(e.to_string()))?;
+        let res = client
+            .execute(client.request(Method::Get, lookup))
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| failed(e.to_string()))?;
+        let (body, _) = transfer::read_decoded(res, client.limits()).map_err(failed)?;
+        let instances: serde_json::Value =
+            serde_json::from_slice(&body).map_err(|e| failed(e.to_string()))?;
+
+        let instance = instances
+            .get(0)
+            .ok_or_else(|| format!("Consul has no healthy instances of '{}'.", service))?;
+        let svc = &instance["Service"];
+        let address = svc["Address"]
+            .as_str()
+            .filter(|a| !a.is_empty())
+            .or_else(|| instance["Node"]["Address"].as_str())
+            .ok_or_else(|| format!("Consul returned no address for '{}'.", service))?;
+        let port = svc["Port"].as_u64().unwrap_or(80);
+
+        let mut target = format!("http://{}:{}{}", address, port, url.path());
+        if let Some(query) = url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        Url::parse(&target)
+            .map(Outcome::Rewrite)
+            .map_err(|e| format!("Invalid address from Consul: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, ClientOptions, RequestSpec};
+    use std::sync::Arc;
+
+    struct Greeting;
+
+    impl SchemeHandler for Greeting {
+        fn handle(&self, url: &Url, _: &TransferOptions, _: &WebClient) -> Result<Outcome, String> {
+            let name = url.host_str().unwrap_or_default();
+            Ok(Outcome::Body(format!("hello {}", name).into_bytes()))
+        }
+    }
+
+    // alias://NAME -> greet://NAME
+    struct Alias;
+
+    impl SchemeHandler for Alias {
+        fn handle(&self, url: &Url, _: &TransferOptions, _: &WebClient) -> Result<Outcome, String> {
+            let target = format!("greet://{}", url.host_str().unwrap_or_default());
+            Url::parse(&target)
+                .map(Outcome::Rewrite)
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    fn greeting_client() -> WebClient {
+        let mut registry = SchemeRegistry::new();
+        registry.register("greet", Box::new(Greeting));
+        registry.register("alias", Box::new(Alias));
+        WebClient::new(ClientOptions {
+            schemes: Some(Arc::new(registry)),
+            ..ClientOptions::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn clients_dispatch_through_their_own_registry() {
+        let client = greeting_client();
+        assert!(client.schemes().supports("greet"));
+        assert!(!client.schemes().supports("file"));
+
+        let opts = TransferOptions {
+            upload: None,
+            ftp_ssl: false,
+            limits: TransferLimits::default(),
+        };
+        let url = Url::parse("alias://world").unwrap();
+        match client.dispatch(&url, &opts) {
+            Ok(Outcome::Body(body)) => assert_eq!(body, b"hello world"),
+            _ => panic!("alias://world wasn't served"),
+        }
+        let url = Url::parse("https://example.com/").unwrap();
+        assert!(matches!(client.dispatch(&url, &opts), Ok(Outcome::Rewrite(u)) if u == url));
+
+        let defaults = WebClient::new(ClientOptions::default()).unwrap();
+        assert!(defaults.schemes().supports("file"));
+        assert!(!defaults.schemes().supports("greet"));
+    }
+
+    #[test]
+    fn send_serves_other_schemes_through_the_registry() {
+        let client = greeting_client();
+        let report = client
+            .send(&RequestSpec::new(Method::Get, "alias://world"))
+            .unwrap();
+        assert_eq!(
+            (report.status, report.text()),
+            (0, "hello world".to_string())
+        );
+        assert!(report.is_success());
+
+        let spec = RequestSpec::new(Method::Post, "greet://world").body(Body::Bytes(b"x".to_vec()));
+        assert_eq!(
+            client.send(&spec).err().map(|e| e.message),
+            Some("greet:// URLs don't take a body.".to_string())
+        );
+        let spec = RequestSpec::new(Method::Get, "gopher://host/");
+        assert_eq!(
+            client.send(&spec).err().map(|e| e.message),
+            Some("No handler registered for 'gopher://'.".to_string())
+        );
+    }
+}
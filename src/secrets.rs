@@ -0,0 +1,146 @@
+// Runtime secret resolution for `{{provider:path#field}}` placeholders.
+//
+// Placeholders are only expanded when `provider` names a registered
+// provider, so unrelated `{{...}}` text in a body is left untouched.
+
+use reqwest::blocking::Client;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+pub trait SecretProvider {
+    fn fetch(&self, path: &str, field: &str) -> Result<String, String>;
+}
+
+#[derive(Default)]
+pub struct SecretResolver {
+    providers: HashMap<String, Box<dyn SecretProvider>>,
+    cache: HashMap<String, String>,
+}
+
+impl SecretResolver {
+    pub fn new() -> Self {
+        SecretResolver {
+            providers: HashMap::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    // Resolver with the built-in providers. Additional stores (e.g. AWS
+    // Secrets Manager) plug in through `register`.
+    pub fn with_defaults() -> Self {
+        let mut resolver = SecretResolver::new();
+        resolver.register("vault", Box::new(VaultProvider));
+        resolver
+    }
+
+    pub fn register(&mut self, name: &str, provider: Box<dyn SecretProvider>) {
+        self.providers.insert(name.to_string(), provider);
+    }
+
+    pub fn resolve(&mut self, input: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                out.push_str(&rest[start..]);
+                return Ok(out);
+            };
+
+            let inner = &after[..end];
+            match inner.split_once(':') {
+                Some((name, reference)) if self.providers.contains_key(name) => {
+                    out.push_str(&self.lookup(name, reference)?);
+                }
+                _ => out.push_str(&rest[start..start + 2 + end + 2]),
+            }
+            rest = &after[end + 2..];
+        }
+
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    // Each placeholder resolved so far with its value, so printed lines
+    // can show the placeholder instead of the secret.
+    pub fn resolved(&self) -> impl Iterator<Item = (String, &str)> {
+        self.cache
+            .iter()
+            .map(|(key, value)| (format!("{{{{{}}}}}", key), value.as_str()))
+    }
+
+    fn lookup(&mut self, name: &str, reference: &str) -> Result<String, String> {
+        let key = format!("{}:{}", name, reference);
+        if let Some(value) = self.cache.get(&key) {
+            return Ok(value.clone());
+        }
+
+        let (path, field) = reference.rsplit_once('#').ok_or_else(|| {
+            format!("Secret reference '{}' is missing a '#field' suffix.", key)
+        })?;
+        let value = self.providers[name].fetch(path, field)?;
+        self.cache.insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+// ---------------- HASHICORP VAULT ----------------
+
+// Reads from Vault's HTTP API using VAULT_ADDR and VAULT_TOKEN (or
+// ~/.vault-token). Both KV v2 (`data.data`) and KV v1 (`data`) layouts work.
+pub struct VaultProvider;
+
+impl SecretProvider for VaultProvider {
+    fn fetch(&self, path: &str, field: &str) -> Result<String, String> {
+        let addr = env::var("VAULT_ADDR")
+            .map_err(|_| "VAULT_ADDR must be set to resolve vault secrets.".to_string())?;
+        let token = vault_token()?;
+
+        let url = format!(
+            "{}/v1/{}",
+            addr.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        );
+        let mut req = Client::new().get(&url).header("X-Vault-Token", token);
+        if let Ok(namespace) = env::var("VAULT_NAMESPACE") {
+            req = req.header("X-Vault-Namespace", namespace);
+        }
+
+        let res = req
+            .send()
+            .map_err(|e| format!("Unable to reach Vault at {}: {}", addr, e))?;
+        if !res.status().is_success() {
+            return Err(format!(
+                "Vault returned status {} for '{}'.",
+                res.status().as_u16(),
+                path
+            ));
+        }
+
+        let body: Value = res
+            .json()
+            .map_err(|e| format!("Invalid response from Vault: {}", e))?;
+        let data = &body["data"];
+        let secret = data["data"].get(field).or_else(|| data.get(field));
+
+        match secret {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(other) => Ok(other.to_string()),
+            None => Err(format!("Field '{}' not found in vault secret '{}'.", field, path)),
+        }
+    }
+}
+
+fn vault_token() -> Result<String, String> {
+    if let Ok(token) = env::var("VAULT_TOKEN") {
+        return Ok(token);
+    }
+    let home = env::var("HOME").unwrap_or_default();
+    fs::read_to_string(format!("{}/.vault-token", home))
+        .map(|t| t.trim().to_string())
+        .map_err(|_| "VAULT_TOKEN must be set to resolve vault secrets.".to_string())
+}
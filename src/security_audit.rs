@@ -0,0 +1,342 @@
+// --security-audit: grade a response's security headers.
+//
+// Each header contributes up to a fixed number of points (100 in total):
+//
+//   Content-Security-Policy    25
+//   Strict-Transport-Security  25  (only achievable over HTTPS)
+//   X-Content-Type-Options     15
+//   X-Frame-Options            15  (or CSP frame-ancestors)
+//   Referrer-Policy            10
+//   Permissions-Policy         10
+
+use reqwest::header::{
+    CONTENT_SECURITY_POLICY, HeaderMap, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use serde::Serialize;
+use url::Url;
+
+// Half a year, the minimum HSTS preload lists accept for full credit.
+const HSTS_MIN_MAX_AGE: u64 = 15_552_000;
+
+const SAFE_REFERRER_POLICIES: [&str; 5] = [
+    "no-referrer",
+    "same-origin",
+    "strict-origin",
+    "strict-origin-when-cross-origin",
+    "origin",
+];
+
+const UNSAFE_REFERRER_POLICIES: [&str; 3] = [
+    "unsafe-url",
+    "no-referrer-when-downgrade",
+    "origin-when-cross-origin",
+];
+
+#[derive(Serialize)]
+pub struct Report {
+    pub score: u32,
+    grade: char,
+    checks: Vec<Check>,
+}
+
+#[derive(Serialize)]
+struct Check {
+    header: &'static str,
+    points: u32,
+    max: u32,
+    value: Option<String>,
+    notes: Vec<String>,
+}
+
+pub fn audit(url: &Url, headers: &HeaderMap) -> Report {
+    let get = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+    };
+    let csp = get(CONTENT_SECURITY_POLICY);
+
+    let checks = vec![
+        check_csp(csp.clone()),
+        check_hsts(url, get(STRICT_TRANSPORT_SECURITY)),
+        check_nosniff(get(X_CONTENT_TYPE_OPTIONS)),
+        check_framing(get(X_FRAME_OPTIONS), csp.as_deref()),
+        check_referrer(get(REFERRER_POLICY)),
+        check_permissions(
+            headers
+                .get("permissions-policy")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        ),
+    ];
+
+    let score = checks.iter().map(|c| c.points).sum();
+    Report {
+        score,
+        grade: grade(score),
+        checks,
+    }
+}
+
+pub fn render_text(report: &Report) -> String {
+    let mut lines = vec![format!(
+        "Security audit: {}/100 (grade {})",
+        report.score, report.grade
+    )];
+    for c in &report.checks {
+        lines.push(format!("  {:>2}/{:<2}  {}", c.points, c.max, c.header));
+        for note in &c.notes {
+            lines.push(format!("         {}", note));
+        }
+    }
+    lines.join("\n")
+}
+
+pub fn render_json(report: &Report) -> String {
+    serde_json::to_string_pretty(report).unwrap()
+}
+
+fn grade(score: u32) -> char {
+    match score {
+        90.. => 'A',
+        75..=89 => 'B',
+        60..=74 => 'C',
+        40..=59 => 'D',
+        _ => 'F',
+    }
+}
+
+fn missing(header: &'static str, max: u32, note: &str) -> Check {
+    Check {
+        header,
+        points: 0,
+        max,
+        value: None,
+        notes: vec![note.to_string()],
+    }
+}
+
+// Directive name -> source list.
+fn directives(csp: &str) -> Vec<(String, Vec<String>)> {
+    csp.split(';')
+        .filter_map(|d| {
+            let mut parts = d.split_whitespace();
+            let name = parts.next()?.to_ascii_lowercase();
+            Some((name, parts.map(|p| p.to_ascii_lowercase()).collect()))
+        })
+        .collect()
+}
+
+fn check_csp(value: Option<String>) -> Check {
+    const NAME: &str = "Content-Security-Policy";
+    let Some(value) = value else {
+        return missing(NAME, 25, "missing; no protection against injected scripts");
+    };
+
+    let dirs = directives(&value);
+    let sources = |name: &str| dirs.iter().find(|(n, _)| n == name).map(|(_, s)| s);
+    let scripts = sources("script-src").or_else(|| sources("default-src"));
+
+    let mut points = 15;
+    let mut notes = Vec::new();
+    match scripts {
+        None => notes.push("no script-src or default-src; scripts are unrestricted".to_string()),
+        Some(s) => {
+            if s.iter().any(|v| v == "'unsafe-inline'")
+                && !s
+                    .iter()
+                    .any(|v| v.starts_with("'nonce-") || v.starts_with("'sha"))
+            {
+                notes.push("allows 'unsafe-inline' scripts".to_string());
+            } else {
+                points += 5;
+            }
+            if s.iter().any(|v| {
+                v == "*" || v == "'unsafe-eval'" || v == "data:" || v == "http:" || v == "https:"
+            }) {
+                notes
+                    .push("script sources include a wildcard, scheme or 'unsafe-eval'".to_string());
+            } else {
+                points += 5;
+            }
+        }
+    }
+
+    Check {
+        header: NAME,
+        points,
+        max: 25,
+        value: Some(value),
+        notes,
+    }
+}
+
+fn check_hsts(url: &Url, value: Option<String>) -> Check {
+    const NAME: &str = "Strict-Transport-Security";
+    if url.scheme() != "https" {
+        return missing(
+            NAME,
+            25,
+            "served over plain HTTP; HSTS only applies to HTTPS",
+        );
+    }
+    let Some(value) = value else {
+        return missing(NAME, 25, "missing; first visits can be downgraded to HTTP");
+    };
+
+    let lower = value.to_ascii_lowercase();
+    let max_age = lower
+        .split(';')
+        .filter_map(|d| d.trim().strip_prefix("max-age="))
+        .find_map(|v| v.trim_matches('"').parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let mut notes = Vec::new();
+    let mut points = if max_age >= HSTS_MIN_MAX_AGE {
+        20
+    } else if max_age > 0 {
+        notes.push(format!("max-age={} is shorter than 180 days", max_age));
+        10
+    } else {
+        notes.push("max-age is missing or 0, which disables HSTS".to_string());
+        0
+    };
+    if lower.contains("includesubdomains") {
+        points += 5;
+    } else if max_age > 0 {
+        notes.push("no includeSubDomains".to_string());
+    }
+
+    Check {
+        header: NAME,
+        points,
+        max: 25,
+        value: Some(value),
+        notes,
+    }
+}
+
+fn check_nosniff(value: Option<String>) -> Check {
+    const NAME: &str = "X-Content-Type-Options";
+    match value {
+        Some(v) if v.eq_ignore_ascii_case("nosniff") => Check {
+            header: NAME,
+            points: 15,
+            max: 15,
+            value: Some(v),
+            notes: Vec::new(),
+        },
+        Some(v) => Check {
+            header: NAME,
+            points: 0,
+            max: 15,
+            notes: vec![format!("'{}' is not a valid value; use nosniff", v)],
+            value: Some(v),
+        },
+        None => missing(NAME, 15, "missing; browsers may MIME-sniff responses"),
+    }
+}
+
+fn check_framing(value: Option<String>, csp: Option<&str>) -> Check {
+    const NAME: &str = "X-Frame-Options";
+    if let Some(ancestors) = csp
+        .map(directives)
+        .and_then(|d| d.into_iter().find(|(n, _)| n == "frame-ancestors"))
+    {
+        return Check {
+            header: NAME,
+            points: 15,
+            max: 15,
+            value,
+            notes: vec![format!(
+                "covered by CSP frame-ancestors {}",
+                ancestors.1.join(" ")
+            )],
+        };
+    }
+
+    match value {
+        Some(v) if v.eq_ignore_ascii_case("deny") || v.eq_ignore_ascii_case("sameorigin") => {
+            Check {
+                header: NAME,
+                points: 15,
+                max: 15,
+                value: Some(v),
+                notes: Vec::new(),
+            }
+        }
+        Some(v) => Check {
+            header: NAME,
+            points: 0,
+            max: 15,
+            notes: vec![format!("'{}' is not supported; use DENY or SAMEORIGIN", v)],
+            value: Some(v),
+        },
+        None => missing(NAME, 15, "missing; the page can be framed (clickjacking)"),
+    }
+}
+
+fn check_referrer(value: Option<String>) -> Check {
+    const NAME: &str = "Referrer-Policy";
+    let Some(value) = value else {
+        return missing(
+            NAME,
+            10,
+            "missing; browsers default to strict-origin-when-cross-origin",
+        );
+    };
+
+    // The last recognized policy in a list wins
+    let effective = value
+        .split(',')
+        .map(|p| p.trim().to_ascii_lowercase())
+        .rfind(|p| {
+            SAFE_REFERRER_POLICIES.contains(&p.as_str())
+                || UNSAFE_REFERRER_POLICIES.contains(&p.as_str())
+        });
+    let Some(effective) = effective else {
+        return Check {
+            header: NAME,
+            points: 0,
+            max: 10,
+            notes: vec![format!("'{}' contains no recognized policy", value)],
+            value: Some(value),
+        };
+    };
+    let (points, notes) = if SAFE_REFERRER_POLICIES.contains(&effective.as_str()) {
+        (10, Vec::new())
+    } else {
+        (
+            0,
+            vec![format!("'{}' leaks full URLs to other sites", effective)],
+        )
+    };
+
+    Check {
+        header: NAME,
+        points,
+        max: 10,
+        value: Some(value),
+        notes,
+    }
+}
+
+fn check_permissions(value: Option<String>) -> Check {
+    const NAME: &str = "Permissions-Policy";
+    match value {
+        Some(v) => Check {
+            header: NAME,
+            points: 10,
+            max: 10,
+            value: Some(v),
+            notes: Vec::new(),
+        },
+        None => missing(
+            NAME,
+            10,
+            "missing; powerful features (camera, geolocation, ...) aren't restricted",
+        ),
+    }
+}
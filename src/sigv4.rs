@@ -0,0 +1,301 @@
+// AWS Signature Version 4 primitives shared by the S3 helpers.
+
+use hmac::{Hmac, Mac};
+use percent_encoding::percent_decode_str;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    // Environment variables first, then the shared ~/.aws/credentials file
+    // for AWS_PROFILE (or "default").
+    pub fn load() -> Result<Self, String> {
+        if let (Ok(access_key), Ok(secret_key)) = (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(Credentials {
+                access_key,
+                secret_key,
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+
+        let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+        let section = read_aws_file("credentials", &profile);
+        match (
+            section.get("aws_access_key_id"),
+            section.get("aws_secret_access_key"),
+        ) {
+            (Some(access_key), Some(secret_key)) => Ok(Credentials {
+                access_key: access_key.clone(),
+                secret_key: secret_key.clone(),
+                session_token: section.get("aws_session_token").cloned(),
+            }),
+            _ => Err("No AWS credentials found in the environment or ~/.aws/credentials.".into()),
+        }
+    }
+}
+
+pub fn default_region() -> String {
+    if let Ok(region) = env::var("AWS_REGION").or_else(|_| env::var("AWS_DEFAULT_REGION")) {
+        return region;
+    }
+    let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+    let section = if profile == "default" {
+        profile
+    } else {
+        format!("profile {}", profile)
+    };
+    read_aws_file("config", &section)
+        .remove("region")
+        .unwrap_or_else(|| "us-east-1".to_string())
+}
+
+fn read_aws_file(name: &str, section: &str) -> HashMap<String, String> {
+    let home = env::var("HOME").unwrap_or_default();
+    let content = fs::read_to_string(format!("{}/.aws/{}", home, name)).unwrap_or_default();
+
+    let mut values = HashMap::new();
+    let mut in_section = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            in_section = line[1..line.len() - 1].trim() == section;
+        } else if in_section && let Some((k, v)) = line.split_once('=') {
+            values.insert(k.trim().to_string(), v.trim().to_string());
+        }
+    }
+    values
+}
+
+// Build a query-string-authenticated (presigned) URL valid for `expires`.
+pub fn presign_url(
+    method: &str,
+    url: &Url,
+    creds: &Credentials,
+    region: &str,
+    service: &str,
+    expires: Duration,
+    now: SystemTime,
+) -> Url {
+    let (amz_date, date) = amz_timestamps(now);
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+
+    let mut params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    params.push(("X-Amz-Algorithm".into(), "AWS4-HMAC-SHA256".into()));
+    params.push((
+        "X-Amz-Credential".into(),
+        format!("{}/{}", creds.access_key, scope),
+    ));
+    params.push(("X-Amz-Date".into(), amz_date.clone()));
+    params.push(("X-Amz-Expires".into(), expires.as_secs().to_string()));
+    if let Some(token) = &creds.session_token {
+        params.push(("X-Amz-Security-Token".into(), token.clone()));
+    }
+    params.push(("X-Amz-SignedHeaders".into(), "host".into()));
+
+    let query = canonical_query(&params);
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+        method,
+        canonical_uri(url),
+        query,
+        host_header(url)
+    );
+    let signature = sign(creds, &amz_date, &date, region, service, &canonical_request);
+
+    let mut signed = url.clone();
+    signed.set_query(Some(&format!("{}&X-Amz-Signature={}", query, signature)));
+    signed
+}
+
+// Headers (x-amz-date, x-amz-content-sha256, authorization, ...) that
+// authenticate a request whose body hashes to `payload_hash`.
+pub fn authorization_headers(
+    method: &str,
+    url: &Url,
+    payload_hash: &str,
+    creds: &Credentials,
+    region: &str,
+    service: &str,
+    now: SystemTime,
+) -> Vec<(String, String)> {
+    let (amz_date, date) = amz_timestamps(now);
+
+    let mut headers = vec![
+        ("host".to_string(), host_header(url)),
+        ("x-amz-content-sha256".to_string(), payload_hash.to_string()),
+        ("x-amz-date".to_string(), amz_date.clone()),
+    ];
+    if let Some(token) = &creds.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    headers.sort();
+
+    let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        canonical_uri(url),
+        canonical_query(&params),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+    let signature = sign(creds, &amz_date, &date, region, service, &canonical_request);
+
+    headers.retain(|(k, _)| k != "host");
+    headers.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}/{}/{}/aws4_request, SignedHeaders={}, Signature={}",
+            creds.access_key, date, region, service, signed_headers, signature
+        ),
+    ));
+    headers
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+pub fn sign(
+    creds: &Credentials,
+    amz_date: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+    canonical_request: &str,
+) -> String {
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = hmac(format!("AWS4{}", creds.secret_key).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    let key = hmac(&key, "aws4_request");
+    hex(&hmac(&key, &string_to_sign))
+}
+
+pub fn host_header(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+pub fn canonical_uri(url: &Url) -> String {
+    let path = url.path();
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    // The URL path is already percent-encoded; decode each segment and
+    // re-encode with the stricter SigV4 rules.
+    path.split('/')
+        .map(|seg| uri_encode(&percent_decode_str(seg).decode_utf8_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn canonical_query(params: &[(String, String)]) -> String {
+    let mut encoded: Vec<(String, String)> = params
+        .iter()
+        .map(|(k, v)| (uri_encode(k), uri_encode(v)))
+        .collect();
+    encoded.sort();
+    encoded
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+pub fn uri_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Returns ("YYYYMMDDTHHMMSSZ", "YYYYMMDD") in UTC.
+pub fn amz_timestamps(now: SystemTime) -> (String, String) {
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let amz_date = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    );
+    (amz_date, date)
+}
+
+// Howard Hinnant's days-to-civil conversion.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Inverse of `civil_from_days`.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
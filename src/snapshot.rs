@@ -0,0 +1,141 @@
+// Golden-file snapshots of response bodies (--snapshot).
+//
+// Bodies are normalized before they are stored or compared: JSON is
+// pretty-printed with keys sorted at every level, other text has its line
+// endings and trailing whitespace cleaned up. This keeps snapshots stable
+// across servers that reorder keys or switch between CRLF and LF. JSON
+// snapshots are compared structurally, skipping any --ignore-path.
+
+use crate::jsondiff::{self, JsonPath};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// Above this many lines the diff only points at the first difference.
+const MAX_DIFF_LINES: usize = 2000;
+
+pub enum Verdict {
+    Created,
+    Updated,
+    Matched,
+    // The diff between the stored snapshot and the new response
+    Changed(String),
+}
+
+pub fn check(
+    path: &Path,
+    body: &str,
+    update: bool,
+    ignore: &[JsonPath],
+) -> Result<Verdict, String> {
+    let current = normalize(body);
+
+    if update || !path.exists() {
+        let existed = path.exists();
+        fs::write(path, &current)
+            .map_err(|e| format!("Unable to write snapshot '{}': {}", path.display(), e))?;
+        return Ok(if existed {
+            Verdict::Updated
+        } else {
+            Verdict::Created
+        });
+    }
+
+    let stored = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read snapshot '{}': {}", path.display(), e))?;
+    if let (Ok(old), Ok(new)) = (
+        serde_json::from_str::<Value>(&stored),
+        serde_json::from_str::<Value>(&current),
+    ) {
+        let changes = jsondiff::diff(&old, &new, ignore);
+        return Ok(if changes.is_empty() {
+            Verdict::Matched
+        } else {
+            Verdict::Changed(jsondiff::render(&changes))
+        });
+    }
+
+    let stored = normalize(&stored);
+    if stored == current {
+        Ok(Verdict::Matched)
+    } else {
+        Ok(Verdict::Changed(line_diff(&stored, &current)))
+    }
+}
+
+pub fn normalize(body: &str) -> String {
+    if let Ok(json) = serde_json::from_str::<Value>(body) {
+        let mut out = serde_json::to_string_pretty(&sorted(&json)).unwrap();
+        out.push('\n');
+        return out;
+    }
+
+    let mut out: String = body
+        .lines()
+        .map(|l| l.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n");
+    out.push('\n');
+    out
+}
+
+// Sorted explicitly rather than relying on serde_json's map ordering, which
+// becomes insertion order if any dependency enables `preserve_order`.
+fn sorted(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<_> = map.keys().collect();
+            keys.sort();
+            let mut out = serde_json::Map::new();
+            for k in keys {
+                out.insert(k.clone(), sorted(&map[k]));
+            }
+            Value::Object(out)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+        other => other.clone(),
+    }
+}
+
+// Changed lines only, prefixed with '-' (snapshot) and '+' (response).
+pub fn line_diff(old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    if a.len() > MAX_DIFF_LINES || b.len() > MAX_DIFF_LINES {
+        let line = a
+            .iter()
+            .zip(&b)
+            .position(|(x, y)| x != y)
+            .unwrap_or(a.len().min(b.len()));
+        return format!("First difference at line {}.", line + 1);
+    }
+
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    out.join("\n")
+}
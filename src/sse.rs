@@ -0,0 +1,172 @@
+// Server-Sent Events: a text/event-stream body parsed as it arrives, so
+// each event is printed when it's complete instead of when the server
+// closes the connection.
+//
+// The line protocol follows the HTML standard: "field: value" lines, ':'
+// comments, and a blank line ending each event. Lines end with CRLF, LF or
+// CR, and an event cut off by the end of the stream is dropped. The last
+// event ID and the server's `retry:` delay outlive the connection, so a
+// reconnect (--sse with --retry) can resume with Last-Event-ID.
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+pub struct Event {
+    // "message" unless the server named it
+    pub name: String,
+    pub data: String,
+    // Set only when this event carried an `id:` field
+    pub id: Option<String>,
+}
+
+pub struct Parser {
+    line: Vec<u8>,
+    // The previous chunk ended with CR, so a leading LF is part of it
+    after_cr: bool,
+    started: bool,
+    name: Option<String>,
+    // Every data line followed by '\n'
+    data: String,
+    id: Option<String>,
+    last_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+// How a stream ended: closed by the server, or broken off with an error.
+pub struct Ended {
+    // The reconnection delay the server asked for
+    pub retry: Option<Duration>,
+    pub error: Option<String>,
+}
+
+struct Session {
+    last_id: Option<String>,
+    retry: Option<Duration>,
+    ended: Option<Option<String>>,
+}
+
+thread_local! {
+    static SESSION: RefCell<Session> = const {
+        RefCell::new(Session {
+            last_id: None,
+            retry: None,
+            ended: None,
+        })
+    };
+}
+
+impl Parser {
+    // A parser for a new connection, carrying over the last event ID of the
+    // previous one.
+    pub fn resume() -> Parser {
+        SESSION.with_borrow(|session| Parser {
+            line: Vec::new(),
+            after_cr: false,
+            started: false,
+            name: None,
+            data: String::new(),
+            id: None,
+            last_id: session.last_id.clone(),
+            retry: session.retry,
+        })
+    }
+
+    pub fn feed(
+        &mut self,
+        chunk: &[u8],
+        mut on_event: impl FnMut(Event) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut chunk = chunk;
+        if !self.started {
+            self.started = !chunk.is_empty();
+            chunk = chunk.strip_prefix(&UTF8_BOM).unwrap_or(chunk);
+        }
+        for &b in chunk {
+            let after_cr = std::mem::replace(&mut self.after_cr, b == b'\r');
+            match b {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(event) = self.process(&String::from_utf8_lossy(&line)) {
+                        on_event(event)?;
+                    }
+                }
+                _ => self.line.push(b),
+            }
+        }
+        Ok(())
+    }
+
+    // Remember where this connection left off for the next one.
+    pub fn finish(&self, error: Option<String>) {
+        SESSION.with_borrow_mut(|session| {
+            session.last_id.clone_from(&self.last_id);
+            session.retry = self.retry;
+            session.ended = Some(error);
+        });
+    }
+
+    fn process(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.name = Some(value.to_string()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse::<u64>() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let name = self.name.take();
+        let id = self.id.take();
+        if id.is_some() {
+            self.last_id.clone_from(&id);
+        }
+        // An event without data lines isn't dispatched
+        let mut data = std::mem::take(&mut self.data);
+        data.pop()?;
+        Some(Event {
+            name: name
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| "message".to_string()),
+            data,
+            id,
+        })
+    }
+}
+
+// How the stream ended, if one ended since the last call.
+pub fn take_ended() -> Option<Ended> {
+    SESSION.with_borrow_mut(|session| {
+        let error = session.ended.take()?;
+        Some(Ended {
+            retry: session.retry,
+            error,
+        })
+    })
+}
+
+// The ID a reconnect sends as Last-Event-ID.
+pub fn last_event_id() -> Option<String> {
+    SESSION.with_borrow(|session| session.last_id.clone())
+}
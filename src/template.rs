@@ -0,0 +1,882 @@
+// Request body templates (--var, or a body file named *.tera/*.tmpl/*.j2).
+//
+// The syntax is the common core of Tera and Jinja2:
+//
+//   {{ user.name | upper }}               print a value
+//   {% if admin and not guest %}...{% elif x %}...{% else %}...{% endif %}
+//   {% for item in items %}{{ loop.index }}: {{ item | json }}{% endfor %}
+//   {# a comment #}
+//
+// A "-" just inside a tag ({{- x -}}, {%- if x -%}) trims the whitespace
+// next to it. Filters are upper, lower, trim, length, json, join(sep),
+// default(value) and replace(from, to); functions are now(), timestamp(),
+// uuid(), env(name) and range(end) / range(start, end). Values are JSON,
+// so `| json` is the way to splice a string into a JSON body with quotes
+// and escaping.
+
+use serde_json::{Map, Value, json};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Bound on nested blocks, so a pathological template can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+// Values for `--var name=value` (a string) and `--var name:=json`.
+pub fn parse_vars(vars: &[String]) -> Result<Map<String, Value>, String> {
+    let mut map = Map::new();
+    for var in vars {
+        if let Some((name, raw)) = var.split_once(":=")
+            && !name.contains('=')
+        {
+            let value = serde_json::from_str(raw)
+                .map_err(|e| format!("--var {}: invalid JSON: {}", name, e))?;
+            map.insert(name.trim().to_string(), value);
+        } else if let Some((name, value)) = var.split_once('=') {
+            map.insert(name.trim().to_string(), Value::String(value.to_string()));
+        } else {
+            return Err(format!(
+                "Invalid --var '{}'; expected name=value or name:=json.",
+                var
+            ));
+        }
+    }
+    Ok(map)
+}
+
+// `name` identifies the template in error messages.
+pub fn render(name: &str, source: &str, vars: &Map<String, Value>) -> Result<String, String> {
+    let at = |(line, e): Error| format!("Template {} line {}: {}", name, line, e);
+    let nodes = parse(source).map_err(at)?;
+    let mut scope = vec![vars.clone()];
+    let mut out = String::new();
+    run(&nodes, &mut scope, &mut out, 0).map_err(at)?;
+    Ok(out)
+}
+
+type Error = (usize, String);
+
+// ---------------- TEMPLATE STRUCTURE ----------------
+
+enum Segment {
+    Text(String),
+    // line, tag kind ('{' or '%'), contents
+    Tag(usize, char, String),
+}
+
+// Splits the source into text and tags, applying "-" whitespace trimming.
+fn segments(src: &str) -> Result<Vec<Segment>, Error> {
+    let mut segments = Vec::new();
+    let mut rest = src;
+    let mut line = 1;
+    let mut trim_next = false;
+
+    while !rest.is_empty() {
+        let start = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|open| rest.find(open))
+            .min();
+        let (text, tag) = match start {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        let mut text = text.to_string();
+        if trim_next {
+            text = text.trim_start().to_string();
+        }
+        line += text.matches('\n').count();
+        if tag.is_empty() {
+            segments.push(Segment::Text(text));
+            break;
+        }
+
+        let kind = tag.as_bytes()[1] as char;
+        let close = match kind {
+            '{' => "}}",
+            '%' => "%}",
+            _ => "#}",
+        };
+        let end = tag[2..]
+            .find(close)
+            .ok_or((line, format!("unclosed '{{{}' tag", kind)))?;
+        let mut inner = &tag[2..2 + end];
+        rest = &tag[2 + end + 2..];
+
+        if let Some(stripped) = inner.strip_prefix('-') {
+            inner = stripped;
+            text = text.trim_end().to_string();
+        }
+        trim_next = false;
+        if let Some(stripped) = inner.strip_suffix('-') {
+            inner = stripped;
+            trim_next = true;
+        }
+        segments.push(Segment::Text(text));
+        if kind != '#' {
+            segments.push(Segment::Tag(line, kind, inner.trim().to_string()));
+        }
+        line += inner.matches('\n').count();
+    }
+    Ok(segments)
+}
+
+enum Node {
+    Text(String),
+    Print(usize, Expr),
+    // (line, condition, body) for the if and each elif
+    If(Vec<(usize, Expr, Vec<Node>)>, Vec<Node>),
+    For {
+        line: usize,
+        key: Option<String>,
+        value: String,
+        iterable: Expr,
+        body: Vec<Node>,
+    },
+}
+
+fn parse(src: &str) -> Result<Vec<Node>, Error> {
+    let mut segments = segments(src)?.into_iter().peekable();
+    let (nodes, end) = block(&mut segments, &[])?;
+    match end {
+        None => Ok(nodes),
+        Some((line, tag)) => Err((line, format!("unexpected {{% {} %}}", tag))),
+    }
+}
+
+type Segments = std::iter::Peekable<std::vec::IntoIter<Segment>>;
+
+// The tag that closed a block, with its line.
+type EndTag = Option<(usize, String)>;
+
+// Parses nodes until one of the `ends` tags (or the end of the source)
+// and returns the tag that ended the block.
+fn block(segments: &mut Segments, ends: &[&str]) -> Result<(Vec<Node>, EndTag), Error> {
+    let mut nodes = Vec::new();
+    while let Some(segment) = segments.next() {
+        let (line, kind, tag) = match segment {
+            Segment::Text(text) => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text));
+                }
+                continue;
+            }
+            Segment::Tag(line, kind, tag) => (line, kind, tag),
+        };
+        if kind == '{' {
+            nodes.push(Node::Print(line, expression(line, &tag)?));
+            continue;
+        }
+
+        let keyword = tag.split_whitespace().next().unwrap_or("");
+        if ends.contains(&keyword) {
+            return Ok((nodes, Some((line, tag))));
+        }
+        match keyword {
+            "if" => nodes.push(if_block(segments, line, &tag)?),
+            "for" => nodes.push(for_block(segments, line, &tag)?),
+            "" => return Err((line, "empty {% %} tag".to_string())),
+            _ => return Err((line, format!("unexpected {{% {} %}}", tag))),
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn if_block(segments: &mut Segments, line: usize, tag: &str) -> Result<Node, Error> {
+    let mut branches = Vec::new();
+    let mut condition = (line, expression(line, tag.trim_start_matches("if"))?);
+    loop {
+        let (body, end) = block(segments, &["elif", "else", "endif"])?;
+        let (end_line, end) = end.ok_or((line, "missing {% endif %}".to_string()))?;
+        branches.push((condition.0, condition.1, body));
+        if let Some(rest) = end.strip_prefix("elif") {
+            condition = (end_line, expression(end_line, rest)?);
+        } else if end == "else" {
+            let (otherwise, close) = block(segments, &["endif"])?;
+            if close.is_none() {
+                return Err((line, "missing {% endif %}".to_string()));
+            }
+            return Ok(Node::If(branches, otherwise));
+        } else {
+            return Ok(Node::If(branches, Vec::new()));
+        }
+    }
+}
+
+fn for_block(segments: &mut Segments, line: usize, tag: &str) -> Result<Node, Error> {
+    let spec = tag.trim_start_matches("for").trim();
+    let (vars, iterable) = spec
+        .split_once(" in ")
+        .ok_or((line, "expected {% for name in expression %}".to_string()))?;
+    let names: Vec<String> = vars.split(',').map(|v| v.trim().to_string()).collect();
+    if names.len() > 2 || names.iter().any(|n| !is_ident(n)) {
+        return Err((line, format!("invalid loop variables '{}'", vars.trim())));
+    }
+    let iterable = expression(line, iterable)?;
+    let (body, end) = block(segments, &["endfor"])?;
+    if end.is_none() {
+        return Err((line, "missing {% endfor %}".to_string()));
+    }
+    let mut names = names.into_iter();
+    let first = names.next().unwrap_or_default();
+    Ok(match names.next() {
+        Some(value) => Node::For {
+            line,
+            key: Some(first),
+            value,
+            iterable,
+            body,
+        },
+        None => Node::For {
+            line,
+            key: None,
+            value: first,
+            iterable,
+            body,
+        },
+    })
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// ---------------- EXPRESSIONS ----------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: [&str; 18] = [
+    "==", "!=", "<=", ">=", "(", ")", "[", "]", ",", ".", "|", "<", ">", "+", "-", "*", "/", "~",
+];
+
+fn lex(line: usize, src: &str) -> Result<Vec<Tok>, Error> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err((line, "unterminated string".to_string())),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(&other) => s.push(other),
+                            None => return Err((line, "unterminated string".to_string())),
+                        }
+                    }
+                    Some(&other) => s.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Tok::Str(s));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| (line, format!("invalid number '{}'", text)))?;
+            tokens.push(Tok::Num(n));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Tok::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            let p = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .ok_or((line, format!("unexpected character '{}'", c)))?;
+            i += p.chars().count();
+            tokens.push(Tok::Punct(p));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Var(String),
+    List(Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Filter(Box<Expr>, String, Vec<Expr>),
+}
+
+fn expression(line: usize, src: &str) -> Result<Expr, Error> {
+    let mut parser = Parser {
+        tokens: lex(line, src)?,
+        pos: 0,
+        line,
+    };
+    if parser.tokens.is_empty() {
+        return Err((line, "empty expression".to_string()));
+    }
+    let expr = parser.binary(0)?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(tok) => parser.error(format!("unexpected {}", describe(tok))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+    line: usize,
+}
+
+// Binary operators by precedence, loosest first.
+const LEVELS: [&[&str]; 6] = [
+    &["or"],
+    &["and"],
+    &["==", "!=", "<", ">", "<=", ">=", "in"],
+    &["~"],
+    &["+", "-"],
+    &["*", "/"],
+];
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, Error> {
+        Err((self.line, message))
+    }
+
+    fn next(&mut self) -> Result<Tok, Error> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok.ok_or((self.line, "unexpected end of expression".to_string()))
+    }
+
+    // Punctuation and keyword operators alike.
+    fn at(&self, op: &str) -> bool {
+        match self.peek() {
+            Some(Tok::Punct(p)) => *p == op,
+            Some(Tok::Ident(i)) => i == op,
+            _ => false,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = self.at(op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), Error> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(tok) => self.error(format!("expected '{}', found {}", op, describe(tok))),
+                None => self.error(format!("expected '{}'", op)),
+            }
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, Error> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = LEVELS[level].iter().find(|op| self.at(op)) {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.eat("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let key = match self.next()? {
+                    Tok::Ident(name) => Value::String(name),
+                    Tok::Num(n) => json!(n as u64),
+                    other => return self.error(format!("unexpected {}", describe(&other))),
+                };
+                expr = Expr::Index(Box::new(expr), Box::new(Expr::Literal(key)));
+            } else if self.eat("[") {
+                let index = self.binary(0)?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.eat("|") {
+                let Tok::Ident(name) = self.next()? else {
+                    return self.error("expected a filter name after '|'".to_string());
+                };
+                let args = if self.at("(") {
+                    self.arguments()?
+                } else {
+                    Vec::new()
+                };
+                expr = Expr::Filter(Box::new(expr), name, args);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // "(a, b)" or Tera's named form "(sep=", ")"; names are only read
+    // for readability and arguments are taken in order.
+    fn arguments(&mut self) -> Result<Vec<Expr>, Error> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            if let (Some(Tok::Ident(_)), Some(Tok::Punct("="))) =
+                (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+            {
+                self.pos += 2;
+            }
+            args.push(self.binary(0)?);
+            if !self.at(")") {
+                self.expect(",")?;
+            }
+        }
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.next()? {
+            Tok::Num(n) => Ok(Expr::Literal(number(n))),
+            Tok::Str(s) => Ok(Expr::Literal(Value::String(s))),
+            Tok::Punct("(") => {
+                let e = self.binary(0)?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Tok::Punct("[") => {
+                let mut items = Vec::new();
+                while !self.eat("]") {
+                    items.push(self.binary(0)?);
+                    if !self.at("]") {
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::List(items))
+            }
+            Tok::Ident(name) => match name.as_str() {
+                "true" | "True" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" | "False" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" | "none" | "None" => Ok(Expr::Literal(Value::Null)),
+                _ if self.at("(") => {
+                    let args = self.arguments()?;
+                    Ok(Expr::Call(name, args))
+                }
+                _ => Ok(Expr::Var(name)),
+            },
+            other => self.error(format!("unexpected {}", describe(&other))),
+        }
+    }
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Ident(i) => format!("'{}'", i),
+        Tok::Str(s) => format!("string \"{}\"", s),
+        Tok::Num(n) => format!("number {}", n),
+        Tok::Punct(p) => format!("'{}'", p),
+    }
+}
+
+// Whole numbers stay integers so they print without a fraction.
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9e15 {
+        json!(n as i64)
+    } else {
+        json!(n)
+    }
+}
+
+// ---------------- EVALUATION ----------------
+
+type Scope = Vec<Map<String, Value>>;
+
+const UNDEFINED: &str = " is not defined";
+
+fn run(nodes: &[Node], scope: &mut Scope, out: &mut String, depth: usize) -> Result<(), Error> {
+    if depth > MAX_DEPTH {
+        return Err((0, "blocks are nested too deeply".to_string()));
+    }
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Print(line, expr) => {
+                let value = eval(expr, scope).map_err(|e| (*line, e))?;
+                out.push_str(&text(&value));
+            }
+            Node::If(branches, otherwise) => {
+                let mut taken = None;
+                for (line, condition, body) in branches {
+                    if holds(condition, scope).map_err(|e| (*line, e))? {
+                        taken = Some(body);
+                        break;
+                    }
+                }
+                run(taken.unwrap_or(otherwise), scope, out, depth + 1)?;
+            }
+            Node::For {
+                line,
+                key,
+                value,
+                iterable,
+                body,
+            } => {
+                let items: Vec<(Value, Value)> =
+                    match eval(iterable, scope).map_err(|e| (*line, e))? {
+                        Value::Array(items) => items
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, v)| (json!(i), v))
+                            .collect(),
+                        Value::Object(map) => map.into_iter().map(|(k, v)| (json!(k), v)).collect(),
+                        Value::Null => Vec::new(),
+                        other => {
+                            return Err((*line, format!("can't loop over {}", type_name(&other))));
+                        }
+                    };
+                let count = items.len();
+                for (i, (k, v)) in items.into_iter().enumerate() {
+                    let mut frame = Map::new();
+                    if let Some(key) = key {
+                        frame.insert(key.clone(), k);
+                    }
+                    frame.insert(value.clone(), v);
+                    frame.insert(
+                        "loop".to_string(),
+                        json!({
+                            "index": i + 1,
+                            "index0": i,
+                            "first": i == 0,
+                            "last": i + 1 == count,
+                            "length": count,
+                        }),
+                    );
+                    scope.push(frame);
+                    let result = run(body, scope, out, depth + 1);
+                    scope.pop();
+                    result?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn eval(expr: &Expr, scope: &Scope) -> Result<Value, String> {
+    Ok(match expr {
+        Expr::Literal(v) => v.clone(),
+        Expr::Var(name) => scope
+            .iter()
+            .rev()
+            .find_map(|frame| frame.get(name))
+            .cloned()
+            .ok_or_else(|| format!("'{}'{}", name, UNDEFINED))?,
+        Expr::List(items) => Value::Array(
+            items
+                .iter()
+                .map(|e| eval(e, scope))
+                .collect::<Result<_, _>>()?,
+        ),
+        Expr::Index(target, index) => {
+            let target = eval(target, scope)?;
+            let index = eval(index, scope)?;
+            match (&target, &index) {
+                (Value::Object(map), Value::String(key)) => map
+                    .get(key)
+                    .cloned()
+                    .ok_or_else(|| format!("no key '{}'", key))?,
+                (Value::Array(items), Value::Number(n)) => n
+                    .as_u64()
+                    .and_then(|i| items.get(i as usize))
+                    .cloned()
+                    .ok_or_else(|| format!("index {} is out of range", n))?,
+                _ => {
+                    return Err(format!(
+                        "can't index {} with {}",
+                        type_name(&target),
+                        type_name(&index)
+                    ));
+                }
+            }
+        }
+        Expr::Not(e) => Value::Bool(!holds(e, scope)?),
+        Expr::Neg(e) => number(-num(&eval(e, scope)?)?),
+        Expr::Binary(op, l, r) => {
+            // Short-circuit, and treat undefined operands as false
+            if *op == "and" || *op == "or" {
+                let left = holds(l, scope)?;
+                if (*op == "and") != left {
+                    return Ok(Value::Bool(left));
+                }
+                return Ok(Value::Bool(holds(r, scope)?));
+            }
+            binary(op, eval(l, scope)?, eval(r, scope)?)?
+        }
+        Expr::Call(name, args) => {
+            let args: Vec<Value> = args
+                .iter()
+                .map(|e| eval(e, scope))
+                .collect::<Result<_, _>>()?;
+            function(name, &args)?
+        }
+        Expr::Filter(target, name, args) => {
+            let args: Vec<Value> = args
+                .iter()
+                .map(|e| eval(e, scope))
+                .collect::<Result<_, _>>()?;
+            match eval(target, scope) {
+                Ok(value) => filter(name, value, &args)?,
+                // `default` exists for exactly this
+                Err(e) if name == "default" && is_undefined(&e) => {
+                    args.first().cloned().unwrap_or(Value::Null)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    })
+}
+
+// Conditions may test variables that aren't defined ("if debug"); those
+// are false rather than errors.
+fn holds(condition: &Expr, scope: &Scope) -> Result<bool, String> {
+    match eval(condition, scope) {
+        Ok(value) => Ok(truthy(&value)),
+        Err(e) if is_undefined(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_undefined(error: &str) -> bool {
+    error.ends_with(UNDEFINED) || error.starts_with("no key ")
+}
+
+fn binary(op: &str, l: Value, r: Value) -> Result<Value, String> {
+    Ok(match op {
+        "==" => Value::Bool(l == r || (l.is_number() && r.is_number() && num(&l)? == num(&r)?)),
+        "!=" => Value::Bool(!(l == r || (l.is_number() && r.is_number() && num(&l)? == num(&r)?))),
+        "~" => Value::String(text(&l) + &text(&r)),
+        "in" => Value::Bool(match &r {
+            Value::Array(items) => items.contains(&l),
+            Value::Object(map) => map.contains_key(&text(&l)),
+            Value::String(s) => s.contains(&text(&l)),
+            _ => {
+                return Err(format!(
+                    "'in' needs a list, object or string, not {}",
+                    type_name(&r)
+                ));
+            }
+        }),
+        "<" | ">" | "<=" | ">=" => {
+            let ordering = match (&l, &r) {
+                (Value::String(a), Value::String(b)) => a.cmp(b),
+                _ => num(&l)?
+                    .partial_cmp(&num(&r)?)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            };
+            Value::Bool(match op {
+                "<" => ordering.is_lt(),
+                ">" => ordering.is_gt(),
+                "<=" => ordering.is_le(),
+                _ => ordering.is_ge(),
+            })
+        }
+        "+" => number(num(&l)? + num(&r)?),
+        "-" => number(num(&l)? - num(&r)?),
+        "*" => number(num(&l)? * num(&r)?),
+        _ => {
+            let divisor = num(&r)?;
+            if divisor == 0.0 {
+                return Err("division by zero".to_string());
+            }
+            number(num(&l)? / divisor)
+        }
+    })
+}
+
+fn function(name: &str, args: &[Value]) -> Result<Value, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(match name {
+        "now" => Value::String(rfc3339(now.as_secs())),
+        "timestamp" => json!(now.as_secs()),
+        "uuid" => Value::String(uuid_v4()?),
+        "env" => {
+            let var = args.first().map(text).unwrap_or_default();
+            match std::env::var(&var) {
+                Ok(v) => Value::String(v),
+                Err(_) => match args.get(1) {
+                    Some(default) => default.clone(),
+                    None => return Err(format!("environment variable {} is not set", var)),
+                },
+            }
+        }
+        "range" => {
+            let (start, end) = match args {
+                [end] => (0, num(end)? as i64),
+                [start, end] => (num(start)? as i64, num(end)? as i64),
+                _ => return Err("range() takes one or two arguments".to_string()),
+            };
+            Value::Array((start..end).map(|i| json!(i)).collect())
+        }
+        _ => return Err(format!("unknown function {}()", name)),
+    })
+}
+
+fn filter(name: &str, value: Value, args: &[Value]) -> Result<Value, String> {
+    Ok(match name {
+        "upper" => Value::String(text(&value).to_uppercase()),
+        "lower" => Value::String(text(&value).to_lowercase()),
+        "trim" => Value::String(text(&value).trim().to_string()),
+        "json" => Value::String(value.to_string()),
+        "length" => json!(match &value {
+            Value::Array(items) => items.len(),
+            Value::Object(map) => map.len(),
+            other => text(other).chars().count(),
+        }),
+        "join" => {
+            let sep = args.first().map(text).unwrap_or_default();
+            match &value {
+                Value::Array(items) => {
+                    Value::String(items.iter().map(text).collect::<Vec<_>>().join(&sep))
+                }
+                other => return Err(format!("join needs a list, not {}", type_name(other))),
+            }
+        }
+        "replace" => match args {
+            [from, to] => Value::String(text(&value).replace(&text(from), &text(to))),
+            _ => return Err("replace takes two arguments".to_string()),
+        },
+        "default" => {
+            if value.is_null() {
+                args.first().cloned().unwrap_or(Value::Null)
+            } else {
+                value
+            }
+        }
+        _ => return Err(format!("unknown filter '{}'", name)),
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn num(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(n) => Ok(n.as_f64().unwrap_or(0.0)),
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .map_err(|_| format!("\"{}\" is not a number", s)),
+        Value::Bool(b) => Ok(f64::from(u8::from(*b))),
+        other => Err(format!("expected a number, not {}", type_name(other))),
+    }
+}
+
+// How a value prints: strings without quotes, null as nothing, lists and
+// objects as JSON.
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+// "2026-10-14T11:17:34Z" for a Unix time.
+fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+fn uuid_v4() -> Result<String, String> {
+    let mut b = [0u8; 16];
+    openssl::rand::rand_bytes(&mut b).map_err(|e| e.to_string())?;
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
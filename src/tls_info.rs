@@ -0,0 +1,176 @@
+// --tls-info: report what the TLS handshake negotiated.
+//
+// reqwest doesn't expose the TLS session of its connections, so this opens
+// a separate probe connection to the same endpoint with OpenSSL (the
+// library native-tls uses here) and reports the protocol version, cipher
+// suite, ALPN protocol and handshake time. A second handshake offering the
+// first session's ticket shows whether the server resumes sessions.
+
+use openssl::ssl::{
+    SslConnector, SslConnectorBuilder, SslMethod, SslSession, SslSessionCacheMode, SslStream,
+    SslVerifyMode, SslVersion,
+};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long to wait for TLS 1.3 session tickets, which arrive after the
+// handshake completes.
+const TICKET_WAIT: Duration = Duration::from_millis(200);
+
+// The protocols offered via ALPN, in order of preference.
+const ALPN_PROTOCOLS: &[u8] = b"\x02h2\x08http/1.1";
+
+// -k and --cacert, for probe connections made outside reqwest.
+#[derive(Clone, Copy, Default)]
+pub struct Trust<'a> {
+    pub insecure: bool,
+    pub ca_file: Option<&'a Path>,
+}
+
+impl Trust<'_> {
+    pub fn apply(&self, builder: &mut SslConnectorBuilder) -> Result<(), String> {
+        if self.insecure {
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+        if let Some(path) = self.ca_file {
+            builder
+                .set_ca_file(path)
+                .map_err(|e| format!("Unable to load '{}': {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct TlsInfo {
+    pub version: String,
+    pub cipher: String,
+    pub alpn: Option<String>,
+    pub connect: Duration,
+    pub handshake: Duration,
+    // None when the server issued no session to resume
+    pub resumed: Option<(bool, Duration)>,
+}
+
+impl TlsInfo {
+    pub fn describe(&self) -> Vec<String> {
+        let resumption = match self.resumed {
+            None => "not offered by the server".to_string(),
+            Some((true, t)) => format!("supported (resumed handshake {})", millis(t)),
+            Some((false, t)) => format!("refused (full handshake again, {})", millis(t)),
+        };
+        vec![
+            format!("TLS version: {}", self.version),
+            format!("TLS cipher: {}", self.cipher),
+            format!(
+                "TLS ALPN: {}",
+                self.alpn
+                    .as_deref()
+                    .unwrap_or("none (server ignored h2, http/1.1)")
+            ),
+            format!(
+                "TLS handshake: {} (after a {} TCP connect)",
+                millis(self.handshake),
+                millis(self.connect)
+            ),
+            format!("TLS session resumption: {}", resumption),
+        ]
+    }
+}
+
+// `name` is the server name sent via SNI and verified against the
+// certificate; `host` and `port` are where to connect.
+pub fn probe(name: &str, host: &str, port: u16, trust: Trust) -> Result<TlsInfo, String> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|_| format!("Could not resolve host: {}.", host))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve host: {}.", host))?;
+
+    let issued: Arc<Mutex<Option<SslSession>>> = Arc::default();
+    let mut builder = SslConnector::builder(SslMethod::tls_client()).map_err(|e| e.to_string())?;
+    builder
+        .set_alpn_protos(ALPN_PROTOCOLS)
+        .map_err(|e| e.to_string())?;
+    trust.apply(&mut builder)?;
+    builder.set_session_cache_mode(SslSessionCacheMode::CLIENT);
+    let sink = Arc::clone(&issued);
+    builder.set_new_session_callback(move |_, session| {
+        *sink.lock().unwrap() = Some(session);
+    });
+    let connector = builder.build();
+
+    let (mut stream, connect, handshake) = connect_tls(&connector, name, addr, None)?;
+    let ssl = stream.ssl();
+    let version = ssl.version_str().to_string();
+    let cipher = ssl
+        .current_cipher()
+        .map(|c| c.standard_name().unwrap_or(c.name()).to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let alpn = ssl
+        .selected_alpn_protocol()
+        .map(|p| String::from_utf8_lossy(p).into_owned());
+    let tls13 = ssl.version2() == Some(SslVersion::TLS1_3);
+
+    if tls13 {
+        // Reading gives OpenSSL the chance to process NewSessionTicket
+        // messages; the server sends nothing else, so this just times out
+        let _ = stream.get_ref().set_read_timeout(Some(TICKET_WAIT));
+        let _ = stream.ssl_read(&mut [0u8; 1]);
+    }
+    let _ = stream.shutdown();
+
+    let session = issued.lock().unwrap().take();
+    let resumed = match session {
+        Some(session) => {
+            let (stream, _, time) = connect_tls(&connector, name, addr, Some(&session))?;
+            Some((stream.ssl().session_reused(), time))
+        }
+        None => None,
+    };
+
+    Ok(TlsInfo {
+        version,
+        cipher,
+        alpn,
+        connect,
+        handshake,
+        resumed,
+    })
+}
+
+fn connect_tls(
+    connector: &SslConnector,
+    name: &str,
+    addr: SocketAddr,
+    session: Option<&SslSession>,
+) -> Result<(SslStream<TcpStream>, Duration, Duration), String> {
+    let started = Instant::now();
+    let tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Unable to connect to {}: {}", addr, e))?;
+    let _ = tcp.set_read_timeout(Some(CONNECT_TIMEOUT));
+    let connect = started.elapsed();
+
+    let mut ssl = connector
+        .configure()
+        .and_then(|c| c.into_ssl(name))
+        .map_err(|e| e.to_string())?;
+    if let Some(session) = session {
+        // SAFETY: the session was issued on a connection made from this
+        // same connector, so it belongs to the same SSL_CTX.
+        unsafe { ssl.set_session(session) }.map_err(|e| e.to_string())?;
+    }
+
+    let started = Instant::now();
+    let stream = ssl
+        .connect(tcp)
+        .map_err(|e| format!("TLS handshake failed: {}", e))?;
+    Ok((stream, connect, started.elapsed()))
+}
+
+fn millis(d: Duration) -> String {
+    format!("{:.1} ms", d.as_secs_f64() * 1000.0)
+}
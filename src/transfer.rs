@@ -0,0 +1,269 @@
+// Body transfer with stall detection.
+//
+// Blocking reads and writes inside reqwest can't be cancelled, so the
+// blocking side runs on a helper thread while the caller watches for
+// progress and gives up once the connection has been silent too long.
+
+use crate::inflate;
+use reqwest::blocking::{Body, Client, Request, Response};
+use reqwest::header::CONTENT_ENCODING;
+use std::io::{self, Read};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CHUNK_SIZE: usize = 16 * 1024;
+const QUEUED_CHUNKS: usize = 16;
+
+// How often stalls and transfer speed are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Throughput is sampled over windows of this length for --speed-limit.
+const SPEED_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Default)]
+pub struct TransferLimits {
+    // Maximum silence between received chunks
+    pub read: Option<Duration>,
+    // Maximum time the upload may go without the socket accepting data
+    pub write: Option<Duration>,
+    // Abort when throughput stays below `speed_limit` bytes/s for `speed_time`
+    pub speed_limit: Option<u64>,
+    pub speed_time: Duration,
+}
+
+// Read a body to the end, enforcing the read timeout and low-speed limit.
+pub fn read_body<R: Read + Send + 'static>(
+    reader: R,
+    limits: &TransferLimits,
+) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    stream_body(reader, limits, |chunk| {
+        body.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    Ok(body)
+}
+
+// The whole body decoded from its Content-Encoding, and how many bytes
+// came over the wire.
+pub fn read_decoded(res: Response, limits: &TransferLimits) -> Result<(Vec<u8>, usize), String> {
+    let coding = res
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = read_body(res, limits)?;
+    let encoded_len = body.len();
+    let body = match coding {
+        Some(coding) => {
+            inflate::decode(body, &coding).map_err(|e| format!("{}.", e.trim_end_matches('.')))?
+        }
+        None => body,
+    };
+    Ok((body, encoded_len))
+}
+
+// Hand the body to `on_chunk` as it arrives, under the same limits, and
+// return its length. An error from `on_chunk` ends the transfer.
+pub fn stream_body<R: Read + Send + 'static>(
+    mut reader: R,
+    limits: &TransferLimits,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<(), String>,
+) -> Result<u64, String> {
+    // Bounded, so a slow consumer holds the reader back instead of the
+    // whole body piling up in the queue
+    let (tx, rx) = mpsc::sync_channel::<io::Result<Vec<u8>>>(QUEUED_CHUNKS);
+    thread::spawn(move || {
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        loop {
+            let chunk = reader.read(&mut buf).map(|n| buf[..n].to_vec());
+            let done = !matches!(&chunk, Ok(c) if !c.is_empty());
+            if tx.send(chunk).is_err() || done {
+                return;
+            }
+        }
+    });
+
+    let mut total = 0u64;
+    let mut last_data = Instant::now();
+    let mut speed = SpeedCheck::new(limits);
+
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(c)) if c.is_empty() => return Ok(total),
+            Ok(Ok(c)) => {
+                on_chunk(&c)?;
+                total += c.len() as u64;
+                speed.bytes += c.len() as u64;
+                last_data = Instant::now();
+            }
+            Ok(Err(e)) => return Err(format!("Error while reading the response: {}", e)),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err("The transfer ended unexpectedly.".to_string());
+            }
+        }
+
+        if let Some(limit) = limits.read
+            && last_data.elapsed() > limit
+        {
+            return Err(format!(
+                "No data received for {}; the transfer stalled.",
+                describe(limit)
+            ));
+        }
+        speed.check()?;
+    }
+}
+
+// curl-style low-speed detection: the transfer fails once every sample
+// window for `speed_time` has been slower than `speed_limit`.
+struct SpeedCheck {
+    limit: Option<u64>,
+    time: Duration,
+    window_start: Instant,
+    bytes: u64,
+    slow_since: Option<Instant>,
+}
+
+impl SpeedCheck {
+    fn new(limits: &TransferLimits) -> Self {
+        SpeedCheck {
+            limit: limits.speed_limit,
+            time: limits.speed_time,
+            window_start: Instant::now(),
+            bytes: 0,
+            slow_since: None,
+        }
+    }
+
+    fn check(&mut self) -> Result<(), String> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+        let elapsed = self.window_start.elapsed();
+        if elapsed < SPEED_WINDOW {
+            return Ok(());
+        }
+
+        let rate = self.bytes as f64 / elapsed.as_secs_f64();
+        if rate < limit as f64 {
+            let since = *self.slow_since.get_or_insert(self.window_start);
+            if since.elapsed() >= self.time {
+                return Err(format!(
+                    "Transfer speed stayed below {} bytes/s for {}; aborting.",
+                    limit,
+                    describe(self.time)
+                ));
+            }
+        } else {
+            self.slow_since = None;
+        }
+
+        self.window_start = Instant::now();
+        self.bytes = 0;
+        Ok(())
+    }
+}
+
+// Why an upload ended without a response.
+pub enum UploadError {
+    Send(reqwest::Error),
+    Stalled(String),
+    Aborted(String),
+}
+
+// Send a request whose body streams from `source`, failing if the upload
+// stops making progress for longer than the write timeout.
+pub fn send_upload<R: Read + Send + 'static>(
+    client: &Client,
+    mut req: Request,
+    source: R,
+    len: u64,
+    limits: &TransferLimits,
+) -> Result<Response, UploadError> {
+    let Some(limit) = limits.write else {
+        *req.body_mut() = Some(Body::sized(source, len));
+        return client.execute(req).map_err(UploadError::Send);
+    };
+
+    let activity = Arc::new(Mutex::new(Activity {
+        last: Instant::now(),
+        finished: false,
+    }));
+    let reader = ActivityReader {
+        inner: source,
+        activity: activity.clone(),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let client = client.clone();
+    thread::spawn(move || {
+        *req.body_mut() = Some(Body::sized(reader, len));
+        let _ = tx.send(client.execute(req));
+    });
+
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(res) => return res.map_err(UploadError::Send),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let a = activity.lock().unwrap();
+                if !a.finished && a.last.elapsed() > limit {
+                    return Err(UploadError::Stalled(format!(
+                        "Upload made no progress for {}; the transfer stalled.",
+                        describe(limit)
+                    )));
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(UploadError::Aborted(
+                    "The upload ended unexpectedly.".to_string(),
+                ));
+            }
+        }
+    }
+}
+
+struct Activity {
+    last: Instant,
+    finished: bool,
+}
+
+// reqwest pulls the next chunk only once the previous one has been written
+// to the socket, so the time between reads tracks write progress.
+struct ActivityReader<R> {
+    inner: R,
+    activity: Arc<Mutex<Activity>>,
+}
+
+impl<R: Read> Read for ActivityReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let mut a = self.activity.lock().unwrap();
+        a.last = Instant::now();
+        a.finished = n == 0;
+        Ok(n)
+    }
+}
+
+pub fn describe(d: Duration) -> String {
+    if d.as_millis() < 1000 {
+        format!("{} ms", d.as_millis())
+    } else {
+        format!("{:.1} s", d.as_secs_f64())
+    }
+}
+
+// Text of a response body, honoring the charset from Content-Type like
+// `Response::text` does.
+pub fn decode_text(body: &[u8], content_type: Option<&str>) -> String {
+    let charset = content_type
+        .and_then(|ct| ct.parse::<mime::Mime>().ok())
+        .and_then(|m| m.get_param("charset").map(|c| c.to_string()));
+    let encoding = charset
+        .and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(body).0.into_owned()
+}
@@ -0,0 +1,117 @@
+// --unix-socket PATH: HTTP to a local daemon (Docker, containerd, ...) that
+// listens on a Unix domain socket instead of a TCP port.
+//
+// The HTTP client only dials TCP, so the request goes to a relay on a
+// loopback port that passes each connection through to the socket. Other
+// local users can reach that port too, so a connection is only relayed
+// when its request carries this run's random token in TOKEN_HEADER, and
+// the header is taken out before the request reaches the daemon. The
+// client doesn't keep these connections alive, so every request is checked.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::thread;
+
+pub const TOKEN_HEADER: &str = "x-unix-socket-relay";
+
+// Longest request head the relay reads while looking for the token
+const MAX_HEAD: usize = 64 * 1024;
+
+pub struct Relay {
+    pub port: u16,
+    pub token: String,
+}
+
+#[cfg(unix)]
+pub fn relay(path: &Path) -> Result<Relay, String> {
+    use std::os::unix::net::UnixStream;
+
+    // Fail up front, rather than as a dropped connection, when nothing
+    // is listening
+    UnixStream::connect(path).map_err(|e| {
+        format!(
+            "Unable to connect to the Unix socket '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    let listener = TcpListener::bind(("127.0.0.1", 0)).map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let mut bytes = [0u8; 16];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| e.to_string())?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let path = path.to_path_buf();
+    let expected = token.clone();
+    thread::spawn(move || {
+        for conn in listener.incoming().flatten() {
+            let path = path.clone();
+            let expected = expected.clone();
+            thread::spawn(move || {
+                let _ = pass_through(conn, &path, &expected);
+            });
+        }
+    });
+    Ok(Relay { port, token })
+}
+
+#[cfg(not(unix))]
+pub fn relay(_path: &Path) -> Result<Relay, String> {
+    Err("--unix-socket needs a system with Unix domain sockets.".into())
+}
+
+#[cfg(unix)]
+fn pass_through(conn: TcpStream, path: &Path, token: &str) -> io::Result<()> {
+    use std::os::unix::net::UnixStream;
+
+    let mut reader = BufReader::new(conn.try_clone()?);
+    let Some(head) = read_head(&mut reader, token)? else {
+        return conn.shutdown(Shutdown::Both);
+    };
+    let mut socket = UnixStream::connect(path)?;
+    socket.write_all(&head)?;
+
+    let mut to_socket = socket.try_clone()?;
+    let upstream = thread::spawn(move || {
+        // What the reader buffered past the head goes first
+        let _ = io::copy(&mut reader, &mut to_socket);
+        let _ = to_socket.shutdown(Shutdown::Write);
+    });
+    let mut to_client = conn;
+    io::copy(&mut socket, &mut to_client)?;
+    let _ = to_client.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+    Ok(())
+}
+
+// The request head without its token line, or None when the token is
+// missing or wrong.
+fn read_head(reader: &mut impl BufRead, token: &str) -> io::Result<Option<Vec<u8>>> {
+    let mut head = Vec::new();
+    let mut authorized = false;
+    let mut read = 0;
+    loop {
+        let mut line = Vec::new();
+        let n = reader
+            .by_ref()
+            .take(MAX_HEAD as u64)
+            .read_until(b'\n', &mut line)?;
+        read += n;
+        if n == 0 || read > MAX_HEAD {
+            return Ok(None);
+        }
+        let text = String::from_utf8_lossy(&line);
+        if let Some((name, value)) = text.split_once(':')
+            && name.trim().eq_ignore_ascii_case(TOKEN_HEADER)
+        {
+            authorized = value.trim() == token;
+            continue;
+        }
+        let end = line == b"\r\n" || line == b"\n";
+        head.extend_from_slice(&line);
+        if end {
+            return Ok(authorized.then_some(head));
+        }
+    }
+}
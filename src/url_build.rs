@@ -0,0 +1,215 @@
+// --path-var and --param: URLs put together from a template and values
+// that are encoded here instead of by hand.
+//
+//   curl 'https://api.example.com/users/{id}/posts' --path-var id='a b/c' \
+//       --param q='rust & go' --param page=2
+//   -> https://api.example.com/users/a%20b%2Fc/posts?q=rust%20%26%20go&page=2
+//
+// A {name} placeholder takes its --path-var value encoded as one path
+// segment, so a '/' or '?' in it can't change the URL's shape; {{...}}
+// secret references are left for the secrets resolver. Placeholders are
+// only looked for when a --path-var is given, and every one must be
+// filled. A --param replaces any parameter of that name already in the
+// URL's query and is appended otherwise; given several times, every value
+// is sent. `--param flag` without '=' sends the bare name.
+
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use std::collections::{BTreeMap, HashSet};
+
+// Everything but RFC 3986's unreserved characters
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+pub fn build(url: &str, path_vars: &[String], params: &[String]) -> Result<String, String> {
+    let url = substitute(url, path_vars)?;
+    add_params(&url, params)
+}
+
+fn substitute(url: &str, path_vars: &[String]) -> Result<String, String> {
+    if path_vars.is_empty() {
+        return Ok(url.to_string());
+    }
+    let mut vars = BTreeMap::new();
+    for arg in path_vars {
+        let Some((name, value)) = arg.split_once('=').filter(|(name, _)| is_name(name)) else {
+            return Err(format!(
+                "--path-var expects name=value with a name of letters, digits, '_' or '-', not '{}'.",
+                arg
+            ));
+        };
+        vars.insert(name, value);
+    }
+
+    let mut out = String::new();
+    let mut used = HashSet::new();
+    let mut rest = url;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") {
+            let end = tail.find("}}").map_or(tail.len(), |e| e + 2);
+            out.push_str(&tail[..end]);
+            rest = &tail[end..];
+            continue;
+        }
+        let name = tail[1..].split_once('}').map(|(name, _)| name);
+        let Some(name) = name.filter(|name| is_name(name)) else {
+            out.push('{');
+            rest = &tail[1..];
+            continue;
+        };
+        let Some(value) = vars.get(name) else {
+            return Err(format!(
+                "The URL has a {{{}}} placeholder but no --path-var {}=VALUE.",
+                name, name
+            ));
+        };
+        out.extend(utf8_percent_encode(value, COMPONENT));
+        used.insert(name);
+        rest = &tail[name.len() + 2..];
+    }
+    out.push_str(rest);
+
+    if let Some(unused) = vars.keys().find(|name| !used.contains(*name)) {
+        return Err(format!(
+            "--path-var {} isn't used: the URL has no {{{}}} placeholder.",
+            unused, unused
+        ));
+    }
+    Ok(out)
+}
+
+fn add_params(url: &str, params: &[String]) -> Result<String, String> {
+    if params.is_empty() {
+        return Ok(url.to_string());
+    }
+    let mut added = Vec::new();
+    let mut names = HashSet::new();
+    for arg in params {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if name.is_empty() {
+            return Err(format!("--param needs a name before '=': '{}'.", arg));
+        }
+        names.insert(name.to_string());
+        let name = utf8_percent_encode(name, COMPONENT).to_string();
+        added.push(match value {
+            Some(value) => format!("{}={}", name, utf8_percent_encode(value, COMPONENT)),
+            None => name,
+        });
+    }
+
+    let (base, fragment) = match url.find('#') {
+        Some(at) => url.split_at(at),
+        None => (url, ""),
+    };
+    let (path, query) = base.split_once('?').unwrap_or((base, ""));
+    let mut pairs: Vec<String> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && !names.contains(&decoded_name(pair)))
+        .map(str::to_string)
+        .collect();
+    pairs.extend(added);
+    Ok(format!("{}?{}{}", path, pairs.join("&"), fragment))
+}
+
+// The name of a query's `name=value` pair, as the server reads it.
+fn decoded_name(pair: &str) -> String {
+    let name = pair.split('=').next().unwrap_or_default().replace('+', " ");
+    percent_decode_str(&name).decode_utf8_lossy().into_owned()
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(url: &str, params: &[&str]) -> Result<String, String> {
+        let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+        build(url, &[], &params)
+    }
+
+    fn vars(url: &str, vars: &[&str]) -> Result<String, String> {
+        let vars: Vec<String> = vars.iter().map(|v| v.to_string()).collect();
+        build(url, &vars, &[])
+    }
+
+    #[test]
+    fn param_values_are_encoded() {
+        assert_eq!(
+            params("https://h/s", &["q=rust & go", "expr=a=b", "tag=#1"]).unwrap(),
+            "https://h/s?q=rust%20%26%20go&expr=a%3Db&tag=%231"
+        );
+        assert_eq!(
+            params("https://h/s", &["city=Zürich", "名前=値"]).unwrap(),
+            "https://h/s?city=Z%C3%BCrich&%E5%90%8D%E5%89%8D=%E5%80%A4"
+        );
+    }
+
+    #[test]
+    fn params_replace_same_named_ones_and_keep_the_fragment() {
+        assert_eq!(
+            params("https://h/s?page=1&q=x#top", &["page=2", "flag"]).unwrap(),
+            "https://h/s?q=x&page=2&flag#top"
+        );
+        assert_eq!(
+            params("https://h/s?a=1", &["a=2", "a=3"]).unwrap(),
+            "https://h/s?a=2&a=3"
+        );
+    }
+
+    #[test]
+    fn param_needs_a_name() {
+        assert!(params("https://h/", &["=x"]).is_err());
+    }
+
+    #[test]
+    fn path_var_is_one_segment() {
+        assert_eq!(
+            vars("https://h/users/{id}/posts", &["id=a b/c?d"]).unwrap(),
+            "https://h/users/a%20b%2Fc%3Fd/posts"
+        );
+    }
+
+    #[test]
+    fn secret_references_are_left_alone() {
+        assert_eq!(
+            vars("https://h/{{env:BASE}}/{id}", &["id=7"]).unwrap(),
+            "https://h/{{env:BASE}}/7"
+        );
+    }
+
+    #[test]
+    fn placeholders_only_with_path_vars() {
+        assert_eq!(vars("https://h/{id}", &[]).unwrap(), "https://h/{id}");
+    }
+
+    #[test]
+    fn missing_path_var_is_an_error() {
+        let err = vars("https://h/{id}/{other}", &["id=1"]).unwrap_err();
+        assert!(err.contains("{other}"), "{}", err);
+    }
+
+    #[test]
+    fn unused_path_var_is_an_error() {
+        let err = vars("https://h/{id}", &["id=1", "extra=2"]).unwrap_err();
+        assert!(err.contains("extra"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_path_var_is_an_error() {
+        assert!(vars("https://h/{id}", &["id"]).is_err());
+        assert!(vars("https://h/{id}", &["a b=1"]).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+// Internationalized and hand-typed URLs.
+//
+// `prepare` turns what a user pasted into something the strict parser and
+// the server both accept: a Unicode host becomes punycode (IDNA/UTS #46),
+// and characters RFC 3986 doesn't allow in the userinfo, path, query or
+// fragment are percent-encoded as UTF-8. Existing %XX escapes are kept; a
+// '%' not followed by two hex digits is encoded itself.
+//
+// `url normalize` prints the canonical form on top of that: lowercase
+// scheme and host, no default port, dot segments resolved, escapes of
+// unreserved characters decoded and the others in uppercase hex.
+
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(StructOpt, Clone, Debug)]
+pub enum UrlCommand {
+    /// Print the canonical form of a URL
+    Normalize { url: String },
+}
+
+pub fn run(cmd: UrlCommand) -> Result<(), String> {
+    match cmd {
+        UrlCommand::Normalize { url } => {
+            println!("{}", normalize(&url)?);
+            Ok(())
+        }
+    }
+}
+
+pub fn prepare(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    let Some((scheme, rest)) = input.split_once("://") else {
+        return Ok(input.to_string());
+    };
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(end);
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => (Some(userinfo), host_port),
+        None => (None, authority),
+    };
+    // An IPv6 literal's colons aren't a port separator
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) if !host_port.ends_with(']') => (host, Some(port)),
+        _ => (host_port, None),
+    };
+
+    let mut out = format!("{}://", scheme);
+    if let Some(userinfo) = userinfo {
+        out.push_str(&escape(userinfo, |c| c == ':'));
+        out.push('@');
+    }
+    out.push_str(&ascii_host(host)?);
+    if let Some(port) = port {
+        out.push(':');
+        out.push_str(port);
+    }
+    let (before_fragment, fragment) = match tail.split_once('#') {
+        Some((path_query, fragment)) => (path_query, Some(fragment)),
+        None => (tail, None),
+    };
+    out.push_str(&escape_path(before_fragment));
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(&escape_path(fragment));
+    }
+    Ok(out)
+}
+
+// A path, query or fragment as `prepare` encodes it.
+pub fn escape_path(part: &str) -> String {
+    escape(part, |c| matches!(c, ':' | '@' | '/' | '?'))
+}
+
+fn ascii_host(host: &str) -> Result<String, String> {
+    if host.is_ascii() {
+        return Ok(host.to_string());
+    }
+    idna::domain_to_ascii(host).map_err(|_| {
+        format!(
+            "The host '{}' is not a valid internationalized domain name.",
+            host
+        )
+    })
+}
+
+// Unreserved characters, sub-delims, valid %XX escapes and whatever
+// `extra` allows pass through; everything else is percent-encoded.
+fn escape(part: &str, extra: impl Fn(char) -> bool) -> String {
+    let mut out = String::with_capacity(part.len());
+    for (i, c) in part.char_indices() {
+        // Line breaks and tabs from a wrapped paste are dropped, as browsers do
+        if matches!(c, '\t' | '\n' | '\r') {
+            continue;
+        }
+        let allowed = c.is_ascii_alphanumeric()
+            || matches!(c, '-' | '.' | '_' | '~')
+            || matches!(
+                c,
+                '!' | '$' | '&' | '\'' | '(' | ')' | '*' | '+' | ',' | ';' | '='
+            )
+            || extra(c)
+            || (c == '%' && is_escape(&part[i..]));
+        if allowed {
+            out.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    out
+}
+
+fn is_escape(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() >= 3 && b[1].is_ascii_hexdigit() && b[2].is_ascii_hexdigit()
+}
+
+pub fn normalize(input: &str) -> Result<String, String> {
+    let prepared = prepare(input)?;
+    let mut url = Url::parse(&prepared).map_err(|e| format!("Invalid URL '{}': {}", input, e))?;
+    let path = canonical_escapes(url.path());
+    url.set_path(&path);
+    if let Some(query) = url.query().map(canonical_escapes) {
+        url.set_query(Some(&query));
+    }
+    if let Some(fragment) = url.fragment().map(canonical_escapes) {
+        url.set_fragment(Some(&fragment));
+    }
+    Ok(url.to_string())
+}
+
+// %7e -> ~, %c3%bc -> %C3%BC
+fn canonical_escapes(part: &str) -> String {
+    let mut out = String::with_capacity(part.len());
+    let mut rest = part;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if !is_escape(rest) {
+            out.push('%');
+            rest = &rest[1..];
+            continue;
+        }
+        let byte = u8::from_str_radix(&rest[1..3], 16).unwrap_or(0);
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&rest[..3].to_ascii_uppercase());
+        }
+        rest = &rest[3..];
+    }
+    out.push_str(rest);
+    out
+}
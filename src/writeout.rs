@@ -0,0 +1,263 @@
+// -w/--write-out: after the transfer, print a template whose %{variable}
+// references are replaced by what the request measured, as curl does:
+//
+//   curl -w '%{http_code} %{time_total}\n' https://example.com
+//
+// '@file' reads the template from a file, '@-' from stdin. \n, \r, \t and
+// \\ are unescaped, %% is a literal %, and %{json} prints every variable as
+// one JSON object.
+//
+// Times are in seconds since the request started. reqwest doesn't expose
+// its connection setup, so time_connect and time_appconnect come from a
+// separate probe connection to the same endpoint, made only when the
+// template asks for them.
+
+use crate::errln;
+use crate::output;
+use reqwest::blocking::Response;
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use url::Url;
+
+const VARIABLES: [&str; 15] = [
+    "content_type",
+    "http_code",
+    "method",
+    "num_redirects",
+    "remote_ip",
+    "remote_port",
+    "response_code",
+    "size_download",
+    "size_header",
+    "time_appconnect",
+    "time_connect",
+    "time_namelookup",
+    "time_starttransfer",
+    "time_total",
+    "url_effective",
+];
+
+struct Received {
+    status: u16,
+    url: Url,
+    content_type: Option<String>,
+    remote: Option<SocketAddr>,
+    size_header: u64,
+    starttransfer: Duration,
+}
+
+struct State {
+    started: Option<Instant>,
+    method: String,
+    namelookup: Duration,
+    redirects: usize,
+    received: Option<Received>,
+    size_download: u64,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const {
+        RefCell::new(State {
+            started: None,
+            method: String::new(),
+            namelookup: Duration::ZERO,
+            redirects: 0,
+            received: None,
+            size_download: 0,
+        })
+    };
+}
+
+// The template of a -w argument.
+pub fn template(spec: &str) -> Result<String, String> {
+    match spec.strip_prefix('@') {
+        Some("-") => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Unable to read stdin: {}", e))?;
+            Ok(text)
+        }
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| format!("Unable to read '{}': {}", path, e))
+        }
+        None => Ok(spec.to_string()),
+    }
+}
+
+// Start the clock; the recording functions do nothing until then.
+pub fn enable(method: &str) {
+    STATE.with_borrow_mut(|state| {
+        state.started = Some(Instant::now());
+        state.method = method.to_string();
+    });
+}
+
+pub fn namelookup(took: Duration) {
+    STATE.with_borrow_mut(|state| state.namelookup = took);
+}
+
+// Called for every redirect followed; `hop` counts from 1.
+pub fn redirected(hop: usize) {
+    STATE.with_borrow_mut(|state| state.redirects = hop);
+}
+
+// The final response's status line and headers have arrived.
+pub fn response(res: &Response) {
+    let Some(started) = STATE.with_borrow(|state| state.started) else {
+        return;
+    };
+    // "HTTP/1.1 200 OK\r\n", every "name: value\r\n" and the blank line
+    let status_line = format!("{:?} {}\r\n", res.version(), res.status()).len();
+    let header_lines: usize = res
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    let received = Received {
+        status: res.status().as_u16(),
+        url: res.url().clone(),
+        content_type: res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        remote: res.remote_addr(),
+        size_header: (status_line + header_lines + 2) as u64,
+        starttransfer: started.elapsed(),
+    };
+    STATE.with_borrow_mut(|state| state.received = Some(received));
+}
+
+pub fn downloaded(bytes: u64) {
+    STATE.with_borrow_mut(|state| state.size_download = bytes);
+}
+
+// Print the template to stdout. `probe` measures the TCP connect and, for
+// HTTPS, the TLS handshake to the URL's endpoint.
+pub fn print(template: &str, probe: impl FnOnce(&Url) -> Option<(Duration, Option<Duration>)>) {
+    let Some(out) = STATE.with_borrow(|state| render(template, state, probe)) else {
+        return;
+    };
+    let mut stdout = output::stdout();
+    let _ = stdout
+        .write_all(out.as_bytes())
+        .and_then(|_| stdout.flush());
+}
+
+fn render(
+    template: &str,
+    state: &State,
+    probe: impl FnOnce(&Url) -> Option<(Duration, Option<Duration>)>,
+) -> Option<String> {
+    let started = state.started?;
+    let total = started.elapsed();
+    let connect = if template.contains("%{time_connect}")
+        || template.contains("%{time_appconnect}")
+        || template.contains("%{json}")
+    {
+        state.received.as_ref().and_then(|r| probe(&r.url))
+    } else {
+        None
+    };
+    let value = |name: &str| variable(state, name, total, connect);
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find(['%', '\\']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if let Some(after) = tail.strip_prefix("%{")
+            && let Some((name, after)) = after.split_once('}')
+        {
+            match name {
+                "json" => out.push_str(&json(value).to_string()),
+                _ => match value(name) {
+                    Some(v) => out.push_str(&plain(&v)),
+                    None => errln!("Warning: Unknown --write-out variable %{{{}}}.", name),
+                },
+            }
+            rest = after;
+            continue;
+        }
+        let (text, len) = match tail.get(..2) {
+            Some("%%") => ("%", 2),
+            Some("\\n") => ("\n", 2),
+            Some("\\r") => ("\r", 2),
+            Some("\\t") => ("\t", 2),
+            Some("\\\\") => ("\\", 2),
+            _ => (&tail[..1], 1),
+        };
+        out.push_str(text);
+        rest = &tail[len..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+fn variable(
+    state: &State,
+    name: &str,
+    total: Duration,
+    connect: Option<(Duration, Option<Duration>)>,
+) -> Option<Value> {
+    let received = state.received.as_ref();
+    let seconds = |d: Duration| Value::from((d.as_secs_f64() * 1e6).round() / 1e6);
+    let time_connect = state.namelookup + connect.map_or(Duration::ZERO, |(c, _)| c);
+    Some(match name {
+        // Like curl, 000 when no response arrived
+        "http_code" | "response_code" => match received {
+            Some(r) => Value::from(format!("{:03}", r.status)),
+            None => Value::from("000"),
+        },
+        "url_effective" => Value::from(received.map_or(String::new(), |r| r.url.to_string())),
+        "content_type" => Value::from(received.and_then(|r| r.content_type.clone())),
+        "method" => Value::from(state.method.clone()),
+        "remote_ip" => Value::from(
+            received
+                .and_then(|r| r.remote)
+                .map_or(String::new(), |a| a.ip().to_string()),
+        ),
+        "remote_port" => match received.and_then(|r| r.remote) {
+            Some(addr) => Value::from(addr.port()),
+            None => Value::from(""),
+        },
+        "num_redirects" => Value::from(state.redirects),
+        "size_download" => Value::from(state.size_download),
+        "size_header" => Value::from(received.map_or(0, |r| r.size_header)),
+        "time_namelookup" => seconds(state.namelookup),
+        "time_connect" => seconds(connect.map_or(Duration::ZERO, |_| time_connect)),
+        "time_appconnect" => seconds(match connect {
+            Some((_, Some(handshake))) => time_connect + handshake,
+            _ => Duration::ZERO,
+        }),
+        "time_starttransfer" => seconds(received.map_or(Duration::ZERO, |r| r.starttransfer)),
+        "time_total" => seconds(total),
+        _ => return None,
+    })
+}
+
+fn json(value: impl Fn(&str) -> Option<Value>) -> Value {
+    let mut map = Map::new();
+    for name in VARIABLES {
+        if let Some(v) = value(name) {
+            map.insert(name.to_string(), v);
+        }
+    }
+    Value::Object(map)
+}
+
+// Strings without their JSON quotes; times with curl's six decimals.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Number(n) if n.is_f64() => format!("{:.6}", n.as_f64().unwrap_or(0.0)),
+        other => other.to_string(),
+    }
+}